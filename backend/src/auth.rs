@@ -0,0 +1,247 @@
+use actix_web::{FromRequest, HttpRequest, dev::Payload, web};
+use jsonwebtoken::{
+    DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::Error as JwtError,
+};
+use serde::{Deserialize, Serialize};
+use std::future::{Ready, ready};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::revocation::RevokedTokens;
+
+/// Default tolerance for clock skew between the issuing and validating hosts
+pub const DEFAULT_LEEWAY_SECS: u64 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String, // User ID
+    pub exp: usize,
+    pub iat: usize,
+    /// Unique token id, used to revoke this token on logout; empty on tokens issued before it existed
+    #[serde(default)]
+    pub jti: String,
+}
+
+/// Clock-skew leeway applied to `exp`/`iat`, configurable via `JWT_LEEWAY_SECS`
+pub fn leeway_secs() -> u64 {
+    std::env::var("JWT_LEEWAY_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_LEEWAY_SECS)
+}
+
+/// Issue a token for `user_id`, valid for `ttl_secs`
+pub fn issue_token(user_id: Uuid, secret: &[u8], ttl_secs: u64) -> Result<String, JwtError> {
+    let now = chrono::Utc::now().timestamp();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        exp: (now + ttl_secs as i64) as usize,
+        iat: now as usize,
+        jti: Uuid::new_v4().to_string(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret),
+    )
+}
+
+/// Decode and validate a token, tolerating `leeway_secs` of clock skew
+pub fn decode_claims(token: &str, secret: &[u8], leeway_secs: u64) -> Result<Claims, JwtError> {
+    let mut validation = Validation::default();
+    validation.leeway = leeway_secs;
+
+    decode::<Claims>(token, &DecodingKey::from_secret(secret), &validation).map(|data| data.claims)
+}
+
+/// Validate the `Authorization: Bearer <token>` header, rejecting revoked tokens
+fn authenticate(req: &HttpRequest) -> Result<Claims, actix_web::Error> {
+    // Tokens are only ever checked against the configured secret, never a built-in default
+    let Some(config) = req.app_data::<web::Data<AppConfig>>() else {
+        return Err(actix_web::error::ErrorInternalServerError(
+            "Authentication is not configured",
+        ));
+    };
+
+    let claims = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Missing bearer token"))
+        .and_then(|token| {
+            decode_claims(token, config.jwt_secret.as_bytes(), leeway_secs())
+                .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token"))
+        })?;
+
+    let revoked = req
+        .app_data::<web::Data<RevokedTokens>>()
+        .is_some_and(|revoked| !claims.jti.is_empty() && revoked.is_revoked(&claims.jti));
+    if revoked {
+        return Err(actix_web::error::ErrorUnauthorized(
+            "Token has been revoked",
+        ));
+    }
+
+    Ok(claims)
+}
+
+/// Extractor for the user authenticated by the `Authorization: Bearer <token>` header
+pub struct AuthUser(pub Uuid);
+
+impl FromRequest for AuthUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let user_id = authenticate(req).and_then(|claims| {
+            Uuid::parse_str(&claims.sub)
+                .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token subject"))
+        });
+
+        ready(user_id.map(AuthUser))
+    }
+}
+
+/// Extractor for the full claims of the bearer token, for routes that act on the token itself
+pub struct AuthClaims(pub Claims);
+
+impl FromRequest for AuthClaims {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req).map(AuthClaims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AuthUser, Claims, decode_claims, issue_token};
+    use crate::config::AppConfig;
+    use crate::revocation::RevokedTokens;
+    use actix_web::{FromRequest, http::StatusCode, test::TestRequest, web};
+    use chrono::Utc;
+    use jsonwebtoken::{EncodingKey, Header, encode};
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn token_expired_by(secs: i64) -> String {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            sub: "user".to_string(),
+            exp: (now - secs) as usize,
+            iat: (now - secs - 3600) as usize,
+            jti: String::new(),
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_recently_expired_token_within_leeway() {
+        assert!(decode_claims(&token_expired_by(10), SECRET, 30).is_ok());
+    }
+
+    #[test]
+    fn test_expired_token_beyond_leeway() {
+        assert!(decode_claims(&token_expired_by(60), SECRET, 30).is_err());
+    }
+
+    #[test]
+    fn test_issued_token_round_trips() {
+        let user_id = uuid::Uuid::new_v4();
+        let token = issue_token(user_id, SECRET, 3600).unwrap();
+
+        let claims = decode_claims(&token, SECRET, 0).unwrap();
+        assert_eq!(claims.sub, user_id.to_string());
+        assert!(decode_claims(&token, b"other-secret", 0).is_err());
+    }
+
+    #[test]
+    fn test_issued_token_expires_after_ttl() {
+        let token = issue_token(uuid::Uuid::new_v4(), SECRET, 900).unwrap();
+        let claims = decode_claims(&token, SECRET, 0).unwrap();
+        assert_eq!(claims.exp - claims.iat, 900);
+    }
+
+    fn config() -> web::Data<AppConfig> {
+        web::Data::new(AppConfig {
+            jwt_secret: String::from_utf8(SECRET.to_vec()).unwrap(),
+            ..AppConfig::for_tests()
+        })
+    }
+
+    async fn extract(authorization: Option<String>) -> Result<AuthUser, actix_web::Error> {
+        let mut req = TestRequest::default().app_data(config());
+        if let Some(value) = authorization {
+            req = req.insert_header(("Authorization", value));
+        }
+        AuthUser::extract(&req.to_http_request()).await
+    }
+
+    fn status(result: Result<AuthUser, actix_web::Error>) -> StatusCode {
+        result.err().unwrap().as_response_error().status_code()
+    }
+
+    #[actix_web::test]
+    async fn test_missing_header_is_unauthorized() {
+        assert_eq!(status(extract(None).await), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn test_expired_token_is_unauthorized() {
+        let header = format!("Bearer {}", token_expired_by(3600));
+        assert_eq!(
+            status(extract(Some(header)).await),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_forged_token_is_unauthorized() {
+        let token = issue_token(uuid::Uuid::new_v4(), b"other-secret", 3600).unwrap();
+        let header = format!("Bearer {}", token);
+        assert_eq!(
+            status(extract(Some(header)).await),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_valid_token_yields_user() {
+        let user_id = uuid::Uuid::new_v4();
+        let token = issue_token(user_id, SECRET, 3600).unwrap();
+
+        let user = extract(Some(format!("Bearer {}", token))).await.unwrap();
+        assert_eq!(user.0, user_id);
+    }
+
+    #[actix_web::test]
+    async fn test_logged_out_token_is_rejected() {
+        let user_id = uuid::Uuid::new_v4();
+        let logged_out = issue_token(user_id, SECRET, 3600).unwrap();
+        let other = issue_token(user_id, SECRET, 3600).unwrap();
+
+        let claims = decode_claims(&logged_out, SECRET, 0).unwrap();
+        let revoked = web::Data::new(RevokedTokens::default());
+        revoked.revoke(&claims.jti, Utc::now() + chrono::Duration::hours(1));
+
+        let extract = |token: &str| {
+            let req = TestRequest::default()
+                .app_data(config())
+                .app_data(revoked.clone())
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_http_request();
+            AuthUser::extract(&req)
+        };
+
+        assert_eq!(status(extract(&logged_out).await), StatusCode::UNAUTHORIZED);
+        assert_eq!(extract(&other).await.unwrap().0, user_id);
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stops calling a failing upstream for a cooldown after `failure_threshold` consecutive
+/// failures, then lets a single trial request through (half-open) to test recovery
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// A permit to send one request now; `None` while open, or while a half-open trial is
+    /// running. The permit must be settled with its outcome.
+    pub fn allow(&self, now: Instant) -> Option<BreakerPermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        let allowed = match state.opened_at {
+            None => true,
+            Some(opened_at) if now.duration_since(opened_at) >= self.cooldown => {
+                if state.trial_in_flight {
+                    false
+                } else {
+                    state.trial_in_flight = true;
+                    true
+                }
+            }
+            Some(_) => false,
+        };
+        drop(state);
+        // Only built when allowed: an unsettled permit records a failure on drop
+        if allowed {
+            Some(BreakerPermit {
+                breaker: self,
+                settled: false,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::default();
+    }
+
+    fn record_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.trial_in_flight = false;
+        if state.opened_at.is_some() || state.consecutive_failures >= self.failure_threshold {
+            // A failed trial restarts the cooldown
+            state.opened_at = Some(now);
+        }
+    }
+}
+
+/// One request let through by [`CircuitBreaker::allow`]. Dropping it unsettled (e.g. the
+/// request future was cancelled) counts as a failure, so a half-open trial can't stay in
+/// flight forever.
+#[must_use = "settle the permit with success() or failure()"]
+pub struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    settled: bool,
+}
+
+impl BreakerPermit<'_> {
+    pub fn success(mut self) {
+        self.settled = true;
+        self.breaker.record_success();
+    }
+
+    pub fn failure(mut self, now: Instant) {
+        self.settled = true;
+        self.breaker.record_failure(now);
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.breaker.record_failure(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CircuitBreaker;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_trips_after_threshold_and_recovers() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            breaker.allow(start).unwrap().failure(start);
+        }
+        assert!(breaker.allow(start + Duration::from_secs(10)).is_none());
+
+        // Half-open: one trial after the cooldown, which fails and re-opens
+        let after_cooldown = start + Duration::from_secs(30);
+        let trial = breaker.allow(after_cooldown).unwrap();
+        assert!(breaker.allow(after_cooldown).is_none());
+        trial.failure(after_cooldown);
+        assert!(
+            breaker
+                .allow(after_cooldown + Duration::from_secs(1))
+                .is_none()
+        );
+
+        // Next trial succeeds and closes the breaker
+        let recovered = after_cooldown + Duration::from_secs(30);
+        breaker.allow(recovered).unwrap().success();
+        assert!(breaker.allow(recovered).is_some());
+        assert!(breaker.allow(recovered).is_some());
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+
+        breaker.allow(now).unwrap().failure(now);
+        breaker.allow(now).unwrap().success();
+        breaker.allow(now).unwrap().failure(now);
+        assert!(breaker.allow(now).is_some());
+    }
+
+    #[test]
+    fn test_dropped_trial_reopens_instead_of_wedging() {
+        let breaker = CircuitBreaker::new(1, Duration::ZERO);
+        let now = Instant::now();
+        breaker.allow(now).unwrap().failure(now);
+
+        // The trial's request is cancelled before it settles
+        drop(breaker.allow(now).unwrap());
+
+        // The breaker re-opened rather than waiting on that trial forever
+        let later = Instant::now();
+        breaker.allow(later).unwrap().success();
+        assert!(breaker.allow(later).is_some());
+    }
+}
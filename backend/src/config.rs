@@ -0,0 +1,559 @@
+use rust_decimal::Decimal;
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const DEFAULT_SOLANA_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
+const DEFAULT_BIND_ADDR: &str = "127.0.0.1:8080";
+
+/// Where balance changes are logged: `stdout` or `file:<path>`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditSinkConfig {
+    Stdout,
+    File(PathBuf),
+}
+
+impl FromStr for AuditSinkConfig {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            _ if value == "stdout" => Ok(Self::Stdout),
+            _ => Err("expected 'stdout' or 'file:<path>'".to_string()),
+        }
+    }
+}
+
+/// Backend settings, loaded and validated once at startup
+#[derive(Clone)]
+pub struct AppConfig {
+    pub database_url: String,
+    pub jwt_secret: String,
+    /// Lifetime of issued tokens
+    pub jwt_ttl_secs: u64,
+    pub solana_rpc_url: String,
+    pub bind_addr: SocketAddr,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    /// Upper bound on heavy admin queries, see [`crate::deadline::QueryDeadline`]
+    pub admin_query_timeout_secs: u64,
+    /// Smallest withdrawal or transfer accepted; zero allows any positive amount
+    pub min_transfer_amount: Decimal,
+    /// Block withdrawals until the user has verified their email
+    pub require_verified_email: bool,
+    /// Read-only listings and stats go here when set
+    pub database_replica_url: Option<String>,
+    pub balance_audit_sink: Option<AuditSinkConfig>,
+    /// Compress large JSON bodies; on by default in release builds
+    pub enable_compression: bool,
+    /// Larger JSON bodies are rejected with 413 Payload Too Large
+    pub max_json_body_bytes: usize,
+    /// How often logouts made on other instances are picked up
+    pub token_revocation_sync_secs: u64,
+    /// How often withdrawals sent without a confirmation are settled
+    pub withdrawal_settlement_secs: u64,
+    /// Shared secret for node operator routes; they refuse every request when unset
+    pub operator_token: Option<String>,
+    /// Shared secret for admin routes, on top of an admin user's bearer token; they refuse every
+    /// request when unset
+    pub admin_token: Option<String>,
+}
+
+// Never print the database credentials or the signing secret
+impl fmt::Debug for AppConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppConfig")
+            .field("jwt_ttl_secs", &self.jwt_ttl_secs)
+            .field("solana_rpc_url", &self.solana_rpc_url)
+            .field("bind_addr", &self.bind_addr)
+            .field("db_max_connections", &self.db_max_connections)
+            .field("db_min_connections", &self.db_min_connections)
+            .field("admin_query_timeout_secs", &self.admin_query_timeout_secs)
+            .field("min_transfer_amount", &self.min_transfer_amount)
+            .field("require_verified_email", &self.require_verified_email)
+            .field("balance_audit_sink", &self.balance_audit_sink)
+            .field("enable_compression", &self.enable_compression)
+            .field("max_json_body_bytes", &self.max_json_body_bytes)
+            .field(
+                "token_revocation_sync_secs",
+                &self.token_revocation_sync_secs,
+            )
+            .field(
+                "withdrawal_settlement_secs",
+                &self.withdrawal_settlement_secs,
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// Every missing or invalid variable, so a deploy can be fixed in one pass
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration: {}", self.problems.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl AppConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Settings pointing at nothing reachable, for route tests that never touch the database
+    /// or RPC; `jwt_secret` is `test-secret`
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        Self {
+            database_url: "postgres://127.0.0.1:1/test".to_string(),
+            jwt_secret: "test-secret".to_string(),
+            jwt_ttl_secs: 3600,
+            solana_rpc_url: "http://127.0.0.1:1".to_string(),
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            db_max_connections: 1,
+            db_min_connections: 0,
+            admin_query_timeout_secs: 10,
+            min_transfer_amount: Decimal::ZERO,
+            require_verified_email: false,
+            database_replica_url: None,
+            balance_audit_sink: None,
+            enable_compression: false,
+            max_json_body_bytes: 256 * 1024,
+            token_revocation_sync_secs: 30,
+            withdrawal_settlement_secs: 60,
+            operator_token: None,
+            admin_token: None,
+        }
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+
+        let mut required = |name: &str| match lookup(name).filter(|value| !value.is_empty()) {
+            Some(value) => value,
+            None => {
+                problems.push(format!("{} is not set", name));
+                String::new()
+            }
+        };
+        let database_url = required("DATABASE_URL");
+        let jwt_secret = required("JWT_SECRET");
+
+        let solana_rpc_url =
+            lookup("SOLANA_RPC_URL").unwrap_or_else(|| DEFAULT_SOLANA_RPC_URL.to_string());
+        if !solana_rpc_url.starts_with("https://") && !solana_rpc_url.starts_with("http://") {
+            problems.push(format!(
+                "SOLANA_RPC_URL must be an http(s) url, got {:?}",
+                solana_rpc_url
+            ));
+        }
+
+        let with_default =
+            |name: &str, default: &str| lookup(name).unwrap_or_else(|| default.to_string());
+        let bind_addr = parse_var(
+            &mut problems,
+            "BIND_ADDR",
+            with_default("BIND_ADDR", DEFAULT_BIND_ADDR),
+        );
+        let jwt_ttl_secs: Option<u64> = parse_var(
+            &mut problems,
+            "JWT_TTL_SECONDS",
+            with_default("JWT_TTL_SECONDS", "3600"),
+        );
+        if jwt_ttl_secs == Some(0) {
+            problems.push("JWT_TTL_SECONDS must be positive".to_string());
+        }
+        let db_max_connections = parse_var(
+            &mut problems,
+            "DB_MAX_CONNECTIONS",
+            with_default("DB_MAX_CONNECTIONS", "20"),
+        );
+        let db_min_connections = parse_var(
+            &mut problems,
+            "DB_MIN_CONNECTIONS",
+            with_default("DB_MIN_CONNECTIONS", "5"),
+        );
+
+        let admin_query_timeout_secs: Option<u64> = parse_var(
+            &mut problems,
+            "ADMIN_QUERY_TIMEOUT_SECS",
+            with_default("ADMIN_QUERY_TIMEOUT_SECS", "10"),
+        );
+        if admin_query_timeout_secs == Some(0) {
+            problems.push("ADMIN_QUERY_TIMEOUT_SECS must be positive".to_string());
+        }
+
+        let min_transfer_amount: Option<Decimal> = parse_var(
+            &mut problems,
+            "MIN_TRANSFER_AMOUNT",
+            with_default("MIN_TRANSFER_AMOUNT", "0"),
+        );
+        if min_transfer_amount.is_some_and(|amount| amount < Decimal::ZERO) {
+            problems.push("MIN_TRANSFER_AMOUNT must not be negative".to_string());
+        }
+
+        let require_verified_email = parse_var::<Flag>(
+            &mut problems,
+            "REQUIRE_VERIFIED_EMAIL",
+            with_default("REQUIRE_VERIFIED_EMAIL", "false"),
+        );
+        let database_replica_url = lookup("DATABASE_REPLICA_URL");
+        if database_replica_url.as_deref() == Some("") {
+            problems.push("DATABASE_REPLICA_URL is set but empty".to_string());
+        }
+        let balance_audit_sink = lookup("BALANCE_AUDIT_SINK")
+            .and_then(|value| parse_var(&mut problems, "BALANCE_AUDIT_SINK", value));
+
+        let compress_by_default = if cfg!(debug_assertions) {
+            "false"
+        } else {
+            "true"
+        };
+        let enable_compression = parse_var::<Flag>(
+            &mut problems,
+            "ENABLE_COMPRESSION",
+            with_default("ENABLE_COMPRESSION", compress_by_default),
+        );
+        let max_json_body_bytes: Option<usize> = parse_var(
+            &mut problems,
+            "MAX_JSON_BODY_BYTES",
+            with_default("MAX_JSON_BODY_BYTES", "262144"),
+        );
+        if max_json_body_bytes == Some(0) {
+            problems.push("MAX_JSON_BODY_BYTES must be positive".to_string());
+        }
+        let token_revocation_sync_secs: Option<u64> = parse_var(
+            &mut problems,
+            "TOKEN_REVOCATION_SYNC_SECS",
+            with_default("TOKEN_REVOCATION_SYNC_SECS", "30"),
+        );
+        if token_revocation_sync_secs == Some(0) {
+            problems.push("TOKEN_REVOCATION_SYNC_SECS must be positive".to_string());
+        }
+        let withdrawal_settlement_secs: Option<u64> = parse_var(
+            &mut problems,
+            "WITHDRAWAL_SETTLEMENT_SECS",
+            with_default("WITHDRAWAL_SETTLEMENT_SECS", "60"),
+        );
+        if withdrawal_settlement_secs == Some(0) {
+            problems.push("WITHDRAWAL_SETTLEMENT_SECS must be positive".to_string());
+        }
+
+        let operator_token = lookup("OPERATOR_TOKEN");
+        if operator_token.as_deref() == Some("") {
+            problems.push("OPERATOR_TOKEN is set but empty".to_string());
+        }
+        let admin_token = lookup("ADMIN_TOKEN");
+        if admin_token.as_deref() == Some("") {
+            problems.push("ADMIN_TOKEN is set but empty".to_string());
+        }
+
+        if let (Some(max), Some(min)) = (db_max_connections, db_min_connections) {
+            if max == 0 || min > max {
+                problems.push(format!(
+                    "DB_MIN_CONNECTIONS ({}) must not exceed a non-zero DB_MAX_CONNECTIONS ({})",
+                    min, max
+                ));
+            }
+        }
+
+        match (
+            bind_addr,
+            jwt_ttl_secs,
+            db_max_connections,
+            db_min_connections,
+            admin_query_timeout_secs,
+            min_transfer_amount,
+            require_verified_email,
+            enable_compression,
+            max_json_body_bytes,
+            token_revocation_sync_secs,
+            withdrawal_settlement_secs,
+        ) {
+            (
+                Some(bind_addr),
+                Some(jwt_ttl_secs),
+                Some(db_max_connections),
+                Some(db_min_connections),
+                Some(admin_query_timeout_secs),
+                Some(min_transfer_amount),
+                Some(Flag(require_verified_email)),
+                Some(Flag(enable_compression)),
+                Some(max_json_body_bytes),
+                Some(token_revocation_sync_secs),
+                Some(withdrawal_settlement_secs),
+            ) if problems.is_empty() => Ok(Self {
+                database_url,
+                jwt_secret,
+                jwt_ttl_secs,
+                solana_rpc_url,
+                bind_addr,
+                db_max_connections,
+                db_min_connections,
+                admin_query_timeout_secs,
+                min_transfer_amount,
+                require_verified_email,
+                database_replica_url,
+                balance_audit_sink,
+                enable_compression,
+                max_json_body_bytes,
+                token_revocation_sync_secs,
+                withdrawal_settlement_secs,
+                operator_token,
+                admin_token,
+            }),
+            _ => Err(ConfigError { problems }),
+        }
+    }
+}
+
+/// An on/off switch: `1`/`true` or `0`/`false`
+struct Flag(bool);
+
+impl FromStr for Flag {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1" | "true" => Ok(Flag(true)),
+            "0" | "false" => Ok(Flag(false)),
+            _ => Err("expected 1, true, 0 or false".to_string()),
+        }
+    }
+}
+
+/// Parse a variable, recording a problem instead of failing on the first bad value
+fn parse_var<T: FromStr>(problems: &mut Vec<String>, name: &str, value: String) -> Option<T>
+where
+    T::Err: Display,
+{
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            problems.push(format!("{} is invalid ({:?}): {}", name, value, e));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppConfig, AuditSinkConfig};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn load(vars: &[(&str, &str)]) -> Result<AppConfig, super::ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        AppConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_all_problems_reported_together() {
+        let err =
+            load(&[("BIND_ADDR", "not-an-addr"), ("DB_MAX_CONNECTIONS", "lots")]).unwrap_err();
+
+        assert_eq!(err.problems.len(), 4);
+        assert_eq!(err.problems[0], "DATABASE_URL is not set");
+        assert_eq!(err.problems[1], "JWT_SECRET is not set");
+        assert!(err.problems[2].starts_with("BIND_ADDR is invalid"));
+        assert!(err.problems[3].starts_with("DB_MAX_CONNECTIONS is invalid"));
+        assert!(
+            err.to_string()
+                .contains("DATABASE_URL is not set; JWT_SECRET is not set")
+        );
+    }
+
+    #[test]
+    fn test_defaults_applied() {
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+        ])
+        .unwrap();
+
+        assert_eq!(config.bind_addr.to_string(), "127.0.0.1:8080");
+        assert_eq!(config.jwt_ttl_secs, 3600);
+        assert_eq!(config.solana_rpc_url, "https://api.mainnet-beta.solana.com");
+        assert_eq!(
+            (config.db_max_connections, config.db_min_connections),
+            (20, 5)
+        );
+        assert_eq!(config.admin_query_timeout_secs, 10);
+        assert_eq!(config.min_transfer_amount, rust_decimal::Decimal::ZERO);
+        assert!(!config.require_verified_email);
+        assert_eq!(config.database_replica_url, None);
+        assert_eq!(config.balance_audit_sink, None);
+        assert_eq!(config.max_json_body_bytes, 256 * 1024);
+        assert_eq!(
+            (
+                config.token_revocation_sync_secs,
+                config.withdrawal_settlement_secs
+            ),
+            (30, 60)
+        );
+        assert!(!format!("{:?}", config).contains("s3cret"));
+    }
+
+    #[test]
+    fn test_min_connections_above_max_rejected() {
+        let err = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("DB_MAX_CONNECTIONS", "2"),
+            ("DB_MIN_CONNECTIONS", "5"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(err.problems.len(), 1);
+        assert!(err.problems[0].starts_with("DB_MIN_CONNECTIONS (5)"));
+    }
+
+    #[test]
+    fn test_jwt_ttl_must_be_positive() {
+        let err = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("JWT_TTL_SECONDS", "0"),
+        ])
+        .unwrap_err();
+        assert_eq!(err.problems, vec!["JWT_TTL_SECONDS must be positive"]);
+
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("JWT_TTL_SECONDS", "900"),
+        ])
+        .unwrap();
+        assert_eq!(config.jwt_ttl_secs, 900);
+    }
+
+    #[test]
+    fn test_bad_min_transfer_amount_rejected() {
+        for (value, problem) in [
+            ("abc", "MIN_TRANSFER_AMOUNT is invalid"),
+            ("-0.5", "MIN_TRANSFER_AMOUNT must not be negative"),
+        ] {
+            let err = load(&[
+                ("DATABASE_URL", "postgres://localhost/app"),
+                ("JWT_SECRET", "s3cret"),
+                ("MIN_TRANSFER_AMOUNT", value),
+            ])
+            .unwrap_err();
+            assert_eq!(err.problems.len(), 1);
+            assert!(err.problems[0].starts_with(problem));
+        }
+
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("MIN_TRANSFER_AMOUNT", "0.01"),
+        ])
+        .unwrap();
+        assert_eq!(config.min_transfer_amount.to_string(), "0.01");
+    }
+
+    #[test]
+    fn test_runtime_settings_parsed() {
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("REQUIRE_VERIFIED_EMAIL", "1"),
+            ("DATABASE_REPLICA_URL", "postgres://replica/app"),
+            ("BALANCE_AUDIT_SINK", "file:/var/log/balances.log"),
+            ("ENABLE_COMPRESSION", "false"),
+            ("MAX_JSON_BODY_BYTES", "1024"),
+            ("TOKEN_REVOCATION_SYNC_SECS", "5"),
+            ("WITHDRAWAL_SETTLEMENT_SECS", "120"),
+        ])
+        .unwrap();
+
+        assert!(config.require_verified_email);
+        assert_eq!(
+            config.database_replica_url.as_deref(),
+            Some("postgres://replica/app")
+        );
+        assert_eq!(
+            config.balance_audit_sink,
+            Some(AuditSinkConfig::File(PathBuf::from(
+                "/var/log/balances.log"
+            )))
+        );
+        assert!(!config.enable_compression);
+        assert_eq!(config.max_json_body_bytes, 1024);
+        assert_eq!(config.token_revocation_sync_secs, 5);
+        assert_eq!(config.withdrawal_settlement_secs, 120);
+        assert!(!format!("{:?}", config).contains("replica"));
+    }
+
+    #[test]
+    fn test_empty_access_tokens_rejected() {
+        let err = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("OPERATOR_TOKEN", ""),
+            ("ADMIN_TOKEN", ""),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.problems,
+            vec![
+                "OPERATOR_TOKEN is set but empty",
+                "ADMIN_TOKEN is set but empty"
+            ]
+        );
+
+        let config = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("OPERATOR_TOKEN", "op-s3cret"),
+            ("ADMIN_TOKEN", "admin-s3cret"),
+        ])
+        .unwrap();
+        assert_eq!(config.operator_token.as_deref(), Some("op-s3cret"));
+        assert_eq!(config.admin_token.as_deref(), Some("admin-s3cret"));
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("op-s3cret") && !debug.contains("admin-s3cret"));
+    }
+
+    #[test]
+    fn test_bad_runtime_settings_reported_together() {
+        let err = load(&[
+            ("DATABASE_URL", "postgres://localhost/app"),
+            ("JWT_SECRET", "s3cret"),
+            ("REQUIRE_VERIFIED_EMAIL", "yes"),
+            ("DATABASE_REPLICA_URL", ""),
+            ("BALANCE_AUDIT_SINK", "syslog"),
+            ("ENABLE_COMPRESSION", "on"),
+            ("MAX_JSON_BODY_BYTES", "0"),
+            ("TOKEN_REVOCATION_SYNC_SECS", "soon"),
+            ("WITHDRAWAL_SETTLEMENT_SECS", "0"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            err.problems
+                .iter()
+                .map(|problem| problem.split_whitespace().next().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "REQUIRE_VERIFIED_EMAIL",
+                "DATABASE_REPLICA_URL",
+                "BALANCE_AUDIT_SINK",
+                "ENABLE_COMPRESSION",
+                "MAX_JSON_BODY_BYTES",
+                "TOKEN_REVOCATION_SYNC_SECS",
+                "WITHDRAWAL_SETTLEMENT_SECS",
+            ]
+        );
+    }
+}
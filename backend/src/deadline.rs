@@ -0,0 +1,78 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+
+/// Upper bound on how long a heavy store query may hold a request. On timeout the query
+/// future is dropped, which makes sqlx cancel it, and the client gets a 504.
+#[derive(Debug, Clone, Copy)]
+pub struct QueryDeadline(pub Duration);
+
+impl QueryDeadline {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self(Duration::from_secs(config.admin_query_timeout_secs))
+    }
+
+    /// Await `query`, or fail with 504 Gateway Timeout once the deadline passes
+    pub async fn run<T, E>(
+        &self,
+        what: &str,
+        query: impl Future<Output = Result<T, E>>,
+    ) -> actix_web::Result<Result<T, E>> {
+        actix_web::rt::time::timeout(self.0, query)
+            .await
+            .map_err(|_| {
+                tracing::warn!("{} exceeded the {:?} query deadline", what, self.0);
+                actix_web::error::ErrorGatewayTimeout(format!("{} timed out", what))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryDeadline;
+    use actix_web::http::StatusCode;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    /// Flags when the query future is dropped, i.e. cancelled
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_slow_query_times_out_with_504() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let flag = DropFlag(Arc::clone(&dropped));
+        let slow_query = async move {
+            let _flag = flag;
+            actix_web::rt::time::sleep(Duration::from_secs(60)).await;
+            Ok::<_, ()>(())
+        };
+
+        let err = QueryDeadline(Duration::from_millis(10))
+            .run("Slow query", slow_query)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[actix_web::test]
+    async fn test_fast_query_passes_through() {
+        let result = QueryDeadline(Duration::from_secs(1))
+            .run("Fast query", async { Err::<(), _>("store error") })
+            .await
+            .unwrap();
+        assert_eq!(result, Err("store error"));
+    }
+}
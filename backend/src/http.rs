@@ -0,0 +1,85 @@
+use reqwest::Client;
+use std::time::Duration;
+
+/// Timeouts for the outbound HTTP client shared by every route that calls Jupiter or the MPC nodes
+#[derive(Debug, Clone, Copy)]
+pub struct HttpTimeouts {
+    pub connect: Duration,
+    pub request: Duration,
+}
+
+impl HttpTimeouts {
+    /// Read `HTTP_CONNECT_TIMEOUT_SECS` (default 5) and `HTTP_TIMEOUT_SECS` (default 10)
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            connect: Duration::from_secs(secs("HTTP_CONNECT_TIMEOUT_SECS", 5)),
+            request: Duration::from_secs(secs("HTTP_TIMEOUT_SECS", 10)),
+        }
+    }
+
+    /// Build the client once at startup so its connection pool is reused across requests
+    pub fn build_client(&self) -> Client {
+        Client::builder()
+            .connect_timeout(self.connect)
+            .timeout(self.request)
+            .build()
+            .expect("Failed to build HTTP client")
+    }
+}
+
+/// Map a failed upstream call to 504 when it timed out, else 502
+pub fn upstream_error(what: &str, e: reqwest::Error) -> actix_web::Error {
+    if e.is_timeout() {
+        tracing::warn!("{} timed out: {}", what, e);
+        actix_web::error::ErrorGatewayTimeout(format!("{} timed out", what))
+    } else {
+        actix_web::error::ErrorBadGateway(format!("{} failed: {}", what, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpTimeouts, upstream_error};
+    use actix_web::http::StatusCode;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_slow_upstream_times_out_with_504() {
+        // Accepts connections but never answers, like a hung upstream
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let client = HttpTimeouts {
+            connect: Duration::from_secs(1),
+            request: Duration::from_millis(100),
+        }
+        .build_client();
+
+        let e = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.get(format!("http://{}/quote", addr)).send(),
+        )
+        .await
+        .expect("request hung past its timeout")
+        .unwrap_err();
+
+        let err = upstream_error("Jupiter request", e);
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::GATEWAY_TIMEOUT
+        );
+    }
+}
@@ -1,35 +1,207 @@
-use actix_web::{App, HttpServer, web};
+use actix_web::{
+    App, HttpServer,
+    middleware::{Compress, Condition, from_fn},
+    web,
+};
+use common::error::json_error_handlers;
+use common::trace::propagate_trace;
 use dotenvy::dotenv;
-use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod auth;
+mod circuit_breaker;
+mod config;
+mod deadline;
+mod http;
+mod rate_limit;
+mod reconcile;
+mod retry;
+mod revocation;
 mod routes;
-use store::Store;
+mod shutdown;
+mod withdrawal;
+use store::{
+    Store, StorePoolConfig,
+    audit::{BalanceAuditSink, FileAuditSink, StdoutAuditSink},
+};
 
+use config::{AppConfig, AuditSinkConfig};
+use deadline::QueryDeadline;
+use http::HttpTimeouts;
+use rate_limit::AuthRateLimits;
+use retry::RetryPolicy;
+use revocation::{RevokedTokens, spawn_sync};
 use routes::*;
+use shutdown::{serve_until, shutdown_signal};
+use withdrawal::spawn_settlement;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
+    tracing_subscriber::fmt().init();
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env");
+    let config = AppConfig::from_env().unwrap_or_else(|e| panic!("{}", e));
 
-    let store = Store::new(&database_url)
+    let pool_config = StorePoolConfig {
+        max_connections: config.db_max_connections,
+        min_connections: config.db_min_connections,
+        ..StorePoolConfig::default()
+    };
+
+    // Containerized deploys often start before the database is accepting connections
+    let retry = RetryPolicy::from_env();
+    let mut store = retry
+        .run("Connecting to database", || {
+            Store::with_options(&config.database_url, pool_config.clone())
+        })
         .await
-        .expect("Failed to connect to database");
+        .expect("Failed to connect to database")
+        .with_min_transfer_amount(config.min_transfer_amount)
+        .with_require_verified_email(config.require_verified_email);
+
+    // Read-only listings and stats go to a replica when DATABASE_REPLICA_URL is set
+    if let Some(replica_url) = &config.database_replica_url {
+        let replica = retry
+            .run("Connecting to read replica", || {
+                pool_config.pool_options().connect(replica_url)
+            })
+            .await
+            .expect("Failed to connect to read replica");
+        store = store.with_read_replica(replica);
+    }
+
+    // Balance audit log: BALANCE_AUDIT_SINK=stdout or BALANCE_AUDIT_SINK=file:<path>
+    if let Some(sink) = &config.balance_audit_sink {
+        let sink: Arc<dyn BalanceAuditSink> = match sink {
+            AuditSinkConfig::File(path) => {
+                Arc::new(FileAuditSink::open(path).expect("Failed to open balance audit file"))
+            }
+            AuditSinkConfig::Stdout => Arc::new(StdoutAuditSink),
+        };
+        store = store.with_audit_sink(sink);
+    }
+
+    retry
+        .run("Running migrations", || store.migrate())
+        .await
+        .expect("Failed to run migrations");
+
+    // Compress large JSON bodies (balances, transaction history)
+    let enable_compression = config.enable_compression;
+    // Oversized JSON bodies are rejected with 413 Payload Too Large
+    let json_limit = config.max_json_body_bytes;
+
+    let bind_addr = config.bind_addr;
+    let config = web::Data::new(config);
+    let store = web::Data::new(store);
+    let server_store = store.clone();
+    // One connection pool with connect/request timeouts, so a hung upstream can't pin a worker
+    let http = HttpTimeouts::from_env().build_client();
+    // Shared across workers so every worker sees the same breaker state
+    let jupiter = web::Data::new(JupiterClient::from_env(http.clone()));
+    let http = web::Data::new(http);
+    let query_deadline = web::Data::new(QueryDeadline::from_config(&config));
 
-    store.migrate().await.expect("Failed to run migrations");
+    // Shared across workers so a client can't multiply its budget by the worker count
+    let auth_limits = web::Data::new(AuthRateLimits::from_env());
 
-    HttpServer::new(|| {
+    // Logouts made on other instances reach this one within TOKEN_REVOCATION_SYNC_SECS
+    let revoked_tokens = web::Data::new(RevokedTokens::default());
+    spawn_sync(
+        revoked_tokens.clone(),
+        store.clone(),
+        Duration::from_secs(config.token_revocation_sync_secs),
+    );
+
+    // Withdrawals sent without a confirmation are debited or failed once the chain knows
+    spawn_settlement(
+        store.clone(),
+        config.solana_rpc_url.clone(),
+        Duration::from_secs(config.withdrawal_settlement_secs),
+    );
+
+    let server = HttpServer::new(move || {
         App::new()
+            .app_data(config.clone())
+            .app_data(server_store.clone())
+            .app_data(http.clone())
+            .app_data(jupiter.clone())
+            .app_data(query_deadline.clone())
+            .app_data(revoked_tokens.clone())
+            .app_data(auth_limits.clone())
+            .app_data(web::JsonConfig::default().limit(json_limit))
+            .wrap(Condition::new(enable_compression, Compress::default()))
+            .wrap(json_error_handlers())
+            .wrap(from_fn(propagate_trace))
             .service(sign_up)
             .service(sign_in)
+            .service(refresh_access_token)
+            .service(logout)
+            .service(request_password_reset)
+            .service(confirm_password_reset)
+            .service(verify_email)
+            .service(resend_email_verification)
             .service(get_user)
             .service(quote)
             .service(swap)
+            .service(decode_swap)
             .service(sol_balance)
             .service(token_balance)
+            .service(node_keyshares)
+            .service(recent_transactions)
+            .service(search_transactions)
+            .service(transaction_status_counts)
+            .service(rotate_agg_pubkey)
+            .service(agg_pubkey_history)
+            .service(resolve_token)
+            .service(deposit_qr)
+            .service(transaction_by_idempotency_key)
+            .service(aggregate_wallet)
+            .service(wallet_tokens)
+            .service(signing_readiness_check)
+            .service(export_wallet)
+            .service(withdraw)
+            .service(balance_reconciliation)
+            .service(readiness)
     })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    // Signals are handled below so the pool can be closed once requests have drained
+    .disable_signals()
+    .bind(bind_addr)?
+    .run();
+
+    serve_until(server, shutdown_signal(), &store).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::middleware::{Compress, Condition};
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse, web};
+
+    #[actix_web::test]
+    async fn test_large_json_is_gzipped_when_enabled() {
+        // A transaction history sized body behind the same middleware main() installs
+        let history = || async { HttpResponse::Ok().json(vec!["confirmed"; 4096]) };
+        for (enabled, expected) in [(true, Some("gzip")), (false, None)] {
+            let app = init_service(
+                App::new()
+                    .wrap(Condition::new(enabled, Compress::default()))
+                    .route("/transactions", web::get().to(history)),
+            )
+            .await;
+
+            let req = TestRequest::get()
+                .uri("/transactions")
+                .insert_header((header::ACCEPT_ENCODING, "gzip"))
+                .to_request();
+            let res = call_service(&app, req).await;
+            let encoding = res
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|value| value.to_str().unwrap());
+            assert_eq!(encoding, expected);
+        }
+    }
 }
@@ -0,0 +1,135 @@
+use actix_web::{HttpRequest, HttpResponse, error::InternalError, http::header};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Past this many tracked keys, buckets that have refilled completely are dropped
+const PRUNE_THRESHOLD: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket per key: `burst` attempts at once, refilling at `per_minute`
+#[derive(Debug)]
+pub struct RateLimiter {
+    burst: f64,
+    refill_per_sec: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, per_minute: u32) -> Self {
+        Self {
+            burst: f64::from(burst.max(1)),
+            refill_per_sec: f64::from(per_minute.max(1)) / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take one token for `key`, or return how long until one is available
+    pub fn check(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.burst);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.refill_per_sec,
+            ))
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.refill_per_sec).min(self.burst)
+    }
+}
+
+/// Limits for routes that verify a password or send email, keyed by client IP and by email
+#[derive(Debug)]
+pub struct AuthRateLimits {
+    pub per_ip: RateLimiter,
+    pub per_email: RateLimiter,
+}
+
+impl AuthRateLimits {
+    /// Read `AUTH_RATE_LIMIT_BURST` (default 5) and `AUTH_RATE_LIMIT_PER_MINUTE` (default 10)
+    pub fn from_env() -> Self {
+        let var = |name: &str, default: u32| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .unwrap_or(default)
+        };
+        let burst = var("AUTH_RATE_LIMIT_BURST", 5);
+        let per_minute = var("AUTH_RATE_LIMIT_PER_MINUTE", 10);
+        Self {
+            per_ip: RateLimiter::new(burst, per_minute),
+            per_email: RateLimiter::new(burst, per_minute),
+        }
+    }
+
+    /// Charge one attempt to the peer IP and to `email`; 429 with `Retry-After` once either runs dry
+    pub fn check(&self, req: &HttpRequest, email: &str) -> Result<(), actix_web::Error> {
+        let now = Instant::now();
+        // The socket address, not X-Forwarded-For, so clients can't pick their own bucket
+        let ip = req
+            .peer_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_default();
+        let email = store::user::normalize_email(email);
+
+        let ip_result = self.per_ip.check(&ip, now);
+        let email_result = self.per_email.check(&email, now);
+        match ip_result.and(email_result) {
+            Ok(()) => Ok(()),
+            Err(retry_after) => {
+                let secs = retry_after.as_secs() + 1;
+                tracing::warn!("Rate limited auth attempt from {} for {}", ip, email);
+                let response = HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, secs.to_string()))
+                    .json(serde_json::json!({
+                        "error": "Too many requests",
+                        "status": 429,
+                    }));
+                Err(InternalError::from_response("Too many requests", response).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_bucket_empties_and_refills() {
+        let limiter = RateLimiter::new(2, 60);
+        let start = Instant::now();
+
+        assert!(limiter.check("ip", start).is_ok());
+        assert!(limiter.check("ip", start).is_ok());
+        let retry_after = limiter.check("ip", start).unwrap_err();
+        assert!(retry_after <= Duration::from_secs(1));
+
+        // Other keys have their own bucket
+        assert!(limiter.check("other", start).is_ok());
+
+        assert!(limiter.check("ip", start + Duration::from_secs(1)).is_ok());
+    }
+}
@@ -0,0 +1,266 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::TokenAccountsFilter};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use store::decimal::{SOL_DECIMALS, format_decimal, serialize_sol};
+use store::stats::{ExpectedBalance, ExpectedTokenBalance};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+use crate::routes::wallet::{SPL_TOKEN_PROGRAM, parse_token_account};
+
+/// A user whose ledger balance disagrees with their wallet on chain
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Discrepancy {
+    pub user_id: Uuid,
+    pub agg_pubkey: String,
+    #[serde(serialize_with = "serialize_sol")]
+    pub expected: Decimal,
+    #[serde(serialize_with = "serialize_sol")]
+    pub actual: Decimal,
+    /// `actual - expected`; negative means custody is short
+    #[serde(serialize_with = "serialize_sol")]
+    pub diff: Decimal,
+}
+
+/// A recorded token balance that disagrees with the wallet's token accounts on chain. Amounts
+/// are formatted with the mint's decimals.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TokenDiscrepancy {
+    pub user_id: Uuid,
+    pub agg_pubkey: String,
+    pub token_mint: String,
+    pub expected: String,
+    pub actual: String,
+    /// `actual - expected`; negative means custody is short
+    pub diff: String,
+}
+
+/// A wallet's token holdings read from chain: mint -> (raw amount summed over accounts, decimals)
+pub type OnChainTokens = HashMap<String, (u64, u8)>;
+
+fn lamports_to_sol(lamports: u64) -> Decimal {
+    Decimal::from_i128_with_scale(i128::from(lamports), SOL_DECIMALS)
+}
+
+/// Compare ledger balances with on-chain lamports keyed by pubkey. Users whose balance could
+/// not be read are skipped rather than reported, since a missing read is not drift.
+pub fn find_discrepancies(
+    expected: &[ExpectedBalance],
+    onchain: &HashMap<String, u64>,
+) -> Vec<Discrepancy> {
+    expected
+        .iter()
+        .filter_map(|balance| {
+            let actual = lamports_to_sol(*onchain.get(&balance.agg_pubkey)?);
+            let diff = actual - balance.balance;
+            (!diff.is_zero()).then(|| Discrepancy {
+                user_id: balance.user_id,
+                agg_pubkey: balance.agg_pubkey.clone(),
+                expected: balance.balance,
+                actual,
+                diff,
+            })
+        })
+        .collect()
+}
+
+/// Compare recorded token balances with on-chain holdings keyed by owner pubkey. Owners whose
+/// token accounts could not be read are skipped; a readable owner without the mint holds zero.
+pub fn find_token_discrepancies(
+    expected: &[ExpectedTokenBalance],
+    onchain: &HashMap<String, OnChainTokens>,
+) -> Vec<TokenDiscrepancy> {
+    expected
+        .iter()
+        .filter_map(|balance| {
+            let holdings = onchain.get(&balance.agg_pubkey)?;
+            let (amount, decimals) = match holdings.get(&balance.token_mint) {
+                Some(&(amount, decimals)) => (amount, u32::from(decimals)),
+                None => (0, balance.balance.scale()),
+            };
+            // A mint with more decimals than `Decimal` can hold can't be compared
+            let actual = Decimal::try_from_i128_with_scale(i128::from(amount), decimals).ok()?;
+            let diff = actual - balance.balance;
+            (!diff.is_zero()).then(|| TokenDiscrepancy {
+                user_id: balance.user_id,
+                agg_pubkey: balance.agg_pubkey.clone(),
+                token_mint: balance.token_mint.clone(),
+                expected: format_decimal(balance.balance, decimals),
+                actual: format_decimal(actual, decimals),
+                diff: format_decimal(diff, decimals),
+            })
+        })
+        .collect()
+}
+
+/// Fetch lamport balances with at most `max_concurrent` RPC calls in flight; failed reads are omitted
+pub async fn fetch_onchain_balances(
+    client: Arc<RpcClient>,
+    pubkeys: Vec<String>,
+    max_concurrent: usize,
+) -> HashMap<String, u64> {
+    let permits = Arc::new(Semaphore::new(max_concurrent.max(1)));
+    let mut reads = JoinSet::new();
+
+    for pubkey in pubkeys {
+        let client = client.clone();
+        let permits = permits.clone();
+        reads.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            let key = Pubkey::from_str(&pubkey).ok()?;
+            match client.get_balance(&key).await {
+                Ok(lamports) => Some((pubkey, lamports)),
+                Err(e) => {
+                    tracing::warn!("Failed to read balance of {}: {}", pubkey, e);
+                    None
+                }
+            }
+        });
+    }
+
+    let mut balances = HashMap::new();
+    while let Some(read) = reads.join_next().await {
+        if let Ok(Some((pubkey, lamports))) = read {
+            balances.insert(pubkey, lamports);
+        }
+    }
+    balances
+}
+
+/// Fetch every owner's SPL token holdings with at most `max_concurrent` RPC calls in flight;
+/// owners whose accounts could not be read are omitted
+pub async fn fetch_onchain_tokens(
+    client: Arc<RpcClient>,
+    owners: Vec<String>,
+    max_concurrent: usize,
+) -> HashMap<String, OnChainTokens> {
+    let permits = Arc::new(Semaphore::new(max_concurrent.max(1)));
+    let mut reads = JoinSet::new();
+
+    for owner in owners {
+        let client = client.clone();
+        let permits = permits.clone();
+        reads.spawn(async move {
+            let _permit = permits.acquire_owned().await.ok()?;
+            let key = Pubkey::from_str(&owner).ok()?;
+            let program = Pubkey::from_str(SPL_TOKEN_PROGRAM).ok()?;
+            let accounts = match client
+                .get_token_accounts_by_owner(&key, TokenAccountsFilter::ProgramId(program))
+                .await
+            {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    tracing::warn!("Failed to read token accounts of {}: {}", owner, e);
+                    return None;
+                }
+            };
+
+            let mut holdings = OnChainTokens::new();
+            for keyed in &accounts {
+                let Some(token) = serde_json::to_value(&keyed.account.data)
+                    .ok()
+                    .and_then(|data| parse_token_account(&data))
+                else {
+                    continue;
+                };
+                let entry = holdings.entry(token.mint).or_insert((0, token.decimals));
+                entry.0 = entry.0.saturating_add(token.amount);
+            }
+            Some((owner, holdings))
+        });
+    }
+
+    let mut tokens = HashMap::new();
+    while let Some(read) = reads.join_next().await {
+        if let Ok(Some((owner, holdings))) = read {
+            tokens.insert(owner, holdings);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnChainTokens, find_discrepancies, find_token_discrepancies};
+    use rust_decimal::Decimal;
+    use std::collections::HashMap;
+    use store::stats::{ExpectedBalance, ExpectedTokenBalance};
+    use uuid::Uuid;
+
+    fn expected(agg_pubkey: &str, balance: Decimal) -> ExpectedBalance {
+        ExpectedBalance {
+            user_id: Uuid::new_v4(),
+            agg_pubkey: agg_pubkey.to_string(),
+            balance,
+        }
+    }
+
+    #[test]
+    fn test_find_discrepancies() {
+        let ledger = vec![
+            expected("in-sync", Decimal::new(15, 1)),
+            expected("short", Decimal::new(2, 0)),
+            expected("unreadable", Decimal::new(1, 0)),
+        ];
+        let onchain = HashMap::from([
+            ("in-sync".to_string(), 1_500_000_000),
+            ("short".to_string(), 1_250_000_000),
+        ]);
+
+        let discrepancies = find_discrepancies(&ledger, &onchain);
+
+        assert_eq!(discrepancies.len(), 1);
+        let short = &discrepancies[0];
+        assert_eq!(short.agg_pubkey, "short");
+        assert_eq!(short.user_id, ledger[1].user_id);
+        assert_eq!(short.actual, Decimal::new(125, 2));
+        assert_eq!(short.diff, Decimal::new(-75, 2));
+    }
+
+    #[test]
+    fn test_surplus_is_a_discrepancy() {
+        let ledger = vec![expected("surplus", Decimal::ZERO)];
+        let onchain = HashMap::from([("surplus".to_string(), 1)]);
+
+        let discrepancies = find_discrepancies(&ledger, &onchain);
+        assert_eq!(discrepancies[0].diff, Decimal::new(1, 9));
+    }
+
+    #[test]
+    fn test_find_token_discrepancies() {
+        let user_id = Uuid::new_v4();
+        let token = |agg_pubkey: &str, token_mint: &str, balance: Decimal| ExpectedTokenBalance {
+            user_id,
+            agg_pubkey: agg_pubkey.to_string(),
+            token_mint: token_mint.to_string(),
+            balance,
+        };
+        let ledger = vec![
+            token("wallet", "in-sync", Decimal::new(25, 1)),
+            token("wallet", "short", Decimal::new(3, 0)),
+            token("wallet", "missing", Decimal::new(1, 0)),
+            token("unreadable", "short", Decimal::new(3, 0)),
+        ];
+        let holdings: OnChainTokens = HashMap::from([
+            ("in-sync".to_string(), (2_500_000, 6)),
+            ("short".to_string(), (1_000_000, 6)),
+        ]);
+        let onchain = HashMap::from([("wallet".to_string(), holdings)]);
+
+        let discrepancies = find_token_discrepancies(&ledger, &onchain);
+
+        assert_eq!(discrepancies.len(), 2);
+        assert_eq!(discrepancies[0].token_mint, "short");
+        assert_eq!(discrepancies[0].actual, "1.000000");
+        assert_eq!(discrepancies[0].diff, "-2.000000");
+        // A mint the wallet no longer holds counts as an empty balance
+        assert_eq!(discrepancies[1].token_mint, "missing");
+        assert_eq!(discrepancies[1].actual, "0");
+        assert_eq!(discrepancies[1].diff, "-1");
+    }
+}
@@ -0,0 +1,112 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Bounded retry with exponential backoff, used at startup to wait for dependencies
+/// such as the database to come up
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Read `DB_CONNECT_ATTEMPTS` (default 10) and `DB_CONNECT_BACKOFF_MS` (default 500)
+    pub fn from_env() -> Self {
+        let attempts = std::env::var("DB_CONNECT_ATTEMPTS")
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(10)
+            .max(1);
+        let backoff_ms = std::env::var("DB_CONNECT_BACKOFF_MS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(500);
+
+        Self {
+            attempts,
+            initial_backoff: Duration::from_millis(backoff_ms),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Run `operation` until it succeeds or the attempts are used up, returning the last error
+    pub async fn run<T, E, F, Fut>(&self, what: &str, mut operation: F) -> Result<T, E>
+    where
+        E: std::fmt::Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.attempts => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        "{} failed (attempt {}/{}): {}, retrying in {:?}",
+                        what,
+                        attempt,
+                        self.attempts,
+                        e,
+                        backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_delayed_start() {
+        // Mock database that only accepts connections from the third attempt on
+        let calls = Cell::new(0);
+        let connect = || {
+            calls.set(calls.get() + 1);
+            let ready = calls.get() >= 3;
+            async move {
+                if ready {
+                    Ok("connected")
+                } else {
+                    Err("connection refused")
+                }
+            }
+        };
+
+        assert_eq!(policy(5).run("connect", connect).await, Ok("connected"));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_attempts() {
+        let calls = Cell::new(0);
+        let result: Result<(), _> = policy(2)
+            .run("connect", || {
+                calls.set(calls.get() + 1);
+                async { Err("connection refused") }
+            })
+            .await;
+
+        assert_eq!(result, Err("connection refused"));
+        assert_eq!(calls.get(), 2);
+    }
+}
@@ -0,0 +1,83 @@
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+use store::{Store, user::StoreError};
+
+/// In-memory copy of the `revoked_tokens` blacklist so the auth extractor never waits on the
+/// database. Logouts on this instance apply immediately; those on other instances show up at
+/// the next sync.
+#[derive(Debug, Default)]
+pub struct RevokedTokens {
+    jtis: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl RevokedTokens {
+    pub fn revoke(&self, jti: &str, expires_at: DateTime<Utc>) {
+        self.jtis
+            .write()
+            .unwrap()
+            .insert(jti.to_string(), expires_at);
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.jtis
+            .read()
+            .unwrap()
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at > Utc::now())
+    }
+
+    /// Purge expired entries from the store, then merge what remains into the cache
+    pub async fn sync(&self, store: &Store) -> Result<(), StoreError> {
+        store.purge_expired_revoked_tokens().await?;
+        let revoked = store.get_revoked_tokens().await?;
+        self.merge(revoked, Utc::now());
+        Ok(())
+    }
+
+    /// The snapshot may predate a `revoke` made on this instance while it was read, so cached
+    /// entries are only dropped once they expire, never because the snapshot lacks them
+    fn merge(&self, snapshot: Vec<(String, DateTime<Utc>)>, now: DateTime<Utc>) {
+        let mut jtis = self.jtis.write().unwrap();
+        jtis.retain(|_, expires_at| *expires_at > now);
+        jtis.extend(snapshot);
+    }
+}
+
+/// Re-sync the blacklist from the store every `every`
+pub fn spawn_sync(revoked: web::Data<RevokedTokens>, store: web::Data<Store>, every: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = revoked.sync(&store).await {
+                tracing::warn!("Failed to sync revoked tokens: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RevokedTokens;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_sync_keeps_local_revocations_missing_from_snapshot() {
+        let now = Utc::now();
+        let revoked = RevokedTokens::default();
+        revoked.revoke("local", now + Duration::minutes(10));
+        revoked.revoke("lapsed", now - Duration::minutes(1));
+
+        revoked.merge(
+            vec![("remote".to_string(), now + Duration::minutes(10))],
+            now,
+        );
+
+        assert!(revoked.is_revoked("local"));
+        assert!(revoked.is_revoked("remote"));
+        assert!(!revoked.jtis.read().unwrap().contains_key("lapsed"));
+    }
+}
@@ -0,0 +1,277 @@
+use actix_web::{HttpResponse, Result, web};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+use store::{
+    Store,
+    user::{StoreError, Transaction, TransactionStatus},
+};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::deadline::QueryDeadline;
+use crate::reconcile::{
+    Discrepancy, TokenDiscrepancy, fetch_onchain_balances, fetch_onchain_tokens,
+    find_discrepancies, find_token_discrepancies,
+};
+use crate::routes::guard::AdminUser;
+use crate::routes::wallet::wallet_network;
+
+const DEFAULT_FEED_SIZE: i64 = 50;
+const MAX_FEED_SIZE: i64 = 500;
+/// Balance reads in flight at once during reconciliation, to stay under RPC rate limits
+const RECONCILE_CONCURRENCY: usize = 8;
+
+#[derive(Deserialize)]
+pub struct RecentTransactionsQuery {
+    pub limit: Option<i64>,
+    pub status: Option<TransactionStatus>,
+}
+
+#[derive(Serialize)]
+pub struct AdminTransactionResponse {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchTransactionsQuery {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TransactionStatusCount {
+    pub status: TransactionStatus,
+    pub count: i64,
+}
+
+#[derive(Serialize)]
+pub struct ReconciliationReport {
+    pub checked: usize,
+    /// Wallets whose on-chain balance could not be read
+    pub unreadable: usize,
+    pub discrepancies: Vec<Discrepancy>,
+    /// Wallets whose token accounts could not be read
+    pub unreadable_tokens: usize,
+    pub token_discrepancies: Vec<TokenDiscrepancy>,
+}
+
+#[derive(Deserialize)]
+pub struct RotateAggPubkeyRequest {
+    pub agg_pubkey: String,
+    pub network: Option<String>, // Defaults to devnet, like wallet aggregation
+}
+
+#[actix_web::get("/admin/transactions")]
+pub async fn recent_transactions(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    deadline: web::Data<QueryDeadline>,
+    query: web::Query<RecentTransactionsQuery>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_FEED_SIZE).clamp(1, MAX_FEED_SIZE);
+
+    let transactions = deadline
+        .run(
+            "Transaction feed",
+            store.get_recent_transactions(limit, query.status),
+        )
+        .await?
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch transactions"))?
+        .into_iter()
+        .map(|(transaction, email)| AdminTransactionResponse { transaction, email })
+        .collect::<Vec<_>>();
+
+    Ok(HttpResponse::Ok().json(transactions))
+}
+
+#[actix_web::get("/admin/transactions/search")]
+pub async fn search_transactions(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    deadline: web::Data<QueryDeadline>,
+    query: web::Query<SearchTransactionsQuery>,
+) -> Result<HttpResponse> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_FEED_SIZE)
+        .clamp(1, MAX_FEED_SIZE);
+
+    let transactions = deadline
+        .run(
+            "Transaction search",
+            store.search_transactions(&query.q, limit),
+        )
+        .await?
+        .map_err(|e| match e {
+            StoreError::InvalidInput(msg) => actix_web::error::ErrorBadRequest(msg),
+            _ => actix_web::error::ErrorInternalServerError("Failed to search transactions"),
+        })?;
+
+    Ok(HttpResponse::Ok().json(transactions))
+}
+
+/// Report every status, with zero for those no transaction is in, so dashboards get a stable shape
+fn status_distribution(counts: Vec<(TransactionStatus, i64)>) -> Vec<TransactionStatusCount> {
+    [
+        TransactionStatus::Pending,
+        TransactionStatus::Confirmed,
+        TransactionStatus::Failed,
+    ]
+    .into_iter()
+    .map(|status| {
+        let count = counts
+            .iter()
+            .filter(|(counted, _)| {
+                std::mem::discriminant(counted) == std::mem::discriminant(&status)
+            })
+            .map(|(_, count)| count)
+            .sum();
+        TransactionStatusCount { status, count }
+    })
+    .collect()
+}
+
+#[actix_web::get("/admin/transaction-status")]
+pub async fn transaction_status_counts(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    deadline: web::Data<QueryDeadline>,
+) -> Result<HttpResponse> {
+    let counts = deadline
+        .run(
+            "Transaction status counts",
+            store.get_transaction_status_counts(),
+        )
+        .await?
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to count transactions"))?;
+
+    Ok(HttpResponse::Ok().json(status_distribution(counts)))
+}
+
+#[actix_web::get("/admin/reconciliation")]
+pub async fn balance_reconciliation(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let expected = store.get_expected_balances().await.map_err(|_| {
+        actix_web::error::ErrorInternalServerError("Failed to fetch expected balances")
+    })?;
+
+    let expected_tokens = store.get_expected_token_balances().await.map_err(|_| {
+        actix_web::error::ErrorInternalServerError("Failed to fetch expected token balances")
+    })?;
+
+    let client = Arc::new(RpcClient::new(config.solana_rpc_url.clone()));
+    let pubkeys = expected
+        .iter()
+        .map(|balance| balance.agg_pubkey.clone())
+        .collect();
+    let onchain = fetch_onchain_balances(client.clone(), pubkeys, RECONCILE_CONCURRENCY).await;
+
+    let mut owners: Vec<String> = expected_tokens
+        .iter()
+        .map(|balance| balance.agg_pubkey.clone())
+        .collect();
+    owners.dedup();
+    let owner_count = owners.len();
+    let onchain_tokens = fetch_onchain_tokens(client, owners, RECONCILE_CONCURRENCY).await;
+
+    Ok(HttpResponse::Ok().json(ReconciliationReport {
+        checked: onchain.len(),
+        unreadable: expected.len() - onchain.len(),
+        discrepancies: find_discrepancies(&expected, &onchain),
+        unreadable_tokens: owner_count - onchain_tokens.len(),
+        token_discrepancies: find_token_discrepancies(&expected_tokens, &onchain_tokens),
+    }))
+}
+
+#[actix_web::put("/admin/users/{id}/agg-pubkey")]
+pub async fn rotate_agg_pubkey(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    path: web::Path<Uuid>,
+    body: web::Json<RotateAggPubkeyRequest>,
+) -> Result<HttpResponse> {
+    Pubkey::from_str(&body.agg_pubkey)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid agg_pubkey: {}", e)))?;
+
+    let user_id = path.into_inner();
+    store
+        .update_user_agg_pubkey(user_id, &body.agg_pubkey)
+        .await
+        .map_err(|e| match e {
+            StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+            _ => actix_web::error::ErrorInternalServerError("Failed to rotate agg_pubkey"),
+        })?;
+    store
+        .set_network_agg_pubkey(
+            user_id,
+            &wallet_network(body.network.as_deref()),
+            &body.agg_pubkey,
+        )
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to rotate agg_pubkey"))?;
+
+    let history = store
+        .get_agg_pubkey_history(user_id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch history"))?;
+
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[actix_web::get("/admin/users/{id}/agg-pubkey/history")]
+pub async fn agg_pubkey_history(
+    _admin: AdminUser,
+    store: web::Data<Store>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let history = store
+        .get_agg_pubkey_history(path.into_inner())
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch history"))?;
+
+    Ok(HttpResponse::Ok().json(history))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::status_distribution;
+    use serde_json::json;
+    use store::user::TransactionStatus;
+
+    #[test]
+    fn test_status_distribution_covers_every_status() {
+        let counts = vec![
+            (TransactionStatus::Confirmed, 7),
+            (TransactionStatus::Pending, 3),
+            (TransactionStatus::Failed, 1),
+        ];
+        assert_eq!(
+            serde_json::to_value(status_distribution(counts)).unwrap(),
+            json!([
+                { "status": "Pending", "count": 3 },
+                { "status": "Confirmed", "count": 7 },
+                { "status": "Failed", "count": 1 },
+            ])
+        );
+
+        // Statuses with no transactions are reported as zero
+        let counts = vec![(TransactionStatus::Pending, 2)];
+        assert_eq!(
+            serde_json::to_value(status_distribution(counts)).unwrap(),
+            json!([
+                { "status": "Pending", "count": 2 },
+                { "status": "Confirmed", "count": 0 },
+                { "status": "Failed", "count": 0 },
+            ])
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use actix_web::{HttpResponse, Result, web};
+use serde::{Deserialize, Serialize};
+use store::{Store, user::StoreError};
+
+use crate::auth::AuthUser;
+
+/// Maximum fractional digits in a SOL amount (lamport precision)
+const MAX_AMOUNT_DECIMALS: usize = 9;
+
+#[derive(Deserialize)]
+pub struct DepositQrQuery {
+    pub amount: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DepositQrResponse {
+    pub uri: String,
+}
+
+/// Build a Solana Pay transfer request URI: `solana:<recipient>?amount=<x>&label=<y>`
+pub fn solana_pay_uri(
+    recipient: &str,
+    amount: Option<&str>,
+    label: Option<&str>,
+) -> Result<String, String> {
+    let mut params = Vec::new();
+
+    if let Some(amount) = amount {
+        validate_amount(amount)?;
+        params.push(format!("amount={}", amount));
+    }
+    if let Some(label) = label {
+        params.push(format!("label={}", urlencoding::encode(label)));
+    }
+
+    if params.is_empty() {
+        Ok(format!("solana:{}", recipient))
+    } else {
+        Ok(format!("solana:{}?{}", recipient, params.join("&")))
+    }
+}
+
+/// Solana Pay amounts are positive decimals without exponents or a leading dot
+fn validate_amount(amount: &str) -> Result<(), String> {
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) {
+        return Err(format!("Invalid amount: {}", amount));
+    }
+    if fraction.len() > MAX_AMOUNT_DECIMALS {
+        return Err(format!(
+            "Amount supports at most {} decimals",
+            MAX_AMOUNT_DECIMALS
+        ));
+    }
+    if amount.chars().all(|c| c == '0' || c == '.') {
+        return Err("Amount must be positive".to_string());
+    }
+
+    Ok(())
+}
+
+#[actix_web::get("/deposit-qr")]
+pub async fn deposit_qr(
+    user: AuthUser,
+    store: web::Data<Store>,
+    query: web::Query<DepositQrQuery>,
+) -> Result<HttpResponse> {
+    let user = store.get_user(user.0).await.map_err(|e| match e {
+        StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+        _ => actix_web::error::ErrorInternalServerError("Failed to fetch user"),
+    })?;
+
+    let agg_pubkey = user
+        .agg_pubkey
+        .ok_or_else(|| actix_web::error::ErrorConflict("Wallet has not been set up yet"))?;
+
+    let uri = solana_pay_uri(&agg_pubkey, query.amount.as_deref(), query.label.as_deref())
+        .map_err(actix_web::error::ErrorBadRequest)?;
+
+    Ok(HttpResponse::Ok().json(DepositQrResponse { uri }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::solana_pay_uri;
+
+    const RECIPIENT: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    #[test]
+    fn test_solana_pay_uri() {
+        assert_eq!(
+            solana_pay_uri(RECIPIENT, Some("1.5"), Some("My Wallet")).unwrap(),
+            format!("solana:{}?amount=1.5&label=My%20Wallet", RECIPIENT)
+        );
+        assert_eq!(
+            solana_pay_uri(RECIPIENT, None, None).unwrap(),
+            format!("solana:{}", RECIPIENT)
+        );
+    }
+
+    #[test]
+    fn test_solana_pay_uri_invalid_amount() {
+        assert!(solana_pay_uri(RECIPIENT, Some("-1"), None).is_err());
+        assert!(solana_pay_uri(RECIPIENT, Some("0"), None).is_err());
+        assert!(solana_pay_uri(RECIPIENT, Some("1e9"), None).is_err());
+        assert!(solana_pay_uri(RECIPIENT, Some("0.0000000001"), None).is_err());
+    }
+}
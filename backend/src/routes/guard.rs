@@ -0,0 +1,170 @@
+use actix_web::{FromRequest, HttpRequest, Result, dev::Payload, web};
+use std::future::Future;
+use std::pin::Pin;
+use store::{
+    Store,
+    user::{StoreError, UserRole},
+};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::config::AppConfig;
+
+/// Check a shared secret header against `expected`. The comparison runs in constant time, so
+/// response timings don't reveal how much of a guess was right.
+fn require_token(req: &HttpRequest, expected: Option<&str>, header: &str) -> Result<()> {
+    let expected =
+        expected.ok_or_else(|| actix_web::error::ErrorForbidden("Access is not configured"))?;
+
+    let provided = req
+        .headers()
+        .get(header)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+
+    if !bool::from(provided.ct_eq(expected.as_bytes())) {
+        return Err(actix_web::error::ErrorForbidden("Access denied"));
+    }
+
+    Ok(())
+}
+
+fn config(req: &HttpRequest) -> Result<&AppConfig> {
+    req.app_data::<web::Data<AppConfig>>()
+        .map(|config| config.get_ref())
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("Access is not configured"))
+}
+
+/// Node operator routes are gated by a shared token passed in `X-Operator-Token`
+pub fn require_operator(req: &HttpRequest) -> Result<()> {
+    require_token(
+        req,
+        config(req)?.operator_token.as_deref(),
+        "X-Operator-Token",
+    )
+}
+
+/// Extractor for admin routes: the shared token in `X-Admin-Token`, plus a bearer token for a
+/// user whose role is `admin`
+pub struct AdminUser(pub Uuid);
+
+impl FromRequest for AdminUser {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let token = config(req)
+            .and_then(|config| require_token(req, config.admin_token.as_deref(), "X-Admin-Token"));
+        let user = AuthUser::from_request(req, payload);
+        let store = req.app_data::<web::Data<Store>>().cloned();
+
+        Box::pin(async move {
+            token?;
+            let AuthUser(user_id) = user.await?;
+            let store = store.ok_or_else(|| {
+                actix_web::error::ErrorInternalServerError("Access is not configured")
+            })?;
+
+            match store.get_user_role(user_id).await {
+                Ok(UserRole::Admin) => Ok(AdminUser(user_id)),
+                Ok(_) | Err(StoreError::UserNotFound) => {
+                    Err(actix_web::error::ErrorForbidden("Access denied"))
+                }
+                Err(_) => Err(actix_web::error::ErrorInternalServerError(
+                    "Failed to check access",
+                )),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdminUser, require_operator};
+    use crate::auth::issue_token;
+    use crate::config::AppConfig;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, HttpResponse, http::StatusCode, web};
+    use sqlx::PgPool;
+    use store::{Store, user::CreateUserRequest};
+
+    fn check(operator_token: Option<&str>, provided: Option<&str>) -> Result<(), StatusCode> {
+        let mut req = TestRequest::default().app_data(web::Data::new(AppConfig {
+            operator_token: operator_token.map(str::to_string),
+            ..AppConfig::for_tests()
+        }));
+        if let Some(provided) = provided {
+            req = req.insert_header(("X-Operator-Token", provided));
+        }
+        require_operator(&req.to_http_request()).map_err(|e| e.as_response_error().status_code())
+    }
+
+    #[test]
+    fn test_operator_token_must_match() {
+        assert_eq!(check(Some("op-s3cret"), Some("op-s3cret")), Ok(()));
+        for provided in [None, Some(""), Some("op-s3cre"), Some("op-s3cret2")] {
+            assert_eq!(
+                check(Some("op-s3cret"), provided),
+                Err(StatusCode::FORBIDDEN)
+            );
+        }
+        // Unconfigured, the routes are closed rather than open to an empty token
+        assert_eq!(check(None, Some("")), Err(StatusCode::FORBIDDEN));
+    }
+
+    #[actix_web::get("/admin/ping")]
+    async fn ping(_admin: AdminUser) -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_admin_routes_need_token_and_admin_role(pool: PgPool) {
+        let store = Store::from_pool(pool.clone());
+        let mut users = Vec::new();
+        for email in ["admin@example.com", "user@example.com"] {
+            let user = store
+                .create_user(CreateUserRequest {
+                    email: email.to_string(),
+                    password: "password123".to_string(),
+                })
+                .await
+                .unwrap();
+            users.push(user.id);
+        }
+        let (admin, user) = (users[0], users[1]);
+        sqlx::query("UPDATE users SET role = 'admin' WHERE id = $1::uuid")
+            .bind(admin.to_string())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let config = AppConfig {
+            admin_token: Some("admin-s3cret".to_string()),
+            ..AppConfig::for_tests()
+        };
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(config.clone()))
+                .service(ping),
+        )
+        .await;
+        let get = |admin_token: &str, user_id| {
+            let bearer = issue_token(user_id, config.jwt_secret.as_bytes(), 3600).unwrap();
+            TestRequest::get()
+                .uri("/admin/ping")
+                .insert_header(("X-Admin-Token", admin_token))
+                .insert_header(("Authorization", format!("Bearer {}", bearer)))
+                .to_request()
+        };
+
+        let res = call_service(&app, get("admin-s3cret", admin)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        // The shared token alone is not enough, nor is an admin's bearer token alone
+        let res = call_service(&app, get("admin-s3cret", user)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = call_service(&app, get("wrong", admin)).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::{HttpResponse, Result, web};
+use reqwest::Client;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::future::Future;
+use std::time::Duration;
+use store::Store;
+
+use crate::config::AppConfig;
+use crate::routes::wallet::mpc_url;
+
+/// Upper bound on each dependency probe, so /ready answers even when a dependency hangs
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize)]
+pub struct ComponentStatus {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool, // Every dependency below is up
+    pub database: ComponentStatus,
+    pub solana_rpc: ComponentStatus,
+    pub mpc: ComponentStatus,
+}
+
+/// Run a probe under `PROBE_TIMEOUT`, turning a timeout into a failed status
+async fn probe(check: impl Future<Output = std::result::Result<(), String>>) -> ComponentStatus {
+    let result = tokio::time::timeout(PROBE_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {:?}", PROBE_TIMEOUT)));
+    ComponentStatus {
+        ok: result.is_ok(),
+        error: result.err(),
+    }
+}
+
+/// Readiness of the whole stack: database, Solana RPC and the MPC server, probed concurrently
+#[actix_web::get("/ready")]
+pub async fn readiness(
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+    http: web::Data<Client>,
+) -> Result<HttpResponse> {
+    let rpc = RpcClient::new_with_timeout(config.solana_rpc_url.clone(), PROBE_TIMEOUT);
+
+    let (database, solana_rpc, mpc) = tokio::join!(
+        probe(async {
+            match store.health_check().await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err("health check query failed".to_string()),
+                Err(e) => Err(e.to_string()),
+            }
+        }),
+        probe(async { rpc.get_health().await.map_err(|e| e.to_string()) }),
+        probe(async {
+            http.get(format!("{}/health", mpc_url()))
+                .timeout(PROBE_TIMEOUT)
+                .send()
+                .await
+                .and_then(|res| res.error_for_status())
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }),
+    );
+
+    let ready = database.ok && solana_rpc.ok && mpc.ok;
+    let response = ReadinessResponse {
+        ready,
+        database,
+        solana_rpc,
+        mpc,
+    };
+    if ready {
+        Ok(HttpResponse::Ok().json(response))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::readiness;
+    use crate::config::AppConfig;
+    use actix_web::{App, http::StatusCode, test, web};
+    use store::Store;
+
+    #[actix_web::test]
+    async fn test_database_down_is_503() {
+        // Nothing listens on port 1, so every probe fails fast
+        let store = Store::for_tests("postgres://127.0.0.1:1/ready_test");
+        let config = AppConfig::for_tests();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::new(reqwest::Client::new()))
+                .service(readiness),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/ready").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: serde_json::Value = test::read_body_json(res).await;
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["database"]["ok"], false);
+        assert!(body["database"]["error"].is_string());
+    }
+}
@@ -1,5 +1,22 @@
+pub mod admin;
+pub mod deposit;
+pub mod guard;
+pub mod health;
+pub mod node;
 pub mod solana;
+pub mod token;
+pub mod transaction;
 pub mod user;
+pub mod wallet;
+pub mod withdrawal;
 
+pub use admin::*;
+pub use deposit::*;
+pub use health::*;
+pub use node::*;
 pub use solana::*;
+pub use token::*;
+pub use transaction::*;
 pub use user::*;
+pub use wallet::*;
+pub use withdrawal::*;
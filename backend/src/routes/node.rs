@@ -0,0 +1,82 @@
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use store::{Store, user::StoreError};
+use uuid::Uuid;
+
+use crate::routes::guard::require_operator;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct NodeKeysharesQuery {
+    pub network: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct NodeKeyshareResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub mpc_node_id: i32,
+    pub public_key: String,
+    pub threshold: i32,
+    pub total_shares: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct NodeKeysharesResponse {
+    pub keyshares: Vec<NodeKeyshareResponse>,
+    pub network: Option<String>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[actix_web::get("/node/{id}/keyshares")]
+pub async fn node_keyshares(
+    req: HttpRequest,
+    store: web::Data<Store>,
+    path: web::Path<i32>,
+    query: web::Query<NodeKeysharesQuery>,
+) -> Result<HttpResponse> {
+    require_operator(&req)?;
+
+    let mpc_node_id = path.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let network = query.network.as_deref().map(str::to_lowercase);
+
+    let (keyshares, total) = store
+        .get_node_keyshares_paged(mpc_node_id, network.as_deref(), limit, offset)
+        .await
+        .map_err(|e| match e {
+            StoreError::InvalidInput(msg) => actix_web::error::ErrorBadRequest(msg),
+            _ => actix_web::error::ErrorInternalServerError("Failed to fetch keyshares"),
+        })?;
+
+    // Never expose the private key share, only public metadata
+    let keyshares = keyshares
+        .into_iter()
+        .map(|keyshare| NodeKeyshareResponse {
+            id: keyshare.id,
+            user_id: keyshare.user_id,
+            mpc_node_id: keyshare.mpc_node_id,
+            public_key: keyshare.public_key,
+            threshold: keyshare.threshold,
+            total_shares: keyshare.total_shares,
+            created_at: keyshare.created_at,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(NodeKeysharesResponse {
+        keyshares,
+        network,
+        total,
+        limit,
+        offset,
+    }))
+}
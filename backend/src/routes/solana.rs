@@ -1,22 +1,117 @@
-use actix_web::{HttpResponse, Result, web};
+use crate::auth::AuthUser;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::config::AppConfig;
+use crate::http::upstream_error;
+use crate::routes::wallet::parse_token_account;
+use actix_web::{HttpResponse, ResponseError, Result, http::StatusCode, web};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
 use reqwest::Client;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use solana_client::{
+    client_error::ClientError, nonblocking::rpc_client::RpcClient, rpc_client::TokenAccountsFilter,
+};
 use solana_sdk::{
     pubkey::Pubkey,
     signature::{Keypair, Signer},
     system_instruction,
-    transaction::Transaction,
+    transaction::{Transaction, VersionedTransaction},
 };
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use store::decimal::format_decimal;
 
-const RPC_URL: &str = "https://api.mainnet-beta.solana.com";
 const JUP_QUOTE_API: &str = "https://quote-api.jup.ag/v6/quote";
 const JUP_SWAP_API: &str = "https://quote-api.jup.ag/v6/swap";
+const DEFAULT_SLIPPAGE_BPS: u64 = 50;
+/// 10_000 bps is 100%; anything above can't be a meaningful tolerance
+const MAX_SLIPPAGE_BPS: u64 = 10_000;
+const SWAP_MODES: [&str; 2] = ["ExactIn", "ExactOut"];
+
+/// Shared Jupiter HTTP client; requests fail fast with 503 while the breaker is open
+pub struct JupiterClient {
+    client: Client,
+    breaker: CircuitBreaker,
+}
+
+impl JupiterClient {
+    /// Wrap the shared HTTP client; configure the breaker via `JUPITER_FAILURE_THRESHOLD`
+    /// (default 5) and `JUPITER_COOLDOWN_SECS` (default 30)
+    pub fn from_env(client: Client) -> Self {
+        let failure_threshold = std::env::var("JUPITER_FAILURE_THRESHOLD")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(5);
+        let cooldown_secs = std::env::var("JUPITER_COOLDOWN_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(30);
+
+        Self {
+            client,
+            breaker: CircuitBreaker::new(failure_threshold, Duration::from_secs(cooldown_secs)),
+        }
+    }
+
+    async fn send_json(&self, request: reqwest::RequestBuilder) -> Result<serde_json::Value> {
+        // Dropped unsettled if this future is cancelled, which counts as a failure
+        let Some(permit) = self.breaker.allow(Instant::now()) else {
+            return Err(actix_web::error::ErrorServiceUnavailable(
+                "Jupiter is unavailable, try again later",
+            ));
+        };
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                permit.failure(Instant::now());
+                return Err(upstream_error("Jupiter request", e));
+            }
+        };
+        let status = response.status();
+        let body = response.json::<serde_json::Value>().await.ok();
+
+        // A 4xx is Jupiter rejecting our input, not Jupiter being down
+        if status.is_success() || status.is_client_error() {
+            permit.success();
+        } else {
+            permit.failure(Instant::now());
+        }
+        match body {
+            Some(value) if status.is_success() => Ok(value),
+            body => Err(jupiter_error(status, body.as_ref())),
+        }
+    }
+}
+
+/// Map a failed Jupiter call to a client error when Jupiter rejected the request, else a 502,
+/// carrying Jupiter's own `error` message when it sent one
+fn jupiter_error(
+    status: reqwest::StatusCode,
+    body: Option<&serde_json::Value>,
+) -> actix_web::Error {
+    let reason = body
+        .and_then(|body| body.get("error"))
+        .and_then(|error| error.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| status.to_string());
+
+    if status.is_client_error() {
+        actix_web::error::ErrorBadRequest(format!("Jupiter rejected the request: {}", reason))
+    } else {
+        actix_web::error::ErrorBadGateway(format!("Jupiter request failed: {}", reason))
+    }
+}
 
 #[derive(Deserialize)]
 pub struct QuoteRequest {
     pub input_mint: String,
     pub output_mint: String,
     pub amount: u64,
+    pub slippage_bps: Option<u64>,
+    pub swap_mode: Option<String>, // "ExactIn" (default) or "ExactOut"
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,12 +128,148 @@ pub struct SwapRequest {
     pub input_mint: String,
     pub output_mint: String,
     pub amount: u64,
+    pub slippage_bps: Option<u64>,
+    pub swap_mode: Option<String>, // "ExactIn" (default) or "ExactOut"
     pub user_pubkey: String,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 pub struct SwapResponse {
-    pub txid: String,
+    pub swap_transaction: String, // Base64 encoded, unsigned transaction built by Jupiter
+    pub last_valid_block_height: u64,
+    pub fee_payer: String, // Must sign first; the user's wallet for a Jupiter swap
+    pub required_signatures: u8,
+}
+
+#[derive(Deserialize)]
+pub struct DecodeSwapRequest {
+    pub swap_transaction: String, // Base64 encoded, as returned by /swap
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodedInstruction {
+    pub program_id: String,
+    pub program: Option<&'static str>, // None for programs outside KNOWN_SWAP_PROGRAMS
+    pub account_count: usize,
+    pub data_len: usize,
+    pub unexpected: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DecodeSwapResponse {
+    pub program_ids: Vec<String>,
+    pub instructions: Vec<DecodedInstruction>,
+    pub has_unexpected_programs: bool,
+}
+
+/// Programs a Jupiter swap is expected to invoke; anything else is flagged
+const KNOWN_SWAP_PROGRAMS: &[(&str, &str)] = &[
+    (
+        "JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTo8WFzyXiTe",
+        "Jupiter Aggregator v6",
+    ),
+    (
+        "ComputeBudget111111111111111111111111111111",
+        "Compute Budget",
+    ),
+    ("11111111111111111111111111111111", "System Program"),
+    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "SPL Token"),
+    (
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        "SPL Token-2022",
+    ),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        "Associated Token Account",
+    ),
+    ("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr", "Memo"),
+];
+
+/// Failures of the chain-facing routes: bad client input is a 400, a failing RPC node a 502
+#[derive(Debug)]
+pub enum SolanaRouteError {
+    InvalidPubkey {
+        field: &'static str,
+        value: String,
+    },
+    SameMint(Pubkey),
+    /// The mint reports more decimals than a `Decimal` can scale to
+    UnsupportedDecimals {
+        mint: Pubkey,
+        decimals: u8,
+    },
+    Rpc(ClientError),
+}
+
+impl Display for SolanaRouteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SolanaRouteError::InvalidPubkey { field, value } => {
+                write!(f, "Invalid {}: {:?} is not a base58 pubkey", field, value)
+            }
+            SolanaRouteError::SameMint(mint) => {
+                write!(f, "input_mint and output_mint are both {}", mint)
+            }
+            SolanaRouteError::UnsupportedDecimals { mint, decimals } => write!(
+                f,
+                "Mint {} has {} decimals; at most {} are supported",
+                mint,
+                decimals,
+                Decimal::MAX_SCALE
+            ),
+            SolanaRouteError::Rpc(e) => write!(f, "Solana RPC request failed: {}", e),
+        }
+    }
+}
+
+impl ResponseError for SolanaRouteError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SolanaRouteError::InvalidPubkey { .. } | SolanaRouteError::SameMint(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            SolanaRouteError::UnsupportedDecimals { .. } | SolanaRouteError::Rpc(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+        }
+    }
+}
+
+impl From<ClientError> for SolanaRouteError {
+    fn from(e: ClientError) -> Self {
+        SolanaRouteError::Rpc(e)
+    }
+}
+
+fn parse_pubkey(field: &'static str, value: &str) -> std::result::Result<Pubkey, SolanaRouteError> {
+    Pubkey::from_str(value).map_err(|_| SolanaRouteError::InvalidPubkey {
+        field,
+        value: value.to_string(),
+    })
+}
+
+/// Validate a swap's mints before they reach the Jupiter URL
+fn parse_mint_pair(
+    input_mint: &str,
+    output_mint: &str,
+) -> std::result::Result<(Pubkey, Pubkey), SolanaRouteError> {
+    let input = parse_pubkey("input_mint", input_mint)?;
+    let output = parse_pubkey("output_mint", output_mint)?;
+    if input == output {
+        return Err(SolanaRouteError::SameMint(input));
+    }
+    Ok((input, output))
+}
+
+/// Scale a raw token amount by the mint's decimals for display
+fn ui_token_amount(
+    mint: Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> std::result::Result<String, SolanaRouteError> {
+    let scaled = Decimal::try_from_i128_with_scale(i128::from(amount), u32::from(decimals))
+        .map_err(|_| SolanaRouteError::UnsupportedDecimals { mint, decimals })?;
+    Ok(format_decimal(scaled, u32::from(decimals)))
 }
 
 #[derive(Serialize)]
@@ -48,93 +279,550 @@ pub struct BalanceResponse {
 
 #[derive(Serialize)]
 pub struct TokenBalanceResponse {
-    pub balance: u64,
+    pub balance: u64, // Raw base units summed across the owner's token accounts
+    pub decimals: u8,
+    pub ui_balance: String, // `balance` scaled by `decimals`
+}
+
+/// Byte range of the little-endian `amount` field in an SPL token account
+const TOKEN_AMOUNT_RANGE: std::ops::Range<usize> = 64..72;
+
+/// Read the `amount` field from raw SPL token account data
+fn decode_token_amount(data: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(TOKEN_AMOUNT_RANGE)?.try_into().ok()?,
+    ))
+}
+
+/// Amount held by one token account, plus its decimals when the node returned jsonParsed data
+fn token_account_amount(data: &serde_json::Value) -> Option<(u64, Option<u8>)> {
+    if let Some(token) = parse_token_account(data) {
+        return Some((token.amount, Some(token.decimals)));
+    }
+
+    // Binary encodings come back as `[data, "base64"]`
+    let [encoded, encoding] = data.as_array()?.as_slice() else {
+        return None;
+    };
+    if encoding.as_str()? != "base64" {
+        return None;
+    }
+    let bytes = STANDARD.decode(encoded.as_str()?).ok()?;
+    decode_token_amount(&bytes).map(|amount| (amount, None))
+}
+
+/// Build the Jupiter quote URL, defaulting to 50 bps slippage and ExactIn
+fn quote_url(
+    input_mint: &str,
+    output_mint: &str,
+    amount: u64,
+    slippage_bps: Option<u64>,
+    swap_mode: Option<&str>,
+) -> Result<String> {
+    let slippage_bps = slippage_bps.unwrap_or(DEFAULT_SLIPPAGE_BPS);
+    if slippage_bps > MAX_SLIPPAGE_BPS {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "slippage_bps must be at most {}",
+            MAX_SLIPPAGE_BPS
+        )));
+    }
+    let swap_mode = swap_mode.unwrap_or(SWAP_MODES[0]);
+    if !SWAP_MODES.contains(&swap_mode) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "swap_mode must be ExactIn or ExactOut",
+        ));
+    }
+
+    Ok(format!(
+        "{}?inputMint={}&outputMint={}&amount={}&slippageBps={}&swapMode={}",
+        JUP_QUOTE_API, input_mint, output_mint, amount, slippage_bps, swap_mode
+    ))
 }
 
 #[actix_web::post("/quote")]
-pub async fn quote(req: web::Json<QuoteRequest>) -> Result<HttpResponse> {
-    let client = Client::new();
-    let url = format!(
-        "{}?inputMint={}&outputMint={}&amount={}&slippageBps=50",
-        JUP_QUOTE_API, req.input_mint, req.output_mint, req.amount
-    );
-
-    let res = client
-        .get(&url)
-        .send()
-        .await
-        .unwrap()
-        .json::<serde_json::Value>()
-        .await
-        .unwrap();
+pub async fn quote(
+    _user: AuthUser,
+    jupiter: web::Data<JupiterClient>,
+    req: web::Json<QuoteRequest>,
+) -> Result<HttpResponse> {
+    parse_mint_pair(&req.input_mint, &req.output_mint)?;
+
+    let url = quote_url(
+        &req.input_mint,
+        &req.output_mint,
+        req.amount,
+        req.slippage_bps,
+        req.swap_mode.as_deref(),
+    )?;
+
+    let res = jupiter.send_json(jupiter.client.get(&url)).await?;
     Ok(HttpResponse::Ok().json(res))
 }
 
 #[actix_web::post("/swap")]
-pub async fn swap(req: web::Json<SwapRequest>) -> Result<HttpResponse> {
-    let client = Client::new();
+pub async fn swap(
+    _user: AuthUser,
+    jupiter: web::Data<JupiterClient>,
+    req: web::Json<SwapRequest>,
+) -> Result<HttpResponse> {
+    parse_mint_pair(&req.input_mint, &req.output_mint)?;
+    parse_pubkey("user_pubkey", &req.user_pubkey)?;
 
     // Step 1: Fetch best route from Jupiter
-    let quote_url = format!(
-        "{}?inputMint={}&outputMint={}&amount={}&slippageBps=50",
-        JUP_QUOTE_API, req.input_mint, req.output_mint, req.amount
-    );
-    let quote_res = client
-        .get(&quote_url)
-        .send()
-        .await
-        .unwrap()
-        .json::<serde_json::Value>()
-        .await
-        .unwrap();
+    let quote_url = quote_url(
+        &req.input_mint,
+        &req.output_mint,
+        req.amount,
+        req.slippage_bps,
+        req.swap_mode.as_deref(),
+    )?;
+    let quote_res = jupiter.send_json(jupiter.client.get(&quote_url)).await?;
 
     // Step 2: Ask Jupiter to build the transaction
-    let swap_tx = client
-        .post(JUP_SWAP_API)
-        .json(&serde_json::json!({
+    let swap_tx = jupiter
+        .send_json(jupiter.client.post(JUP_SWAP_API).json(&serde_json::json!({
             "userPublicKey": req.user_pubkey,
             "quoteResponse": quote_res,
             "wrapAndUnwrapSol": true
-        }))
-        .send()
-        .await
-        .unwrap()
-        .json::<serde_json::Value>()
-        .await
-        .unwrap();
+        })))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(extract_swap_transaction(&swap_tx)?))
+}
+
+/// Decode a base64, bincode-serialized versioned transaction
+fn decode_versioned_transaction(encoded: &str) -> Option<VersionedTransaction> {
+    let bytes = STANDARD.decode(encoded).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
 
-    Ok(HttpResponse::Ok().json(swap_tx))
+/// Pull the fields clients need out of Jupiter's swap response, checking the transaction decodes
+fn extract_swap_transaction(swap_tx: &serde_json::Value) -> Result<SwapResponse> {
+    let swap_transaction = swap_tx
+        .get("swapTransaction")
+        .and_then(|value| value.as_str())
+        .ok_or_else(|| {
+            actix_web::error::ErrorBadGateway("Jupiter response is missing swapTransaction")
+        })?;
+
+    let last_valid_block_height = swap_tx
+        .get("lastValidBlockHeight")
+        .and_then(|value| value.as_u64())
+        .ok_or_else(|| {
+            actix_web::error::ErrorBadGateway("Jupiter response is missing lastValidBlockHeight")
+        })?;
+
+    let transaction = decode_versioned_transaction(swap_transaction).ok_or_else(|| {
+        actix_web::error::ErrorBadGateway("Jupiter returned an undecodable swapTransaction")
+    })?;
+    let header = transaction.message.header();
+    let fee_payer = transaction
+        .message
+        .static_account_keys()
+        .first()
+        .ok_or_else(|| actix_web::error::ErrorBadGateway("Jupiter swap has no fee payer"))?
+        .to_string();
+
+    Ok(SwapResponse {
+        swap_transaction: swap_transaction.to_string(),
+        last_valid_block_height,
+        fee_payer,
+        required_signatures: header.num_required_signatures,
+    })
+}
+
+#[actix_web::post("/swap/decode")]
+pub async fn decode_swap(req: web::Json<DecodeSwapRequest>) -> Result<HttpResponse> {
+    Ok(HttpResponse::Ok().json(decode_swap_transaction(&req.swap_transaction)?))
+}
+
+/// Summarise the instructions of a base64 swap transaction without signing or sending it
+fn decode_swap_transaction(swap_transaction: &str) -> Result<DecodeSwapResponse> {
+    let transaction = decode_versioned_transaction(swap_transaction).ok_or_else(|| {
+        actix_web::error::ErrorBadRequest("swap_transaction is not a base64 transaction")
+    })?;
+
+    // Program ids always live in the static keys, even when accounts come from lookup tables
+    let account_keys = transaction.message.static_account_keys();
+    let mut program_ids: Vec<String> = Vec::new();
+    let mut instructions = Vec::new();
+
+    for instruction in transaction.message.instructions() {
+        let program_id = account_keys
+            .get(instruction.program_id_index as usize)
+            .ok_or_else(|| {
+                actix_web::error::ErrorBadRequest("Instruction program id out of range")
+            })?
+            .to_string();
+        let program = KNOWN_SWAP_PROGRAMS
+            .iter()
+            .find(|(id, _)| *id == program_id)
+            .map(|(_, name)| *name);
+
+        if !program_ids.contains(&program_id) {
+            program_ids.push(program_id.clone());
+        }
+        instructions.push(DecodedInstruction {
+            program_id,
+            program,
+            account_count: instruction.accounts.len(),
+            data_len: instruction.data.len(),
+            unexpected: program.is_none(),
+        });
+    }
+
+    Ok(DecodeSwapResponse {
+        has_unexpected_programs: instructions.iter().any(|ix| ix.unexpected),
+        program_ids,
+        instructions,
+    })
 }
 
 #[actix_web::get("/sol-balance/{pubkey}")]
-pub async fn sol_balance() -> Result<HttpResponse> {
-    let client = RpcClient::new(RPC_URL.to_string());
-    let pubkey = Pubkey::from_str(&path.into_inner()).unwrap();
-    let balance = client.get_balance(&pubkey).unwrap();
+pub async fn sol_balance(
+    _user: AuthUser,
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> std::result::Result<HttpResponse, SolanaRouteError> {
+    let pubkey = parse_pubkey("pubkey", &path.into_inner())?;
+    let client = RpcClient::new(config.solana_rpc_url.clone());
+    let balance = client.get_balance(&pubkey).await?;
     Ok(HttpResponse::Ok().json(BalanceResponse { balance }))
 }
 
 #[actix_web::get("/token-balance/{pubkey}/{mint}")]
-pub async fn token_balance() -> Result<HttpResponse> {
-    let client = RpcClient::new(RPC_URL.to_string());
+pub async fn token_balance(
+    _user: AuthUser,
+    config: web::Data<AppConfig>,
+    path: web::Path<(String, String)>,
+) -> std::result::Result<HttpResponse, SolanaRouteError> {
     let (pubkey_str, mint_str) = path.into_inner();
-    let pubkey = Pubkey::from_str(&pubkey_str).unwrap();
-    let mint = Pubkey::from_str(&mint_str).unwrap();
+    let pubkey = parse_pubkey("pubkey", &pubkey_str)?;
+    let mint = parse_pubkey("mint", &mint_str)?;
 
+    let client = RpcClient::new(config.solana_rpc_url.clone());
     let balances = client
-        .get_token_accounts_by_owner(
-            &pubkey,
-            solana_client::rpc_client::TokenAccountsFilter::Mint(mint),
-        )
-        .unwrap();
+        .get_token_accounts_by_owner(&pubkey, TokenAccountsFilter::Mint(mint))
+        .await?;
 
-    let balance = if let Some(account) = balances.value.first() {
-        let data = &account.account.data;
-        // decode SPL Token account data here...
-        0u64
-    } else {
-        0u64
+    // Sum across every token account the owner holds for this mint; none at all is a zero balance
+    let mut balance = 0u64;
+    let mut decimals = None;
+    for keyed in &balances {
+        let Some((amount, account_decimals)) = serde_json::to_value(&keyed.account.data)
+            .ok()
+            .and_then(|data| token_account_amount(&data))
+        else {
+            continue;
+        };
+        balance = balance.saturating_add(amount);
+        decimals = decimals.or(account_decimals);
+    }
+
+    // Binary account data carries no decimals; those live on the mint
+    let decimals = match decimals {
+        Some(decimals) => decimals,
+        None => client.get_token_supply(&mint).await?.decimals,
     };
 
-    Ok(HttpResponse::Ok().json(TokenBalanceResponse { balance }))
+    Ok(HttpResponse::Ok().json(TokenBalanceResponse {
+        balance,
+        decimals,
+        ui_balance: ui_token_amount(mint, balance, decimals)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        SolanaRouteError, decode_swap_transaction, decode_token_amount, extract_swap_transaction,
+        jupiter_error, parse_mint_pair, parse_pubkey, quote_url, sol_balance, token_account_amount,
+        token_balance, ui_token_amount,
+    };
+    use crate::auth::issue_token;
+    use crate::config::AppConfig;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, ResponseError, web};
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::{VersionedMessage, v0},
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::VersionedTransaction,
+    };
+    use std::str::FromStr;
+
+    /// A v0 transaction shaped like Jupiter's swap output: compute budget, then the route
+    fn recorded_swap_transaction(extra: Option<Instruction>) -> String {
+        let payer = Pubkey::new_unique();
+        let jupiter = Pubkey::from_str("JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTo8WFzyXiTe").unwrap();
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(200_000),
+            ComputeBudgetInstruction::set_compute_unit_price(5_000),
+            Instruction::new_with_bytes(
+                jupiter,
+                &[229, 23, 203, 151, 122, 227, 173, 42],
+                vec![
+                    AccountMeta::new(payer, true),
+                    AccountMeta::new(Pubkey::new_unique(), false),
+                ],
+            ),
+        ];
+        instructions.extend(extra);
+
+        let message =
+            v0::Message::try_compile(&payer, &instructions, &[], Hash::default()).unwrap();
+        let transaction = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::V0(message),
+        };
+        STANDARD.encode(bincode::serialize(&transaction).unwrap())
+    }
+
+    #[test]
+    fn test_extract_swap_transaction() {
+        // Mocked Jupiter /swap response
+        let encoded = recorded_swap_transaction(None);
+        let swap_tx = serde_json::json!({
+            "swapTransaction": encoded,
+            "lastValidBlockHeight": 279_632_475u64,
+        });
+
+        let response = extract_swap_transaction(&swap_tx).unwrap();
+        assert_eq!(response.swap_transaction, encoded);
+        assert_eq!(response.last_valid_block_height, 279_632_475);
+        assert_eq!(response.required_signatures, 1);
+        assert!(Pubkey::from_str(&response.fee_payer).is_ok());
+    }
+
+    #[test]
+    fn test_extract_swap_transaction_rejects_undecodable_base64() {
+        let swap_tx = serde_json::json!({
+            "swapTransaction": "AQAAAA==",
+            "lastValidBlockHeight": 279_632_475u64,
+        });
+
+        let err = extract_swap_transaction(&swap_tx).unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn test_jupiter_error_payloads_map_to_status() {
+        let body = serde_json::json!({ "error": "Could not find any route" });
+
+        let err = jupiter_error(reqwest::StatusCode::BAD_REQUEST, Some(&body));
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(err.to_string().contains("Could not find any route"));
+
+        let err = jupiter_error(reqwest::StatusCode::SERVICE_UNAVAILABLE, None);
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn test_decode_swap_transaction() {
+        let decoded = decode_swap_transaction(&recorded_swap_transaction(None)).unwrap();
+
+        assert_eq!(
+            decoded.program_ids,
+            vec![
+                "ComputeBudget111111111111111111111111111111",
+                "JUP6LkbZbjS1jKKwapdHNy74zcZ3tQVkTo8WFzyXiTe",
+            ]
+        );
+        assert_eq!(decoded.instructions.len(), 3);
+        assert_eq!(
+            decoded.instructions[2].program,
+            Some("Jupiter Aggregator v6")
+        );
+        assert_eq!(decoded.instructions[2].account_count, 2);
+        assert!(!decoded.has_unexpected_programs);
+    }
+
+    #[test]
+    fn test_decode_swap_transaction_flags_unexpected_program() {
+        let rogue = Instruction::new_with_bytes(Pubkey::new_unique(), &[1], vec![]);
+        let decoded = decode_swap_transaction(&recorded_swap_transaction(Some(rogue))).unwrap();
+
+        assert!(decoded.has_unexpected_programs);
+        assert!(decoded.instructions[3].unexpected);
+        assert_eq!(decoded.instructions[3].program, None);
+    }
+
+    #[test]
+    fn test_decode_swap_transaction_rejects_garbage() {
+        let err = decode_swap_transaction("not base64!").unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn test_extract_swap_transaction_missing_field() {
+        let swap_tx = serde_json::json!({ "error": "Route not found" });
+
+        let err = extract_swap_transaction(&swap_tx).unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_GATEWAY
+        );
+    }
+
+    #[test]
+    fn test_invalid_pubkey_is_bad_request() {
+        let err = parse_pubkey("pubkey", "not-a-pubkey").unwrap_err();
+        assert!(matches!(
+            err,
+            SolanaRouteError::InvalidPubkey {
+                field: "pubkey",
+                ..
+            }
+        ));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("not-a-pubkey"));
+
+        assert!(parse_pubkey("mint", "So11111111111111111111111111111111111111112").is_ok());
+    }
+
+    #[test]
+    fn test_ui_token_amount_rejects_unscalable_decimals() {
+        let mint = Pubkey::new_unique();
+        assert_eq!(ui_token_amount(mint, 1_500_000, 6).unwrap(), "1.500000");
+
+        let err = ui_token_amount(mint, 1, 29).unwrap_err();
+        assert!(matches!(
+            err,
+            SolanaRouteError::UnsupportedDecimals { decimals: 29, .. }
+        ));
+        assert_eq!(err.status_code(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn test_mint_pair_validation() {
+        let sol = "So11111111111111111111111111111111111111112";
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+        let err = parse_mint_pair(sol, "EPjFWdd5-typo").unwrap_err();
+        assert!(matches!(
+            err,
+            SolanaRouteError::InvalidPubkey {
+                field: "output_mint",
+                ..
+            }
+        ));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let err = parse_mint_pair(sol, sol).unwrap_err();
+        assert!(matches!(err, SolanaRouteError::SameMint(_)));
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        assert!(parse_mint_pair(sol, usdc).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_balance_routes_reject_invalid_pubkey_segments() {
+        // Its RPC url is never reached: the pubkey is rejected before any RPC call
+        let config = AppConfig::for_tests();
+        let token = issue_token(uuid::Uuid::new_v4(), b"test-secret", 3600).unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(config))
+                .service(sol_balance)
+                .service(token_balance),
+        )
+        .await;
+
+        for uri in [
+            "/sol-balance/not-a-pubkey",
+            "/token-balance/not-a-pubkey/So11111111111111111111111111111111111111112",
+            "/token-balance/So11111111111111111111111111111111111111112/bad-mint",
+        ] {
+            let req = TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    /// A 165-byte SPL token account blob: mint, owner, then the amount at offset 64
+    fn token_account_blob(amount: u64) -> Vec<u8> {
+        let mut data = vec![0u8; 165];
+        data[0..32].copy_from_slice(Pubkey::new_unique().as_ref());
+        data[32..64].copy_from_slice(Pubkey::new_unique().as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[108] = 1; // Initialized
+        data
+    }
+
+    #[test]
+    fn test_decode_token_amount() {
+        assert_eq!(
+            decode_token_amount(&token_account_blob(1_234_567)),
+            Some(1_234_567)
+        );
+        assert_eq!(decode_token_amount(&[0u8; 70]), None);
+    }
+
+    #[test]
+    fn test_token_account_amount_from_base64_and_parsed_data() {
+        let encoded = STANDARD.encode(token_account_blob(42_000_000));
+        assert_eq!(
+            token_account_amount(&serde_json::json!([encoded, "base64"])),
+            Some((42_000_000, None))
+        );
+
+        let parsed = serde_json::json!({
+            "program": "spl-token",
+            "parsed": { "info": {
+                "mint": "So11111111111111111111111111111111111111112",
+                "tokenAmount": { "amount": "2500", "decimals": 9 }
+            }}
+        });
+        assert_eq!(token_account_amount(&parsed), Some((2500, Some(9))));
+
+        assert_eq!(
+            token_account_amount(&serde_json::json!(["abc", "base58"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_quote_url_overrides_slippage_and_mode() {
+        let url = quote_url("in", "out", 1_000, Some(125), Some("ExactOut")).unwrap();
+        assert!(url.contains("slippageBps=125"));
+        assert!(url.contains("swapMode=ExactOut"));
+
+        let url = quote_url("in", "out", 1_000, None, None).unwrap();
+        assert!(url.contains("slippageBps=50"));
+        assert!(url.contains("swapMode=ExactIn"));
+    }
+
+    #[test]
+    fn test_quote_url_rejects_bad_parameters() {
+        for err in [
+            quote_url("in", "out", 1, None, Some("exactin")).unwrap_err(),
+            quote_url("in", "out", 1, Some(10_001), None).unwrap_err(),
+        ] {
+            assert_eq!(
+                err.as_response_error().status_code(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+    }
 }
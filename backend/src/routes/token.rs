@@ -0,0 +1,72 @@
+use actix_web::{HttpResponse, Result, web};
+use serde::Deserialize;
+use store::Store;
+
+#[derive(Deserialize)]
+pub struct ResolveTokenQuery {
+    pub symbol: Option<String>,
+    pub mint: Option<String>,
+}
+
+#[actix_web::get("/token/resolve")]
+pub async fn resolve_token(
+    store: web::Data<Store>,
+    query: web::Query<ResolveTokenQuery>,
+) -> Result<HttpResponse> {
+    let metadata = match (&query.symbol, &query.mint) {
+        (Some(symbol), None) => store.get_token_metadata_by_symbol(symbol).await,
+        (None, Some(mint)) => store.get_token_metadata_by_mint(mint).await,
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Provide exactly one of symbol or mint",
+            ));
+        }
+    }
+    .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to resolve token"))?
+    .ok_or_else(|| actix_web::error::ErrorNotFound("Token not found"))?;
+
+    Ok(HttpResponse::Ok().json(metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_token;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use actix_web::{App, http::StatusCode, web};
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use store::Store;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_resolve_token(pool: PgPool) {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Store::from_pool(pool)))
+                .service(resolve_token),
+        )
+        .await;
+
+        // Symbols match case-insensitively; mints exactly
+        for uri in [
+            "/token/resolve?symbol=usdc".to_string(),
+            format!("/token/resolve?mint={}", USDC),
+        ] {
+            let res = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+            let metadata: Value = read_body_json(res).await;
+            assert_eq!(metadata["mint"], USDC);
+            assert_eq!(metadata["symbol"], "USDC");
+            assert_eq!(metadata["decimals"], 6);
+        }
+
+        for (uri, expected) in [
+            ("/token/resolve?symbol=NOPE", StatusCode::NOT_FOUND),
+            ("/token/resolve", StatusCode::BAD_REQUEST),
+        ] {
+            let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), expected, "{}", uri);
+        }
+    }
+}
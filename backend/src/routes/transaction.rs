@@ -0,0 +1,102 @@
+use actix_web::{HttpResponse, Result, web};
+use store::{Store, transaction::MAX_IDEMPOTENCY_KEY_LEN};
+
+use crate::auth::AuthUser;
+
+#[actix_web::get("/transactions/by-key/{key}")]
+pub async fn transaction_by_idempotency_key(
+    user: AuthUser,
+    store: web::Data<Store>,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let key = path.into_inner();
+    if key.is_empty() || key.chars().count() > MAX_IDEMPOTENCY_KEY_LEN {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Idempotency key must be 1 to {} characters",
+            MAX_IDEMPOTENCY_KEY_LEN
+        )));
+    }
+
+    // Scoped to the caller, so one user can't probe another user's keys
+    let transaction = store
+        .get_transaction_by_idempotency_key(user.0, &key)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch transaction"))?
+        .ok_or_else(|| actix_web::error::ErrorNotFound("Transaction not found"))?;
+
+    Ok(HttpResponse::Ok().json(transaction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::transaction_by_idempotency_key;
+    use crate::auth::issue_token;
+    use crate::config::AppConfig;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use actix_web::{App, http::StatusCode, web};
+    use rust_decimal::Decimal;
+    use serde_json::Value;
+    use sqlx::PgPool;
+    use store::{
+        Store,
+        user::{CreateUserRequest, TransactionType},
+    };
+    use uuid::Uuid;
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_lookup_by_idempotency_key_is_scoped_to_caller(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let mut users = Vec::new();
+        for email in ["alice@example.com", "bob@example.com"] {
+            let user = store
+                .create_user(CreateUserRequest {
+                    email: email.to_string(),
+                    password: "password123".to_string(),
+                })
+                .await
+                .unwrap();
+            users.push(user.id);
+        }
+        let (alice, bob) = (users[0], users[1]);
+        let transaction = store
+            .create_transaction(
+                alice,
+                TransactionType::Deposit,
+                Decimal::ONE,
+                None,
+                None,
+                None,
+                None,
+                Some("order-42"),
+            )
+            .await
+            .unwrap();
+
+        let config = AppConfig::for_tests();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(config.clone()))
+                .service(transaction_by_idempotency_key),
+        )
+        .await;
+        let get = |key: &str, user_id: Uuid| {
+            let token = issue_token(user_id, config.jwt_secret.as_bytes(), 3600).unwrap();
+            TestRequest::get()
+                .uri(&format!("/transactions/by-key/{}", key))
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
+        };
+
+        let res = call_service(&app, get("order-42", alice)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["id"], transaction.id.to_string());
+
+        // Another user's key is indistinguishable from one that was never used
+        let res = call_service(&app, get("order-42", bob)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res = call_service(&app, get("order-43", alice)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
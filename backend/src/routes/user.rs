@@ -1,5 +1,19 @@
-use actix_web::{HttpResponse, Result, web};
+use actix_web::{HttpRequest, HttpResponse, Result, web};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use store::{
+    Store,
+    password_reset::PASSWORD_RESET_TTL,
+    user::{CreateUserRequest, StoreError},
+    verification::EMAIL_VERIFICATION_TTL,
+};
+use uuid::Uuid;
+
+use crate::auth::{AuthClaims, AuthUser, issue_token};
+use crate::config::AppConfig;
+use crate::rate_limit::AuthRateLimits;
+use crate::revocation::RevokedTokens;
 
 #[derive(Deserialize)]
 pub struct SignUpRequest {
@@ -13,16 +27,48 @@ pub struct SignInRequest {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirm {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Serialize)]
+struct TokenDelivery<'a> {
+    /// `verify_email` or `password_reset`
+    purpose: &'a str,
+    email: &'a str,
+    token: &'a str,
+    expires_in_secs: i64,
+}
+
 #[derive(Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
     pub email: String,
-    pub created_at: chrono::DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
 }
 
 #[derive(Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    /// Long-lived opaque token for `POST /token/refresh`; each use returns a replacement
+    pub refresh_token: String,
 }
 
 #[derive(Serialize)]
@@ -30,59 +76,554 @@ pub struct SignupResponse {
     message: String,
 }
 
+/// Duplicate accounts are 409 and validation failures 400; only real DB failures are 500
+fn signup_error(e: StoreError) -> actix_web::Error {
+    match e {
+        StoreError::UserExists => actix_web::error::ErrorConflict("User already exists"),
+        StoreError::InvalidInput(msg) => actix_web::error::ErrorBadRequest(msg),
+        _ => actix_web::error::ErrorInternalServerError("DB insert failed"),
+    }
+}
+
 #[actix_web::post("/signup")]
-pub async fn sign_up(req: web::Json<SignUpRequest>) -> Result<HttpResponse> {
-    let password_hash = argon2::hash_encoded(
-        req.password.as_bytes(),
-        &Uuid::new_v4().as_bytes(),
-        &Config::default(),
-    )
-    .unwrap();
-
-    let user_id = store
-        .create_user(&req.email, &password_hash)
+pub async fn sign_up(
+    store: web::Data<Store>,
+    http: web::Data<Client>,
+    req: web::Json<SignUpRequest>,
+) -> Result<HttpResponse> {
+    let req = req.into_inner();
+    let user = store
+        .create_user(CreateUserRequest {
+            email: req.email,
+            password: req.password,
+        })
         .await
-        .map_err(|_| actix_web::error::ErrorInternalServerError("DB insert failed"))?;
+        .map_err(signup_error)?;
+
+    // The account exists either way; a failed hand-off only delays verification
+    match store.create_email_verification(user.id).await {
+        Ok(token) => spawn_token_delivery(
+            &http,
+            "verify_email",
+            user.email.clone(),
+            token,
+            EMAIL_VERIFICATION_TTL,
+        ),
+        Err(e) => tracing::warn!("Failed to issue verification token for {}: {}", user.id, e),
+    }
 
     Ok(HttpResponse::Created().json(SignupResponse {
-        message: format!("User {} created successfully", req.email),
+        message: format!("User {} created successfully", user.email),
     }))
 }
 
 #[actix_web::post("/signin")]
-pub async fn sign_in(req: web::Json<SignInRequest>) -> Result<HttpResponse> {
-    if let Some(user) = store.find_user_by_email(&req.email).await.unwrap() {
-        if argon2::verify_encoded(&user.password_hash, req.password.as_bytes()).unwrap_or(false) {
-            let claims = Claims {
-                sub: user.id.to_string(),
-                exp: (Utc::now().timestamp() + 3600) as usize,
-            };
-            let token = encode(
-                &Header::default(),
-                &claims,
-                &EncodingKey::from_secret("secret".as_ref()),
-            )
-            .unwrap();
+pub async fn sign_in(
+    http_req: HttpRequest,
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+    limits: web::Data<AuthRateLimits>,
+    req: web::Json<SignInRequest>,
+) -> Result<HttpResponse> {
+    // Each attempt costs a password hash verification, so throttle before touching the store
+    limits.check(&http_req, &req.email)?;
+
+    // Unknown emails and wrong passwords get the same answer so accounts can't be enumerated
+    let user = store
+        .authenticate_user(&req.email, &req.password)
+        .await
+        .map_err(|e| match e {
+            StoreError::UserNotFound | StoreError::InvalidInput(_) => {
+                actix_web::error::ErrorUnauthorized("Invalid credentials")
+            }
+            _ => actix_web::error::ErrorInternalServerError("Failed to sign in"),
+        })?;
+
+    let token = issue_token(user.id, config.jwt_secret.as_bytes(), config.jwt_ttl_secs)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to issue token"))?;
+    let refresh_token = store
+        .issue_refresh_token(user.id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to issue token"))?;
 
-            return Ok(HttpResponse::Ok().json(AuthResponse { token }));
+    Ok(HttpResponse::Ok().json(AuthResponse {
+        token,
+        refresh_token,
+    }))
+}
+
+/// End the session: revoke the refresh token's family so it can't mint new access tokens,
+/// and the presented access token until it would have expired
+#[actix_web::post("/logout")]
+pub async fn logout(
+    auth: AuthClaims,
+    store: web::Data<Store>,
+    revoked: web::Data<RevokedTokens>,
+    req: web::Json<RefreshRequest>,
+) -> Result<HttpResponse> {
+    let claims = auth.0;
+    if claims.jti.is_empty() {
+        return Err(actix_web::error::ErrorBadRequest(
+            "Token predates revocation support; sign in again to get a revocable token",
+        ));
+    }
+    let expires_at = DateTime::from_timestamp(claims.exp as i64, 0)
+        .ok_or_else(|| actix_web::error::ErrorBadRequest("Invalid token expiry"))?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token subject"))?;
+
+    store
+        .revoke_refresh_token_family(user_id, &req.refresh_token)
+        .await
+        .map_err(|e| match e {
+            StoreError::InvalidRefreshToken(_) => {
+                actix_web::error::ErrorUnauthorized("Invalid refresh token")
+            }
+            _ => actix_web::error::ErrorInternalServerError("Failed to log out"),
+        })?;
+
+    store
+        .revoke_token(&claims.jti, expires_at)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to log out"))?;
+    revoked.revoke(&claims.jti, expires_at);
+
+    Ok(HttpResponse::Ok().json(SignupResponse {
+        message: "Logged out".to_string(),
+    }))
+}
+
+/// Rejected refresh tokens are 401 so clients fall back to signing in again
+fn refresh_error(e: StoreError) -> actix_web::Error {
+    match e {
+        StoreError::RefreshTokenReplayed => {
+            tracing::warn!("Refresh token replay detected; token family revoked");
+            actix_web::error::ErrorUnauthorized("Invalid refresh token")
+        }
+        StoreError::InvalidRefreshToken(_) => {
+            actix_web::error::ErrorUnauthorized("Invalid refresh token")
         }
+        _ => actix_web::error::ErrorInternalServerError("Failed to refresh token"),
     }
+}
 
-    Err(actix_web::error::ErrorUnauthorized("Invalid credentials"))
+#[actix_web::post("/token/refresh")]
+pub async fn refresh_access_token(
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+    req: web::Json<RefreshRequest>,
+) -> Result<HttpResponse> {
+    let (user_id, refresh_token) = store
+        .rotate_refresh_token(&req.refresh_token)
+        .await
+        .map_err(refresh_error)?;
+
+    let token = issue_token(user_id, config.jwt_secret.as_bytes(), config.jwt_ttl_secs)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to issue token"))?;
+
+    Ok(HttpResponse::Ok().json(AuthResponse {
+        token,
+        refresh_token,
+    }))
 }
 
 #[actix_web::get("/user/{id}")]
-pub async fn get_user(path: web::Path<u32>) -> Result<HttpResponse> {
+pub async fn get_user(
+    auth: AuthUser,
+    store: web::Data<Store>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
     let user_id = path.into_inner();
+    if user_id != auth.0 {
+        return Err(actix_web::error::ErrorForbidden(
+            "Cannot view another user's profile",
+        ));
+    }
+
+    let user = store.get_user(user_id).await.map_err(|e| match e {
+        StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+        _ => actix_web::error::ErrorInternalServerError("Failed to fetch user"),
+    })?;
+
+    Ok(HttpResponse::Ok().json(UserResponse {
+        id: user.id,
+        email: user.email,
+        created_at: user.created_at,
+    }))
+}
+
+/// Where reset and verification tokens are handed off for delivery (e.g. a mailer),
+/// configurable via `EMAIL_WEBHOOK_URL`
+fn email_webhook_url() -> Option<String> {
+    std::env::var("EMAIL_WEBHOOK_URL").ok()
+}
+
+/// Hand a token off in the background, so the response never waits on (or reveals) delivery
+fn spawn_token_delivery(
+    http: &Client,
+    purpose: &'static str,
+    email: String,
+    token: String,
+    ttl: chrono::Duration,
+) {
+    let http = http.clone();
+    tokio::spawn(async move { deliver_token(&http, purpose, &email, &token, ttl).await });
+}
+
+async fn deliver_token(
+    http: &Client,
+    purpose: &str,
+    email: &str,
+    token: &str,
+    ttl: chrono::Duration,
+) {
+    let Some(url) = email_webhook_url() else {
+        tracing::warn!(
+            "EMAIL_WEBHOOK_URL is not set; {} token for {} was not delivered",
+            purpose,
+            email
+        );
+        return;
+    };
 
-    if let Some(user) = store.find_user_by_id(user_id).await.unwrap() {
-        let response = UserResponse {
-            id: user.id,
-            email: user.email,
-            created_at: user.created_at,
+    let delivery = TokenDelivery {
+        purpose,
+        email,
+        token,
+        expires_in_secs: ttl.num_seconds(),
+    };
+    match http.post(&url).json(&delivery).send().await {
+        Ok(res) if res.status().is_success() => {}
+        Ok(res) => tracing::warn!(
+            "{} delivery for {} rejected: {}",
+            purpose,
+            email,
+            res.status()
+        ),
+        Err(e) => tracing::warn!("{} delivery for {} failed: {}", purpose, email, e),
+    }
+}
+
+#[actix_web::post("/password-reset/request")]
+pub async fn request_password_reset(
+    http_req: HttpRequest,
+    store: web::Data<Store>,
+    http: web::Data<Client>,
+    limits: web::Data<AuthRateLimits>,
+    req: web::Json<PasswordResetRequest>,
+) -> Result<HttpResponse> {
+    limits.check(&http_req, &req.email)?;
+
+    // Known and unknown emails get the same answer so accounts can't be enumerated
+    match store.create_password_reset(&req.email).await {
+        Ok(token) => spawn_token_delivery(
+            &http,
+            "password_reset",
+            req.email.clone(),
+            token,
+            PASSWORD_RESET_TTL,
+        ),
+        Err(StoreError::UserNotFound) => {}
+        Err(_) => {
+            return Err(actix_web::error::ErrorInternalServerError(
+                "Failed to request password reset",
+            ));
+        }
+    }
+
+    Ok(HttpResponse::Accepted().json(SignupResponse {
+        message: "If the account exists, a reset link has been sent".to_string(),
+    }))
+}
+
+#[actix_web::post("/password-reset/confirm")]
+pub async fn confirm_password_reset(
+    store: web::Data<Store>,
+    req: web::Json<PasswordResetConfirm>,
+) -> Result<HttpResponse> {
+    store
+        .reset_password(&req.token, &req.new_password)
+        .await
+        .map_err(|e| match e {
+            StoreError::InvalidInput(msg) => actix_web::error::ErrorBadRequest(msg),
+            _ => actix_web::error::ErrorInternalServerError("Failed to reset password"),
+        })?;
+
+    Ok(HttpResponse::Ok().json(SignupResponse {
+        message: "Password has been reset".to_string(),
+    }))
+}
+
+#[actix_web::post("/verify-email")]
+pub async fn verify_email(
+    store: web::Data<Store>,
+    req: web::Json<VerifyEmailRequest>,
+) -> Result<HttpResponse> {
+    store.verify_email(&req.token).await.map_err(|e| match e {
+        StoreError::InvalidInput(msg) => actix_web::error::ErrorBadRequest(msg),
+        _ => actix_web::error::ErrorInternalServerError("Failed to verify email"),
+    })?;
+
+    Ok(HttpResponse::Ok().json(SignupResponse {
+        message: "Email has been verified".to_string(),
+    }))
+}
+
+/// Send a fresh verification link, e.g. when the one issued at sign-up expired
+#[actix_web::post("/verify-email/resend")]
+pub async fn resend_email_verification(
+    http_req: HttpRequest,
+    user: AuthUser,
+    store: web::Data<Store>,
+    http: web::Data<Client>,
+    limits: web::Data<AuthRateLimits>,
+) -> Result<HttpResponse> {
+    let user = store.get_user(user.0).await.map_err(|e| match e {
+        StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+        _ => actix_web::error::ErrorInternalServerError("Failed to resend verification"),
+    })?;
+    limits.check(&http_req, &user.email)?;
+
+    let verified_at = store
+        .get_email_verified_at(user.id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to resend verification"))?;
+    if verified_at.is_some() {
+        return Ok(HttpResponse::Ok().json(SignupResponse {
+            message: "Email is already verified".to_string(),
+        }));
+    }
+
+    let token = store
+        .create_email_verification(user.id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to resend verification"))?;
+    spawn_token_delivery(
+        &http,
+        "verify_email",
+        user.email,
+        token,
+        EMAIL_VERIFICATION_TTL,
+    );
+
+    Ok(HttpResponse::Accepted().json(SignupResponse {
+        message: "A verification link has been sent".to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{confirm_password_reset, get_user, refresh_error, sign_in, sign_up, signup_error};
+    use crate::auth::{decode_claims, issue_token};
+    use crate::config::AppConfig;
+    use crate::rate_limit::{AuthRateLimits, RateLimiter};
+    use actix_web::http::header;
+    use actix_web::test::read_body_json;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use actix_web::{App, http::StatusCode, web};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+    use std::time::Instant;
+    use store::{
+        Store,
+        user::{CreateUserRequest, StoreError},
+    };
+    use uuid::Uuid;
+
+    fn unreachable_store() -> Store {
+        Store::for_tests("postgres://127.0.0.1:1/user_test")
+    }
+
+    #[test]
+    fn test_duplicate_signup_is_409() {
+        let err = signup_error(StoreError::UserExists);
+        assert_eq!(err.as_response_error().status_code(), StatusCode::CONFLICT);
+
+        let err = signup_error(StoreError::DatabaseError(sqlx::Error::PoolTimedOut));
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_rejected_refresh_token_is_401() {
+        let err = refresh_error(StoreError::RefreshTokenReplayed);
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let err = refresh_error(StoreError::InvalidRefreshToken("token expired".to_string()));
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_signup_rejects_short_password() {
+        // Validation runs before any query, so no database is needed
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_store()))
+                .app_data(web::Data::new(reqwest::Client::new()))
+                .service(sign_up),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/signup")
+            .set_json(serde_json::json!({"email": "user@example.com", "password": "short"}))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_sign_up_then_sign_in(pool: PgPool) {
+        let config = AppConfig::for_tests();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(Store::from_pool(pool)))
+                .app_data(web::Data::new(reqwest::Client::new()))
+                .app_data(web::Data::new(config.clone()))
+                .app_data(web::Data::new(AuthRateLimits {
+                    per_ip: RateLimiter::new(10, 60),
+                    per_email: RateLimiter::new(10, 60),
+                }))
+                .service(sign_up)
+                .service(sign_in),
+        )
+        .await;
+        let credentials = json!({"email": "Alice@Example.com", "password": "password123"});
+
+        let req = TestRequest::post()
+            .uri("/signup")
+            .set_json(&credentials)
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::CREATED);
+        let req = TestRequest::post()
+            .uri("/signup")
+            .set_json(&credentials)
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::CONFLICT);
+
+        let req = TestRequest::post()
+            .uri("/signin")
+            .set_json(json!({"email": "alice@example.com", "password": "wrong-password"}))
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+
+        let req = TestRequest::post()
+            .uri("/signin")
+            .set_json(&credentials)
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = read_body_json(res).await;
+        assert!(body["refresh_token"].is_string());
+        let claims = decode_claims(
+            body["token"].as_str().unwrap(),
+            config.jwt_secret.as_bytes(),
+            0,
+        )
+        .unwrap();
+        assert!(Uuid::parse_str(&claims.sub).is_ok());
+    }
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_get_user_by_uuid(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user = store
+            .create_user(CreateUserRequest {
+                email: "bob@example.com".to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .unwrap();
+        let config = AppConfig::for_tests();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(store))
+                .app_data(web::Data::new(config.clone()))
+                .service(get_user),
+        )
+        .await;
+        let get = |id: &str, token_for: Uuid| {
+            let token = issue_token(token_for, config.jwt_secret.as_bytes(), 3600).unwrap();
+            TestRequest::get()
+                .uri(&format!("/user/{}", id))
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request()
         };
-        Ok(HttpResponse::Ok().json(response))
-    } else {
-        Err(actix_web::error::ErrorNotFound("User not found"))
+
+        let res = call_service(&app, get(&user.id.to_string(), user.id)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["id"], user.id.to_string());
+        assert_eq!(body["email"], "bob@example.com");
+        assert!(body["created_at"].is_string());
+
+        // A valid token whose user has since gone
+        let missing = Uuid::new_v4();
+        let res = call_service(&app, get(&missing.to_string(), missing)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        // Not a UUID: the path never matches a user
+        let res = call_service(&app, get("42", user.id)).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_reset_rejects_short_password() {
+        // The password is checked before the token, so no database is needed
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_store()))
+                .service(confirm_password_reset),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/password-reset/confirm")
+            .set_json(serde_json::json!({"token": "abc", "new_password": "short"}))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_sign_in_over_limit_is_429() {
+        let limits = web::Data::new(AuthRateLimits {
+            per_ip: RateLimiter::new(1, 1),
+            per_email: RateLimiter::new(1, 1),
+        });
+        // Spend this email's only attempt up front so the request never reaches the store
+        limits
+            .per_email
+            .check("victim@example.com", Instant::now())
+            .unwrap();
+
+        let config = AppConfig::for_tests();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(unreachable_store()))
+                .app_data(web::Data::new(config))
+                .app_data(limits)
+                .service(sign_in),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/signin")
+            .set_json(serde_json::json!({"email": "Victim@example.com", "password": "password123"}))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
     }
 }
@@ -0,0 +1,504 @@
+use actix_web::{HttpResponse, Result, web};
+use common::trace::{TRACEPARENT, TraceContext};
+use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_client::TokenAccountsFilter};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use store::{
+    Store,
+    decimal::format_decimal,
+    user::{KeyshareParticipation, KeysharePublicInfo, StoreError},
+};
+
+use crate::auth::AuthUser;
+use crate::config::AppConfig;
+use crate::http::upstream_error;
+
+/// Base URL of the MPC server, configurable via `MPC_URL`
+pub(crate) fn mpc_url() -> String {
+    std::env::var("MPC_URL").unwrap_or_else(|_| "http://127.0.0.1:8081".to_string())
+}
+
+#[derive(Serialize)]
+struct MpcAggregateKeysRequest {
+    public_keys: Vec<String>,
+    key_for_coefficient: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MpcAggregateKeysResponse {
+    aggregated_public_key: String,
+}
+
+/// Network a wallet belongs to when the request doesn't name one, matching the keyshare column default
+pub(crate) const DEFAULT_NETWORK: &str = "devnet";
+
+/// Lowercase a requested network, falling back to `DEFAULT_NETWORK`
+pub(crate) fn wallet_network(network: Option<&str>) -> String {
+    network.map_or_else(|| DEFAULT_NETWORK.to_string(), str::to_lowercase)
+}
+
+#[derive(Deserialize)]
+pub struct AggregateWalletQuery {
+    pub network: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AggregateWalletResponse {
+    pub agg_pubkey: String,
+    pub network: String,
+    pub share_count: usize,
+}
+
+pub(crate) const SPL_TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// A token holding read from chain, summed across the owner's token accounts for one mint
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnChainToken {
+    pub mint: String,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct WalletToken {
+    pub mint: String,
+    pub amount: String,
+    pub decimals: u8,
+    /// Balance recorded in `token_balances`, if any
+    pub stored_balance: Option<String>,
+    /// On-chain amount and stored balance disagree
+    pub discrepancy: bool,
+}
+
+#[derive(Serialize)]
+pub struct WalletTokensResponse {
+    pub owner: String,
+    pub tokens: Vec<WalletToken>,
+    pub has_discrepancies: bool,
+}
+
+/// Read `{ mint, amount, decimals }` from a jsonParsed SPL token account
+pub(crate) fn parse_token_account(data: &serde_json::Value) -> Option<OnChainToken> {
+    let info = data.get("parsed")?.get("info")?;
+    let token_amount = info.get("tokenAmount")?;
+    Some(OnChainToken {
+        mint: info.get("mint")?.as_str()?.to_string(),
+        amount: token_amount.get("amount")?.as_str()?.parse().ok()?,
+        decimals: u8::try_from(token_amount.get("decimals")?.as_u64()?).ok()?,
+    })
+}
+
+/// Merge on-chain holdings with stored balances (keyed by mint), flagging any that disagree
+fn reconcile_tokens(
+    onchain: Vec<OnChainToken>,
+    stored: &BTreeMap<String, (Decimal, u32)>,
+) -> Vec<WalletToken> {
+    let mut holdings: BTreeMap<String, (u64, u8)> = BTreeMap::new();
+    for token in onchain {
+        let entry = holdings.entry(token.mint).or_insert((0, token.decimals));
+        entry.0 = entry.0.saturating_add(token.amount);
+    }
+
+    let mut tokens: Vec<WalletToken> = holdings
+        .iter()
+        .filter_map(|(mint, &(amount, decimals))| {
+            let Ok(amount) =
+                Decimal::try_from_i128_with_scale(i128::from(amount), u32::from(decimals))
+            else {
+                tracing::warn!(
+                    "Skipping mint {} with unsupported decimals {}",
+                    mint,
+                    decimals
+                );
+                return None;
+            };
+            let stored_balance = stored.get(mint).map(|&(balance, _)| balance);
+            Some(WalletToken {
+                mint: mint.clone(),
+                amount: format_decimal(amount, u32::from(decimals)),
+                decimals,
+                stored_balance: stored_balance
+                    .map(|balance| format_decimal(balance, u32::from(decimals))),
+                discrepancy: stored_balance.unwrap_or(Decimal::ZERO) != amount,
+            })
+        })
+        .collect();
+
+    // Stored balances with no token account on chain are discrepancies unless they are zero
+    for (mint, &(balance, decimals)) in stored {
+        if !holdings.contains_key(mint) {
+            tokens.push(WalletToken {
+                mint: mint.clone(),
+                amount: format_decimal(Decimal::ZERO, decimals),
+                decimals: u8::try_from(decimals).unwrap_or(u8::MAX),
+                stored_balance: Some(format_decimal(balance, decimals)),
+                discrepancy: !balance.is_zero(),
+            });
+        }
+    }
+
+    tokens
+}
+
+#[actix_web::get("/wallet/tokens")]
+pub async fn wallet_tokens(
+    user: AuthUser,
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse> {
+    let user_id = user.0;
+    let user = store.get_user(user_id).await.map_err(|e| match e {
+        StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+        _ => actix_web::error::ErrorInternalServerError("Failed to fetch user"),
+    })?;
+    let agg_pubkey = user
+        .agg_pubkey
+        .ok_or_else(|| actix_web::error::ErrorConflict("Wallet has not been set up yet"))?;
+    let owner = Pubkey::from_str(&agg_pubkey)
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Stored agg_pubkey is invalid"))?;
+
+    let client = RpcClient::new(config.solana_rpc_url.clone());
+    let accounts = client
+        .get_token_accounts_by_owner(
+            &owner,
+            TokenAccountsFilter::ProgramId(Pubkey::from_str(SPL_TOKEN_PROGRAM).unwrap()),
+        )
+        .await
+        .map_err(|e| {
+            actix_web::error::ErrorBadGateway(format!("Failed to fetch token accounts: {}", e))
+        })?;
+
+    let onchain = accounts
+        .iter()
+        .filter_map(|keyed| {
+            let data = serde_json::to_value(&keyed.account.data).ok()?;
+            parse_token_account(&data)
+        })
+        .collect();
+
+    let stored = store
+        .get_user_token_balances(user_id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch balances"))?
+        .into_iter()
+        .map(|balance| {
+            (
+                balance.token_mint,
+                (
+                    balance.balance,
+                    balance
+                        .decimals
+                        .map_or(balance.balance.scale(), |decimals| decimals.max(0) as u32),
+                ),
+            )
+        })
+        .collect();
+
+    let tokens = reconcile_tokens(onchain, &stored);
+    Ok(HttpResponse::Ok().json(WalletTokensResponse {
+        owner: agg_pubkey,
+        has_discrepancies: tokens.iter().any(|token| token.discrepancy),
+        tokens,
+    }))
+}
+
+/// Threshold assumed before any keyshare exists, matching `Store::has_sufficient_keyshares`
+const DEFAULT_SIGNING_THRESHOLD: i32 = 2;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct SigningReadinessResponse {
+    pub ready: bool,
+    pub active_nodes: Vec<i32>,
+    pub needed: i32,
+}
+
+fn signing_readiness(keyshares: &[KeyshareParticipation]) -> SigningReadinessResponse {
+    let needed = keyshares
+        .iter()
+        .map(|keyshare| keyshare.threshold)
+        .max()
+        .unwrap_or(DEFAULT_SIGNING_THRESHOLD);
+    let active_nodes: Vec<i32> = keyshares
+        .iter()
+        .filter(|keyshare| keyshare.active)
+        .map(|keyshare| keyshare.mpc_node_id)
+        .collect();
+
+    SigningReadinessResponse {
+        ready: active_nodes.len() >= needed.max(0) as usize,
+        active_nodes,
+        needed,
+    }
+}
+
+#[actix_web::post("/wallet/signing-readiness")]
+pub async fn signing_readiness_check(
+    user: AuthUser,
+    store: web::Data<Store>,
+) -> Result<HttpResponse> {
+    let keyshares = store
+        .get_keyshare_participation(user.0)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch keyshares"))?;
+
+    Ok(HttpResponse::Ok().json(signing_readiness(&keyshares)))
+}
+
+/// Public wallet configuration for backups; built only from data that is safe to share
+#[derive(Serialize)]
+pub struct WalletExport {
+    pub agg_pubkey: Option<String>,
+    pub threshold: Option<i32>,
+    pub total_shares: Option<i32>,
+    pub nodes: Vec<KeysharePublicInfo>,
+}
+
+fn wallet_export(agg_pubkey: Option<String>, nodes: Vec<KeysharePublicInfo>) -> WalletExport {
+    WalletExport {
+        agg_pubkey,
+        threshold: nodes.iter().map(|node| node.threshold).max(),
+        total_shares: nodes.iter().map(|node| node.total_shares).max(),
+        nodes,
+    }
+}
+
+#[actix_web::get("/wallet/export")]
+pub async fn export_wallet(user: AuthUser, store: web::Data<Store>) -> Result<HttpResponse> {
+    let user_id = user.0;
+    let user = store.get_user(user_id).await.map_err(|e| match e {
+        StoreError::UserNotFound => actix_web::error::ErrorNotFound("User not found"),
+        _ => actix_web::error::ErrorInternalServerError("Failed to fetch user"),
+    })?;
+    // Queried without the private_key_share column, so secrets are never even loaded
+    let nodes = store
+        .get_keyshare_public_info(user_id)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch keyshares"))?;
+
+    Ok(HttpResponse::Ok().json(wallet_export(user.agg_pubkey, nodes)))
+}
+
+/// Aggregating a wallet the shares can't sign for would store an unusable address: 409
+fn check_aggregation_quorum(share_count: usize, has_threshold: bool) -> Result<()> {
+    if share_count == 0 || !has_threshold {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "Insufficient keyshares: have {}",
+            share_count
+        )));
+    }
+    Ok(())
+}
+
+#[actix_web::post("/wallet/aggregate")]
+pub async fn aggregate_wallet(
+    user: AuthUser,
+    trace: TraceContext,
+    store: web::Data<Store>,
+    http: web::Data<Client>,
+    query: web::Query<AggregateWalletQuery>,
+) -> Result<HttpResponse> {
+    let network = wallet_network(query.network.as_deref());
+
+    // Only public keys are needed here, so the secret shares never leave the database
+    let public_keys = store
+        .get_keyshare_public_keys(user.0, Some(network.as_str()))
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch keyshares"))?;
+    let has_threshold = store
+        .has_sufficient_keyshares(user.0, None)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to fetch keyshares"))?;
+
+    let share_count = public_keys.len();
+    check_aggregation_quorum(share_count, has_threshold)?;
+
+    let response = http
+        .post(format!("{}/aggregate-keys", mpc_url()))
+        .header(TRACEPARENT, trace.traceparent())
+        .json(&MpcAggregateKeysRequest {
+            public_keys,
+            key_for_coefficient: None,
+        })
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|e| upstream_error("MPC key aggregation", e))?
+        .json::<MpcAggregateKeysResponse>()
+        .await
+        .map_err(|e| actix_web::error::ErrorBadGateway(format!("Invalid MPC response: {}", e)))?;
+
+    store
+        .update_user_agg_pubkey(user.0, &response.aggregated_public_key)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to store agg_pubkey"))?;
+    store
+        .set_network_agg_pubkey(user.0, &network, &response.aggregated_public_key)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to store agg_pubkey"))?;
+
+    Ok(HttpResponse::Ok().json(AggregateWalletResponse {
+        agg_pubkey: response.aggregated_public_key,
+        network,
+        share_count,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        OnChainToken, SigningReadinessResponse, check_aggregation_quorum, parse_token_account,
+        reconcile_tokens, signing_readiness, wallet_export, wallet_network,
+    };
+    use actix_web::http::StatusCode;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use store::user::{KeyshareParticipation, KeysharePublicInfo};
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+
+    fn holding(mint: &str, amount: u64, decimals: u8) -> OnChainToken {
+        OnChainToken {
+            mint: mint.to_string(),
+            amount,
+            decimals,
+        }
+    }
+
+    #[test]
+    fn test_parse_json_parsed_token_account() {
+        let data = serde_json::json!({
+            "program": "spl-token",
+            "parsed": {
+                "type": "account",
+                "info": {
+                    "mint": USDC,
+                    "owner": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+                    "tokenAmount": { "amount": "1500000", "decimals": 6, "uiAmountString": "1.5" }
+                }
+            },
+            "space": 165
+        });
+        assert_eq!(
+            parse_token_account(&data),
+            Some(holding(USDC, 1_500_000, 6))
+        );
+
+        // Binary-encoded accounts aren't decoded here
+        assert_eq!(
+            parse_token_account(&serde_json::json!(["AAAA", "base64"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reconcile_flags_discrepancies() {
+        let stored = BTreeMap::from([
+            (USDC.to_string(), (Decimal::new(25, 1), 6)),
+            (BONK.to_string(), (Decimal::new(100, 0), 5)),
+        ]);
+        // Two USDC token accounts holding 1.5 + 1.0, no BONK account at all
+        let tokens = reconcile_tokens(
+            vec![holding(USDC, 1_500_000, 6), holding(USDC, 1_000_000, 6)],
+            &stored,
+        );
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].mint, USDC);
+        assert_eq!(tokens[0].amount, "2.500000");
+        assert!(!tokens[0].discrepancy);
+        assert_eq!(tokens[1].mint, BONK);
+        assert_eq!(tokens[1].stored_balance.as_deref(), Some("100.00000"));
+        assert!(tokens[1].discrepancy);
+    }
+
+    #[test]
+    fn test_reconcile_untracked_holding_is_discrepancy() {
+        let tokens = reconcile_tokens(vec![holding(USDC, 1, 6)], &BTreeMap::new());
+        assert_eq!(tokens[0].stored_balance, None);
+        assert!(tokens[0].discrepancy);
+    }
+
+    fn share(mpc_node_id: i32, active: bool) -> KeyshareParticipation {
+        KeyshareParticipation {
+            mpc_node_id,
+            active,
+            threshold: 2,
+        }
+    }
+
+    #[test]
+    fn test_signing_ready_with_quorum() {
+        let readiness = signing_readiness(&[share(1, true), share(2, false), share(3, true)]);
+        assert_eq!(
+            readiness,
+            SigningReadinessResponse {
+                ready: true,
+                active_nodes: vec![1, 3],
+                needed: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_signing_not_ready_under_quorum() {
+        let readiness = signing_readiness(&[share(1, true), share(2, false), share(3, false)]);
+        assert!(!readiness.ready);
+        assert_eq!(readiness.active_nodes, vec![1]);
+
+        // No keyshares at all still reports the default threshold
+        let readiness = signing_readiness(&[]);
+        assert!(!readiness.ready);
+        assert_eq!(readiness.needed, 2);
+    }
+
+    #[test]
+    fn test_aggregation_needs_a_signing_quorum() {
+        assert!(check_aggregation_quorum(3, true).is_ok());
+        for (share_count, has_threshold) in [(0, true), (1, false)] {
+            let err = check_aggregation_quorum(share_count, has_threshold).unwrap_err();
+            assert_eq!(err.as_response_error().status_code(), StatusCode::CONFLICT);
+        }
+    }
+
+    #[test]
+    fn test_wallet_export_has_no_secret_fields() {
+        let nodes = (1..=3)
+            .map(|mpc_node_id| KeysharePublicInfo {
+                mpc_node_id,
+                public_key: format!("pubkey-{}", mpc_node_id),
+                threshold: 2,
+                total_shares: 3,
+            })
+            .collect();
+        let export = serde_json::to_value(wallet_export(Some("agg".to_string()), nodes)).unwrap();
+
+        assert_eq!(export["agg_pubkey"], "agg");
+        assert_eq!(export["threshold"], 2);
+        assert_eq!(export["total_shares"], 3);
+        assert_eq!(export["nodes"].as_array().unwrap().len(), 3);
+
+        // Guard against a secret-bearing field ever being added to the export
+        let node_fields: Vec<&str> = export["nodes"][0]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(
+            node_fields,
+            vec!["mpc_node_id", "public_key", "threshold", "total_shares"]
+        );
+        assert!(!export.to_string().contains("private"));
+    }
+
+    #[test]
+    fn test_wallet_network_defaults_and_normalizes() {
+        assert_eq!(wallet_network(None), "devnet");
+        assert_eq!(wallet_network(Some("MainNet")), "mainnet");
+    }
+}
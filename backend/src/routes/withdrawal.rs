@@ -0,0 +1,72 @@
+use actix_web::{HttpResponse, Result, web};
+use common::trace::TraceContext;
+use reqwest::Client;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::str::FromStr;
+use store::Store;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::config::AppConfig;
+use crate::routes::wallet::mpc_url;
+use crate::withdrawal::{
+    MpcAggregateSigner, PartialSignatures, WithdrawalIntent, orchestrate_withdrawal,
+};
+
+#[derive(Deserialize)]
+pub struct WithdrawRequest {
+    pub to: String,
+    pub amount: String,
+    /// Lets a client that lost the response find the withdrawal via `/transactions/by-key`
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(flatten)]
+    pub partials: PartialSignatures,
+}
+
+#[derive(Serialize)]
+pub struct WithdrawResponse {
+    pub transaction_id: Uuid,
+    pub signature: String,
+}
+
+/// Withdraw SOL from the caller's custody wallet: record, aggregate the MPC signatures,
+/// broadcast, and debit only once the transfer has landed
+#[actix_web::post("/withdrawals")]
+pub async fn withdraw(
+    user: AuthUser,
+    store: web::Data<Store>,
+    config: web::Data<AppConfig>,
+    http: web::Data<Client>,
+    trace: TraceContext,
+    body: web::Json<WithdrawRequest>,
+) -> Result<HttpResponse> {
+    let body = body.into_inner();
+    let amount = Decimal::from_str(&body.amount).map_err(|_| {
+        actix_web::error::ErrorBadRequest(format!("Invalid amount: {}", body.amount))
+    })?;
+
+    let intent = WithdrawalIntent {
+        user_id: user.0,
+        to: body.to,
+        amount,
+        idempotency_key: body.idempotency_key,
+    };
+    let signer = MpcAggregateSigner {
+        http: &http,
+        mpc_url: mpc_url(),
+        traceparent: trace.traceparent(),
+        partials: body.partials,
+    };
+    let rpc = RpcClient::new(config.solana_rpc_url.clone());
+
+    let (transaction_id, signature) =
+        orchestrate_withdrawal(store.get_ref(), &signer, &rpc, &intent).await?;
+
+    Ok(HttpResponse::Ok().json(WithdrawResponse {
+        transaction_id,
+        signature,
+    }))
+}
@@ -0,0 +1,65 @@
+use actix_web::dev::Server;
+use std::future::Future;
+use store::Store;
+
+/// Resolve on Ctrl-C or, on Unix, SIGTERM (what orchestrators send before killing the process)
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = sigterm.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Run `server` until it stops or `shutdown` resolves. On shutdown the server stops accepting
+/// connections and lets in-flight requests finish; the database pool is closed either way.
+pub async fn serve_until(
+    server: Server,
+    shutdown: impl Future<Output = ()>,
+    store: &Store,
+) -> std::io::Result<()> {
+    let handle = server.handle();
+    let mut server = std::pin::pin!(server);
+
+    let result = tokio::select! {
+        result = &mut server => result,
+        _ = shutdown => {
+            tracing::info!("Received shutdown signal, draining in-flight requests...");
+            // The server future processes the stop command, so it must keep being polled
+            let (result, ()) = tokio::join!(server, handle.stop(true));
+            result
+        }
+    };
+
+    store.close().await;
+    tracing::info!("Database pool closed");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::serve_until;
+    use actix_web::{App, HttpResponse, HttpServer, web};
+    use store::Store;
+
+    #[actix_web::test]
+    async fn test_pool_closed_after_server_stops() {
+        // Lazy, so no database is needed; close() still marks the pool closed
+        let store = Store::for_tests("postgres://localhost/shutdown_test");
+        let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+            .workers(1)
+            .disable_signals()
+            .bind("127.0.0.1:0")
+            .unwrap()
+            .run();
+
+        serve_until(server, async {}, &store).await.unwrap();
+        assert!(store.pool.is_closed());
+    }
+}
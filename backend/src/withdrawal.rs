@@ -0,0 +1,974 @@
+use actix_web::{ResponseError, http::StatusCode, web};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::Utc;
+use common::trace::TRACEPARENT;
+use reqwest::Client;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use store::{
+    Store,
+    decimal::{LEDGER_DECIMALS, SOL_DECIMALS},
+    pagination::Cursor,
+    user::{StoreError, TransactionType},
+};
+use uuid::Uuid;
+
+/// Submitted withdrawals loaded per settlement page
+const SETTLEMENT_BATCH: i64 = 100;
+/// Failure reason for a withdrawal whose blockhash expired without it ever landing
+const EXPIRED_BEFORE_LANDING: &str = "Blockhash expired before the transfer landed";
+/// How long a submitted withdrawal is left to its request before settlement picks it up, so
+/// the sweep doesn't race a `send_and_confirm_transaction` that is still waiting
+const SETTLE_AFTER_MINUTES: i64 = 5;
+
+/// A user's request to send `amount` SOL from custody to `to`
+#[derive(Debug, Clone)]
+pub struct WithdrawalIntent {
+    pub user_id: Uuid,
+    pub to: String,
+    pub amount: Decimal,
+    /// Stored on the withdrawal's row, so a client unsure whether its request went through can
+    /// look it up instead of withdrawing twice
+    pub idempotency_key: Option<String>,
+}
+
+/// The ledger side of a withdrawal. Opening one holds the amount but must not debit the
+/// balance; only `complete` does, so a withdrawal that never reaches the chain never costs the
+/// user anything.
+pub(crate) trait WithdrawalLedger {
+    /// Open a pending row, returning its id and the wallet the transfer must be sent from
+    async fn open(&self, intent: &WithdrawalIntent) -> Result<(Uuid, String), StoreError>;
+    /// Attach the signature, and the block height past which it can no longer land, to the
+    /// pending row before it is sent
+    async fn submit(
+        &self,
+        transaction_id: Uuid,
+        signature: String,
+        last_valid_block_height: u64,
+    ) -> Result<(), StoreError>;
+    /// Debit a transfer that landed. No policy is re-checked: the funds have already left custody.
+    async fn complete(&self, transaction_id: Uuid, signature: String) -> Result<(), StoreError>;
+    async fn fail(&self, transaction_id: Uuid, reason: String) -> Result<(), StoreError>;
+}
+
+/// Produces a fully signed transaction, base64-encoded bincode
+pub(crate) trait WithdrawalSigner {
+    async fn sign(&self, intent: &WithdrawalIntent) -> Result<String, String>;
+}
+
+/// What the chain reports for a sent signature
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SendOutcome {
+    Landed,
+    /// Processed but failed, so nothing left custody
+    Rejected(String),
+    /// Not seen (yet); it may still land
+    Unknown,
+}
+
+/// Submits signed transactions and looks up what became of them
+pub(crate) trait WithdrawalBroadcaster {
+    /// Upper bound on the block height at which a transaction signed now can still land, or
+    /// `None` if the chain can't be read
+    async fn last_valid_block_height(&self) -> Option<u64>;
+    /// Send and wait for confirmation. An error does not mean the transfer did not land.
+    async fn broadcast(&self, transaction: &Transaction) -> Result<(), String>;
+    async fn outcome(&self, signature: &str) -> SendOutcome;
+    /// The chain is past `last_valid_block_height` and never processed `signature`, so it can
+    /// no longer land. False whenever either can't be read.
+    async fn expired(&self, signature: &str, last_valid_block_height: u64) -> bool;
+}
+
+#[derive(Debug)]
+pub enum WithdrawalError {
+    /// Rejected or failed before anything was signed
+    Ledger(StoreError),
+    Signing(String),
+    /// The signed transaction pays from a wallet other than the user's
+    WrongWallet {
+        expected: String,
+        actual: String,
+    },
+    /// The chain processed the transfer and rejected it
+    Broadcast(String),
+    /// Sent, but whether it landed is not known yet; the row stays pending until settled
+    Unconfirmed {
+        transaction_id: Uuid,
+        signature: String,
+        error: String,
+    },
+    /// The transfer landed on chain but the debit could not be recorded; needs reconciliation
+    Unrecorded {
+        transaction_id: Uuid,
+        signature: String,
+        error: StoreError,
+    },
+}
+
+impl Display for WithdrawalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WithdrawalError::Ledger(e) => write!(f, "{}", e),
+            WithdrawalError::Signing(e) => write!(f, "Failed to sign withdrawal: {}", e),
+            WithdrawalError::WrongWallet { expected, actual } => write!(
+                f,
+                "Withdrawal must be paid from {}, not {}",
+                expected, actual
+            ),
+            WithdrawalError::Broadcast(e) => write!(f, "Failed to broadcast withdrawal: {}", e),
+            WithdrawalError::Unconfirmed {
+                transaction_id,
+                signature,
+                error,
+            } => write!(
+                f,
+                "Withdrawal {} was sent as {} but is not confirmed yet ({}); it stays pending until it settles",
+                transaction_id, signature, error
+            ),
+            WithdrawalError::Unrecorded {
+                transaction_id,
+                signature,
+                error,
+            } => write!(
+                f,
+                "Withdrawal {} was sent as {} but could not be recorded: {}",
+                transaction_id, signature, error
+            ),
+        }
+    }
+}
+
+impl ResponseError for WithdrawalError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WithdrawalError::Ledger(
+                StoreError::InvalidInput(_) | StoreError::InsufficientBalance { .. },
+            ) => StatusCode::BAD_REQUEST,
+            WithdrawalError::Ledger(
+                StoreError::AddressNotWhitelisted(_) | StoreError::EmailNotVerified,
+            )
+            | WithdrawalError::WrongWallet { .. } => StatusCode::FORBIDDEN,
+            WithdrawalError::Ledger(StoreError::UserNotFound) => StatusCode::NOT_FOUND,
+            WithdrawalError::Ledger(
+                StoreError::MpcSetupIncomplete(_) | StoreError::IdempotencyKeyReused(_),
+            ) => StatusCode::CONFLICT,
+            WithdrawalError::Signing(_) | WithdrawalError::Broadcast(_) => StatusCode::BAD_GATEWAY,
+            WithdrawalError::Unconfirmed { .. } => StatusCode::GATEWAY_TIMEOUT,
+            WithdrawalError::Ledger(_) | WithdrawalError::Unrecorded { .. } => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+}
+
+/// Exact lamport count of a SOL amount. The ledger only keeps `LEDGER_DECIMALS` places, so a
+/// finer amount is refused: it would be held and debited rounded while the exact lamports left.
+fn sol_to_lamports(amount: Decimal) -> Result<u64, StoreError> {
+    if amount.normalize().scale() > LEDGER_DECIMALS {
+        return Err(StoreError::InvalidInput(format!(
+            "Amount {} has more than {} decimal places",
+            amount, LEDGER_DECIMALS
+        )));
+    }
+    amount
+        .checked_mul(Decimal::from(10u64.pow(SOL_DECIMALS)))
+        .and_then(|lamports| lamports.to_u64())
+        .ok_or_else(|| StoreError::InvalidInput(format!("Amount {} is out of range", amount)))
+}
+
+/// Decode the signer's output and check that it pays from `wallet`, returning it with its
+/// signature
+fn decode_signed_transfer(
+    signed_transaction_base64: &str,
+    wallet: &str,
+) -> Result<(Transaction, String), WithdrawalError> {
+    let bytes = STANDARD
+        .decode(signed_transaction_base64)
+        .map_err(|e| WithdrawalError::Signing(format!("Invalid transaction encoding: {}", e)))?;
+    let transaction: Transaction = bincode::deserialize(&bytes)
+        .map_err(|e| WithdrawalError::Signing(format!("Invalid transaction: {}", e)))?;
+
+    let fee_payer = transaction
+        .message
+        .account_keys
+        .first()
+        .map(|key| key.to_string())
+        .unwrap_or_default();
+    if fee_payer != wallet {
+        return Err(WithdrawalError::WrongWallet {
+            expected: wallet.to_string(),
+            actual: fee_payer,
+        });
+    }
+
+    let signature = transaction
+        .signatures
+        .first()
+        .filter(|signature| **signature != Signature::default())
+        .ok_or_else(|| WithdrawalError::Signing("Transaction is not signed".to_string()))?
+        .to_string();
+    Ok((transaction, signature))
+}
+
+/// Mark the row failed and hand back the error that caused it
+async fn fail_withdrawal(
+    ledger: &impl WithdrawalLedger,
+    transaction_id: Uuid,
+    error: WithdrawalError,
+) -> WithdrawalError {
+    if let Err(fail_error) = ledger.fail(transaction_id, error.to_string()).await {
+        tracing::error!(
+            "Failed to mark withdrawal {} failed: {}",
+            transaction_id,
+            fail_error
+        );
+    }
+    error
+}
+
+/// Run a withdrawal end to end: open a pending row, sign via MPC, broadcast, then debit.
+/// The row is failed only when nothing was sent or the chain rejected the transfer; a send whose
+/// outcome is unknown stays pending with its signature until `settle_withdrawal` resolves it.
+pub(crate) async fn orchestrate_withdrawal(
+    ledger: &impl WithdrawalLedger,
+    signer: &impl WithdrawalSigner,
+    broadcaster: &impl WithdrawalBroadcaster,
+    intent: &WithdrawalIntent,
+) -> Result<(Uuid, String), WithdrawalError> {
+    let (transaction_id, wallet) = ledger.open(intent).await.map_err(WithdrawalError::Ledger)?;
+
+    let prepared = match signer.sign(intent).await {
+        Ok(signed) => decode_signed_transfer(&signed, &wallet),
+        Err(e) => Err(WithdrawalError::Signing(e)),
+    };
+    let (transaction, signature) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => return Err(fail_withdrawal(ledger, transaction_id, e).await),
+    };
+
+    // Without an expiry height a lost send could never be told apart from a slow one
+    let Some(last_valid_block_height) = broadcaster.last_valid_block_height().await else {
+        let error = WithdrawalError::Broadcast("Failed to read the block height".to_string());
+        return Err(fail_withdrawal(ledger, transaction_id, error).await);
+    };
+
+    // Recorded before sending, so a crash mid-send still leaves the signature to settle by
+    if let Err(e) = ledger
+        .submit(transaction_id, signature.clone(), last_valid_block_height)
+        .await
+    {
+        return Err(fail_withdrawal(ledger, transaction_id, WithdrawalError::Ledger(e)).await);
+    }
+
+    if let Err(error) = broadcaster.broadcast(&transaction).await {
+        // A send or confirmation error can arrive after the transfer landed, so ask the chain
+        match broadcaster.outcome(&signature).await {
+            SendOutcome::Landed => {}
+            SendOutcome::Rejected(reason) => {
+                let error = WithdrawalError::Broadcast(format!("{}: {}", error, reason));
+                return Err(fail_withdrawal(ledger, transaction_id, error).await);
+            }
+            SendOutcome::Unknown => {
+                tracing::warn!(
+                    "Withdrawal {} sent as {} is unconfirmed: {}",
+                    transaction_id,
+                    signature,
+                    error
+                );
+                return Err(WithdrawalError::Unconfirmed {
+                    transaction_id,
+                    signature,
+                    error,
+                });
+            }
+        }
+    }
+
+    // The funds have left custody, so the row stays pending for reconciliation rather than failing
+    if let Err(error) = ledger.complete(transaction_id, signature.clone()).await {
+        tracing::error!(
+            "Withdrawal {} landed as {} but was not recorded: {}",
+            transaction_id,
+            signature,
+            error
+        );
+        return Err(WithdrawalError::Unrecorded {
+            transaction_id,
+            signature,
+            error,
+        });
+    }
+
+    Ok((transaction_id, signature))
+}
+
+/// Resolve a withdrawal left pending with its signature: debit it once it landed, fail it once
+/// the chain rejected it or its blockhash expired unseen (releasing the hold), and leave it
+/// pending while it may still land
+pub(crate) async fn settle_withdrawal(
+    ledger: &impl WithdrawalLedger,
+    broadcaster: &impl WithdrawalBroadcaster,
+    transaction_id: Uuid,
+    signature: String,
+    last_valid_block_height: Option<u64>,
+) -> Result<SendOutcome, StoreError> {
+    let mut outcome = broadcaster.outcome(&signature).await;
+    let expired = match (&outcome, last_valid_block_height) {
+        (SendOutcome::Unknown, Some(height)) => broadcaster.expired(&signature, height).await,
+        _ => false,
+    };
+    if expired {
+        outcome = SendOutcome::Rejected(EXPIRED_BEFORE_LANDING.to_string());
+    }
+    match &outcome {
+        SendOutcome::Landed => ledger.complete(transaction_id, signature).await?,
+        SendOutcome::Rejected(reason) => ledger.fail(transaction_id, reason.clone()).await?,
+        SendOutcome::Unknown => {}
+    }
+    Ok(outcome)
+}
+
+/// Settle withdrawals whose send outcome was unknown every `every`
+pub fn spawn_settlement(store: web::Data<Store>, rpc_url: String, every: Duration) {
+    tokio::spawn(async move {
+        let rpc = RpcClient::new(rpc_url);
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            ticker.tick().await;
+            settle_submitted(store.get_ref(), &rpc).await;
+        }
+    });
+}
+
+/// One settlement pass over every submitted withdrawal, paging past the ones still unresolved
+async fn settle_submitted(store: &Store, rpc: &RpcClient) {
+    let idle_since = Utc::now() - chrono::Duration::minutes(SETTLE_AFTER_MINUTES);
+    let mut after = None;
+    loop {
+        let submitted = match store
+            .get_submitted_withdrawals(idle_since, after, SETTLEMENT_BATCH)
+            .await
+        {
+            Ok(submitted) => submitted,
+            Err(e) => {
+                tracing::warn!("Failed to load submitted withdrawals: {}", e);
+                return;
+            }
+        };
+        let Some(last) = submitted.last() else {
+            return;
+        };
+        after = Some(Cursor::new(last.created_at, last.id));
+
+        for withdrawal in submitted {
+            let last_valid_block_height = withdrawal
+                .last_valid_block_height
+                .and_then(|height| u64::try_from(height).ok());
+            if let Err(e) = settle_withdrawal(
+                store,
+                rpc,
+                withdrawal.id,
+                withdrawal.tx_signature,
+                last_valid_block_height,
+            )
+            .await
+            {
+                tracing::warn!("Failed to settle withdrawal {}: {}", withdrawal.id, e);
+            }
+        }
+    }
+}
+
+impl WithdrawalLedger for Store {
+    async fn open(&self, intent: &WithdrawalIntent) -> Result<(Uuid, String), StoreError> {
+        let wallet = self
+            .get_user(intent.user_id)
+            .await?
+            .agg_pubkey
+            .ok_or_else(|| {
+                StoreError::MpcSetupIncomplete("User has no aggregated public key".to_string())
+            })?;
+        // Checked before the row is opened, since the signer sends exactly this many lamports
+        sol_to_lamports(intent.amount)?;
+
+        // Holds the amount against other pending withdrawals, so an overdrawn request is
+        // refused before anything is signed
+        let transaction = self
+            .create_transaction(
+                intent.user_id,
+                TransactionType::Withdrawal,
+                intent.amount,
+                None,
+                None,
+                Some(intent.to.clone()),
+                None,
+                intent.idempotency_key.as_deref(),
+            )
+            .await?;
+        Ok((transaction.id, wallet))
+    }
+
+    async fn submit(
+        &self,
+        transaction_id: Uuid,
+        signature: String,
+        last_valid_block_height: u64,
+    ) -> Result<(), StoreError> {
+        self.mark_withdrawal_submitted(transaction_id, &signature, last_valid_block_height)
+            .await
+    }
+
+    async fn complete(&self, transaction_id: Uuid, signature: String) -> Result<(), StoreError> {
+        self.record_withdrawal_debit(transaction_id, signature)
+            .await
+    }
+
+    async fn fail(&self, transaction_id: Uuid, reason: String) -> Result<(), StoreError> {
+        self.fail_transaction(transaction_id, Some(reason)).await
+    }
+}
+
+/// Partial signatures the client collected from the MPC nodes' signing rounds
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PartialSignatures {
+    pub recent_block_hash: String,
+    pub public_keys: Vec<String>,
+    pub signers: Option<Vec<String>>,
+    pub partial_signatures: Vec<String>,
+}
+
+/// Aggregates the nodes' partial signatures into a signed transaction via the MPC server
+pub(crate) struct MpcAggregateSigner<'a> {
+    pub http: &'a Client,
+    pub mpc_url: String,
+    pub traceparent: String,
+    pub partials: PartialSignatures,
+}
+
+#[derive(Serialize)]
+struct MpcAggregateSignaturesRequest<'a> {
+    /// Lamports, so the transfer signed is exactly the amount the ledger debits
+    base_units: u64,
+    to: &'a str,
+    #[serde(flatten)]
+    partials: &'a PartialSignatures,
+}
+
+#[derive(Deserialize)]
+struct MpcAggregateSignaturesResponse {
+    signed_transaction_base64: String,
+}
+
+impl WithdrawalSigner for MpcAggregateSigner<'_> {
+    async fn sign(&self, intent: &WithdrawalIntent) -> Result<String, String> {
+        let base_units = sol_to_lamports(intent.amount).map_err(|e| e.to_string())?;
+        let request = MpcAggregateSignaturesRequest {
+            base_units,
+            to: &intent.to,
+            partials: &self.partials,
+        };
+
+        let signed = self
+            .http
+            .post(format!("{}/aggregate-signatures", self.mpc_url))
+            .header(TRACEPARENT, &self.traceparent)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json::<MpcAggregateSignaturesResponse>()
+            .await
+            .map_err(|e| format!("Invalid MPC response: {}", e))?;
+        Ok(signed.signed_transaction_base64)
+    }
+}
+
+impl WithdrawalBroadcaster for RpcClient {
+    async fn last_valid_block_height(&self) -> Option<u64> {
+        // The signed transaction's blockhash is no newer than the latest, so it expires no later
+        match self
+            .get_latest_blockhash_with_commitment(self.commitment())
+            .await
+        {
+            Ok((_, last_valid_block_height)) => Some(last_valid_block_height),
+            Err(e) => {
+                tracing::warn!("Failed to read the latest blockhash: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn broadcast(&self, transaction: &Transaction) -> Result<(), String> {
+        self.send_and_confirm_transaction(transaction)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn outcome(&self, signature: &str) -> SendOutcome {
+        let Ok(parsed) = Signature::from_str(signature) else {
+            return SendOutcome::Unknown;
+        };
+        // Searches history too, so a transfer that landed long ago is still found
+        match self
+            .get_signature_status_with_commitment_and_history(&parsed, self.commitment(), true)
+            .await
+        {
+            Ok(Some(Ok(()))) => SendOutcome::Landed,
+            Ok(Some(Err(e))) => SendOutcome::Rejected(e.to_string()),
+            Ok(None) => SendOutcome::Unknown,
+            Err(e) => {
+                tracing::warn!("Failed to read status of {}: {}", signature, e);
+                SendOutcome::Unknown
+            }
+        }
+    }
+
+    async fn expired(&self, signature: &str, last_valid_block_height: u64) -> bool {
+        let Ok(parsed) = Signature::from_str(signature) else {
+            return false;
+        };
+        // Read the height first: a transfer can only land at or below the limit, so once the
+        // chain is past it, a status lookup made afterwards is final
+        match self.get_block_height().await {
+            Ok(height) if height > last_valid_block_height => {}
+            Ok(_) => return false,
+            Err(e) => {
+                tracing::warn!("Failed to read the block height: {}", e);
+                return false;
+            }
+        }
+        matches!(
+            self.get_signature_status_with_commitment_and_history(&parsed, self.commitment(), true)
+                .await,
+            Ok(None)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        EXPIRED_BEFORE_LANDING, SendOutcome, WithdrawalBroadcaster, WithdrawalError,
+        WithdrawalIntent, WithdrawalLedger, WithdrawalSigner, orchestrate_withdrawal,
+        settle_withdrawal, sol_to_lamports,
+    };
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use rust_decimal::Decimal;
+    use solana_sdk::{
+        hash::Hash,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+    use std::cell::RefCell;
+    use std::str::FromStr;
+    use store::user::StoreError;
+    use uuid::Uuid;
+
+    #[derive(Debug, PartialEq)]
+    enum Row {
+        Pending,
+        Submitted(String),
+        Confirmed(String),
+        Failed(String),
+    }
+
+    /// In-memory ledger that only debits on `complete`, like `record_withdrawal_debit`
+    struct MockLedger {
+        wallet: String,
+        balance: RefCell<Decimal>,
+        rows: RefCell<Vec<(Uuid, Decimal, Row)>>,
+    }
+
+    impl MockLedger {
+        fn new(wallet: &Keypair, balance: Decimal) -> Self {
+            Self {
+                wallet: wallet.pubkey().to_string(),
+                balance: RefCell::new(balance),
+                rows: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn set_row(&self, transaction_id: Uuid, state: Row) {
+            let mut rows = self.rows.borrow_mut();
+            let row = rows.iter_mut().find(|row| row.0 == transaction_id).unwrap();
+            row.2 = state;
+        }
+    }
+
+    impl WithdrawalLedger for MockLedger {
+        async fn open(&self, intent: &WithdrawalIntent) -> Result<(Uuid, String), StoreError> {
+            let id = Uuid::new_v4();
+            self.rows
+                .borrow_mut()
+                .push((id, intent.amount, Row::Pending));
+            Ok((id, self.wallet.clone()))
+        }
+
+        async fn submit(
+            &self,
+            transaction_id: Uuid,
+            signature: String,
+            _last_valid_block_height: u64,
+        ) -> Result<(), StoreError> {
+            self.set_row(transaction_id, Row::Submitted(signature));
+            Ok(())
+        }
+
+        async fn complete(
+            &self,
+            transaction_id: Uuid,
+            signature: String,
+        ) -> Result<(), StoreError> {
+            let amount = self
+                .rows
+                .borrow()
+                .iter()
+                .find(|row| row.0 == transaction_id)
+                .unwrap()
+                .1;
+            *self.balance.borrow_mut() -= amount;
+            self.set_row(transaction_id, Row::Confirmed(signature));
+            Ok(())
+        }
+
+        async fn fail(&self, transaction_id: Uuid, reason: String) -> Result<(), StoreError> {
+            self.set_row(transaction_id, Row::Failed(reason));
+            Ok(())
+        }
+    }
+
+    struct MockSigner(Result<String, String>);
+
+    impl MockSigner {
+        /// Signs a transfer paid from `payer`, as the MPC aggregation would
+        fn paying_from(payer: &Keypair) -> Self {
+            let transfer = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer],
+                Some(&payer.pubkey()),
+                &[payer],
+                Hash::default(),
+            );
+            MockSigner(Ok(
+                STANDARD.encode(bincode::serialize(&transaction).unwrap())
+            ))
+        }
+    }
+
+    impl WithdrawalSigner for MockSigner {
+        async fn sign(&self, _intent: &WithdrawalIntent) -> Result<String, String> {
+            self.0.clone()
+        }
+    }
+
+    struct MockRpc {
+        sent: Result<(), String>,
+        outcome: SendOutcome,
+        /// Block height the chain has reached
+        block_height: u64,
+        broadcasts: RefCell<usize>,
+    }
+
+    impl MockRpc {
+        fn new(sent: Result<(), String>, outcome: SendOutcome) -> Self {
+            Self {
+                sent,
+                outcome,
+                block_height: 0,
+                broadcasts: RefCell::new(0),
+            }
+        }
+
+        fn at_height(mut self, block_height: u64) -> Self {
+            self.block_height = block_height;
+            self
+        }
+    }
+
+    impl WithdrawalBroadcaster for MockRpc {
+        async fn last_valid_block_height(&self) -> Option<u64> {
+            Some(self.block_height + 150)
+        }
+
+        async fn broadcast(&self, _transaction: &Transaction) -> Result<(), String> {
+            *self.broadcasts.borrow_mut() += 1;
+            self.sent.clone()
+        }
+
+        async fn outcome(&self, _signature: &str) -> SendOutcome {
+            self.outcome.clone()
+        }
+
+        async fn expired(&self, _signature: &str, last_valid_block_height: u64) -> bool {
+            self.outcome == SendOutcome::Unknown && self.block_height > last_valid_block_height
+        }
+    }
+
+    fn intent() -> WithdrawalIntent {
+        WithdrawalIntent {
+            user_id: Uuid::new_v4(),
+            to: "11111111111111111111111111111111".to_string(),
+            amount: Decimal::ONE,
+            idempotency_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_broadcast_leaves_balance_untouched() {
+        let wallet = Keypair::new();
+        let ledger = MockLedger::new(&wallet, Decimal::TEN);
+        let rpc = MockRpc::new(
+            Err("transaction simulation failed".to_string()),
+            SendOutcome::Rejected("insufficient funds".to_string()),
+        );
+
+        let result =
+            orchestrate_withdrawal(&ledger, &MockSigner::paying_from(&wallet), &rpc, &intent())
+                .await;
+
+        assert!(matches!(result, Err(WithdrawalError::Broadcast(_))));
+        assert_eq!(*ledger.balance.borrow(), Decimal::TEN);
+        let rows = ledger.rows.borrow();
+        assert!(matches!(&rows[0].2, Row::Failed(reason) if reason.contains("insufficient funds")));
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_broadcast_stays_pending() {
+        let wallet = Keypair::new();
+        let ledger = MockLedger::new(&wallet, Decimal::TEN);
+        let rpc = MockRpc::new(
+            Err("unable to confirm transaction".to_string()),
+            SendOutcome::Unknown,
+        );
+
+        let result =
+            orchestrate_withdrawal(&ledger, &MockSigner::paying_from(&wallet), &rpc, &intent())
+                .await;
+
+        let (transaction_id, signature) = match result {
+            Err(WithdrawalError::Unconfirmed {
+                transaction_id,
+                signature,
+                ..
+            }) => (transaction_id, signature),
+            other => panic!("expected an unconfirmed withdrawal, got {:?}", other),
+        };
+        assert_eq!(*ledger.balance.borrow(), Decimal::TEN);
+        assert_eq!(ledger.rows.borrow()[0].2, Row::Submitted(signature.clone()));
+
+        // Once the chain reports it landed, settlement debits it
+        let landed = MockRpc::new(Ok(()), SendOutcome::Landed);
+        let outcome = settle_withdrawal(
+            &ledger,
+            &landed,
+            transaction_id,
+            signature.clone(),
+            Some(150),
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome, SendOutcome::Landed);
+        assert_eq!(*ledger.balance.borrow(), Decimal::new(9, 0));
+        assert_eq!(ledger.rows.borrow()[0].2, Row::Confirmed(signature));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_error_after_landing_still_debits() {
+        let wallet = Keypair::new();
+        let ledger = MockLedger::new(&wallet, Decimal::TEN);
+        let rpc = MockRpc::new(Err("request timed out".to_string()), SendOutcome::Landed);
+
+        let (_, signature) =
+            orchestrate_withdrawal(&ledger, &MockSigner::paying_from(&wallet), &rpc, &intent())
+                .await
+                .unwrap();
+
+        assert_eq!(*ledger.balance.borrow(), Decimal::new(9, 0));
+        assert_eq!(ledger.rows.borrow()[0].2, Row::Confirmed(signature));
+    }
+
+    #[tokio::test]
+    async fn test_failed_signing_is_never_broadcast() {
+        let ledger = MockLedger::new(&Keypair::new(), Decimal::TEN);
+        let signer = MockSigner(Err("missing partial signature".to_string()));
+        // Would succeed if it were reached
+        let rpc = MockRpc::new(Ok(()), SendOutcome::Landed);
+
+        let result = orchestrate_withdrawal(&ledger, &signer, &rpc, &intent()).await;
+
+        assert!(matches!(result, Err(WithdrawalError::Signing(_))));
+        assert_eq!(*rpc.broadcasts.borrow(), 0);
+        assert_eq!(*ledger.balance.borrow(), Decimal::TEN);
+        assert!(matches!(ledger.rows.borrow()[0].2, Row::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_from_another_wallet_is_never_broadcast() {
+        let ledger = MockLedger::new(&Keypair::new(), Decimal::TEN);
+        let rpc = MockRpc::new(Ok(()), SendOutcome::Landed);
+
+        let result = orchestrate_withdrawal(
+            &ledger,
+            &MockSigner::paying_from(&Keypair::new()),
+            &rpc,
+            &intent(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(WithdrawalError::WrongWallet { .. })));
+        assert_eq!(*rpc.broadcasts.borrow(), 0);
+        assert!(matches!(ledger.rows.borrow()[0].2, Row::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_successful_withdrawal_debits_once() {
+        let wallet = Keypair::new();
+        let ledger = MockLedger::new(&wallet, Decimal::TEN);
+        let rpc = MockRpc::new(Ok(()), SendOutcome::Unknown);
+
+        let (_, signature) =
+            orchestrate_withdrawal(&ledger, &MockSigner::paying_from(&wallet), &rpc, &intent())
+                .await
+                .unwrap();
+
+        assert_eq!(*rpc.broadcasts.borrow(), 1);
+        assert_eq!(*ledger.balance.borrow(), Decimal::new(9, 0));
+        assert_eq!(ledger.rows.borrow()[0].2, Row::Confirmed(signature));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_withdrawal_fails_once_its_blockhash_expires() {
+        let wallet = Keypair::new();
+        let ledger = MockLedger::new(&wallet, Decimal::TEN);
+        let rpc = MockRpc::new(Err("request timed out".to_string()), SendOutcome::Unknown);
+
+        let (transaction_id, signature) = match orchestrate_withdrawal(
+            &ledger,
+            &MockSigner::paying_from(&wallet),
+            &rpc,
+            &intent(),
+        )
+        .await
+        {
+            Err(WithdrawalError::Unconfirmed {
+                transaction_id,
+                signature,
+                ..
+            }) => (transaction_id, signature),
+            other => panic!("expected an unconfirmed withdrawal, got {:?}", other),
+        };
+
+        // Unseen but still within its blockhash's lifetime, so it may yet land
+        let unseen = MockRpc::new(Ok(()), SendOutcome::Unknown).at_height(150);
+        let outcome = settle_withdrawal(
+            &ledger,
+            &unseen,
+            transaction_id,
+            signature.clone(),
+            Some(150),
+        )
+        .await
+        .unwrap();
+        assert_eq!(outcome, SendOutcome::Unknown);
+        assert_eq!(ledger.rows.borrow()[0].2, Row::Submitted(signature.clone()));
+
+        // Without a recorded height it can never be ruled out
+        let past = MockRpc::new(Ok(()), SendOutcome::Unknown).at_height(151);
+        let outcome = settle_withdrawal(&ledger, &past, transaction_id, signature.clone(), None)
+            .await
+            .unwrap();
+        assert_eq!(outcome, SendOutcome::Unknown);
+
+        // Once the chain is past it, it can no longer land and the hold is released
+        let outcome = settle_withdrawal(&ledger, &past, transaction_id, signature, Some(150))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            SendOutcome::Rejected(EXPIRED_BEFORE_LANDING.to_string())
+        );
+        assert_eq!(*ledger.balance.borrow(), Decimal::TEN);
+        assert_eq!(
+            ledger.rows.borrow()[0].2,
+            Row::Failed(EXPIRED_BEFORE_LANDING.to_string())
+        );
+    }
+
+    #[test]
+    fn test_sol_to_lamports_is_exact() {
+        // 16 significant digits: an f64 would round this to a different lamport count
+        let amount = Decimal::from_str("99999999.99999999").unwrap();
+        assert_eq!(sol_to_lamports(amount).unwrap(), 99_999_999_999_999_990);
+        assert_eq!(sol_to_lamports(Decimal::new(1, 8)).unwrap(), 10);
+
+        // Finer than the ledger keeps, negative, or past u64
+        for amount in ["0.000000001", "0.123456789", "-1", "18446744074"] {
+            assert!(matches!(
+                sol_to_lamports(Decimal::from_str(amount).unwrap()),
+                Err(StoreError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[sqlx::test(migrations = "../store/migration")]
+    async fn test_store_debits_exactly_the_lamports_sent(pool: sqlx::PgPool) {
+        let store = store::Store::from_pool(pool);
+        let user = store
+            .create_user(store::user::CreateUserRequest {
+                email: "alice@example.com".to_string(),
+                password: "password123".to_string(),
+            })
+            .await
+            .unwrap();
+        store.add_user_balance(user.id, Decimal::TEN).await.unwrap();
+        store
+            .update_user_agg_pubkey(user.id, &Keypair::new().pubkey().to_string())
+            .await
+            .unwrap();
+        let intent = |amount: &str| WithdrawalIntent {
+            user_id: user.id,
+            to: Pubkey::new_unique().to_string(),
+            amount: Decimal::from_str(amount).unwrap(),
+            idempotency_key: Some(format!("withdraw-{}", amount)),
+        };
+
+        // Postgres would round this one to 8 places, so it is refused before a row is opened
+        assert!(matches!(
+            WithdrawalLedger::open(&store, &intent("0.123456789")).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+
+        let withdrawal = intent("0.12345678");
+        let (id, _) = WithdrawalLedger::open(&store, &withdrawal).await.unwrap();
+        WithdrawalLedger::complete(&store, id, "sig".to_string())
+            .await
+            .unwrap();
+
+        let stored = store
+            .get_transaction_by_idempotency_key(user.id, "withdraw-0.12345678")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.id, id);
+
+        let lamports = sol_to_lamports(withdrawal.amount).unwrap();
+        let debited = Decimal::TEN - store.get_user_balance(user.id).await.unwrap();
+        assert_eq!(
+            debited,
+            Decimal::from(lamports) / Decimal::from(1_000_000_000u64)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use actix_web::{
+    HttpResponse, Result,
+    dev::ServiceResponse,
+    http::header,
+    middleware::{ErrorHandlerResponse, ErrorHandlers},
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    status: u16,
+}
+
+/// Render every error response that isn't already JSON (unknown routes, wrong methods,
+/// payload/deserialization failures, handler errors) as `{ "error": "...", "status": N }`
+pub fn json_error_handlers<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().default_handler(render_json_error)
+}
+
+fn render_json_error<B>(res: ServiceResponse<B>) -> Result<ErrorHandlerResponse<B>> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_json {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let status = res.status();
+    let error = res
+        .response()
+        .error()
+        .map(|e| e.to_string())
+        .filter(|message| !message.is_empty())
+        .unwrap_or_else(|| {
+            status
+                .canonical_reason()
+                .unwrap_or("Unknown error")
+                .to_string()
+        });
+
+    let (req, _) = res.into_parts();
+    let response = HttpResponse::build(status).json(ErrorBody {
+        error,
+        status: status.as_u16(),
+    });
+
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, response).map_into_right_body(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::json_error_handlers;
+    use actix_web::{App, HttpResponse, test, web};
+    use serde_json::Value;
+
+    #[actix_web::test]
+    async fn test_default_errors_are_json() {
+        let app = test::init_service(
+            App::new()
+                .wrap(json_error_handlers())
+                // A resource, so the wrong method gets a 405 rather than falling through to 404
+                .service(web::resource("/ping").route(web::get().to(HttpResponse::Ok))),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/missing").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["error"], "Not Found");
+
+        let req = test::TestRequest::post().uri("/ping").to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["status"], 405);
+        assert_eq!(body["error"], "Method Not Allowed");
+    }
+}
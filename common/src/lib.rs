@@ -0,0 +1,5 @@
+//! HTTP plumbing shared by the backend and the MPC server, so both answer errors and carry
+//! traces the same way
+
+pub mod error;
+pub mod trace;
@@ -0,0 +1,225 @@
+use actix_web::{
+    Error, FromRequest, HttpMessage, HttpRequest,
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+};
+use rand::RngCore;
+use std::future::{Ready, ready};
+use std::time::Instant;
+use tracing::Instrument;
+
+/// W3C trace context header, set by the backend on calls into the MPC server
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Correlation id echoed on every response; a caller-supplied one is kept, otherwise the
+/// trace id is used, so a client can quote it when reporting a failed call
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// Accept printable ASCII up to a sane length, so a hostile id can't bloat or break log lines
+fn valid_request_id(value: &str) -> bool {
+    !value.is_empty() && value.len() <= 128 && value.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Trace this request belongs to, so logs can be followed across backend and MPC calls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String, // 32 lowercase hex chars
+    pub span_id: String,  // 16 lowercase hex chars, this service's span
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` value (`00-<trace_id>-<parent_id>-<flags>`), continuing the
+    /// caller's trace under a fresh span id
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        };
+        if version != "00"
+            || parts.next().is_some()
+            || !is_hex(trace_id, 32)
+            || !is_hex(parent_id, 16)
+            || !is_hex(flags, 2)
+            || trace_id.chars().all(|c| c == '0')
+        {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            span_id: new_span_id(),
+        })
+    }
+
+    /// Start a new trace for requests that arrive without one
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_hex::<16>(),
+            span_id: new_span_id(),
+        }
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-01", self.trace_id, self.span_id)
+    }
+}
+
+fn new_span_id() -> String {
+    random_hex::<8>()
+}
+
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Continue (or start) the request's trace: logs emitted while handling it carry the trace
+/// and request ids, completion is logged with status and latency, and both the `traceparent`
+/// and `x-request-id` are echoed back on the response
+pub async fn propagate_trace(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let trace = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(TraceContext::from_traceparent)
+        .unwrap_or_else(TraceContext::new_root);
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| valid_request_id(value))
+        .map_or_else(|| trace.trace_id.clone(), str::to_string);
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        trace_id = %trace.trace_id,
+        span_id = %trace.span_id,
+        method = %req.method(),
+        path = %req.path()
+    );
+    let traceparent = trace.traceparent();
+    req.extensions_mut().insert(trace);
+
+    let started = Instant::now();
+    let mut res = next.call(req).instrument(span.clone()).await?;
+    tracing::info!(
+        parent: &span,
+        status = res.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64,
+        "request completed"
+    );
+    for (name, value) in [(TRACEPARENT, &traceparent), (REQUEST_ID, &request_id)] {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            res.headers_mut()
+                .insert(HeaderName::from_static(name), value);
+        }
+    }
+    Ok(res)
+}
+
+impl FromRequest for TraceContext {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let trace = req
+            .extensions()
+            .get::<TraceContext>()
+            .cloned()
+            .unwrap_or_else(TraceContext::new_root);
+        ready(Ok(trace))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REQUEST_ID, TRACEPARENT, TraceContext, propagate_trace};
+    use actix_web::test::{TestRequest, call_service, init_service, read_body};
+    use actix_web::{App, HttpResponse, middleware::from_fn, web};
+
+    const INCOMING: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_traceparent() {
+        let trace = TraceContext::from_traceparent(INCOMING).unwrap();
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(trace.span_id, "00f067aa0ba902b7");
+        assert_eq!(trace.span_id.len(), 16);
+
+        assert!(TraceContext::from_traceparent("01-abc-def-01").is_none());
+        assert!(
+            TraceContext::from_traceparent(
+                "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+            )
+            .is_none()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_handler_sees_incoming_trace_id() {
+        let app =
+            init_service(App::new().wrap(from_fn(propagate_trace)).route(
+                "/trace",
+                web::get().to(|trace: TraceContext| async move {
+                    HttpResponse::Ok().body(trace.trace_id)
+                }),
+            ))
+            .await;
+
+        let req = TestRequest::get()
+            .uri("/trace")
+            .insert_header((TRACEPARENT, INCOMING))
+            .to_request();
+        let res = call_service(&app, req).await;
+        let traceparent = res.headers().get(TRACEPARENT).unwrap().to_str().unwrap();
+        assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+
+        let body = read_body(res).await;
+        assert_eq!(body, "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_round_trips() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(propagate_trace))
+                .route("/ping", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/ping")
+            .insert_header((REQUEST_ID, "client-req-42"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID).unwrap(), "client-req-42");
+
+        // Without one, the trace id doubles as the correlation id
+        let req = TestRequest::get()
+            .uri("/ping")
+            .insert_header((TRACEPARENT, INCOMING))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(REQUEST_ID).unwrap(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        let req = TestRequest::get()
+            .uri("/ping")
+            .insert_header((REQUEST_ID, "has spaces"))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_ne!(res.headers().get(REQUEST_ID).unwrap(), "has spaces");
+    }
+}
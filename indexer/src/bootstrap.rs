@@ -0,0 +1,171 @@
+use serde_json::json;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{OptionalContext, RpcKeyedAccount};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_upgradeable;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::token_account::TOKEN_PROGRAM_ID;
+use crate::{AccountFilter, AccountUpdate};
+
+/// One `getProgramAccounts` scan per owner-style filter, narrowed by any `Memcmp`/`DataSize`
+/// filters the same way the subscription is. `Account`/`Accounts` filters are not scanned.
+pub fn snapshot_queries(filters: &[AccountFilter]) -> Vec<(Pubkey, Vec<RpcFilterType>)> {
+    let data_filters: Vec<RpcFilterType> = filters
+        .iter()
+        .filter_map(|filter| match filter {
+            AccountFilter::Memcmp { offset, bytes } => Some(RpcFilterType::Memcmp(
+                Memcmp::new_raw_bytes(*offset as usize, bytes.clone()),
+            )),
+            AccountFilter::DataSize(size) => Some(RpcFilterType::DataSize(*size)),
+            _ => None,
+        })
+        .collect();
+
+    filters
+        .iter()
+        .filter_map(|filter| match filter {
+            AccountFilter::Owner(owner) => Some(*owner),
+            AccountFilter::ProgramData => Some(bpf_loader_upgradeable::id()),
+            AccountFilter::TokenAccount => Pubkey::from_str(TOKEN_PROGRAM_ID).ok(),
+            _ => None,
+        })
+        .map(|program| (program, data_filters.clone()))
+        .collect()
+}
+
+/// A snapshot account as read at `slot`: it replaces anything persisted from earlier slots, and
+/// any streamed update from a later slot replaces it
+pub fn seed_update(pubkey: &Pubkey, account: Account, slot: u64) -> AccountUpdate {
+    AccountUpdate {
+        pubkey: pubkey.to_string(),
+        lamports: account.lamports,
+        owner: account.owner.to_string(),
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+        data: account.data,
+        write_version: 0,
+        slot,
+    }
+}
+
+/// Current state of every account the filters would stream, read over RPC and stamped with the
+/// slot each scan was served at
+pub async fn fetch_snapshot(
+    rpc: &RpcClient,
+    filters: &[AccountFilter],
+) -> Result<Vec<AccountUpdate>, ClientError> {
+    // Only used if a node ignores `with_context`; taken before any scan, it never dates the
+    // snapshot later than it was read
+    let floor_slot = rpc.get_slot().await?;
+
+    let mut snapshot = Vec::new();
+    for (program, data_filters) in snapshot_queries(filters) {
+        let config = RpcProgramAccountsConfig {
+            filters: (!data_filters.is_empty()).then_some(data_filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            with_context: Some(true),
+        };
+        // Sent raw because `get_program_accounts_with_config` drops the response context
+        let response: OptionalContext<Vec<RpcKeyedAccount>> = rpc
+            .send(
+                RpcRequest::GetProgramAccounts,
+                json!([program.to_string(), config]),
+            )
+            .await?;
+        let (slot, accounts) = match response {
+            OptionalContext::Context(response) => (response.context.slot, response.value),
+            OptionalContext::NoContext(accounts) => (floor_slot, accounts),
+        };
+        info!(
+            "Bootstrapped {} accounts owned by {} at slot {}",
+            accounts.len(),
+            program,
+            slot
+        );
+        snapshot.extend(accounts.into_iter().filter_map(|keyed| {
+            let pubkey = Pubkey::from_str(&keyed.pubkey).ok()?;
+            let account = keyed.account.decode::<Account>()?;
+            Some(seed_update(&pubkey, account, slot))
+        }));
+    }
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{seed_update, snapshot_queries};
+    use crate::AccountFilter;
+    use crate::cache::AccountCache;
+    use crate::token_account::TOKEN_PROGRAM_ID;
+    use solana_client::rpc_filter::RpcFilterType;
+    use solana_sdk::account::Account;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_snapshot_queries_follow_owner_filters() {
+        let owner = Pubkey::new_unique();
+        let queries = snapshot_queries(&[
+            AccountFilter::Owner(owner),
+            AccountFilter::Account(Pubkey::new_unique()),
+            AccountFilter::TokenAccount,
+            AccountFilter::DataSize(165),
+        ]);
+
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].0, owner);
+        assert_eq!(queries[1].0.to_string(), TOKEN_PROGRAM_ID);
+        assert!(
+            queries
+                .iter()
+                .all(|(_, filters)| matches!(filters[..], [RpcFilterType::DataSize(165)]))
+        );
+    }
+
+    #[test]
+    fn test_seeded_accounts_are_readable_before_streaming() {
+        let owner = Pubkey::new_unique();
+        let pubkey = Pubkey::new_unique();
+        let account = Account {
+            lamports: 1_000,
+            data: vec![1, 2, 3],
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        let mut cache = AccountCache::new();
+        let mut stale = seed_update(&pubkey, account.clone(), 5);
+        stale.lamports = 500;
+        stale.write_version = 7;
+        cache.apply(stale.clone());
+
+        // The snapshot replaces whatever was recorded before it was read
+        assert!(cache.apply(seed_update(&pubkey, account, 10)));
+        let seeded = cache.get(&pubkey.to_string()).unwrap().clone();
+        assert_eq!(seeded.lamports, 1_000);
+        assert_eq!(seeded.owner, owner.to_string());
+
+        // Buffered stream updates from before the snapshot don't roll it back
+        assert!(!cache.apply(stale));
+        assert_eq!(cache.get(&pubkey.to_string()).unwrap().lamports, 1_000);
+
+        // A streamed update from a later slot replaces the snapshot
+        let mut streamed = seeded;
+        streamed.lamports = 2_000;
+        streamed.slot = 11;
+        streamed.write_version = 1;
+        assert!(cache.apply(streamed));
+        assert_eq!(cache.get(&pubkey.to_string()).unwrap().lamports, 2_000);
+    }
+}
@@ -0,0 +1,292 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::{AccountUpdate, TransactionUpdate};
+
+/// Log evictions once per this many, rather than on every one
+const EVICTION_LOG_INTERVAL: u64 = 1_000;
+
+/// Aggregate view of the tracked accounts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IndexerMetrics {
+    pub total_accounts: usize,
+    pub per_owner_counts: BTreeMap<String, usize>,
+    pub total_lamports: u64,
+}
+
+/// Latest state of each account, built from the indexer's update channel (or fed directly).
+/// With a `max_accounts` cap the least recently updated accounts are evicted first.
+#[derive(Debug, Default)]
+pub struct AccountCache {
+    /// Each account with the sequence number of its last update
+    accounts: HashMap<String, (AccountUpdate, u64)>,
+    /// Update order: sequence number to pubkey, oldest first
+    order: BTreeMap<u64, String>,
+    next_seq: u64,
+    max_accounts: Option<usize>,
+    evicted: u64,
+}
+
+impl AccountCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A cache holding at most `max_accounts` accounts (`None` for unbounded)
+    pub fn with_max_accounts(max_accounts: Option<usize>) -> Self {
+        Self {
+            max_accounts,
+            ..Self::default()
+        }
+    }
+
+    /// Keep `update` unless a newer version (by slot, then write_version) is already cached.
+    /// Returns whether it was kept.
+    pub fn apply(&mut self, update: AccountUpdate) -> bool {
+        if let Some((current, seq)) = self.accounts.get(&update.pubkey) {
+            if (current.slot, current.write_version) > (update.slot, update.write_version) {
+                return false;
+            }
+            self.order.remove(seq);
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.order.insert(seq, update.pubkey.clone());
+        self.accounts.insert(update.pubkey.clone(), (update, seq));
+        self.evict_over_cap();
+        true
+    }
+
+    fn evict_over_cap(&mut self) {
+        let Some(max_accounts) = self.max_accounts else {
+            return;
+        };
+        while self.accounts.len() > max_accounts {
+            let Some((_, pubkey)) = self.order.pop_first() else {
+                break;
+            };
+            self.accounts.remove(&pubkey);
+            self.evicted += 1;
+            if self.evicted % EVICTION_LOG_INTERVAL == 0 {
+                info!(
+                    "Evicted {} least recently updated accounts (cap {})",
+                    self.evicted, max_accounts
+                );
+            }
+        }
+    }
+
+    /// Accounts dropped so far to stay under the cap
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Apply updates until the indexer drops its sender
+    pub async fn consume(&mut self, mut updates: mpsc::Receiver<AccountUpdate>) {
+        while let Some(update) = updates.recv().await {
+            self.apply(update);
+        }
+    }
+
+    pub fn get(&self, pubkey: &str) -> Option<&AccountUpdate> {
+        self.accounts.get(pubkey).map(|(account, _)| account)
+    }
+
+    pub fn by_owner(&self, owner: &str) -> Vec<&AccountUpdate> {
+        self.values()
+            .filter(|account| account.owner == owner)
+            .collect()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &AccountUpdate> {
+        self.accounts.values().map(|(account, _)| account)
+    }
+
+    /// Account count per owner and lamports summed across every cached account, in one pass
+    pub fn metrics(&self) -> IndexerMetrics {
+        let mut metrics = IndexerMetrics {
+            total_accounts: self.len(),
+            ..IndexerMetrics::default()
+        };
+        for account in self.values() {
+            *metrics
+                .per_owner_counts
+                .entry(account.owner.clone())
+                .or_default() += 1;
+            metrics.total_lamports = metrics.total_lamports.saturating_add(account.lamports);
+        }
+        metrics
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+/// Ring buffer of the latest decoded transactions; the oldest is dropped once it is full
+#[derive(Debug)]
+pub struct RecentTransactions {
+    transactions: VecDeque<TransactionUpdate>,
+    capacity: usize,
+}
+
+impl RecentTransactions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            transactions: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record `transaction` unless its signature is already held, e.g. redelivered after a resubscribe
+    pub fn push(&mut self, transaction: TransactionUpdate) {
+        if self.get(&transaction.signature).is_some() {
+            return;
+        }
+        if self.transactions.len() == self.capacity {
+            self.transactions.pop_front();
+        }
+        self.transactions.push_back(transaction);
+    }
+
+    pub fn get(&self, signature: &str) -> Option<&TransactionUpdate> {
+        self.transactions
+            .iter()
+            .find(|transaction| transaction.signature == signature)
+    }
+
+    /// Newest first
+    pub fn iter(&self) -> impl Iterator<Item = &TransactionUpdate> {
+        self.transactions.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountCache, IndexerMetrics, RecentTransactions};
+    use crate::{AccountUpdate, TransactionUpdate};
+    use tokio::sync::mpsc;
+
+    fn update(pubkey: &str, lamports: u64, slot: u64) -> AccountUpdate {
+        AccountUpdate {
+            pubkey: pubkey.to_string(),
+            lamports,
+            owner: "11111111111111111111111111111111".to_string(),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            write_version: 1,
+            slot,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_consumes_channel() {
+        let (sender, receiver) = mpsc::channel(8);
+        tokio::spawn(async move {
+            for account in [update("a", 1, 10), update("b", 2, 10), update("a", 3, 11)] {
+                sender.send(account).await.unwrap();
+            }
+        });
+
+        let mut cache = AccountCache::new();
+        cache.consume(receiver).await;
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a").unwrap().lamports, 3);
+        assert_eq!(cache.by_owner("11111111111111111111111111111111").len(), 2);
+    }
+
+    #[test]
+    fn test_stale_update_is_ignored() {
+        let mut cache = AccountCache::new();
+        assert!(cache.apply(update("a", 5, 20)));
+        assert!(!cache.apply(update("a", 1, 19)));
+        assert_eq!(cache.get("a").unwrap().lamports, 5);
+    }
+
+    #[test]
+    fn test_cap_evicts_least_recently_updated() {
+        let mut cache = AccountCache::with_max_accounts(Some(3));
+        for (i, pubkey) in ["a", "b", "c"].iter().enumerate() {
+            cache.apply(update(pubkey, 1, i as u64));
+        }
+        // Touching "a" makes "b" the least recently updated
+        cache.apply(update("a", 2, 10));
+
+        for (i, pubkey) in ["d", "e"].iter().enumerate() {
+            cache.apply(update(pubkey, 1, 20 + i as u64));
+        }
+
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.evicted(), 2);
+        assert!(cache.get("b").is_none() && cache.get("c").is_none());
+        assert_eq!(cache.get("a").unwrap().lamports, 2);
+        assert_eq!(cache.by_owner("11111111111111111111111111111111").len(), 3);
+    }
+
+    #[test]
+    fn test_metrics_count_accounts_per_owner() {
+        let mut cache = AccountCache::new();
+        cache.apply(update("a", 10, 1));
+        cache.apply(update("b", 20, 1));
+        cache.apply(AccountUpdate {
+            owner: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+            ..update("c", 5, 1)
+        });
+        // A newer version of "a" replaces it rather than counting twice
+        cache.apply(update("a", 15, 2));
+
+        assert_eq!(
+            cache.metrics(),
+            IndexerMetrics {
+                total_accounts: 3,
+                per_owner_counts: [
+                    ("11111111111111111111111111111111".to_string(), 2),
+                    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(), 1),
+                ]
+                .into_iter()
+                .collect(),
+                total_lamports: 40,
+            }
+        );
+    }
+
+    #[test]
+    fn test_recent_transactions_are_bounded() {
+        let transaction = |signature: &str, slot: u64| TransactionUpdate {
+            signature: signature.to_string(),
+            slot,
+            is_vote: false,
+            err: None,
+            fee: Some(5000),
+            accounts: vec!["custody".to_string()],
+        };
+        let mut recent = RecentTransactions::new(2);
+        recent.push(transaction("a", 1));
+        recent.push(transaction("b", 2));
+        // Redelivered after a resubscribe
+        recent.push(transaction("a", 1));
+        recent.push(transaction("c", 3));
+
+        assert_eq!(recent.len(), 2);
+        assert!(recent.get("a").is_none());
+        let signatures: Vec<&str> = recent.iter().map(|tx| tx.signature.as_str()).collect();
+        assert_eq!(signatures, ["c", "b"]);
+    }
+}
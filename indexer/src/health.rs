@@ -0,0 +1,110 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Liveness of the indexer, shared between the subscription loop and the `/health` route
+#[derive(Debug, Default)]
+pub struct IndexerHealth {
+    grpc_healthy: AtomicBool,
+    subscribed: AtomicBool,
+    last_message_at: AtomicU64, // Unix seconds, 0 until the first message arrives
+    last_ping_at: AtomicU64,    // Unix seconds, 0 until the server first pings
+    account_count: AtomicUsize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    pub grpc_healthy: bool,
+    pub subscribed: bool,
+    pub seconds_since_last_message: Option<u64>,
+    pub seconds_since_last_ping: Option<u64>,
+    pub account_count: usize,
+    pub healthy: bool,
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl IndexerHealth {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn set_grpc_healthy(&self, healthy: bool) {
+        self.grpc_healthy.store(healthy, Ordering::Relaxed);
+    }
+
+    pub fn set_subscribed(&self, subscribed: bool) {
+        self.subscribed.store(subscribed, Ordering::Relaxed);
+    }
+
+    pub fn record_message(&self, now: u64, account_count: usize) {
+        self.last_message_at.store(now, Ordering::Relaxed);
+        self.account_count.store(account_count, Ordering::Relaxed);
+    }
+
+    pub fn record_ping(&self, now: u64) {
+        self.last_ping_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Snapshot the current state. While subscribed, the indexer is unhealthy if no
+    /// message arrived within `max_staleness_secs` (measured from `now` in unix seconds).
+    pub fn report(&self, now: u64, max_staleness_secs: u64) -> HealthReport {
+        let grpc_healthy = self.grpc_healthy.load(Ordering::Relaxed);
+        let subscribed = self.subscribed.load(Ordering::Relaxed);
+        let last_message_at = self.last_message_at.load(Ordering::Relaxed);
+        let seconds_since_last_message =
+            (last_message_at > 0).then(|| now.saturating_sub(last_message_at));
+        let last_ping_at = self.last_ping_at.load(Ordering::Relaxed);
+        let seconds_since_last_ping = (last_ping_at > 0).then(|| now.saturating_sub(last_ping_at));
+
+        let stale = subscribed
+            && seconds_since_last_message.is_none_or(|elapsed| elapsed > max_staleness_secs);
+
+        HealthReport {
+            grpc_healthy,
+            subscribed,
+            seconds_since_last_message,
+            seconds_since_last_ping,
+            account_count: self.account_count.load(Ordering::Relaxed),
+            healthy: grpc_healthy && !stale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexerHealth;
+
+    #[test]
+    fn test_staleness_flips_status() {
+        let health = IndexerHealth::new();
+        health.set_grpc_healthy(true);
+        health.set_subscribed(true);
+
+        // Subscribed but nothing received yet counts as stale
+        assert!(!health.report(1_000, 30).healthy);
+
+        health.record_message(1_000, 7);
+        let report = health.report(1_010, 30);
+        assert!(report.healthy);
+        assert_eq!(report.seconds_since_last_message, Some(10));
+        assert_eq!(report.account_count, 7);
+
+        assert!(!health.report(1_031, 30).healthy);
+    }
+
+    #[test]
+    fn test_reports_last_ping() {
+        let health = IndexerHealth::new();
+        assert_eq!(health.report(1_000, 30).seconds_since_last_ping, None);
+
+        health.record_ping(1_000);
+        assert_eq!(health.report(1_015, 30).seconds_since_last_ping, Some(15));
+    }
+}
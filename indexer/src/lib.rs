@@ -0,0 +1,1105 @@
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use store::{Store, indexed_account::IndexedAccount};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use yellowstone_grpc_proto::prelude::{
+    CommitmentLevel, SubscribeRequest, SubscribeRequestFilterAccounts,
+    SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterMemcmp,
+    SubscribeRequestFilterTransactions, SubscribeRequestPing, SubscribeUpdateTransactionInfo,
+    subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
+    subscribe_request_filter_accounts_filter_memcmp::Data as MemcmpData,
+    subscribe_update::UpdateOneof,
+};
+pub mod bootstrap;
+pub mod cache;
+pub mod health;
+pub mod stream;
+pub mod token_account;
+pub mod yellowstone;
+
+use crate::bootstrap::fetch_snapshot;
+use crate::cache::{AccountCache, IndexerMetrics, RecentTransactions};
+use crate::health::{IndexerHealth, unix_now};
+use crate::stream::{Backoff, StartupWatch, StreamError, with_reconnect};
+use crate::token_account::{ParsedTokenAccount, TOKEN_PROGRAM_ID, parse_token_account};
+use crate::yellowstone::GeyserGrpcClient;
+
+#[derive(Debug, Clone)]
+pub struct AccountUpdate {
+    pub pubkey: String,
+    pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub data: Vec<u8>,
+    pub write_version: u64,
+    pub slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionUpdate {
+    pub signature: String,
+    pub slot: u64,
+    pub is_vote: bool,
+    /// Why the transaction failed, `None` when it succeeded
+    pub err: Option<String>,
+    pub fee: Option<u64>,
+    /// Static account keys followed by any addresses loaded from lookup tables
+    pub accounts: Vec<String>,
+}
+
+pub struct AccountIndexer {
+    client: GeyserGrpcClient<impl tonic::service::Interceptor>,
+    accounts: AccountCache,
+    transactions: RecentTransactions,
+    /// Caller-supplied channel that receives every account update, for embedding the indexer
+    updates: Option<mpsc::Sender<AccountUpdate>>,
+    /// Persist updates to Postgres; without it the indexer only keeps the in-memory map
+    store: Option<Arc<Store>>,
+    /// Also keep updates in `accounts` (always on without a store, handy for tests)
+    keep_in_memory: bool,
+    persisted_count: usize,
+    /// Highest slot seen in any update; checkpointed to the store as it advances
+    last_slot: Option<u64>,
+    /// Checkpoint loaded at startup, used to report the gap once the stream catches up
+    resumed_from: Option<u64>,
+    health: Arc<IndexerHealth>,
+    commitment: CommitmentLevel,
+    /// Where to read the `getProgramAccounts` snapshot taken on every subscribe, if enabled
+    bootstrap_rpc: Option<Arc<RpcClient>>,
+}
+
+impl AccountIndexer {
+    /// `max_accounts` caps the in-memory map, evicting the least recently updated accounts
+    pub async fn new(
+        endpoint: &str,
+        token: Option<&str>,
+        max_accounts: Option<usize>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut builder = GeyserGrpcClient::build_from_shared(endpoint)?;
+
+        if let Some(token) = token {
+            builder = builder.x_token(Some(token))?;
+        }
+
+        Ok(Self::with_client(builder.connect().await?, max_accounts))
+    }
+
+    fn with_client(
+        client: GeyserGrpcClient<impl tonic::service::Interceptor>,
+        max_accounts: Option<usize>,
+    ) -> Self {
+        Self {
+            client,
+            accounts: AccountCache::with_max_accounts(max_accounts),
+            transactions: RecentTransactions::new(RECENT_TRANSACTIONS_CAPACITY),
+            updates: None,
+            store: None,
+            keep_in_memory: true,
+            persisted_count: 0,
+            last_slot: None,
+            resumed_from: None,
+            health: IndexerHealth::new(),
+            commitment: CommitmentLevel::Confirmed,
+            bootstrap_rpc: None,
+        }
+    }
+
+    /// Persist updates to `store`; the in-memory map is kept only if `keep_in_memory` is set
+    pub fn with_store(self, store: Arc<Store>, keep_in_memory: bool) -> Self {
+        Self {
+            store: Some(store),
+            keep_in_memory,
+            ..self
+        }
+    }
+
+    /// Load the stored checkpoint so `last_processed_slot` starts where the previous run stopped
+    pub async fn resume_from_checkpoint(&mut self) -> Result<Option<u64>, store::user::StoreError> {
+        let Some(store) = &self.store else {
+            return Ok(None);
+        };
+        let checkpoint = store.get_indexer_checkpoint(CHECKPOINT_NAME).await?;
+        match checkpoint {
+            Some(slot) => info!("Resuming after checkpointed slot {}", slot),
+            None => info!("No checkpoint stored, starting fresh"),
+        }
+        self.last_slot = checkpoint;
+        self.resumed_from = checkpoint;
+        Ok(checkpoint)
+    }
+
+    /// Highest slot seen so far (or loaded from the checkpoint)
+    pub fn last_processed_slot(&self) -> Option<u64> {
+        self.last_slot
+    }
+
+    /// Advance the checkpoint to `slot` if it is newer than anything seen
+    async fn record_slot(&mut self, slot: u64) {
+        let Some(slot) = advance_slot(self.last_slot, slot) else {
+            return;
+        };
+        if let Some(resumed_from) = self.resumed_from.take() {
+            info!(
+                "First update at slot {}, {} slots after the checkpoint",
+                slot,
+                slot - resumed_from
+            );
+        }
+        self.last_slot = Some(slot);
+
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_indexer_checkpoint(CHECKPOINT_NAME, slot).await {
+                error!("Failed to checkpoint slot {}: {}", slot, e);
+            }
+        }
+    }
+
+    /// Subscribe at `commitment` instead of the default `Confirmed`
+    pub fn with_commitment(self, commitment: CommitmentLevel) -> Self {
+        Self { commitment, ..self }
+    }
+
+    /// Seed existing accounts over RPC `getProgramAccounts` each time the stream subscribes, so
+    /// lookups work before the stream catches up and changes missed while disconnected are
+    /// picked up. Scans whole programs, so callers opt in.
+    pub fn with_bootstrap(self, rpc: RpcClient) -> Self {
+        Self {
+            bootstrap_rpc: Some(Arc::new(rpc)),
+            ..self
+        }
+    }
+
+    /// Push every account update into `updates`; a full channel applies backpressure to the stream
+    pub fn with_updates(self, updates: mpsc::Sender<AccountUpdate>) -> Self {
+        Self {
+            updates: Some(updates),
+            ..self
+        }
+    }
+
+    pub async fn index_accounts(
+        &mut self,
+        account_filters: Vec<AccountFilter>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.index(account_filters, Vec::new()).await
+    }
+
+    /// Track transactions matching `transaction_filters`, e.g. deposits to custody addresses
+    pub async fn index_transactions(
+        &mut self,
+        transaction_filters: Vec<TransactionFilter>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.index(Vec::new(), transaction_filters).await
+    }
+
+    /// Subscribe to account and transaction updates on one stream
+    pub async fn index(
+        &mut self,
+        account_filters: Vec<AccountFilter>,
+        transaction_filters: Vec<TransactionFilter>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        info!(
+            "Starting indexing with {} account filters and {} transaction filters",
+            account_filters.len(),
+            transaction_filters.len()
+        );
+
+        let request =
+            build_subscribe_request(&account_filters, &transaction_filters, self.commitment);
+
+        let startup_grace = Duration::from_secs(
+            std::env::var("INDEXER_STARTUP_GRACE_SECS")
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .unwrap_or(10),
+        );
+
+        // Keep resubscribing until shutdown; only ctrl-c stops the indexer
+        let backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+        with_reconnect(self, backoff, |indexer| {
+            Box::pin(indexer.subscribe_and_consume(
+                request.clone(),
+                &account_filters,
+                startup_grace,
+            ))
+        })
+        .await
+    }
+
+    /// One subscription: consume updates until the stream fails or ends, both of which warrant a resubscribe
+    async fn subscribe_and_consume(
+        &mut self,
+        request: SubscribeRequest,
+        account_filters: &[AccountFilter],
+        startup_grace: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        info!("Subscribing to account updates...");
+        // Keep the request sink so server pings can be answered on the same stream
+        let (mut sink, mut stream) = self
+            .client
+            .subscribe_with_request(Some(request))
+            .await
+            .map_err(|e| StreamError::Subscribe(e.to_string()))?;
+        self.health.set_subscribed(true);
+        let mut watch = StartupWatch::new(startup_grace);
+
+        // The snapshot is read only once the subscription is open, so nothing changed during the
+        // scan falls in a gap. It runs alongside the stream; slot order decides which version
+        // of an account wins, whichever arrives first.
+        let mut snapshot = self.bootstrap_rpc.clone().map(|rpc| {
+            let filters = account_filters.to_vec();
+            tokio::spawn(async move { fetch_snapshot(&rpc, &filters).await })
+        });
+
+        // Handle updates
+        loop {
+            let update = tokio::select! {
+                fetched = async { snapshot.as_mut().expect("guarded by is_some").await },
+                    if snapshot.is_some() =>
+                {
+                    snapshot = None;
+                    let accounts = match fetched {
+                        Ok(Ok(accounts)) => accounts,
+                        Ok(Err(e)) => {
+                            self.health.set_subscribed(false);
+                            return Err(e.into());
+                        }
+                        Err(e) => {
+                            self.health.set_subscribed(false);
+                            return Err(e.into());
+                        }
+                    };
+                    let seeded = accounts.len();
+                    for account in accounts {
+                        self.record_account(account).await;
+                    }
+                    info!("Seeded {} accounts from the bootstrap snapshot", seeded);
+                    continue;
+                }
+                update = stream.next() => update,
+            };
+            let Some(update) = update else {
+                break;
+            };
+            match update {
+                Ok(msg) => {
+                    watch.record_message();
+                    if let Some(update_oneof) = msg.update_oneof {
+                        if let Some(reply) = ping_reply(&update_oneof) {
+                            self.health.record_ping(unix_now());
+                            if let Err(e) = sink.send(reply).await {
+                                warn!("Failed to answer server ping: {}", e);
+                            }
+                        }
+                        self.handle_update(update_oneof).await;
+                    }
+                }
+                Err(status) => {
+                    error!("Stream error: {}", status);
+                    self.health.set_subscribed(false);
+                    return Err(watch.on_error(status).into());
+                }
+            }
+        }
+
+        self.health.set_subscribed(false);
+        watch.on_close()?;
+        Err(StreamError::Ended(watch.received()).into())
+    }
+
+    async fn handle_update(&mut self, update: UpdateOneof) {
+        match update {
+            UpdateOneof::Account(account_update) => {
+                self.record_slot(account_update.slot).await;
+                if let Some(account) = account_update.account {
+                    let pubkey = bs58::encode(&account.pubkey).into_string();
+
+                    let account_data = AccountUpdate {
+                        pubkey: pubkey.clone(),
+                        lamports: account.lamports,
+                        owner: bs58::encode(&account.owner).into_string(),
+                        executable: account.executable,
+                        rent_epoch: account.rent_epoch,
+                        data: account.data,
+                        write_version: account.write_version,
+                        slot: account_update.slot,
+                    };
+
+                    info!(
+                        "Account update: {} (owner: {}, lamports: {})",
+                        pubkey, account_data.owner, account_data.lamports
+                    );
+                    self.record_account(account_data).await;
+                }
+            }
+            UpdateOneof::Slot(slot_update) => {
+                self.record_slot(slot_update.slot).await;
+                info!(
+                    "Slot update: {} (status: {:?})",
+                    slot_update.slot, slot_update.status
+                );
+            }
+            UpdateOneof::Transaction(tx_update) => {
+                if let Some(transaction) = tx_update.transaction {
+                    let transaction = decode_transaction(tx_update.slot, transaction);
+                    info!(
+                        "Transaction update: {} (slot: {}, accounts: {}, err: {:?})",
+                        transaction.signature,
+                        transaction.slot,
+                        transaction.accounts.len(),
+                        transaction.err
+                    );
+                    self.transactions.push(transaction);
+                }
+            }
+            UpdateOneof::Ping(_) => {
+                // Answered in subscribe_and_consume, which owns the request sink
+            }
+            _ => {
+                // Handle other update types as needed
+            }
+        }
+
+        // Any message, including pings, proves the stream is alive
+        self.health.record_message(unix_now(), self.account_count());
+    }
+
+    /// Publish, persist and cache one account, whether streamed or from the bootstrap snapshot
+    async fn record_account(&mut self, account_data: AccountUpdate) {
+        let receiver_gone = match &self.updates {
+            Some(updates) => updates.send(account_data.clone()).await.is_err(),
+            None => false,
+        };
+        if receiver_gone {
+            warn!("Update receiver dropped, no longer publishing account updates");
+            self.updates = None;
+        }
+
+        if let Some(store) = &self.store {
+            match persist_account(store, &account_data).await {
+                Ok(true) => self.persisted_count += 1,
+                Ok(false) => {} // A newer version is already stored
+                Err(e) => error!("Failed to persist account {}: {}", account_data.pubkey, e),
+            }
+        }
+        if self.keep_in_memory {
+            self.accounts.apply(account_data);
+        }
+    }
+
+    pub fn get_account(&self, pubkey: &str) -> Option<&AccountUpdate> {
+        self.accounts.get(pubkey)
+    }
+
+    pub fn get_accounts_by_owner(&self, owner: &str) -> Vec<&AccountUpdate> {
+        self.accounts.by_owner(owner)
+    }
+
+    /// Decoded SPL token accounts of `mint` held in memory, for tracking per-mint balances
+    pub fn get_token_accounts_by_mint(&self, mint: &str) -> Vec<ParsedTokenAccount> {
+        self.accounts
+            .values()
+            .filter_map(parse_token_account)
+            .filter(|token_account| token_account.mint == mint)
+            .collect()
+    }
+
+    pub fn get_transaction(&self, signature: &str) -> Option<&TransactionUpdate> {
+        self.transactions.get(signature)
+    }
+
+    /// The most recently decoded transactions, newest first
+    pub fn recent_transactions(&self) -> Vec<&TransactionUpdate> {
+        self.transactions.iter().collect()
+    }
+
+    /// Transactions that referenced `account`, e.g. deposits to a custody address
+    pub fn get_transactions_by_account(&self, account: &str) -> Vec<&TransactionUpdate> {
+        self.transactions
+            .iter()
+            .filter(|transaction| transaction.accounts.iter().any(|key| key == account))
+            .collect()
+    }
+
+    /// Accounts held in memory, or updates written to the store when memory is off
+    pub fn account_count(&self) -> usize {
+        if self.keep_in_memory {
+            self.accounts.len()
+        } else {
+            self.persisted_count
+        }
+    }
+
+    /// Per-owner counts and total lamports over the in-memory accounts. `total_accounts`
+    /// follows `account_count`, so it still reflects persisted updates when memory is off.
+    pub fn metrics(&self) -> IndexerMetrics {
+        IndexerMetrics {
+            total_accounts: self.account_count(),
+            ..self.accounts.metrics()
+        }
+    }
+
+    pub async fn health_check(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let health_response = self.client.health_check().await;
+        self.health.set_grpc_healthy(health_response.is_ok());
+        let health_response = health_response?;
+        info!("Health check: {:?}", health_response.status);
+        Ok(())
+    }
+
+    /// Shared liveness handle for the `/health` route
+    pub fn health(&self) -> Arc<IndexerHealth> {
+        Arc::clone(&self.health)
+    }
+}
+
+/// How many decoded transactions are kept for `recent_transactions`
+const RECENT_TRANSACTIONS_CAPACITY: usize = 1_000;
+
+/// Checkpoint row used by this indexer in `indexer_checkpoint`
+const CHECKPOINT_NAME: &str = "account_indexer";
+
+/// The new high-water mark if `seen` is past `last`, `None` when it's old news
+fn advance_slot(last: Option<u64>, seen: u64) -> Option<u64> {
+    match last {
+        Some(last) if seen <= last => None,
+        _ => Some(seen),
+    }
+}
+
+async fn persist_account(
+    store: &Store,
+    account: &AccountUpdate,
+) -> Result<bool, store::user::StoreError> {
+    let row = IndexedAccount::new(
+        account.pubkey.clone(),
+        account.lamports,
+        account.owner.clone(),
+        account.executable,
+        account.rent_epoch,
+        account.data.clone(),
+        account.write_version,
+        account.slot,
+    )?;
+    store.upsert_indexed_account(&row).await
+}
+
+/// One subscription carrying both filter sets at `commitment`
+fn build_subscribe_request(
+    account_filters: &[AccountFilter],
+    transaction_filters: &[TransactionFilter],
+    commitment: CommitmentLevel,
+) -> SubscribeRequest {
+    SubscribeRequest {
+        accounts: build_accounts_filter(account_filters),
+        slots: HashMap::new(),
+        transactions: build_transactions_filter(transaction_filters),
+        transactions_status: HashMap::new(),
+        blocks: HashMap::new(),
+        blocks_meta: HashMap::new(),
+        entry: HashMap::new(),
+        commitment: Some(commitment as i32),
+        accounts_data_slice: vec![],
+        ping: None,
+    }
+}
+
+/// Parse a `YELLOWSTONE_COMMITMENT` value
+pub fn parse_commitment(value: &str) -> Result<CommitmentLevel, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "processed" => Ok(CommitmentLevel::Processed),
+        "confirmed" => Ok(CommitmentLevel::Confirmed),
+        "finalized" => Ok(CommitmentLevel::Finalized),
+        other => Err(format!(
+            "Unknown commitment level {:?}, expected processed, confirmed or finalized",
+            other
+        )),
+    }
+}
+
+/// Idle streams are dropped by some providers unless the client answers their pings
+fn ping_reply(update: &UpdateOneof) -> Option<SubscribeRequest> {
+    match update {
+        UpdateOneof::Ping(_) => Some(SubscribeRequest {
+            ping: Some(SubscribeRequestPing { id: 1 }),
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+/// Decode signature, fee, outcome and involved accounts from a transaction update
+fn decode_transaction(slot: u64, info: SubscribeUpdateTransactionInfo) -> TransactionUpdate {
+    let mut accounts: Vec<String> = info
+        .transaction
+        .and_then(|transaction| transaction.message)
+        .map(|message| {
+            message
+                .account_keys
+                .iter()
+                .map(|key| bs58::encode(key).into_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let Some(meta) = &info.meta {
+        accounts.extend(
+            meta.loaded_writable_addresses
+                .iter()
+                .chain(&meta.loaded_readonly_addresses)
+                .map(|key| bs58::encode(key).into_string()),
+        );
+    }
+
+    TransactionUpdate {
+        signature: bs58::encode(&info.signature).into_string(),
+        slot,
+        is_vote: info.is_vote,
+        err: info
+            .meta
+            .as_ref()
+            .and_then(|meta| meta.err.as_ref())
+            .map(|err| decode_transaction_error(&err.err)),
+        fee: info.meta.as_ref().map(|meta| meta.fee),
+        accounts,
+    }
+}
+
+/// The proto carries the bincode-encoded `TransactionError`; fall back to hex if it won't decode
+fn decode_transaction_error(bytes: &[u8]) -> String {
+    match bincode::deserialize::<TransactionError>(bytes) {
+        Ok(err) => err.to_string(),
+        Err(_) => format!(
+            "Undecoded transaction error 0x{}",
+            bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>()
+        ),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum AccountFilter {
+    Owner(#[serde(with = "pubkey_string")] Pubkey),
+    Account(#[serde(with = "pubkey_string")] Pubkey),
+    Accounts(#[serde(with = "pubkey_string::vec")] Vec<Pubkey>), // Many specific accounts in a single subscription entry
+    ProgramData,
+    TokenAccount,
+    /// Only accounts whose data holds `bytes` at `offset`, e.g. a token account's mint at 0
+    Memcmp {
+        offset: u64,
+        bytes: Vec<u8>,
+    },
+    /// Only accounts whose data is exactly this many bytes, e.g. 165 for token accounts
+    DataSize(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum TransactionFilter {
+    /// Transactions that touch any of these accounts
+    AccountInclude(#[serde(with = "pubkey_string::vec")] Vec<Pubkey>),
+    Signature(String),
+    /// Also deliver vote transactions (off by default)
+    Vote(bool),
+    /// Also deliver failed transactions (off by default)
+    Failed(bool),
+}
+
+/// Pubkeys appear as base58 strings in filter files rather than byte arrays
+mod pubkey_string {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(pubkey)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Pubkey::from_str(&value).map_err(D::Error::custom)
+    }
+
+    pub mod vec {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            pubkeys: &[Pubkey],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(pubkeys.iter().map(|pubkey| pubkey.to_string()))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Pubkey>, D::Error> {
+            Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|value| Pubkey::from_str(value).map_err(D::Error::custom))
+                .collect()
+        }
+    }
+}
+
+/// Filters indexed when no `INDEXER_FILTERS_PATH` is configured
+fn default_filters() -> Vec<AccountFilter> {
+    vec![
+        // Index all token accounts
+        AccountFilter::TokenAccount,
+        // Index a specific account (replace with actual pubkey)
+        AccountFilter::Account(Pubkey::from_str("11111111111111111111111111111112").unwrap()),
+        // Index accounts owned by System Program
+        AccountFilter::Owner(Pubkey::from_str("11111111111111111111111111111111").unwrap()),
+        // Index program data accounts
+        AccountFilter::ProgramData,
+    ]
+}
+
+/// Load filters from the JSON file at `INDEXER_FILTERS_PATH`, or fall back to the defaults
+pub fn load_filters() -> Result<Vec<AccountFilter>, Box<dyn std::error::Error>> {
+    match std::env::var("INDEXER_FILTERS_PATH") {
+        Ok(path) => {
+            let contents = std::fs::read_to_string(&path)?;
+            let filters: Vec<AccountFilter> = serde_json::from_str(&contents)?;
+            info!("Loaded {} filters from {}", filters.len(), path);
+            Ok(filters)
+        }
+        Err(_) => Ok(default_filters()),
+    }
+}
+
+/// Map each owner/account `AccountFilter` to one named subscription entry.
+/// `Memcmp` and `DataSize` narrow every entry; on their own they form a single entry.
+fn build_accounts_filter(
+    account_filters: &[AccountFilter],
+) -> HashMap<String, SubscribeRequestFilterAccounts> {
+    let data_filters: Vec<SubscribeRequestFilterAccountsFilter> = account_filters
+        .iter()
+        .filter_map(|filter| {
+            let filter = match filter {
+                AccountFilter::Memcmp { offset, bytes } => {
+                    AccountsFilterOneof::Memcmp(SubscribeRequestFilterAccountsFilterMemcmp {
+                        offset: *offset,
+                        data: Some(MemcmpData::Bytes(bytes.clone())),
+                    })
+                }
+                AccountFilter::DataSize(size) => AccountsFilterOneof::Datasize(*size),
+                _ => return None,
+            };
+            Some(SubscribeRequestFilterAccountsFilter {
+                filter: Some(filter),
+            })
+        })
+        .collect();
+
+    let mut entries: Vec<SubscribeRequestFilterAccounts> = account_filters
+        .iter()
+        .filter_map(|filter| {
+            let accounts_filter = match filter {
+                AccountFilter::Owner(owner) => SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_string()],
+                    ..Default::default()
+                },
+                AccountFilter::Account(pubkey) => SubscribeRequestFilterAccounts {
+                    account: vec![pubkey.to_string()],
+                    ..Default::default()
+                },
+                AccountFilter::Accounts(pubkeys) => SubscribeRequestFilterAccounts {
+                    account: pubkeys.iter().map(|pubkey| pubkey.to_string()).collect(),
+                    ..Default::default()
+                },
+                AccountFilter::ProgramData => SubscribeRequestFilterAccounts {
+                    owner: vec!["BPFLoaderUpgradeab1e11111111111111111111111".to_string()],
+                    ..Default::default()
+                },
+                AccountFilter::TokenAccount => SubscribeRequestFilterAccounts {
+                    owner: vec![TOKEN_PROGRAM_ID.to_string()],
+                    ..Default::default()
+                },
+                AccountFilter::Memcmp { .. } | AccountFilter::DataSize(_) => return None,
+            };
+
+            Some(SubscribeRequestFilterAccounts {
+                filters: data_filters.clone(),
+                ..accounts_filter
+            })
+        })
+        .collect();
+    if entries.is_empty() && !data_filters.is_empty() {
+        entries.push(SubscribeRequestFilterAccounts {
+            filters: data_filters,
+            ..Default::default()
+        });
+    }
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| (format!("filter_{}", index), entry))
+        .collect()
+}
+
+/// Map each account or signature `TransactionFilter` to one named subscription entry.
+/// The vote/failed toggles apply to every entry; toggles alone subscribe to all transactions.
+fn build_transactions_filter(
+    transaction_filters: &[TransactionFilter],
+) -> HashMap<String, SubscribeRequestFilterTransactions> {
+    let mut vote = false;
+    let mut failed = false;
+    for filter in transaction_filters {
+        match filter {
+            TransactionFilter::Vote(enabled) => vote = *enabled,
+            TransactionFilter::Failed(enabled) => failed = *enabled,
+            _ => {}
+        }
+    }
+    let base = SubscribeRequestFilterTransactions {
+        vote: Some(vote),
+        failed: Some(failed),
+        ..Default::default()
+    };
+
+    let mut entries: Vec<SubscribeRequestFilterTransactions> = transaction_filters
+        .iter()
+        .filter_map(|filter| match filter {
+            TransactionFilter::AccountInclude(pubkeys) => {
+                Some(SubscribeRequestFilterTransactions {
+                    account_include: pubkeys.iter().map(|pubkey| pubkey.to_string()).collect(),
+                    ..base.clone()
+                })
+            }
+            TransactionFilter::Signature(signature) => Some(SubscribeRequestFilterTransactions {
+                signature: Some(signature.clone()),
+                ..base.clone()
+            }),
+            TransactionFilter::Vote(_) | TransactionFilter::Failed(_) => None,
+        })
+        .collect();
+    if entries.is_empty() && !transaction_filters.is_empty() {
+        entries.push(base);
+    }
+
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| (format!("tx_filter_{}", index), entry))
+        .collect()
+}
+
+/// Load transaction filters from the JSON file at `INDEXER_TRANSACTION_FILTERS_PATH`, if set
+pub fn load_transaction_filters() -> Result<Vec<TransactionFilter>, Box<dyn std::error::Error>> {
+    match std::env::var("INDEXER_TRANSACTION_FILTERS_PATH") {
+        Ok(path) => {
+            let contents = std::fs::read_to_string(&path)?;
+            let filters: Vec<TransactionFilter> = serde_json::from_str(&contents)?;
+            info!("Loaded {} transaction filters from {}", filters.len(), path);
+            Ok(filters)
+        }
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AccountFilter, AccountIndexer, AccountsFilterOneof, CommitmentLevel, MemcmpData,
+        TransactionFilter, advance_slot, build_accounts_filter, build_subscribe_request,
+        build_transactions_filter, decode_transaction, decode_transaction_error, parse_commitment,
+        ping_reply,
+    };
+    use crate::yellowstone::GeyserGrpcClient;
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+    use tokio::sync::mpsc;
+    use yellowstone_grpc_proto::prelude::{
+        Message, SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdatePing,
+        SubscribeUpdateSlot, SubscribeUpdateTransactionInfo, Transaction, TransactionError,
+        TransactionStatusMeta, subscribe_update::UpdateOneof,
+    };
+
+    /// An indexer whose gRPC channel is never dialed, for feeding updates in directly
+    fn offline_indexer() -> AccountIndexer {
+        let client = GeyserGrpcClient::build_from_static("http://127.0.0.1:10000")
+            .connect_lazy()
+            .unwrap();
+        AccountIndexer::with_client(client, None)
+    }
+
+    fn account_update(pubkey: &Pubkey, lamports: u64, slot: u64) -> UpdateOneof {
+        UpdateOneof::Account(SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: pubkey.to_bytes().to_vec(),
+                lamports,
+                owner: Pubkey::default().to_bytes().to_vec(),
+                write_version: 1,
+                ..Default::default()
+            }),
+            slot,
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_handled_updates_arrive_on_receiver() {
+        let (sender, mut receiver) = mpsc::channel(8);
+        let mut indexer = offline_indexer().with_updates(sender);
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        indexer.handle_update(account_update(&a, 1, 10)).await;
+        indexer.handle_update(account_update(&b, 2, 11)).await;
+
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first.pubkey, a.to_string());
+        assert_eq!((first.lamports, first.slot), (1, 10));
+        assert_eq!(receiver.recv().await.unwrap().pubkey, b.to_string());
+
+        // Published updates are still cached and checkpointed
+        assert_eq!(indexer.get_account(&a.to_string()).unwrap().lamports, 1);
+        assert_eq!(indexer.last_processed_slot(), Some(11));
+
+        // Dropping the receiver stops publishing without stopping indexing
+        drop(receiver);
+        indexer.handle_update(account_update(&a, 3, 12)).await;
+        assert_eq!(indexer.get_account(&a.to_string()).unwrap().lamports, 3);
+    }
+
+    #[test]
+    fn test_deserialize_filters_file() {
+        let json = r#"[
+            { "type": "owner", "value": "11111111111111111111111111111111" },
+            { "type": "account", "value": "11111111111111111111111111111112" },
+            { "type": "accounts", "value": ["11111111111111111111111111111112", "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"] },
+            { "type": "program_data" },
+            { "type": "token_account" }
+        ]"#;
+
+        let filters: Vec<AccountFilter> = serde_json::from_str(json).unwrap();
+
+        let system = Pubkey::from_str("11111111111111111111111111111111").unwrap();
+        let account = Pubkey::from_str("11111111111111111111111111111112").unwrap();
+        let token = Pubkey::from_str("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA").unwrap();
+        assert_eq!(
+            filters,
+            vec![
+                AccountFilter::Owner(system),
+                AccountFilter::Account(account),
+                AccountFilter::Accounts(vec![account, token]),
+                AccountFilter::ProgramData,
+                AccountFilter::TokenAccount,
+            ]
+        );
+
+        // Round-trips back to the same base58 representation
+        let encoded = serde_json::to_string(&filters).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<AccountFilter>>(&encoded).unwrap(),
+            filters
+        );
+
+        assert!(
+            serde_json::from_str::<Vec<AccountFilter>>(r#"[{ "type": "owner", "value": "bad" }]"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_accounts_filter_single_entry() {
+        let pubkeys: Vec<_> = (0..50).map(|_| Pubkey::new_unique()).collect();
+
+        let accounts_filter = build_accounts_filter(&[AccountFilter::Accounts(pubkeys.clone())]);
+
+        assert_eq!(accounts_filter.len(), 1);
+        let entry = &accounts_filter["filter_0"];
+        assert_eq!(entry.account.len(), 50);
+        assert_eq!(entry.account[0], pubkeys[0].to_string());
+        assert!(entry.owner.is_empty());
+    }
+
+    #[test]
+    fn test_transactions_filter_entries() {
+        let custody = Pubkey::new_unique();
+        let filters: Vec<TransactionFilter> = serde_json::from_str(&format!(
+            r#"[
+                {{ "type": "account_include", "value": ["{}"] }},
+                {{ "type": "signature", "value": "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW" }},
+                {{ "type": "failed", "value": true }}
+            ]"#,
+            custody
+        ))
+        .unwrap();
+
+        let transactions_filter = build_transactions_filter(&filters);
+
+        assert_eq!(transactions_filter.len(), 2);
+        let by_account = &transactions_filter["tx_filter_0"];
+        assert_eq!(by_account.account_include, vec![custody.to_string()]);
+        assert_eq!(by_account.vote, Some(false));
+        assert_eq!(by_account.failed, Some(true));
+        assert!(transactions_filter["tx_filter_1"].signature.is_some());
+
+        // Toggles alone still subscribe, and no filters means no transaction stream at all
+        assert_eq!(
+            build_transactions_filter(&[TransactionFilter::Vote(true)]).len(),
+            1
+        );
+        assert!(build_transactions_filter(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_decode_transaction_update() {
+        let payer = Pubkey::new_unique();
+        let custody = Pubkey::new_unique();
+        let looked_up = Pubkey::new_unique();
+
+        let info = SubscribeUpdateTransactionInfo {
+            signature: vec![7u8; 64],
+            is_vote: false,
+            transaction: Some(Transaction {
+                signatures: vec![vec![7u8; 64]],
+                message: Some(Message {
+                    account_keys: vec![payer.to_bytes().to_vec(), custody.to_bytes().to_vec()],
+                    ..Default::default()
+                }),
+            }),
+            meta: Some(TransactionStatusMeta {
+                err: Some(TransactionError {
+                    err: bincode::serialize(
+                        &solana_sdk::transaction::TransactionError::InsufficientFundsForFee,
+                    )
+                    .unwrap(),
+                }),
+                fee: 5000,
+                loaded_readonly_addresses: vec![looked_up.to_bytes().to_vec()],
+                ..Default::default()
+            }),
+            index: 0,
+        };
+
+        let update = decode_transaction(42, info);
+
+        assert_eq!(update.signature, bs58::encode([7u8; 64]).into_string());
+        assert_eq!(update.slot, 42);
+        assert_eq!(update.err.as_deref(), Some("Insufficient funds for fee"));
+        assert_eq!(update.fee, Some(5000));
+        assert_eq!(
+            update.accounts,
+            vec![
+                payer.to_string(),
+                custody.to_string(),
+                looked_up.to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memcmp_and_datasize_narrow_token_accounts() {
+        let mint = Pubkey::new_unique();
+        let filters: Vec<AccountFilter> = serde_json::from_str(&format!(
+            r#"[
+                {{ "type": "token_account" }},
+                {{ "type": "memcmp", "value": {{ "offset": 0, "bytes": {:?} }} }},
+                {{ "type": "data_size", "value": 165 }}
+            ]"#,
+            mint.to_bytes()
+        ))
+        .unwrap();
+
+        let accounts_filter = build_accounts_filter(&filters);
+
+        // One token-account entry, narrowed by both data filters
+        assert_eq!(accounts_filter.len(), 1);
+        let entry = &accounts_filter["filter_0"];
+        assert_eq!(entry.owner.len(), 1);
+        assert_eq!(entry.filters.len(), 2);
+        match &entry.filters[0].filter {
+            Some(AccountsFilterOneof::Memcmp(memcmp)) => {
+                assert_eq!(memcmp.offset, 0);
+                assert_eq!(
+                    memcmp.data,
+                    Some(MemcmpData::Bytes(mint.to_bytes().to_vec()))
+                );
+            }
+            other => panic!("expected memcmp, got {:?}", other),
+        }
+        assert_eq!(
+            entry.filters[1].filter,
+            Some(AccountsFilterOneof::Datasize(165))
+        );
+    }
+
+    #[test]
+    fn test_data_filters_alone_form_one_entry() {
+        let accounts_filter = build_accounts_filter(&[AccountFilter::DataSize(82)]);
+
+        assert_eq!(accounts_filter.len(), 1);
+        let entry = &accounts_filter["filter_0"];
+        assert!(entry.owner.is_empty() && entry.account.is_empty());
+        assert_eq!(
+            entry.filters[0].filter,
+            Some(AccountsFilterOneof::Datasize(82))
+        );
+    }
+
+    #[test]
+    fn test_ping_is_acknowledged() {
+        let reply = ping_reply(&UpdateOneof::Ping(SubscribeUpdatePing {})).unwrap();
+        assert!(reply.ping.is_some());
+        // The reply carries only the ping, nothing else about the subscription
+        assert!(reply.accounts.is_empty() && reply.transactions.is_empty());
+
+        let slot = UpdateOneof::Slot(SubscribeUpdateSlot::default());
+        assert!(ping_reply(&slot).is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_only_moves_forward() {
+        assert_eq!(advance_slot(None, 100), Some(100));
+        assert_eq!(advance_slot(Some(100), 101), Some(101));
+        // Replayed or out-of-order updates never move the checkpoint back
+        assert_eq!(advance_slot(Some(100), 100), None);
+        assert_eq!(advance_slot(Some(100), 42), None);
+    }
+
+    #[test]
+    fn test_subscribe_request_carries_commitment() {
+        let filters = vec![AccountFilter::Owner(
+            Pubkey::from_str("11111111111111111111111111111111").unwrap(),
+        )];
+        let commitment = parse_commitment("Finalized").unwrap();
+
+        let request = build_subscribe_request(&filters, &[], commitment);
+
+        assert_eq!(request.commitment, Some(CommitmentLevel::Finalized as i32));
+        assert_eq!(request.accounts.len(), 1);
+    }
+
+    #[test]
+    fn test_unknown_commitment_is_rejected() {
+        assert_eq!(
+            parse_commitment("processed"),
+            Ok(CommitmentLevel::Processed)
+        );
+        let message = parse_commitment("fast").unwrap_err();
+        assert!(message.contains("fast"));
+    }
+
+    #[test]
+    fn test_undecodable_transaction_error_is_kept_as_hex() {
+        assert_eq!(
+            decode_transaction_error(&[0xff]),
+            "Undecoded transaction error 0xff"
+        );
+    }
+}
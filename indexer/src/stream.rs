@@ -0,0 +1,269 @@
+use futures::future::LocalBoxFuture;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    /// The provider closed the subscription before it yielded anything, usually a hiccup
+    #[error("stream closed after {0:?} without yielding a message")]
+    ClosedEmpty(Duration),
+    #[error("subscribe request failed: {0}")]
+    Subscribe(String),
+    #[error("stream failed after {received} messages: {reason}")]
+    Disconnected { reason: String, received: u64 },
+    #[error("stream ended after {0} messages")]
+    Ended(u64),
+}
+
+impl StreamError {
+    /// Whether the subscription delivered updates before it stopped, i.e. it was healthy for a while
+    fn made_progress(&self) -> bool {
+        match self {
+            StreamError::Disconnected { received, .. } | StreamError::Ended(received) => {
+                *received > 0
+            }
+            StreamError::ClosedEmpty(_) | StreamError::Subscribe(_) => false,
+        }
+    }
+}
+
+/// Tracks whether a fresh subscription has yielded anything during its startup grace period
+#[derive(Debug)]
+pub struct StartupWatch {
+    started: Instant,
+    grace: Duration,
+    received: u64,
+}
+
+impl StartupWatch {
+    pub fn new(grace: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            grace,
+            received: 0,
+        }
+    }
+
+    pub fn record_message(&mut self) {
+        self.received += 1;
+    }
+
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// Classify a closed stream: closing silently within the grace period is a failure, not completion
+    pub fn on_close(&self) -> Result<(), StreamError> {
+        let elapsed = self.started.elapsed();
+        if self.received == 0 && elapsed < self.grace {
+            return Err(StreamError::ClosedEmpty(elapsed));
+        }
+        Ok(())
+    }
+
+    /// Wrap an error yielded by the stream, remembering how far the subscription got
+    pub fn on_error(&self, reason: impl std::fmt::Display) -> StreamError {
+        StreamError::Disconnected {
+            reason: reason.to_string(),
+            received: self.received,
+        }
+    }
+}
+
+/// Exponential reconnect delay, doubling from `initial` up to `max`
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial.min(max),
+        }
+    }
+
+    /// Delay before the next attempt, with jitter so many indexers don't reconnect in lockstep
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = jitter(self.current);
+        self.current = self.current.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial.min(self.max);
+    }
+}
+
+/// Pick a delay in `[delay / 2, delay]`; clock nanos are random enough to spread reconnects
+fn jitter(delay: Duration) -> Duration {
+    let half = delay / 2;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.subsec_nanos())
+        .unwrap_or(0);
+    half + half.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+/// Run `attempt` until it completes normally, resubscribing with backoff after any `StreamError`
+pub async fn with_reconnect<St, F>(
+    state: &mut St,
+    mut backoff: Backoff,
+    mut attempt: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: for<'a> FnMut(&'a mut St) -> LocalBoxFuture<'a, Result<(), Box<dyn std::error::Error>>>,
+{
+    let mut consecutive_failures = 0u32;
+    loop {
+        let e = match attempt(state).await {
+            Err(e) => e,
+            Ok(()) => return Ok(()),
+        };
+        let Some(stream_error) = e.downcast_ref::<StreamError>() else {
+            return Err(e);
+        };
+
+        // A subscription that delivered updates was healthy; start counting afresh
+        if stream_error.made_progress() {
+            consecutive_failures = 0;
+            backoff.reset();
+        }
+        consecutive_failures += 1;
+
+        let delay = backoff.next_delay();
+        warn!(
+            "Subscription failed ({} consecutive): {}; reconnecting in {:?}",
+            consecutive_failures, e, delay
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, StartupWatch, StreamError, with_reconnect};
+    use futures::{FutureExt, StreamExt, stream};
+    use std::time::Duration;
+
+    /// Consume a mock stream the same way `index_accounts` consumes the gRPC stream
+    async fn consume(
+        messages: Vec<Result<u64, String>>,
+        grace: Duration,
+    ) -> Result<(), StreamError> {
+        let mut watch = StartupWatch::new(grace);
+        let mut stream = stream::iter(messages);
+        while let Some(message) = stream.next().await {
+            match message {
+                Ok(_) => watch.record_message(),
+                Err(status) => return Err(watch.on_error(status)),
+            }
+        }
+        watch.on_close()
+    }
+
+    fn no_delay() -> Backoff {
+        Backoff::new(Duration::ZERO, Duration::ZERO)
+    }
+
+    #[tokio::test]
+    async fn test_empty_stream_triggers_reconnect() {
+        let mut subscriptions = 0u32;
+
+        let result = with_reconnect(&mut subscriptions, no_delay(), |subscriptions| {
+            async move {
+                *subscriptions += 1;
+                // The first subscription closes immediately, the second yields updates
+                let messages = if *subscriptions == 1 {
+                    vec![]
+                } else {
+                    vec![Ok(1), Ok(2)]
+                };
+                consume(messages, Duration::from_secs(10)).await?;
+                Ok(())
+            }
+            .boxed_local()
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(subscriptions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_resubscribes() {
+        let mut subscriptions = 0u32;
+
+        let result = with_reconnect(&mut subscriptions, no_delay(), |subscriptions| {
+            async move {
+                *subscriptions += 1;
+                // The first subscription errors mid-stream, the second runs to completion
+                let messages = if *subscriptions == 1 {
+                    vec![Ok(1), Err("transport error".to_string())]
+                } else {
+                    vec![Ok(2), Ok(3)]
+                };
+                consume(messages, Duration::from_secs(10)).await?;
+                Ok(())
+            }
+            .boxed_local()
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(subscriptions, 2);
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_not_retried() {
+        let mut subscriptions = 0u32;
+
+        let result = with_reconnect(&mut subscriptions, no_delay(), |subscriptions| {
+            async move {
+                *subscriptions += 1;
+                Err("invalid filter".into())
+            }
+            .boxed_local()
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(subscriptions, 1);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(30));
+        let delays: Vec<Duration> = (0..8).map(|_| backoff.next_delay()).collect();
+
+        let ceilings = [1, 2, 4, 8, 16, 30, 30, 30].map(Duration::from_secs);
+        for (delay, ceiling) in delays.iter().zip(ceilings) {
+            assert!(*delay <= ceiling && *delay >= ceiling / 2, "{:?}", delay);
+        }
+
+        backoff.reset();
+        assert!(backoff.next_delay() <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_error_records_progress() {
+        let mut watch = StartupWatch::new(Duration::from_secs(10));
+        assert!(!watch.on_error("reset").made_progress());
+        watch.record_message();
+        assert!(matches!(
+            watch.on_error("reset"),
+            StreamError::Disconnected { received: 1, .. }
+        ));
+        assert!(watch.on_error("reset").made_progress());
+    }
+
+    #[test]
+    fn test_quiet_close_after_grace_is_completion() {
+        let watch = StartupWatch::new(Duration::ZERO);
+        assert!(watch.on_close().is_ok());
+    }
+}
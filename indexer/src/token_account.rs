@@ -0,0 +1,93 @@
+use serde::Serialize;
+
+use crate::AccountUpdate;
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// Size of an SPL token account: mint, owner, amount, delegate, state, native, delegated, close authority
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Byte offset of the `AccountState` field; 0 means the account is uninitialized
+const STATE_OFFSET: usize = 108;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParsedTokenAccount {
+    pub pubkey: String,
+    pub mint: String,
+    pub owner: String,
+    pub amount: u64,
+}
+
+/// Decode mint, owner and amount from an initialized SPL token account, `None` for anything else
+pub fn parse_token_account(account: &AccountUpdate) -> Option<ParsedTokenAccount> {
+    if account.owner != TOKEN_PROGRAM_ID || account.data.len() != TOKEN_ACCOUNT_LEN {
+        return None;
+    }
+    let data = &account.data;
+    if data[STATE_OFFSET] == 0 {
+        return None;
+    }
+
+    Some(ParsedTokenAccount {
+        pubkey: account.pubkey.clone(),
+        mint: bs58::encode(&data[0..32]).into_string(),
+        owner: bs58::encode(&data[32..64]).into_string(),
+        amount: u64::from_le_bytes(data[64..72].try_into().ok()?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{STATE_OFFSET, TOKEN_ACCOUNT_LEN, TOKEN_PROGRAM_ID, parse_token_account};
+    use crate::AccountUpdate;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> AccountUpdate {
+        let mut data = vec![0u8; TOKEN_ACCOUNT_LEN];
+        data[0..32].copy_from_slice(mint.as_ref());
+        data[32..64].copy_from_slice(owner.as_ref());
+        data[64..72].copy_from_slice(&amount.to_le_bytes());
+        data[STATE_OFFSET] = 1; // Initialized
+
+        AccountUpdate {
+            pubkey: Pubkey::new_unique().to_string(),
+            lamports: 2_039_280,
+            owner: TOKEN_PROGRAM_ID.to_string(),
+            executable: false,
+            rent_epoch: 0,
+            data,
+            write_version: 1,
+            slot: 1,
+        }
+    }
+
+    #[test]
+    fn test_parse_token_account() {
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let account = token_account(&mint, &owner, 1_500_000);
+
+        let parsed = parse_token_account(&account).unwrap();
+        assert_eq!(parsed.pubkey, account.pubkey);
+        assert_eq!(parsed.mint, mint.to_string());
+        assert_eq!(parsed.owner, owner.to_string());
+        assert_eq!(parsed.amount, 1_500_000);
+    }
+
+    #[test]
+    fn test_rejects_non_token_accounts() {
+        let account = token_account(&Pubkey::new_unique(), &Pubkey::new_unique(), 1);
+
+        let mut short = account.clone();
+        short.data.truncate(72);
+        assert_eq!(parse_token_account(&short), None);
+
+        let mut other_program = account.clone();
+        other_program.owner = Pubkey::new_unique().to_string();
+        assert_eq!(parse_token_account(&other_program), None);
+
+        let mut uninitialized = account;
+        uninitialized.data[STATE_OFFSET] = 0;
+        assert_eq!(parse_token_account(&uninitialized), None);
+    }
+}
@@ -1,24 +1,89 @@
-use actix_web::{App, Error, HttpResponse, HttpServer, Result, web::post};
+use actix_web::{
+    App, HttpResponse, HttpServer, Result,
+    middleware::from_fn,
+    web::{self, get, post},
+};
+use common::error::json_error_handlers;
+use common::trace::propagate_trace;
+use serde::{Deserialize, Serialize};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::{
     hash::Hash,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_instruction,
-    transaction::Transaction,
+    system_instruction::MAX_PERMITTED_DATA_LENGTH,
+    transaction::{Transaction, VersionedTransaction},
 };
+use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
 
-pub mod error;
 pub mod native_token;
+pub mod rpc;
+pub mod secret_state;
 pub mod serialization;
 pub mod tss;
 
 use crate::{
-    serialization::{AggMessage1, Error, PartialSignature, SecretAggStepOne},
-    tss::{key_agg, sign_and_broadcast, step_one, step_two},
+    native_token::{
+        ComputeBudget, DurableNonce, TransferAsset, build_transfer_message_v0,
+        build_transfer_transaction, lamports_to_sol, sol_to_lamports,
+        unsigned_versioned_transaction, use_v0,
+    },
+    rpc::{
+        Network, SUPPORTED_COMMITMENTS, allow_private_rpc, confirm_commitment,
+        decode_signed_transaction, default_network, ensure_airdrop_network, fetch_lookup_tables,
+        send_and_confirm, send_config, simulate_unsigned, validate_rpc_url,
+    },
+    serialization::{
+        AggMessage1, Deserialize as _, Error, PartialSignature, Serialize as _, pubkey_from_bytes,
+    },
+    tss::{key_agg, sign_and_broadcast, signing_set, step_one, step_two},
 };
 
+/// Upper bound on the RPC probe so the health check itself never hangs
+const HEALTH_RPC_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str, // "ok", or "degraded" when Solana RPC is unreachable
+    version: &'static str,
+    rpc_reachable: bool,
+}
+
+#[derive(Serialize)]
+struct InfoResponse {
+    default_network: Network,
+    version: &'static str,
+    supported_commitments: Vec<&'static str>,
+}
+
+#[derive(Deserialize)]
+struct RentExemptionQuery {
+    data_len: usize,
+    rpc_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RentExemptionResponse {
+    data_len: usize,
+    lamports: u64,
+    sol: String, // Exact, unlike an f64
+}
+
+#[derive(Deserialize)]
+struct AirdropRequest {
+    pubkey: String,
+    amount_sol: f64,
+    rpc_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AirdropResponse {
+    transaction_signature: String,
+}
+
 #[derive(Deserialize)]
 struct GenerateRequest {
     // No parameters needed for key generation
@@ -36,7 +101,15 @@ struct SendSingleRequest {
     to: String,
     amount: f64,
     memo: Option<String>,
+    #[serde(alias = "priority_fee")]
+    compute_unit_price_micro_lamports: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    version: Option<u8>,                // None for legacy, 0 for a v0 transaction
+    lookup_tables: Option<Vec<String>>, // Address lookup tables for v0 transactions
+    network: Option<String>,            // mainnet/testnet/devnet; must agree with rpc_url
     rpc_url: Option<String>,
+    skip_preflight: Option<bool>,
+    preflight_commitment: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -44,6 +117,26 @@ struct SendSingleResponse {
     transaction_signature: String,
 }
 
+#[derive(Deserialize)]
+struct SendSingleDryRunRequest {
+    from: String,
+    to: String,
+    amount: f64,
+    memo: Option<String>,
+    compute_unit_price_micro_lamports: Option<u64>,
+    compute_unit_limit: Option<u32>,
+    version: Option<u8>,
+    lookup_tables: Option<Vec<String>>,
+    rpc_url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SendSingleDryRunResponse {
+    err: Option<String>,
+    logs: Vec<String>,
+    units_consumed: Option<u64>,
+}
+
 #[derive(Deserialize)]
 struct AggregateKeysRequest {
     public_keys: Vec<String>,
@@ -69,13 +162,21 @@ struct AggSendStep1Response {
 #[derive(Deserialize)]
 struct AggSendStep2Request {
     private_key: String,
-    amount: f64,
+    amount: f64,                // SOL, or whole tokens when token_mint is set
+    token_mint: Option<String>, // SPL mint to transfer instead of SOL; requires decimals
+    decimals: Option<u8>,
+    compute_unit_price_micro_lamports: Option<u64>,
+    compute_unit_limit: Option<u32>,
     to: String,
     memo: Option<String>,
+    nonce_account: Option<String>, // Durable nonce; recent_block_hash is then its stored value
+    nonce_authority: Option<String>,
     recent_block_hash: String,
     public_keys: Vec<String>,
-    first_messages: Vec<String>, // Base64 encoded AggMessage1s
-    secret_state: String,        // Base64 encoded SecretAggStepOne
+    signers: Option<Vec<String>>, // Subset of public_keys taking part; defaults to all of them
+    threshold: Option<usize>,     // Minimum number of signers; defaults to all public_keys
+    first_messages: Vec<String>,  // Base64 encoded AggMessage1s
+    secret_state: String,         // Base64 encoded SecretAggStepOne + HMAC tag
 }
 
 #[derive(Serialize)]
@@ -84,14 +185,51 @@ struct AggSendStep2Response {
 }
 
 #[derive(Deserialize)]
-struct AggregateSigsBroadcastRequest {
-    amount: f64,
+struct BroadcastRequest {
+    signed_transaction_base64: String, // Base64 encoded bincode Transaction
+    rpc_url: Option<String>,
+    skip_preflight: Option<bool>,
+    preflight_commitment: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BroadcastResponse {
+    transaction_signature: String,
+}
+
+#[derive(Deserialize)]
+struct AggregateSignaturesRequest {
+    amount: Option<f64>, // Same amount as in agg-send-step2; or give base_units instead
+    base_units: Option<u64>, // Exact amount in lamports, or the token's smallest unit
+    token_mint: Option<String>, // Same asset and compute budget as in agg-send-step2
+    decimals: Option<u8>,
+    compute_unit_price_micro_lamports: Option<u64>,
+    compute_unit_limit: Option<u32>,
     to: String,
     memo: Option<String>,
+    nonce_account: Option<String>, // Durable nonce; recent_block_hash is then its stored value
+    nonce_authority: Option<String>,
     recent_block_hash: String,
     public_keys: Vec<String>,
-    partial_signatures: Vec<String>, // Base64 encoded PartialSignatures
+    signers: Option<Vec<String>>, // Same subset, in the same order, as in agg-send-step2
+    threshold: Option<usize>,
+    partial_signatures: Vec<String>, // Base64 encoded PartialSignatures, one per signer
+}
+
+#[derive(Serialize)]
+struct AggregateSignaturesResponse {
+    signed_transaction_base64: String, // Base64 encoded bincode Transaction, accepted by /broadcast
+    transaction_signature: String,
+}
+
+#[derive(Deserialize)]
+struct AggregateSigsBroadcastRequest {
+    #[serde(flatten)]
+    aggregate: AggregateSignaturesRequest,
+    network: Option<String>,
     rpc_url: Option<String>,
+    skip_preflight: Option<bool>,
+    preflight_commitment: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -101,34 +239,225 @@ struct AggregateSigsBroadcastResponse {
 
 #[actix_web::main]
 async fn main() -> Result<(), std::io::Error> {
-    HttpServer::new(|| {
+    tracing_subscriber::fmt().init();
+
+    // Oversized JSON bodies (e.g. huge first_messages arrays) are rejected with 413
+    let json_limit = std::env::var("MAX_JSON_BODY_BYTES")
+        .ok()
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(256 * 1024);
+
+    // Defaults to 8081 so the MPC node can share a host with the backend on 8080
+    let bind_addr = std::env::var("MPC_BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:8081".to_string());
+    let bind_addr = SocketAddr::from_str(&bind_addr).unwrap_or_else(|e| {
+        panic!(
+            "Invalid MPC_BIND_ADDR {:?}, expected host:port such as 127.0.0.1:8081: {}",
+            bind_addr, e
+        )
+    });
+    let workers = std::env::var("MPC_WORKERS").ok().map(|value| {
+        value
+            .parse::<usize>()
+            .ok()
+            .filter(|workers| *workers > 0)
+            .unwrap_or_else(|| {
+                panic!(
+                    "Invalid MPC_WORKERS {:?}, expected a positive integer",
+                    value
+                )
+            })
+    });
+
+    // Resolve MPC_DEFAULT_NETWORK up front so a bad value fails at startup
+    let network = default_network();
+    tracing::info!("Serving {:?} on {}", network, bind_addr);
+
+    let mut server = HttpServer::new(move || {
         App::new()
+            .app_data(web::JsonConfig::default().limit(json_limit))
+            .wrap(json_error_handlers())
+            .wrap(from_fn(propagate_trace))
+            .route("/health", get().to(health))
+            .route("/info", get().to(info))
+            .route("/rent-exemption", get().to(rent_exemption))
             .route("/generate", post().to(generate))
+            .route("/airdrop", post().to(airdrop))
             .route("/send-single", post().to(send_single))
+            .route("/send-single-dryrun", post().to(send_single_dryrun))
             .route("/aggregate-keys", post().to(aggregate_keys))
             .route("/agg-send-step1", post().to(agg_send_step1))
             .route("/agg-send-step2", post().to(agg_send_step2))
+            .route("/aggregate-signatures", post().to(aggregate_signatures))
             .route(
                 "/aggregate-signatures-broadcast",
                 post().to(aggregate_signatures_broadcast),
             )
+            .route("/broadcast", post().to(broadcast))
+    });
+    if let Some(workers) = workers {
+        server = server.workers(workers);
+    }
+
+    server.bind(bind_addr)?.run().await
+}
+
+/// Most signers accepted in one round, configurable via `MPC_MAX_PARTICIPANTS` (default 64)
+fn max_participants() -> usize {
+    static MAX: OnceLock<usize> = OnceLock::new();
+    *MAX.get_or_init(|| {
+        std::env::var("MPC_MAX_PARTICIPANTS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(64)
+    })
+}
+
+/// Reject oversized participant arrays before any of their entries are decoded
+fn check_participants(field_name: &str, len: usize) -> Result<()> {
+    let max = max_participants();
+    if len > max {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "{} has {} entries, at most {} participants are supported",
+            field_name, len, max
+        )));
+    }
+    Ok(())
+}
+
+/// Pick the RPC url for a send: an explicit `rpc_url` must not contradict `network`
+/// (e.g. devnet with a mainnet-beta url). Urls that don't name a cluster, such as a local
+/// validator or a private provider, are accepted as-is.
+fn resolve_rpc(network: Option<&str>, rpc_url: Option<&str>) -> Result<String, Error> {
+    let network = network.map(Network::from_str).transpose()?;
+
+    match (network, rpc_url) {
+        (Some(network), Some(rpc_url)) => match Network::from_rpc_url(rpc_url) {
+            Some(url_network) if url_network != network => Err(Error::WrongNetwork(format!(
+                "{:?} requested but rpc_url {} points at {:?}",
+                network, rpc_url, url_network
+            ))),
+            _ => Ok(rpc_url.to_string()),
+        },
+        (Some(network), None) => Ok(network.rpc_url().to_string()),
+        (None, Some(rpc_url)) => Ok(rpc_url.to_string()),
+        (None, None) => Ok(default_network().rpc_url().to_string()),
+    }
+}
+
+/// Liveness check; always 200 while the process serves requests, with RPC reachability as detail
+async fn health() -> Result<HttpResponse> {
+    let rpc_reachable = web::block(|| {
+        RpcClient::new_with_timeout(default_network().rpc_url(), HEALTH_RPC_TIMEOUT)
+            .get_health()
+            .is_ok()
     })
-    .bind("127.0.0.1:8080")?
-    .run()
     .await
+    .unwrap_or(false);
+
+    let response = HealthResponse {
+        status: if rpc_reachable { "ok" } else { "degraded" },
+        version: env!("CARGO_PKG_VERSION"),
+        rpc_reachable,
+    };
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn generate() -> Result<HttpResponse, Error> {
+async fn info() -> Result<HttpResponse> {
+    let response = InfoResponse {
+        default_network: default_network(),
+        version: env!("CARGO_PKG_VERSION"),
+        supported_commitments: SUPPORTED_COMMITMENTS.to_vec(),
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn rent_exemption(query: web::Query<RentExemptionQuery>) -> Result<HttpResponse> {
+    if query.data_len as u64 > MAX_PERMITTED_DATA_LENGTH {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "data_len exceeds the maximum account size of {} bytes",
+            MAX_PERMITTED_DATA_LENGTH
+        )));
+    }
+
+    let rpc_url = query
+        .rpc_url
+        .as_deref()
+        .unwrap_or(default_network().rpc_url());
+    validate_rpc_url(rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new(rpc_url);
+
+    Ok(HttpResponse::Ok().json(rent_exemption_quote(&client, query.data_len)?))
+}
+
+/// Ask `client` for the rent-exempt minimum of an account holding `data_len` bytes
+fn rent_exemption_quote(client: &RpcClient, data_len: usize) -> Result<RentExemptionResponse> {
+    let lamports = client
+        .get_minimum_balance_for_rent_exemption(data_len)
+        .map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!(
+                "Failed to get rent exemption: {}",
+                e
+            ))
+        })?;
+
+    Ok(RentExemptionResponse {
+        data_len,
+        lamports,
+        sol: lamports_to_sol(lamports).to_string(),
+    })
+}
+
+async fn generate(_req: web::Json<GenerateRequest>) -> Result<HttpResponse> {
     let mut rng = rand::thread_rng();
     let keypair = Keypair::generate(&mut rng);
     let response = GenerateResponse {
         public_key: keypair.pubkey().to_string(),
         private_key: bs58::encode(keypair.to_bytes()).into_string(),
     };
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn send_single() -> Result<HttpResponse, Error> {
+/// Fund an account from the devnet/testnet faucet, e.g. a fee payer for integration tests
+async fn airdrop(req: web::Json<AirdropRequest>) -> Result<HttpResponse> {
+    let pubkey = Pubkey::from_str(&req.pubkey)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid pubkey: {}", e)))?;
+
+    if !(req.amount_sol.is_finite() && req.amount_sol > 0.0) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "amount_sol must be a positive number",
+        ));
+    }
+
+    let rpc_url = req
+        .rpc_url
+        .as_deref()
+        .unwrap_or(default_network().rpc_url());
+    let url = validate_rpc_url(rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    ensure_airdrop_network(&url).map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new_with_commitment(rpc_url, confirm_commitment());
+
+    let lamports = sol_to_lamports(req.amount_sol)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let signature = client
+        .request_airdrop(&pubkey, lamports)
+        .map_err(Error::AirdropFailed)
+        .and_then(|signature| {
+            client
+                .poll_for_signature_with_commitment(&signature, client.commitment())
+                .map_err(Error::ConfirmingTransactionFailed)?;
+            Ok(signature)
+        })
+        .map_err(|e| actix_web::error::ErrorBadGateway(e.to_string()))?;
+
+    let response = AirdropResponse {
+        transaction_signature: signature.to_string(),
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn send_single(req: web::Json<SendSingleRequest>) -> Result<HttpResponse> {
     let keypair_bytes = bs58::decode(&req.private_key)
         .into_vec()
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid private key: {}", e)))?;
@@ -140,43 +469,132 @@ async fn send_single() -> Result<HttpResponse, Error> {
         actix_web::error::ErrorBadRequest(format!("Invalid destination address: {}", e))
     })?;
 
-    let rpc_url = req
-        .rpc_url
-        .as_deref()
-        .unwrap_or("https://api.devnet.solana.com");
-    let client = RpcClient::new(rpc_url);
+    let rpc_url = resolve_rpc(req.network.as_deref(), req.rpc_url.as_deref())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    validate_rpc_url(&rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new_with_commitment(rpc_url, confirm_commitment());
+    let config = send_config(req.skip_preflight, req.preflight_commitment.as_deref())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
 
     // Create transaction
-    let lamports = sol_to_lamports(req.amount);
+    let lamports = sol_to_lamports(req.amount)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
     let recent_blockhash = client.get_latest_blockhash().map_err(|e| {
         actix_web::error::ErrorInternalServerError(format!("Failed to get recent blockhash: {}", e))
     })?;
 
-    let mut transaction = Transaction::new_with_payer(
-        &[system_instruction::transfer(
+    let budget = ComputeBudget::new(
+        req.compute_unit_price_micro_lamports,
+        req.compute_unit_limit,
+    );
+    let transaction = if use_v0(req.version)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?
+    {
+        let lookup_tables =
+            fetch_lookup_tables(&client, req.lookup_tables.as_deref().unwrap_or_default())
+                .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+        let message = build_transfer_message_v0(
             &keypair.pubkey(),
             &to_pubkey,
             lamports,
-        )],
-        Some(&keypair.pubkey()),
-    );
-
-    transaction.sign(&[&keypair], recent_blockhash);
+            req.memo.clone(),
+            budget,
+            &lookup_tables,
+            recent_blockhash,
+        )
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+        VersionedTransaction::try_new(message, &[&keypair]).map_err(|e| {
+            actix_web::error::ErrorInternalServerError(format!("Failed to sign transaction: {}", e))
+        })?
+    } else {
+        let mut transaction = build_transfer_transaction(
+            &keypair.pubkey(),
+            &to_pubkey,
+            lamports,
+            req.memo.clone(),
+            budget,
+        );
+        transaction.sign(&[&keypair], recent_blockhash);
+        VersionedTransaction::from(transaction)
+    };
 
-    let signature = client
-        .send_and_confirm_transaction(&transaction)
-        .map_err(|e| {
-            actix_web::error::ErrorInternalServerError(format!("Failed to send transaction: {}", e))
-        })?;
+    let signature = send_and_confirm(&client, &transaction, config).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to send transaction: {}", e))
+    })?;
 
     let response = SendSingleResponse {
         transaction_signature: signature.to_string(),
     };
 
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn aggregate_keys() -> Result<HttpResponse, Error> {
+/// Simulate a single transfer without signing or broadcasting it, so clients can catch
+/// insufficient funds or bad accounts cheaply
+async fn send_single_dryrun(req: web::Json<SendSingleDryRunRequest>) -> Result<HttpResponse> {
+    let from_pubkey = Pubkey::from_str(&req.from)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid source address: {}", e)))?;
+
+    let to_pubkey = Pubkey::from_str(&req.to).map_err(|e| {
+        actix_web::error::ErrorBadRequest(format!("Invalid destination address: {}", e))
+    })?;
+
+    let rpc_url = req
+        .rpc_url
+        .as_deref()
+        .unwrap_or(default_network().rpc_url());
+    validate_rpc_url(rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new_with_commitment(rpc_url, confirm_commitment());
+
+    let lamports = sol_to_lamports(req.amount)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let budget = ComputeBudget::new(
+        req.compute_unit_price_micro_lamports,
+        req.compute_unit_limit,
+    );
+    let transaction =
+        if use_v0(req.version).map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))? {
+            let lookup_tables =
+                fetch_lookup_tables(&client, req.lookup_tables.as_deref().unwrap_or_default())
+                    .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+            // The blockhash is replaced by the node during simulation
+            let message = build_transfer_message_v0(
+                &from_pubkey,
+                &to_pubkey,
+                lamports,
+                req.memo.clone(),
+                budget,
+                &lookup_tables,
+                Hash::default(),
+            )
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+            unsigned_versioned_transaction(message)
+        } else {
+            VersionedTransaction::from(build_transfer_transaction(
+                &from_pubkey,
+                &to_pubkey,
+                lamports,
+                req.memo.clone(),
+                budget,
+            ))
+        };
+
+    let result = simulate_unsigned(&client, &transaction).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to simulate transaction: {}", e))
+    })?;
+
+    let response = SendSingleDryRunResponse {
+        err: result.err.map(|e| e.to_string()),
+        logs: result.logs.unwrap_or_default(),
+        units_consumed: result.units_consumed,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn aggregate_keys(req: web::Json<AggregateKeysRequest>) -> Result<HttpResponse> {
     let public_keys: Result<Vec<Pubkey>, _> = req
         .public_keys
         .iter()
@@ -198,16 +616,18 @@ async fn aggregate_keys() -> Result<HttpResponse, Error> {
     let agg_key = key_agg(public_keys, key_for_coeff)
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Key aggregation failed: {}", e)))?;
 
-    let agg_pubkey = Pubkey::new(&*agg_key.agg_public_key.to_bytes(true));
+    let agg_pubkey = pubkey_from_bytes(&*agg_key.agg_public_key.to_bytes(true)).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Invalid aggregated key: {}", e))
+    })?;
 
     let response = AggregateKeysResponse {
         aggregated_public_key: agg_pubkey.to_string(),
     };
 
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn agg_send_step1() -> Result<HttpResponse, Error> {
+async fn agg_send_step1(req: web::Json<AggSendStep1Request>) -> Result<HttpResponse> {
     let keypair_bytes = bs58::decode(&req.private_key)
         .into_vec()
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid private key: {}", e)))?;
@@ -220,17 +640,20 @@ async fn agg_send_step1() -> Result<HttpResponse, Error> {
     let mut msg1_bytes = Vec::new();
     message1.serialize(&mut msg1_bytes);
 
-    let mut secret_bytes = Vec::new();
-    secret_state.serialize(&mut secret_bytes);
+    let secret_bytes = secret_state::seal(&secret_state);
 
     let response = AggSendStep1Response {
         message1: base64::encode(msg1_bytes),
         secret_state: base64::encode(secret_bytes),
     };
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn agg_send_step2() -> Result<HttpResponse, Error> {
+async fn agg_send_step2(req: web::Json<AggSendStep2Request>) -> Result<HttpResponse> {
+    check_participants("public_keys", req.public_keys.len())?;
+    check_participants("first_messages", req.first_messages.len())?;
+    check_participants("signers", req.signers.as_ref().map_or(0, Vec::len))?;
+
     let keypair_bytes = bs58::decode(&req.private_key)
         .into_vec()
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid private key: {}", e)))?;
@@ -253,6 +676,20 @@ async fn agg_send_step2() -> Result<HttpResponse, Error> {
     let public_keys = public_keys
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid public key: {}", e)))?;
 
+    let signers = req
+        .signers
+        .as_ref()
+        .map(|signers| {
+            signers
+                .iter()
+                .map(|key_str| Pubkey::from_str(key_str))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid signer: {}", e)))?;
+    let signing_keys = signing_set(&public_keys, signers.as_deref(), req.threshold)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
     // Deserialize first messages
     let first_messages: Result<Vec<AggMessage1>, _> = req
         .first_messages
@@ -264,20 +701,39 @@ async fn agg_send_step2() -> Result<HttpResponse, Error> {
         })
         .collect();
     let first_messages = first_messages.map_err(|e| actix_web::error::ErrorBadRequest(e))?;
+    if first_messages.len() + 1 != signing_keys.len() {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Expected {} first_messages, one from each other signer",
+            signing_keys.len() - 1
+        )));
+    }
 
     // Deserialize secret state
     let secret_bytes = base64::decode(&req.secret_state)
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid secret state: {}", e)))?;
-    let secret_state = SecretAggStepOne::deserialize(&secret_bytes)
+    let secret_state = secret_state::open(&secret_bytes)
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid secret state: {}", e)))?;
 
+    let asset = TransferAsset::from_request(req.token_mint.as_deref(), req.decimals)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let budget = ComputeBudget::new(
+        req.compute_unit_price_micro_lamports,
+        req.compute_unit_limit,
+    );
+    let nonce =
+        DurableNonce::from_request(req.nonce_account.as_deref(), req.nonce_authority.as_deref())
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
     let partial_sig = step_two(
         keypair,
         req.amount,
+        asset,
         to_pubkey,
         req.memo.clone(),
+        budget,
+        nonce,
         recent_block_hash,
-        public_keys,
+        signing_keys,
         first_messages,
         secret_state,
     )
@@ -289,10 +745,15 @@ async fn agg_send_step2() -> Result<HttpResponse, Error> {
     let response = AggSendStep2Response {
         partial_signature: base64::encode(sig_bytes),
     };
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-async fn aggregate_signatures_broadcast() -> Result<HttpResponse, Error> {
+/// Validate the request and aggregate its partial signatures into a fully signed transaction
+fn aggregate_transaction(req: &AggregateSignaturesRequest) -> Result<Transaction> {
+    check_participants("public_keys", req.public_keys.len())?;
+    check_participants("partial_signatures", req.partial_signatures.len())?;
+    check_participants("signers", req.signers.as_ref().map_or(0, Vec::len))?;
+
     let to_pubkey = Pubkey::from_str(&req.to).map_err(|e| {
         actix_web::error::ErrorBadRequest(format!("Invalid destination address: {}", e))
     })?;
@@ -308,6 +769,38 @@ async fn aggregate_signatures_broadcast() -> Result<HttpResponse, Error> {
     let public_keys = public_keys
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid public key: {}", e)))?;
 
+    let signers = req
+        .signers
+        .as_ref()
+        .map(|signers| {
+            signers
+                .iter()
+                .map(|key_str| Pubkey::from_str(key_str))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid signer: {}", e)))?;
+    let signing_keys = signing_set(&public_keys, signers.as_deref(), req.threshold)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    // Reject a missing or short list up front rather than failing deep inside aggregation
+    let threshold = req.threshold.unwrap_or(public_keys.len());
+    if req.partial_signatures.len() < threshold {
+        return Err(actix_web::error::ErrorBadRequest(
+            Error::ThresholdNotMet {
+                required: threshold,
+                signers: req.partial_signatures.len(),
+            }
+            .to_string(),
+        ));
+    }
+    if req.partial_signatures.len() != signing_keys.len() {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "Expected {} partial_signatures, one from each signer",
+            signing_keys.len()
+        )));
+    }
+
     // Deserialize partial signatures
     let partial_signatures: Result<Vec<PartialSignature>, _> = req
         .partial_signatures
@@ -322,35 +815,464 @@ async fn aggregate_signatures_broadcast() -> Result<HttpResponse, Error> {
     let partial_signatures =
         partial_signatures.map_err(|e| actix_web::error::ErrorBadRequest(e))?;
 
-    let transaction = sign_and_broadcast(
-        req.amount,
+    let asset = TransferAsset::from_request(req.token_mint.as_deref(), req.decimals)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let base_units = match (req.amount, req.base_units) {
+        (Some(amount), None) => asset
+            .base_units(amount)
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?,
+        (None, Some(base_units)) => base_units,
+        _ => {
+            return Err(actix_web::error::ErrorBadRequest(
+                "Exactly one of amount and base_units must be given",
+            ));
+        }
+    };
+    let budget = ComputeBudget::new(
+        req.compute_unit_price_micro_lamports,
+        req.compute_unit_limit,
+    );
+    let nonce =
+        DurableNonce::from_request(req.nonce_account.as_deref(), req.nonce_authority.as_deref())
+            .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    sign_and_broadcast(
+        base_units,
+        asset,
         to_pubkey,
         req.memo.clone(),
+        budget,
+        nonce,
         recent_block_hash,
-        public_keys,
+        signing_keys,
         partial_signatures,
     )
-    .map_err(|e| actix_web::error::ErrorBadRequest(format!("Aggregation failed: {}", e)))?;
+    .map_err(|e| actix_web::error::ErrorBadRequest(format!("Aggregation failed: {}", e)))
+}
+
+/// Aggregate partial signatures and return the signed transaction without sending it, for
+/// callers that submit through their own RPC
+async fn aggregate_signatures(req: web::Json<AggregateSignaturesRequest>) -> Result<HttpResponse> {
+    let transaction = aggregate_transaction(&req)?;
+    let bytes = bincode::serialize(&transaction).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to encode transaction: {}", e))
+    })?;
+
+    let response = AggregateSignaturesResponse {
+        signed_transaction_base64: base64::encode(bytes),
+        transaction_signature: transaction.signatures[0].to_string(),
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
+
+async fn aggregate_signatures_broadcast(
+    req: web::Json<AggregateSigsBroadcastRequest>,
+) -> Result<HttpResponse> {
+    let transaction = aggregate_transaction(&req.aggregate)?;
+
+    let rpc_url = resolve_rpc(req.network.as_deref(), req.rpc_url.as_deref())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    validate_rpc_url(&rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new_with_commitment(rpc_url, confirm_commitment());
+    let config = send_config(req.skip_preflight, req.preflight_commitment.as_deref())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+
+    let signature = send_and_confirm(&client, &transaction, config).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to send transaction: {}", e))
+    })?;
+
+    let response = AggregateSigsBroadcastResponse {
+        transaction_signature: signature.to_string(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Broadcast a transaction that was built and signed entirely client-side
+async fn broadcast(req: web::Json<BroadcastRequest>) -> Result<HttpResponse> {
+    let transaction = decode_signed_transaction(&req.signed_transaction_base64)
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
 
     let rpc_url = req
         .rpc_url
         .as_deref()
-        .unwrap_or("https://api.devnet.solana.com");
-    let client = RpcClient::new(rpc_url);
+        .unwrap_or(default_network().rpc_url());
+    validate_rpc_url(rpc_url, allow_private_rpc())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
+    let client = RpcClient::new_with_commitment(rpc_url, confirm_commitment());
+    let config = send_config(req.skip_preflight, req.preflight_commitment.as_deref())
+        .map_err(|e| actix_web::error::ErrorBadRequest(e.to_string()))?;
 
-    let signature = client
-        .send_and_confirm_transaction(&transaction)
-        .map_err(|e| {
-            actix_web::error::ErrorInternalServerError(format!("Failed to send transaction: {}", e))
-        })?;
+    let signature = send_and_confirm(&client, &transaction, config).map_err(|e| {
+        actix_web::error::ErrorInternalServerError(format!("Failed to send transaction: {}", e))
+    })?;
 
-    let response = AggregateSigsBroadcastResponse {
+    let response = BroadcastResponse {
         transaction_signature: signature.to_string(),
     };
-
-    Ok(HttpResponse::Ok().body("Hello, world!"))
+    Ok(HttpResponse::Ok().json(response))
 }
 
-fn sol_to_lamports(sol: f64) -> u64 {
-    (sol * 1_000_000_000.0) as u64
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use serde_json::{Value, json};
+
+    fn private_key(keypair: &Keypair) -> String {
+        bs58::encode(keypair.to_bytes()).into_string()
+    }
+
+    async fn post_json(uri: &str, body: Value) -> (StatusCode, Value) {
+        let app = init_service(
+            App::new()
+                .wrap(json_error_handlers())
+                .route("/generate", post().to(generate))
+                .route("/send-single", post().to(send_single))
+                .route("/aggregate-keys", post().to(aggregate_keys))
+                .route("/agg-send-step1", post().to(agg_send_step1))
+                .route("/agg-send-step2", post().to(agg_send_step2))
+                .route("/aggregate-signatures", post().to(aggregate_signatures))
+                .route(
+                    "/aggregate-signatures-broadcast",
+                    post().to(aggregate_signatures_broadcast),
+                ),
+        )
+        .await;
+        let req = TestRequest::post().uri(uri).set_json(body).to_request();
+        let res = call_service(&app, req).await;
+        let status = res.status();
+        (status, read_body_json(res).await)
+    }
+
+    #[test]
+    fn test_resolve_rpc() {
+        assert_eq!(
+            resolve_rpc(Some("devnet"), None).unwrap(),
+            Network::Devnet.rpc_url()
+        );
+        assert_eq!(
+            resolve_rpc(Some("mainnet"), Some("https://api.mainnet-beta.solana.com")).unwrap(),
+            "https://api.mainnet-beta.solana.com"
+        );
+        assert_eq!(
+            resolve_rpc(Some("devnet"), Some("http://localhost:8899")).unwrap(),
+            "http://localhost:8899"
+        );
+        assert!(matches!(
+            resolve_rpc(Some("devnet"), Some("https://api.mainnet-beta.solana.com")),
+            Err(Error::WrongNetwork(_))
+        ));
+        assert!(matches!(
+            resolve_rpc(Some("localnet"), None),
+            Err(Error::WrongNetwork(_))
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_health_returns_ok() {
+        let app = init_service(App::new().route("/health", get().to(health))).await;
+        let req = TestRequest::get().uri("/health").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        // RPC may be unreachable from the test environment, which only degrades the status
+        let expected = if body["rpc_reachable"] == true {
+            "ok"
+        } else {
+            "degraded"
+        };
+        assert_eq!(body["status"], expected);
+    }
+
+    #[actix_web::test]
+    async fn test_info_reports_version_and_network() {
+        let app = init_service(App::new().route("/info", get().to(info))).await;
+        let req = TestRequest::get().uri("/info").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(
+            body["default_network"],
+            serde_json::to_value(default_network()).unwrap()
+        );
+        assert_eq!(
+            body["supported_commitments"],
+            json!(["processed", "confirmed", "finalized"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_rent_exemption_rejects_oversized_account() {
+        let app = init_service(App::new().route("/rent-exemption", get().to(rent_exemption))).await;
+        // Rejected before any RPC call is made
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/rent-exemption?data_len={}",
+                MAX_PERMITTED_DATA_LENGTH + 1
+            ))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_rent_exemption_quote_shape() {
+        // The mock RPC quotes 20 lamports for any size
+        let client = RpcClient::new_mock("succeeds".to_string());
+        let quote = rent_exemption_quote(&client, 165).unwrap();
+        assert_eq!(
+            serde_json::to_value(quote).unwrap(),
+            json!({ "data_len": 165, "lamports": 20, "sol": "0.000000020" })
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_generate_returns_keypair() {
+        let (status, body) = post_json("/generate", json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let private_key = bs58::decode(body["private_key"].as_str().unwrap())
+            .into_vec()
+            .unwrap();
+        let keypair = Keypair::from_bytes(&private_key).unwrap();
+        assert_eq!(body["public_key"], keypair.pubkey().to_string());
+    }
+
+    #[actix_web::test]
+    async fn test_send_single_rejects_bad_key_as_json() {
+        // A successful send needs a live RPC node, so only the request wiring is covered here
+        let (status, body) = post_json(
+            "/send-single",
+            json!({ "private_key": "not-a-key", "to": Pubkey::new_unique().to_string(), "amount": 0.1 }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["status"], 400);
+        assert!(
+            body["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid private key")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_aggregate_keys_returns_pubkey() {
+        let keys = [Keypair::new().pubkey(), Keypair::new().pubkey()];
+        let (status, body) = post_json(
+            "/aggregate-keys",
+            json!({ "public_keys": [keys[0].to_string(), keys[1].to_string()] }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let expected = key_agg(keys.to_vec(), None).unwrap();
+        let expected = pubkey_from_bytes(&*expected.agg_public_key.to_bytes(true)).unwrap();
+        assert_eq!(body["aggregated_public_key"], expected.to_string());
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_body_is_413() {
+        let app = init_service(
+            App::new()
+                .app_data(web::JsonConfig::default().limit(1024))
+                .wrap(json_error_handlers())
+                .route("/agg-send-step2", post().to(agg_send_step2)),
+        )
+        .await;
+
+        let req = TestRequest::post()
+            .uri("/agg-send-step2")
+            .set_json(json!({ "first_messages": vec!["x".repeat(64); 64] }))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: Value = read_body_json(res).await;
+        assert_eq!(body["status"], 413);
+    }
+
+    #[actix_web::test]
+    async fn test_oversized_participant_arrays_rejected_early() {
+        // Entries are garbage, so a 400 naming the array proves nothing was decoded
+        let too_many = vec!["not-a-pubkey"; max_participants() + 1];
+
+        let (status, body) = post_json(
+            "/agg-send-step2",
+            json!({
+                "private_key": "not-a-key",
+                "amount": 0.1,
+                "to": "not-a-pubkey",
+                "recent_block_hash": Hash::default().to_string(),
+                "public_keys": too_many,
+                "first_messages": [],
+                "secret_state": "",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let expected = format!("public_keys has {} entries", too_many.len());
+        assert!(body["error"].as_str().unwrap().starts_with(&expected));
+
+        let (status, body) = post_json(
+            "/aggregate-signatures-broadcast",
+            json!({
+                "amount": 0.1,
+                "to": "not-a-pubkey",
+                "recent_block_hash": Hash::default().to_string(),
+                "public_keys": [],
+                "partial_signatures": too_many,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(
+            body["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("partial_signatures has")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_agg_send_steps_and_broadcast_wiring() {
+        let signers = [Keypair::new(), Keypair::new()];
+        let public_keys: Vec<String> = signers.iter().map(|k| k.pubkey().to_string()).collect();
+        let to = Pubkey::new_unique().to_string();
+        let recent_block_hash = Hash::default().to_string();
+
+        let mut step1 = Vec::new();
+        for signer in &signers {
+            let (status, body) = post_json(
+                "/agg-send-step1",
+                json!({ "private_key": private_key(signer) }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body["message1"].is_string());
+            assert!(body["secret_state"].is_string());
+            step1.push(body);
+        }
+
+        let mut partial_signatures = Vec::new();
+        for (i, signer) in signers.iter().enumerate() {
+            let (status, body) = post_json(
+                "/agg-send-step2",
+                json!({
+                    "private_key": private_key(signer),
+                    "amount": 0.1,
+                    "to": to,
+                    "recent_block_hash": recent_block_hash,
+                    "public_keys": public_keys,
+                    "first_messages": [step1[1 - i]["message1"]],
+                    "secret_state": step1[i]["secret_state"],
+                }),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            partial_signatures.push(body["partial_signature"].clone());
+        }
+
+        // Aggregating alone needs no RPC node; the result must be a valid, fully signed transaction
+        let (status, body) = post_json(
+            "/aggregate-signatures",
+            json!({
+                "amount": 0.1,
+                "to": to,
+                "recent_block_hash": recent_block_hash,
+                "public_keys": public_keys,
+                "partial_signatures": partial_signatures,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let bytes = base64::decode(body["signed_transaction_base64"].as_str().unwrap()).unwrap();
+        let transaction: Transaction = bincode::deserialize(&bytes).unwrap();
+        assert!(transaction.verify().is_ok());
+        assert_eq!(
+            body["transaction_signature"],
+            transaction.signatures[0].to_string()
+        );
+        let aggregated = key_agg(signers.iter().map(|k| k.pubkey()).collect(), None).unwrap();
+        assert_eq!(
+            transaction.message.account_keys[0],
+            pubkey_from_bytes(&*aggregated.agg_public_key.to_bytes(true)).unwrap()
+        );
+
+        // The same transfer given exactly in lamports aggregates to the same transaction
+        let exact_request = |amount: Value| {
+            json!({
+                "amount": amount,
+                "base_units": 100_000_000,
+                "to": to,
+                "recent_block_hash": recent_block_hash,
+                "public_keys": public_keys,
+                "partial_signatures": partial_signatures,
+            })
+        };
+        let (status, exact) = post_json("/aggregate-signatures", exact_request(Value::Null)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            exact["transaction_signature"],
+            body["transaction_signature"]
+        );
+        let (status, _) = post_json("/aggregate-signatures", exact_request(json!(0.1))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Broadcasting needs a live RPC node, so stop at the rejected destination address
+        let (status, body) = post_json(
+            "/aggregate-signatures-broadcast",
+            json!({
+                "amount": 0.1,
+                "to": "not-a-pubkey",
+                "recent_block_hash": recent_block_hash,
+                "public_keys": public_keys,
+                "partial_signatures": partial_signatures,
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["status"], 400);
+    }
+
+    #[actix_web::test]
+    async fn test_broadcast_rejects_missing_partial_signatures() {
+        let public_keys: Vec<String> = (0..3).map(|_| Pubkey::new_unique().to_string()).collect();
+        let request = |partial_signatures: Value, threshold: Option<usize>| {
+            json!({
+                "amount": 0.1,
+                "to": Pubkey::new_unique().to_string(),
+                "recent_block_hash": Hash::default().to_string(),
+                "public_keys": public_keys,
+                "partial_signatures": partial_signatures,
+                "threshold": threshold,
+            })
+        };
+
+        let (status, body) =
+            post_json("/aggregate-signatures-broadcast", request(json!([]), None)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"],
+            "Threshold not met: 3 signers required, 0 provided"
+        );
+
+        // Meets a 2-of-3 threshold but not the full signer set it implies
+        let (status, body) = post_json(
+            "/aggregate-signatures-broadcast",
+            request(json!(["not-base64", "not-base64"]), Some(2)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body["error"],
+            "Expected 3 partial_signatures, one from each signer"
+        );
+    }
 }
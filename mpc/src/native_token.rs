@@ -1,33 +1,712 @@
-use anchor_lang::prelude::*;
+use crate::serialization::Error;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{VersionedMessage, v0},
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
 
-pub fn lamports_to_sol(lamports: u64) -> f64 {
-    lamports as f64 / 1_000_000_000.0
+const LAMPORTS_PER_SOL_DECIMALS: u32 = 9;
+
+/// Exact SOL value of `lamports`, always carrying all 9 decimal places
+pub fn lamports_to_sol(lamports: u64) -> Decimal {
+    Decimal::from_i128_with_scale(i128::from(lamports), LAMPORTS_PER_SOL_DECIMALS)
+}
+
+/// Convert a SOL amount to lamports without float rounding, rejecting negative, non-finite,
+/// sub-lamport and out-of-range amounts instead of saturating
+pub fn sol_to_lamports(sol: f64) -> Result<u64, Error> {
+    ui_amount_to_base_units(sol, LAMPORTS_PER_SOL_DECIMALS)
+}
+
+/// Convert a UI amount to base units of a token with `decimals` places, with the same checks
+/// as `sol_to_lamports`
+pub fn ui_amount_to_base_units(amount: f64, decimals: u32) -> Result<u64, Error> {
+    let invalid = || Error::InvalidAmount(amount.to_string());
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(invalid());
+    }
+    // `f64`'s Display is the shortest string that round-trips, so 0.1 parses as exactly 0.1
+    let amount: Decimal = amount.to_string().parse().map_err(|_| invalid())?;
+    let scale = 10u64.checked_pow(decimals).ok_or_else(invalid)?;
+    let units = amount
+        .checked_mul(Decimal::from(scale))
+        .ok_or_else(invalid)?;
+    if !units.fract().is_zero() {
+        return Err(invalid());
+    }
+    units.to_u64().ok_or_else(invalid)
+}
+
+/// What a threshold-signed send moves: native SOL, or an SPL token of `mint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferAsset {
+    Sol,
+    Spl { mint: Pubkey, decimals: u8 },
+}
+
+impl TransferAsset {
+    /// `token_mint` and `decimals` come as a pair; neither means native SOL
+    pub fn from_request(token_mint: Option<&str>, decimals: Option<u8>) -> Result<Self, Error> {
+        match (token_mint, decimals) {
+            (None, None) => Ok(Self::Sol),
+            (Some(mint), Some(decimals)) => Ok(Self::Spl {
+                mint: Pubkey::from_str(mint).map_err(|_| Error::InvalidPubkey)?,
+                decimals,
+            }),
+            _ => Err(Error::InvalidTransaction(
+                "token_mint and decimals must be given together".to_string(),
+            )),
+        }
+    }
+
+    /// Convert a UI amount of this asset to its base units (lamports for SOL)
+    pub fn base_units(self, amount: f64) -> Result<u64, Error> {
+        match self {
+            Self::Sol => sol_to_lamports(amount),
+            Self::Spl { decimals, .. } => ui_amount_to_base_units(amount, u32::from(decimals)),
+        }
+    }
+}
+
+/// A `transfer_checked` between two token accounts; the mint and decimals are checked on chain,
+/// so a wrong `decimals` fails the transaction instead of moving the wrong amount
+pub fn build_spl_transfer(
+    from_ata: &Pubkey,
+    to_ata: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Vec<Instruction>, Error> {
+    let instruction = spl_token::instruction::transfer_checked(
+        &spl_token::id(),
+        from_ata,
+        mint,
+        to_ata,
+        owner,
+        &[],
+        amount,
+        decimals,
+    )
+    .map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+    Ok(vec![instruction])
 }
 
-pub fn sol_to_lamports(sol: f64) -> u64 {
-    (sol * 1_000_000_000.0) as u64
+/// Optional compute-budget instructions, so transfers can outbid others for block space
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub unit_price_micro_lamports: Option<u64>,
+    pub unit_limit: Option<u32>,
 }
 
-// Add this function to your tss.rs or create a separate transaction module
-pub fn create_unsigned_transaction(
+impl ComputeBudget {
+    pub fn new(unit_price_micro_lamports: Option<u64>, unit_limit: Option<u32>) -> Self {
+        Self {
+            unit_price_micro_lamports,
+            unit_limit,
+        }
+    }
+
+    /// The instructions to prepend; empty when neither value is set
+    fn instructions(&self) -> Vec<Instruction> {
+        let mut instructions = Vec::new();
+        if let Some(units) = self.unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if let Some(micro_lamports) = self.unit_price_micro_lamports {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_price(
+                micro_lamports,
+            ));
+        }
+        instructions
+    }
+}
+
+/// A durable nonce account to sign against instead of a recent blockhash, which would
+/// expire before partial signatures from slow or offline nodes are collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DurableNonce {
+    pub account: Pubkey,
+    /// Defaults to the sending wallet, the only signer an aggregated transaction has
+    pub authority: Option<Pubkey>,
+}
+
+impl DurableNonce {
+    /// `nonce_authority` is only meaningful alongside `nonce_account`
+    pub fn from_request(
+        nonce_account: Option<&str>,
+        nonce_authority: Option<&str>,
+    ) -> Result<Option<Self>, Error> {
+        let parse = |key: &str| Pubkey::from_str(key).map_err(|_| Error::InvalidPubkey);
+        match (nonce_account, nonce_authority) {
+            (None, None) => Ok(None),
+            (Some(account), authority) => Ok(Some(Self {
+                account: parse(account)?,
+                authority: authority.map(parse).transpose()?,
+            })),
+            (None, Some(_)) => Err(Error::InvalidTransaction(
+                "nonce_authority requires nonce_account".to_string(),
+            )),
+        }
+    }
+
+    /// The instruction every durable-nonce transaction must start with
+    fn advance_instruction(&self, fee_payer: &Pubkey) -> Result<Instruction, Error> {
+        let authority = self.authority.unwrap_or(*fee_payer);
+        // Any other authority would need a signature the MuSig2 round can't produce
+        if authority != *fee_payer {
+            return Err(Error::InvalidTransaction(format!(
+                "nonce authority {} must be the sending wallet {}",
+                authority, fee_payer
+            )));
+        }
+        Ok(system_instruction::advance_nonce_account(
+            &self.account,
+            &authority,
+        ))
+    }
+}
+
+/// Build the transaction both MuSig2 steps sign. SPL tokens move between the owners'
+/// associated token accounts; the destination's must already exist. With a durable `nonce`,
+/// the caller passes the nonce account's stored value where the recent blockhash would go.
+pub fn build_asset_transfer_transaction(
+    from: &Pubkey,
+    to: &Pubkey,
     amount: f64,
+    asset: TransferAsset,
+    memo: Option<String>,
+    budget: ComputeBudget,
+    nonce: Option<DurableNonce>,
+) -> Result<Transaction, Error> {
+    let base_units = asset.base_units(amount)?;
+    build_asset_transfer_in_base_units(from, to, base_units, asset, memo, budget, nonce)
+}
+
+/// `build_asset_transfer_transaction` for an amount already in the asset's base units, for
+/// callers holding exact amounts that must not round through `f64`
+pub fn build_asset_transfer_in_base_units(
+    from: &Pubkey,
     to: &Pubkey,
+    base_units: u64,
+    asset: TransferAsset,
     memo: Option<String>,
+    budget: ComputeBudget,
+    nonce: Option<DurableNonce>,
+) -> Result<Transaction, Error> {
+    let mut instructions = nonce
+        .map(|nonce| nonce.advance_instruction(from))
+        .transpose()?
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    match asset {
+        TransferAsset::Sol => {
+            instructions.extend(transfer_instructions(from, to, base_units, memo, budget))
+        }
+        TransferAsset::Spl { mint, decimals } => {
+            instructions.extend(budget.instructions());
+            instructions.extend(build_spl_transfer(
+                &get_associated_token_address(from, &mint),
+                &get_associated_token_address(to, &mint),
+                &mint,
+                from,
+                base_units,
+                decimals,
+            )?);
+            instructions.extend(memo.map(memo_instruction));
+        }
+    }
+    Ok(build_transaction_with_ixs(instructions, from))
+}
+
+/// Build the unsigned transfer transaction used by every send path.
+/// Single and aggregated sends must produce the exact same message, otherwise the
+/// aggregated signature won't verify, so instruction layout lives only here.
+pub fn build_transfer_transaction(
     from: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<String>,
+    budget: ComputeBudget,
 ) -> Transaction {
-    use solana_sdk::{system_instruction, transaction::Transaction};
+    build_transaction_with_ixs(
+        transfer_instructions(from, to, lamports, memo, budget),
+        from,
+    )
+}
+
+/// Build an unsigned legacy transaction from arbitrary instructions, e.g. SPL transfers or
+/// program calls, so they can go through the same aggregated-signature path as SOL transfers
+pub fn build_transaction_with_ixs(
+    instructions: Vec<Instruction>,
+    fee_payer: &Pubkey,
+) -> Transaction {
+    Transaction::new_with_payer(&instructions, Some(fee_payer))
+}
+
+/// Build the same transfer as a v0 message, letting accounts resolve through `lookup_tables`
+pub fn build_transfer_message_v0(
+    from: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<String>,
+    budget: ComputeBudget,
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage, Error> {
+    let instructions = transfer_instructions(from, to, lamports, memo, budget);
+    let message = v0::Message::try_compile(from, &instructions, lookup_tables, recent_blockhash)
+        .map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+    Ok(VersionedMessage::V0(message))
+}
+
+/// Wrap a message with placeholder signatures, for simulation with `sig_verify` off
+pub fn unsigned_versioned_transaction(message: VersionedMessage) -> VersionedTransaction {
+    let signatures = vec![Signature::default(); message.header().num_required_signatures as usize];
+    VersionedTransaction {
+        signatures,
+        message,
+    }
+}
+
+/// Whether the request asked for a v0 transaction; legacy (`None`) stays the default
+pub fn use_v0(version: Option<u8>) -> Result<bool, Error> {
+    match version {
+        None => Ok(false),
+        Some(0) => Ok(true),
+        Some(v) => Err(Error::InvalidTransaction(format!(
+            "unsupported transaction version {}",
+            v
+        ))),
+    }
+}
+
+fn transfer_instructions(
+    from: &Pubkey,
+    to: &Pubkey,
+    lamports: u64,
+    memo: Option<String>,
+    budget: ComputeBudget,
+) -> Vec<Instruction> {
+    let mut instructions = budget.instructions();
+
+    instructions.push(system_instruction::transfer(from, to, lamports));
+
+    instructions.extend(memo.map(memo_instruction));
+
+    instructions
+}
 
-    let lamports = (amount * 1_000_000_000.0) as u64;
-    let mut instructions = vec![system_instruction::transfer(from, to, lamports)];
+fn memo_instruction(memo: String) -> Instruction {
+    Instruction::new_with_bytes(spl_memo::id(), memo.as_bytes(), vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ComputeBudget, DurableNonce, TransferAsset, build_asset_transfer_transaction,
+        build_spl_transfer, build_transaction_with_ixs, build_transfer_message_v0,
+        build_transfer_transaction, lamports_to_sol, sol_to_lamports,
+        unsigned_versioned_transaction, use_v0,
+    };
+    use crate::serialization::{AggMessage1, Error, pubkey_from_bytes};
+    use crate::tss::{key_agg, sign_and_broadcast, step_one, step_two};
+    use rust_decimal::Decimal;
+    use solana_sdk::{
+        address_lookup_table_account::AddressLookupTableAccount, compute_budget, hash::Hash,
+        instruction::Instruction, message::VersionedMessage, pubkey::Pubkey, signature::Keypair,
+        signer::Signer, system_instruction, system_program, transaction::VersionedTransaction,
+    };
+
+    #[test]
+    fn test_v0_transfer_uses_lookup_table() {
+        let payer = Keypair::new();
+        let to = Pubkey::new_unique();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![to],
+        };
+
+        let message = build_transfer_message_v0(
+            &payer.pubkey(),
+            &to,
+            1_000,
+            Some("memo".to_string()),
+            ComputeBudget::default(),
+            &[table.clone()],
+            Hash::new_unique(),
+        )
+        .unwrap();
+
+        let VersionedMessage::V0(v0_message) = &message else {
+            panic!("expected a v0 message");
+        };
+        // The destination is loaded through the table instead of the static keys
+        assert!(!v0_message.account_keys.contains(&to));
+        assert_eq!(v0_message.address_table_lookups[0].account_key, table.key);
+
+        let unsigned = unsigned_versioned_transaction(message.clone());
+        assert_eq!(unsigned.signatures.len(), 1);
+
+        let signed = VersionedTransaction::try_new(message, &[&payer]).unwrap();
+        assert!(signed.verify_with_results().iter().all(|ok| *ok));
+    }
+
+    #[test]
+    fn test_use_v0() {
+        assert!(!use_v0(None).unwrap());
+        assert!(use_v0(Some(0)).unwrap());
+        assert!(use_v0(Some(1)).is_err());
+    }
+
+    #[test]
+    fn test_aggregated_signature_covers_the_single_send_message() {
+        let signers = [Keypair::new(), Keypair::new()];
+        let keys: Vec<Pubkey> = signers.iter().map(|k| k.pubkey()).collect();
+        let to = Pubkey::new_unique();
+        let memo = Some("invoice-42".to_string());
+        let budget = ComputeBudget::new(Some(1_000), Some(200_000));
+        let recent_block_hash = Hash::new_unique();
+        let copy = |k: &Keypair| Keypair::from_bytes(&k.to_bytes()).unwrap();
+
+        // Two-party MuSig2 round: step_two converts the SOL amount and builds its own message
+        let (first, second) = (step_one(copy(&signers[0])), step_one(copy(&signers[1])));
+        let sign = |keypair: Keypair, other: AggMessage1, secret| {
+            step_two(
+                keypair,
+                0.5,
+                TransferAsset::Sol,
+                to,
+                memo.clone(),
+                budget,
+                None,
+                recent_block_hash,
+                keys.clone(),
+                vec![other],
+                secret,
+            )
+            .unwrap()
+        };
+        let partials = vec![
+            sign(copy(&signers[0]), second.0.clone(), first.1),
+            sign(copy(&signers[1]), first.0, second.1),
+        ];
+        let aggregated = sign_and_broadcast(
+            sol_to_lamports(0.5).unwrap(),
+            TransferAsset::Sol,
+            to,
+            memo.clone(),
+            budget,
+            None,
+            recent_block_hash,
+            keys.clone(),
+            partials,
+        )
+        .unwrap();
+
+        // send_single converts up front and signs build_transfer_transaction's message
+        let agg_pubkey =
+            pubkey_from_bytes(&*key_agg(keys, None).unwrap().agg_public_key.to_bytes(true))
+                .unwrap();
+        let mut single = build_transfer_transaction(
+            &agg_pubkey,
+            &to,
+            sol_to_lamports(0.5).unwrap(),
+            memo,
+            budget,
+        );
+        single.message.recent_blockhash = recent_block_hash;
+
+        assert_eq!(aggregated.message_data(), single.message_data());
+        assert!(aggregated.signatures[0].verify(agg_pubkey.as_ref(), &single.message_data()));
+    }
 
-    if let Some(memo_text) = memo {
-        let memo_instruction = solana_program::instruction::Instruction::new_with_bytes(
-            spl_memo::id(),
-            memo_text.as_bytes(),
-            vec![],
+    #[test]
+    fn test_instruction_order() {
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+
+        let tx = build_transfer_transaction(
+            &from,
+            &to,
+            1,
+            Some("memo".to_string()),
+            ComputeBudget::new(Some(5), None),
+        );
+        let programs: Vec<_> = tx
+            .message
+            .instructions
+            .iter()
+            .map(|ix| tx.message.account_keys[ix.program_id_index as usize])
+            .collect();
+        assert_eq!(
+            programs,
+            vec![compute_budget::id(), system_program::id(), spl_memo::id()]
+        );
+
+        let plain = build_transfer_transaction(&from, &to, 1, None, ComputeBudget::default());
+        assert_eq!(plain.message.instructions.len(), 1);
+        assert_eq!(plain.message.account_keys[0], from);
+    }
+
+    #[test]
+    fn test_memo_appended_only_when_some() {
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let has_memo = |tx: &solana_sdk::transaction::Transaction| {
+            tx.message
+                .instructions
+                .iter()
+                .any(|ix| tx.message.account_keys[ix.program_id_index as usize] == spl_memo::id())
+        };
+
+        let with_memo = build_transfer_transaction(
+            &from,
+            &to,
+            1,
+            Some("memo".to_string()),
+            ComputeBudget::default(),
+            None,
+        );
+        let last = with_memo.message.instructions.last().unwrap();
+        assert_eq!(last.data, b"memo");
+        assert!(has_memo(&with_memo));
+
+        let without_memo =
+            build_transfer_transaction(&from, &to, 1, None, ComputeBudget::default());
+        assert!(!has_memo(&without_memo));
+    }
+
+    #[test]
+    fn test_arbitrary_instructions_keep_fee_payer() {
+        let fee_payer = Pubkey::new_unique();
+        let program = Pubkey::new_unique();
+        let instructions = vec![
+            Instruction::new_with_bytes(program, &[1, 2, 3], vec![]),
+            system_instruction::transfer(&fee_payer, &Pubkey::new_unique(), 5),
+        ];
+
+        let tx = build_transaction_with_ixs(instructions, &fee_payer);
+        assert_eq!(tx.message.account_keys[0], fee_payer);
+        assert_eq!(tx.message.instructions.len(), 2);
+        assert_eq!(
+            tx.message.account_keys[tx.message.instructions[0].program_id_index as usize],
+            program
         );
-        instructions.push(memo_instruction);
     }
 
-    Transaction::new_with_payer(&instructions, Some(from))
+    #[test]
+    fn test_sol_to_lamports_is_exact() {
+        // 0.1 * 1e9 as f64 is 99_999_999.99..., which truncated to 99_999_999
+        assert_eq!(sol_to_lamports(0.1).unwrap(), 100_000_000);
+        assert_eq!(sol_to_lamports(0.0).unwrap(), 0);
+        assert_eq!(sol_to_lamports(1.000000001).unwrap(), 1_000_000_001);
+    }
+
+    #[test]
+    fn test_sol_to_lamports_rejects_invalid_amounts() {
+        // u64::MAX lamports is about 18.4 billion SOL
+        for sol in [2e10, 1e30, -0.5, f64::NAN, f64::INFINITY, 1e-10] {
+            assert!(
+                matches!(sol_to_lamports(sol), Err(Error::InvalidAmount(_))),
+                "{} should be rejected",
+                sol
+            );
+        }
+    }
+
+    #[test]
+    fn test_lamports_to_sol_is_exact() {
+        assert_eq!(lamports_to_sol(100_000_000), Decimal::new(1, 1));
+        assert_eq!(
+            lamports_to_sol(u64::MAX).to_string(),
+            "18446744073.709551615"
+        );
+    }
+
+    #[test]
+    fn test_spl_transfer_targets_token_program() {
+        let from_ata = Pubkey::new_unique();
+        let to_ata = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let instructions =
+            build_spl_transfer(&from_ata, &to_ata, &mint, &owner, 1_500_000, 6).unwrap();
+        assert_eq!(instructions.len(), 1);
+        let ix = &instructions[0];
+        assert_eq!(ix.program_id, spl_token::id());
+
+        // TransferChecked: tag 12, amount as u64 LE, then decimals
+        assert_eq!(ix.data[0], 12);
+        assert_eq!(ix.data[1..9], 1_500_000u64.to_le_bytes());
+        assert_eq!(ix.data[9], 6);
+        let accounts: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(accounts, vec![from_ata, mint, to_ata, owner]);
+        assert!(ix.accounts[3].is_signer);
+    }
+
+    #[test]
+    fn test_asset_selection() {
+        let mint = Pubkey::new_unique();
+        assert_eq!(
+            TransferAsset::from_request(None, None).unwrap(),
+            TransferAsset::Sol
+        );
+        assert_eq!(
+            TransferAsset::from_request(Some(&mint.to_string()), Some(6)).unwrap(),
+            TransferAsset::Spl { mint, decimals: 6 }
+        );
+        assert!(TransferAsset::from_request(Some(&mint.to_string()), None).is_err());
+        assert!(TransferAsset::from_request(None, Some(6)).is_err());
+
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let spl = build_asset_transfer_transaction(
+            &from,
+            &to,
+            2.5,
+            TransferAsset::Spl { mint, decimals: 6 },
+            Some("memo".to_string()),
+            ComputeBudget::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(spl.message.account_keys[0], from);
+        assert_eq!(spl.message.instructions.len(), 2);
+        assert_eq!(
+            spl.message.instructions[0].data[1..9],
+            2_500_000u64.to_le_bytes()
+        );
+
+        let budget = ComputeBudget::new(Some(10), None);
+        let sol = build_asset_transfer_transaction(
+            &from,
+            &to,
+            0.5,
+            TransferAsset::Sol,
+            None,
+            budget,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            sol.message_data(),
+            build_transfer_transaction(&from, &to, 500_000_000, None, budget).message_data()
+        );
+    }
+
+    #[test]
+    fn test_compute_budget_precedes_transfer() {
+        let from = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let program_ids = |tx: &solana_sdk::transaction::Transaction| -> Vec<Pubkey> {
+            tx.message
+                .instructions
+                .iter()
+                .map(|ix| tx.message.account_keys[ix.program_id_index as usize])
+                .collect()
+        };
+
+        let budget = ComputeBudget::new(Some(25_000), Some(300_000));
+        let sol = build_transfer_transaction(&from, &to, 1, None, budget);
+        assert_eq!(
+            program_ids(&sol),
+            vec![
+                compute_budget::id(),
+                compute_budget::id(),
+                system_program::id()
+            ]
+        );
+
+        let mint = Pubkey::new_unique();
+        let spl = build_asset_transfer_transaction(
+            &from,
+            &to,
+            1.0,
+            TransferAsset::Spl { mint, decimals: 0 },
+            None,
+            budget,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            program_ids(&spl),
+            vec![compute_budget::id(), compute_budget::id(), spl_token::id()]
+        );
+    }
+
+    #[test]
+    fn test_durable_nonce_advance_comes_first() {
+        let payer = Keypair::new();
+        let from = payer.pubkey();
+        let to = Pubkey::new_unique();
+        let nonce = DurableNonce {
+            account: Pubkey::new_unique(),
+            authority: None,
+        };
+        // The value stored in the nonce account, used in place of a recent blockhash
+        let nonce_value = Hash::new_unique();
+
+        let mut tx = build_asset_transfer_transaction(
+            &from,
+            &to,
+            0.5,
+            TransferAsset::Sol,
+            Some("memo".to_string()),
+            ComputeBudget::new(Some(1_000), None),
+            Some(nonce),
+        )
+        .unwrap();
+        tx.sign(&[&payer], nonce_value);
+
+        let first = &tx.message.instructions[0];
+        assert_eq!(
+            tx.message.account_keys[first.program_id_index as usize],
+            system_program::id()
+        );
+        let advance = system_instruction::advance_nonce_account(&nonce.account, &from);
+        assert_eq!(first.data, advance.data);
+        assert_eq!(
+            tx.message.account_keys[first.accounts[0] as usize],
+            nonce.account
+        );
+        assert_eq!(tx.message.recent_blockhash, nonce_value);
+        assert!(tx.verify().is_ok());
+    }
+
+    #[test]
+    fn test_durable_nonce_authority_must_be_sender() {
+        let from = Pubkey::new_unique();
+        let nonce = DurableNonce::from_request(
+            Some(&Pubkey::new_unique().to_string()),
+            Some(&Pubkey::new_unique().to_string()),
+        )
+        .unwrap();
+
+        let result = build_asset_transfer_transaction(
+            &from,
+            &Pubkey::new_unique(),
+            1.0,
+            TransferAsset::Sol,
+            None,
+            ComputeBudget::default(),
+            nonce,
+        );
+        assert!(matches!(result, Err(Error::InvalidTransaction(_))));
+
+        assert_eq!(DurableNonce::from_request(None, None).unwrap(), None);
+        assert!(DurableNonce::from_request(None, Some(&from.to_string())).is_err());
+    }
 }
@@ -0,0 +1,554 @@
+use crate::serialization::Error;
+use serde::Serialize;
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::{
+    client_error::ClientError,
+    rpc_client::{RpcClient, SerializableTransaction},
+    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    rpc_response::RpcSimulateTransactionResult,
+};
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::Transaction,
+};
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use url::{Host, Url};
+
+/// Commitment levels accepted for `preflight_commitment`
+pub const SUPPORTED_COMMITMENTS: [&str; 3] = ["processed", "confirmed", "finalized"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+}
+
+impl Network {
+    /// Public RPC endpoint used when the request doesn't specify one
+    pub fn rpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://api.mainnet-beta.solana.com",
+            Network::Testnet => "https://api.testnet.solana.com",
+            Network::Devnet => "https://api.devnet.solana.com",
+        }
+    }
+
+    /// Cluster an RPC url points at, judged by its host name; None for custom endpoints
+    pub fn from_rpc_url(rpc_url: &str) -> Option<Network> {
+        let host = Url::parse(rpc_url).ok()?.host_str()?.to_ascii_lowercase();
+        if host.contains("mainnet") {
+            Some(Network::Mainnet)
+        } else if host.contains("testnet") {
+            Some(Network::Testnet)
+        } else if host.contains("devnet") {
+            Some(Network::Devnet)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "mainnet" | "mainnet-beta" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            _ => Err(Error::WrongNetwork(s.to_string())),
+        }
+    }
+}
+
+/// Network this node serves by default, configurable via `MPC_DEFAULT_NETWORK` (devnet if unset).
+/// Panics on an unrecognized value, so call it once at startup to fail fast.
+pub fn default_network() -> Network {
+    static NETWORK: OnceLock<Network> = OnceLock::new();
+    *NETWORK.get_or_init(|| {
+        configured_network(std::env::var("MPC_DEFAULT_NETWORK").ok().as_deref())
+            .unwrap_or_else(|e| panic!("{}", e))
+    })
+}
+
+/// The network an `MPC_DEFAULT_NETWORK` value names, devnet when it is unset
+fn configured_network(value: Option<&str>) -> Result<Network, Error> {
+    value.map_or(Ok(Network::Devnet), Network::from_str)
+}
+
+/// Whether RPC urls may point at private/loopback addresses (e.g. a local test validator)
+pub fn allow_private_rpc() -> bool {
+    std::env::var("ALLOW_PRIVATE_RPC")
+        .map(|value| matches!(value.as_str(), "1" | "true"))
+        .unwrap_or(false)
+}
+
+/// Validate a caller-supplied RPC url before connecting to it, guarding against typos and SSRF.
+/// Only `https` is accepted, except `http` for localhost; private and loopback hosts are
+/// rejected unless `allow_private` is set.
+pub fn validate_rpc_url(rpc_url: &str, allow_private: bool) -> Result<Url, Error> {
+    let url =
+        Url::parse(rpc_url).map_err(|e| Error::InvalidRpcUrl(format!("{}: {}", rpc_url, e)))?;
+
+    let host = url
+        .host()
+        .ok_or_else(|| Error::InvalidRpcUrl(format!("{}: missing host", rpc_url)))?;
+
+    let is_localhost = match &host {
+        Host::Domain(domain) => domain.eq_ignore_ascii_case("localhost"),
+        Host::Ipv4(ip) => ip.is_loopback(),
+        Host::Ipv6(ip) => ip.is_loopback(),
+    };
+
+    match url.scheme() {
+        "https" => {}
+        "http" if is_localhost => {}
+        scheme => {
+            return Err(Error::InvalidRpcUrl(format!(
+                "{}: unsupported scheme {}",
+                rpc_url, scheme
+            )));
+        }
+    }
+
+    let is_private = match host {
+        Host::Domain(_) => is_localhost,
+        Host::Ipv4(ip) => is_private_ip(IpAddr::V4(ip)),
+        Host::Ipv6(ip) => is_private_ip(IpAddr::V6(ip)),
+    };
+
+    if is_private && !allow_private {
+        return Err(Error::InvalidRpcUrl(format!(
+            "{}: private or loopback addresses are not allowed",
+            rpc_url
+        )));
+    }
+
+    Ok(url)
+}
+
+/// Refuse airdrops against mainnet, where the faucet doesn't exist and a request is always a mistake
+pub fn ensure_airdrop_network(rpc_url: &Url) -> Result<(), Error> {
+    let is_mainnet = rpc_url
+        .host_str()
+        .is_some_and(|host| host.to_ascii_lowercase().contains("mainnet"));
+    if is_mainnet {
+        return Err(Error::WrongNetwork(format!(
+            "{} (airdrops are only available on devnet, testnet or a local validator)",
+            rpc_url
+        )));
+    }
+    Ok(())
+}
+
+/// Build the send config from the optional request settings; preflight runs by default
+pub fn send_config(
+    skip_preflight: Option<bool>,
+    preflight_commitment: Option<&str>,
+) -> Result<RpcSendTransactionConfig, Error> {
+    let preflight_commitment = preflight_commitment
+        .map(|level| {
+            CommitmentLevel::from_str(level)
+                .map_err(|_| Error::InvalidCommitment(level.to_string()))
+        })
+        .transpose()?;
+
+    Ok(RpcSendTransactionConfig {
+        skip_preflight: skip_preflight.unwrap_or(false),
+        preflight_commitment,
+        ..RpcSendTransactionConfig::default()
+    })
+}
+
+/// Send a transaction with the given config and wait until it reaches the client's commitment
+pub fn send_and_confirm(
+    client: &RpcClient,
+    transaction: &impl SerializableTransaction,
+    config: RpcSendTransactionConfig,
+) -> Result<Signature, ClientError> {
+    let signature = client.send_transaction_with_config(transaction, config)?;
+    client.poll_for_signature_with_commitment(&signature, client.commitment())?;
+    Ok(signature)
+}
+
+/// Simulate an unsigned transaction: signatures aren't checked and the node fills in a
+/// recent blockhash, so nothing needs signing before a dry run
+pub fn simulate_unsigned(
+    client: &RpcClient,
+    transaction: &impl SerializableTransaction,
+) -> Result<RpcSimulateTransactionResult, ClientError> {
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..RpcSimulateTransactionConfig::default()
+    };
+    Ok(client
+        .simulate_transaction_with_config(transaction, config)?
+        .value)
+}
+
+/// Fetch and decode the address lookup tables referenced by a v0 transaction
+pub fn fetch_lookup_tables(
+    client: &RpcClient,
+    addresses: &[String],
+) -> Result<Vec<AddressLookupTableAccount>, Error> {
+    addresses
+        .iter()
+        .map(|address| {
+            let key = Pubkey::from_str(address).map_err(|_| Error::InvalidPubkey)?;
+            let account = client.get_account(&key).map_err(|e| {
+                Error::InvalidTransaction(format!("lookup table {}: {}", address, e))
+            })?;
+            let table = AddressLookupTable::deserialize(&account.data).map_err(|e| {
+                Error::InvalidTransaction(format!("lookup table {}: {}", address, e))
+            })?;
+            Ok(AddressLookupTableAccount {
+                key,
+                addresses: table.addresses.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Commitment used when confirming broadcasts
+pub fn confirm_commitment() -> CommitmentConfig {
+    CommitmentConfig::confirmed()
+}
+
+/// Decode a base64 bincode-serialized transaction and check every required signature is valid
+pub fn decode_signed_transaction(encoded: &str) -> Result<Transaction, Error> {
+    let bytes = base64::decode(encoded)
+        .map_err(|e| Error::InvalidTransaction(format!("bad base64: {}", e)))?;
+    let transaction: Transaction = bincode::deserialize(&bytes)
+        .map_err(|e| Error::InvalidTransaction(format!("bad encoding: {}", e)))?;
+
+    if !transaction.is_signed() {
+        return Err(Error::InvalidTransaction(
+            "transaction is missing signatures".to_string(),
+        ));
+    }
+    transaction
+        .verify()
+        .map_err(|e| Error::InvalidTransaction(e.to_string()))?;
+
+    Ok(transaction)
+}
+
+fn is_private_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_private_ip(IpAddr::V4(mapped));
+            }
+            ip.is_loopback() || ip.is_unspecified() || is_unique_local(&ip) || is_link_local(&ip)
+        }
+    }
+}
+
+// fc00::/7
+fn is_unique_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+
+// fe80::/10
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Network, configured_network, confirm_commitment, decode_signed_transaction,
+        ensure_airdrop_network, send_and_confirm, send_config, simulate_unsigned, validate_rpc_url,
+    };
+    use crate::native_token::{ComputeBudget, build_transfer_transaction};
+    use crate::serialization::Error;
+    use serde_json::{Value, json};
+    use solana_client::rpc_client::RpcClient;
+    use solana_sdk::commitment_config::CommitmentLevel;
+    use solana_sdk::transaction::TransactionError;
+    use solana_sdk::{hash::Hash, pubkey::Pubkey, signature::Keypair, signer::Signer};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    /// A JSON-RPC node on localhost that answers each method with `result` and records every
+    /// request body it receives
+    fn mock_rpc(
+        result: impl Fn(&str) -> Value + Send + 'static,
+    ) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let captured = Arc::clone(&requests);
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = BufReader::new(stream.unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                stream.read_exact(&mut body).unwrap();
+                let request: Value = serde_json::from_slice(&body).unwrap();
+
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": result(request["method"].as_str().unwrap()),
+                })
+                .to_string();
+                captured.lock().unwrap().push(request);
+                write!(
+                    stream.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    #[test]
+    fn test_decode_signed_transaction() {
+        let keypair = Keypair::new();
+        let mut tx = build_transfer_transaction(
+            &keypair.pubkey(),
+            &Pubkey::new_unique(),
+            1_000,
+            None,
+            ComputeBudget::default(),
+        );
+
+        let unsigned = base64::encode(bincode::serialize(&tx).unwrap());
+        assert!(matches!(
+            decode_signed_transaction(&unsigned),
+            Err(Error::InvalidTransaction(_))
+        ));
+
+        tx.sign(&[&keypair], Hash::new_unique());
+        let signed = base64::encode(bincode::serialize(&tx).unwrap());
+        let decoded = decode_signed_transaction(&signed).unwrap();
+        assert_eq!(decoded.signatures, tx.signatures);
+
+        assert!(decode_signed_transaction("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_airdrop_refused_on_mainnet() {
+        let mainnet = validate_rpc_url(Network::Mainnet.rpc_url(), false).unwrap();
+        assert!(matches!(
+            ensure_airdrop_network(&mainnet),
+            Err(Error::WrongNetwork(_))
+        ));
+
+        let devnet = validate_rpc_url(Network::Devnet.rpc_url(), false).unwrap();
+        assert!(ensure_airdrop_network(&devnet).is_ok());
+        let local = validate_rpc_url("http://127.0.0.1:8899", true).unwrap();
+        assert!(ensure_airdrop_network(&local).is_ok());
+    }
+
+    #[test]
+    fn test_network_from_str() {
+        assert_eq!(Network::from_str("Devnet").unwrap(), Network::Devnet);
+        assert_eq!(Network::from_str("mainnet-beta").unwrap(), Network::Mainnet);
+        assert!(matches!(
+            Network::from_str("localnet"),
+            Err(Error::WrongNetwork(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_network_follows_config() {
+        assert_eq!(configured_network(None).unwrap(), Network::Devnet);
+        assert_eq!(
+            configured_network(Some("mainnet-beta")).unwrap(),
+            Network::Mainnet
+        );
+        assert!(matches!(
+            configured_network(Some("localnet")),
+            Err(Error::WrongNetwork(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_config_defaults() {
+        let config = send_config(None, None).unwrap();
+        assert!(!config.skip_preflight);
+        assert_eq!(config.preflight_commitment, None);
+    }
+
+    #[test]
+    fn test_send_config_honors_settings() {
+        let config = send_config(Some(true), Some("processed")).unwrap();
+        assert!(config.skip_preflight);
+        assert_eq!(
+            config.preflight_commitment,
+            Some(CommitmentLevel::Processed)
+        );
+
+        assert!(matches!(
+            send_config(None, Some("eventually")),
+            Err(Error::InvalidCommitment(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_config_reaches_the_rpc_node() {
+        let keypair = Keypair::new();
+        let mut tx = build_transfer_transaction(
+            &keypair.pubkey(),
+            &Pubkey::new_unique(),
+            1_000,
+            None,
+            ComputeBudget::default(),
+        );
+        tx.sign(&[&keypair], Hash::new_unique());
+        let signature = tx.signatures[0].to_string();
+
+        let (url, requests) = mock_rpc(move |method| match method {
+            "getVersion" => json!({ "solana-core": "1.16.0", "feature-set": 0 }),
+            "sendTransaction" => json!(signature),
+            "getSignatureStatuses" => json!({
+                "context": { "slot": 1 },
+                "value": [{
+                    "slot": 1,
+                    "confirmations": null,
+                    "err": null,
+                    "status": { "Ok": null },
+                    "confirmationStatus": "finalized",
+                }],
+            }),
+            other => panic!("unexpected RPC call {}", other),
+        });
+        let client = RpcClient::new_with_commitment(url, confirm_commitment());
+
+        let config = send_config(Some(true), Some("processed")).unwrap();
+        assert_eq!(
+            send_and_confirm(&client, &tx, config).unwrap(),
+            tx.signatures[0]
+        );
+
+        let requests = requests.lock().unwrap();
+        let sent = requests
+            .iter()
+            .find(|request| request["method"] == "sendTransaction")
+            .unwrap();
+        assert_eq!(sent["params"][1]["skipPreflight"], true);
+        assert_eq!(sent["params"][1]["preflightCommitment"], "processed");
+    }
+
+    #[test]
+    fn test_simulate_unsigned_reports_success_and_failure() {
+        // An unfunded sender fails simulation the way a real node reports it
+        let (url, requests) = mock_rpc(|method| match method {
+            "getVersion" => json!({ "solana-core": "1.16.0", "feature-set": 0 }),
+            "simulateTransaction" => json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "err": "AccountNotFound",
+                    "logs": [],
+                    "accounts": null,
+                    "unitsConsumed": 0,
+                    "returnData": null,
+                },
+            }),
+            other => panic!("unexpected RPC call {}", other),
+        });
+        let transaction = build_transfer_transaction(
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            1_000,
+            None,
+            ComputeBudget::default(),
+        );
+        let client = RpcClient::new_with_commitment(url, confirm_commitment());
+        let result = simulate_unsigned(&client, &transaction).unwrap();
+        assert_eq!(result.err, Some(TransactionError::AccountNotFound));
+
+        let requests = requests.lock().unwrap();
+        let simulated = requests
+            .iter()
+            .find(|request| request["method"] == "simulateTransaction")
+            .unwrap();
+        assert_eq!(simulated["params"][1]["sigVerify"], false);
+        assert_eq!(simulated["params"][1]["replaceRecentBlockhash"], true);
+
+        let (url, _) = mock_rpc(|method| match method {
+            "getVersion" => json!({ "solana-core": "1.16.0", "feature-set": 0 }),
+            "simulateTransaction" => json!({
+                "context": { "slot": 1 },
+                "value": {
+                    "err": null,
+                    "logs": [
+                        "Program 11111111111111111111111111111111 invoke [1]",
+                        "Program 11111111111111111111111111111111 success",
+                    ],
+                    "accounts": null,
+                    "unitsConsumed": 150,
+                    "returnData": null,
+                },
+            }),
+            other => panic!("unexpected RPC call {}", other),
+        });
+        let client = RpcClient::new_with_commitment(url, confirm_commitment());
+        let result = simulate_unsigned(&client, &transaction).unwrap();
+        assert_eq!(result.err, None);
+        assert_eq!(result.units_consumed, Some(150));
+        assert_eq!(result.logs.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_public_https_url() {
+        assert!(validate_rpc_url("https://api.devnet.solana.com", false).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_scheme() {
+        let res = validate_rpc_url("htp://api.devnet.solana.com", false);
+        assert!(matches!(res, Err(Error::InvalidRpcUrl(_))));
+    }
+
+    #[test]
+    fn test_internal_ip() {
+        let res = validate_rpc_url("https://169.254.169.254/latest/meta-data", false);
+        assert!(matches!(res, Err(Error::InvalidRpcUrl(_))));
+
+        let res = validate_rpc_url("https://10.0.0.5:8899", false);
+        assert!(matches!(res, Err(Error::InvalidRpcUrl(_))));
+    }
+
+    #[test]
+    fn test_localhost_only_when_allowed() {
+        assert!(validate_rpc_url("http://localhost:8899", false).is_err());
+        assert!(validate_rpc_url("http://127.0.0.1:8899", true).is_ok());
+        assert!(validate_rpc_url("http://10.0.0.5:8899", true).is_err());
+    }
+}
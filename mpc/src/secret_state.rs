@@ -0,0 +1,96 @@
+use crate::serialization::{Deserialize, Error, SecretAggStepOne, Serialize};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const TAG_SIZE: usize = 32;
+
+/// Key used to authenticate the secret state handed back to clients between steps.
+/// Read from `MPC_STATE_KEY`; falls back to a random per-process key, in which case a
+/// secret state is only accepted by the process that issued it.
+fn state_key() -> &'static [u8] {
+    static KEY: OnceLock<Vec<u8>> = OnceLock::new();
+    KEY.get_or_init(|| match std::env::var("MPC_STATE_KEY") {
+        Ok(key) if !key.is_empty() => key.into_bytes(),
+        _ => {
+            let mut key = vec![0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key
+        }
+    })
+}
+
+/// Serialize the secret state and append an HMAC tag so step 2 can detect tampering
+pub fn seal(secret_state: &SecretAggStepOne) -> Vec<u8> {
+    seal_with_key(secret_state, state_key())
+}
+
+/// Verify the HMAC tag and deserialize the secret state sealed by [`seal`]
+pub fn open(buffer: &[u8]) -> Result<SecretAggStepOne, Error> {
+    open_with_key(buffer, state_key())
+}
+
+fn seal_with_key(secret_state: &SecretAggStepOne, key: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    secret_state.serialize(&mut buffer);
+
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(&buffer);
+    buffer.extend_from_slice(&mac.finalize().into_bytes());
+    buffer
+}
+
+fn open_with_key(buffer: &[u8], key: &[u8]) -> Result<SecretAggStepOne, Error> {
+    if buffer.len() < TAG_SIZE {
+        return Err(Error::BufferTooShort);
+    }
+    let (payload, tag) = buffer.split_at(buffer.len() - TAG_SIZE);
+
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    mac.verify_slice(tag)
+        .map_err(|_| Error::TamperedSecretState)?;
+
+    SecretAggStepOne::deserialize(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_with_key, seal_with_key};
+    use crate::serialization::Error;
+    use crate::tss::step_one;
+    use solana_sdk::signature::Keypair;
+
+    const KEY: &[u8] = b"test-state-key";
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let (_, secret) = step_one(Keypair::generate(&mut rand::thread_rng()));
+        let sealed = seal_with_key(&secret, KEY);
+        assert!(open_with_key(&sealed, KEY).unwrap() == secret);
+    }
+
+    #[test]
+    fn test_tampered_state_rejected() {
+        let (_, secret) = step_one(Keypair::generate(&mut rand::thread_rng()));
+        let mut sealed = seal_with_key(&secret, KEY);
+        sealed[0] ^= 0x01;
+        assert!(matches!(
+            open_with_key(&sealed, KEY),
+            Err(Error::TamperedSecretState)
+        ));
+    }
+
+    #[test]
+    fn test_wrong_key_rejected() {
+        let (_, secret) = step_one(Keypair::generate(&mut rand::thread_rng()));
+        let sealed = seal_with_key(&secret, KEY);
+        assert!(matches!(
+            open_with_key(&sealed, b"other-key"),
+            Err(Error::TamperedSecretState)
+        ));
+    }
+}
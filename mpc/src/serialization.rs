@@ -28,6 +28,23 @@ pub enum Error {
     InvalidScalar(curv::ErrorKey),
     BufferTooShort,
     InvalidPubkey,
+    InvalidRpcUrl(String),
+    TamperedSecretState,
+    InvalidCommitment(String),
+    InvalidTransaction(String),
+    ThresholdNotMet {
+        required: usize,
+        signers: usize,
+    },
+    InvalidThreshold {
+        threshold: usize,
+        total: usize,
+    },
+    InvalidSigner(Pubkey),
+    InvalidMagic(u8),
+    UnsupportedVersion(u8),
+    TrailingBytes(usize),
+    InvalidAmount(String),
 }
 
 impl Display for Error {
@@ -64,6 +81,43 @@ impl Display for Error {
             Self::InvalidScalar(e) => write!(f, "Invalid scalar: {}", e),
             Self::BufferTooShort => write!(f, "Buffer too short"),
             Self::InvalidPubkey => write!(f, "Invalid public key"),
+            Self::InvalidRpcUrl(e) => write!(f, "Invalid RPC url: {}", e),
+            Self::TamperedSecretState => {
+                write!(f, "The secret state failed its integrity check")
+            }
+            Self::InvalidCommitment(level) => write!(
+                f,
+                "Unrecognized commitment: {}, please select processed/confirmed/finalized",
+                level
+            ),
+            Self::InvalidTransaction(e) => write!(f, "Invalid signed transaction: {}", e),
+            Self::ThresholdNotMet { required, signers } => write!(
+                f,
+                "Threshold not met: {} signers required, {} provided",
+                required, signers
+            ),
+            Self::InvalidThreshold { threshold, total } => write!(
+                f,
+                "Invalid threshold {}, must be between 1 and {}",
+                threshold, total
+            ),
+            Self::InvalidSigner(key) => write!(
+                f,
+                "Signer {} is duplicated or not in the list of pubkeys",
+                key
+            ),
+            Self::InvalidMagic(magic) => {
+                write!(
+                    f,
+                    "Unexpected magic byte {:#04x} for this message type",
+                    magic
+                )
+            }
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported wire format version {}", version)
+            }
+            Self::TrailingBytes(count) => write!(f, "{} unexpected trailing bytes", count),
+            Self::InvalidAmount(amount) => write!(f, "Invalid SOL amount: {}", amount),
         }
     }
 }
@@ -82,6 +136,59 @@ impl From<ed25519_dalek::SignatureError> for Error {
 
 impl std::error::Error for Error {}
 
+/// Build a `Pubkey` from raw bytes without panicking on a wrong-length slice.
+///
+/// Aggregated keys come from `Point<Ed25519>::to_bytes(true)`, whose compressed
+/// encoding is exactly the 32 bytes Solana expects for a `Pubkey`.
+pub fn pubkey_from_bytes(bytes: &[u8]) -> Result<Pubkey, Error> {
+    Pubkey::try_from(bytes).map_err(|_| Error::InvalidPubkey)
+}
+
+/// Ed25519 points are encoded compressed, which is 32 bytes (not 33 as for secp256k1)
+const POINT_SIZE: usize = 32;
+const SCALAR_SIZE: usize = 32;
+const PUBKEY_SIZE: usize = 32;
+const SIGNATURE_SIZE: usize = 64;
+
+/// Every encoding starts with a per-type magic byte and the wire format version, so nodes
+/// running different builds reject each other's messages instead of misreading them.
+/// Older versions are not accepted.
+const WIRE_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 2;
+const AGG_MESSAGE1_MAGIC: u8 = 0xA1;
+const SECRET_AGG_STEP_ONE_MAGIC: u8 = 0xA2;
+const PARTIAL_SIGNATURE_MAGIC: u8 = 0xA3;
+
+fn write_header(buffer: &mut Vec<u8>, magic: u8) {
+    buffer.push(magic);
+    buffer.push(WIRE_VERSION);
+}
+
+/// Check the header and return the payload that follows it, which must be exactly `payload_len` bytes
+fn read_payload(buffer: &[u8], magic: u8, payload_len: usize) -> Result<&[u8], Error> {
+    let payload = read_header(buffer, magic)?;
+    if payload.len() < payload_len {
+        return Err(Error::BufferTooShort);
+    }
+    if payload.len() > payload_len {
+        return Err(Error::TrailingBytes(payload.len() - payload_len));
+    }
+    Ok(payload)
+}
+
+fn read_header(buffer: &[u8], magic: u8) -> Result<&[u8], Error> {
+    if buffer.len() < HEADER_SIZE {
+        return Err(Error::BufferTooShort);
+    }
+    if buffer[0] != magic {
+        return Err(Error::InvalidMagic(buffer[0]));
+    }
+    if buffer[1] != WIRE_VERSION {
+        return Err(Error::UnsupportedVersion(buffer[1]));
+    }
+    Ok(&buffer[HEADER_SIZE..])
+}
+
 pub trait Serialize {
     fn serialize(&self, buffer: &mut Vec<u8>);
 }
@@ -96,8 +203,15 @@ pub struct AggMessage1 {
     pub public_nonces: PublicPartialNonces,
 }
 
+impl PartialEq for AggMessage1 {
+    fn eq(&self, other: &Self) -> bool {
+        self.sender == other.sender && self.public_nonces.R == other.public_nonces.R
+    }
+}
+
 impl Serialize for AggMessage1 {
     fn serialize(&self, buffer: &mut Vec<u8>) {
+        write_header(buffer, AGG_MESSAGE1_MAGIC);
         buffer.extend_from_slice(&self.sender.to_bytes());
 
         // Serialize R values
@@ -109,14 +223,14 @@ impl Serialize for AggMessage1 {
 
 impl Deserialize for AggMessage1 {
     fn deserialize(buffer: &[u8]) -> Result<Self, Error> {
-        if buffer.len() < 32 + 64 {
-            return Err(Error::BufferTooShort);
-        }
+        let buffer = read_payload(buffer, AGG_MESSAGE1_MAGIC, PUBKEY_SIZE + 2 * POINT_SIZE)?;
 
-        let sender = Pubkey::new(&buffer[0..32]);
+        let (sender, rest) = buffer.split_at(PUBKEY_SIZE);
+        let sender = pubkey_from_bytes(sender)?;
 
-        let r1 = Point::from_bytes(&buffer[32..65]).map_err(Error::InvalidPoint)?;
-        let r2 = Point::from_bytes(&buffer[65..98]).map_err(Error::InvalidPoint)?;
+        let r1 = Point::from_bytes(&rest[..POINT_SIZE]).map_err(Error::InvalidPoint)?;
+        let r2 =
+            Point::from_bytes(&rest[POINT_SIZE..2 * POINT_SIZE]).map_err(Error::InvalidPoint)?;
 
         let public_nonces = PublicPartialNonces { R: [r1, r2] };
 
@@ -132,8 +246,26 @@ pub struct SecretAggStepOne {
     pub public_nonces: PublicPartialNonces,
 }
 
+// Never print the private nonces
+impl std::fmt::Debug for SecretAggStepOne {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretAggStepOne")
+            .field("private_nonces", &"<redacted>")
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for SecretAggStepOne {
+    fn eq(&self, other: &Self) -> bool {
+        self.private_nonces.k == other.private_nonces.k
+            && self.public_nonces.R == other.public_nonces.R
+    }
+}
+
 impl Serialize for SecretAggStepOne {
     fn serialize(&self, buffer: &mut Vec<u8>) {
+        write_header(buffer, SECRET_AGG_STEP_ONE_MAGIC);
+
         // Serialize private nonces
         for k in &self.private_nonces.k {
             buffer.extend_from_slice(&k.to_bytes());
@@ -148,15 +280,20 @@ impl Serialize for SecretAggStepOne {
 
 impl Deserialize for SecretAggStepOne {
     fn deserialize(buffer: &[u8]) -> Result<Self, Error> {
-        if buffer.len() < 128 {
-            return Err(Error::BufferTooShort);
-        }
+        let buffer = read_payload(
+            buffer,
+            SECRET_AGG_STEP_ONE_MAGIC,
+            2 * SCALAR_SIZE + 2 * POINT_SIZE,
+        )?;
 
-        let k1 = Scalar::from_bytes(&buffer[0..32]).map_err(Error::InvalidScalar)?;
-        let k2 = Scalar::from_bytes(&buffer[32..64]).map_err(Error::InvalidScalar)?;
+        let (scalars, points) = buffer.split_at(2 * SCALAR_SIZE);
 
-        let r1 = Point::from_bytes(&buffer[64..97]).map_err(Error::InvalidPoint)?;
-        let r2 = Point::from_bytes(&buffer[97..130]).map_err(Error::InvalidPoint)?;
+        let k1 = Scalar::from_bytes(&scalars[..SCALAR_SIZE]).map_err(Error::InvalidScalar)?;
+        let k2 = Scalar::from_bytes(&scalars[SCALAR_SIZE..]).map_err(Error::InvalidScalar)?;
+
+        let r1 = Point::from_bytes(&points[..POINT_SIZE]).map_err(Error::InvalidPoint)?;
+        let r2 =
+            Point::from_bytes(&points[POINT_SIZE..2 * POINT_SIZE]).map_err(Error::InvalidPoint)?;
 
         Ok(SecretAggStepOne {
             private_nonces: PrivatePartialNonces { k: [k1, k2] },
@@ -165,22 +302,186 @@ impl Deserialize for SecretAggStepOne {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub struct PartialSignature(pub Signature);
 
 impl Serialize for PartialSignature {
     fn serialize(&self, buffer: &mut Vec<u8>) {
+        write_header(buffer, PARTIAL_SIGNATURE_MAGIC);
         buffer.extend_from_slice(&self.0.as_ref());
     }
 }
 
 impl Deserialize for PartialSignature {
     fn deserialize(buffer: &[u8]) -> Result<Self, Error> {
-        if buffer.len() < 64 {
-            return Err(Error::BufferTooShort);
-        }
+        let buffer = read_payload(buffer, PARTIAL_SIGNATURE_MAGIC, SIGNATURE_SIZE)?;
 
-        let signature = Signature::new(&buffer[0..64]);
+        let signature = Signature::new(&buffer[..SIGNATURE_SIZE]);
         Ok(PartialSignature(signature))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AggMessage1, Deserialize, Error, PartialSignature, SecretAggStepOne, Serialize,
+        pubkey_from_bytes,
+    };
+    use crate::tss::step_one;
+    use rand::RngCore;
+    use solana_sdk::signature::{Keypair, Signature};
+
+    const ITERATIONS: usize = 32;
+
+    fn roundtrip<T: Serialize + Deserialize>(value: &T) -> T {
+        let mut buffer = Vec::new();
+        value.serialize(&mut buffer);
+        T::deserialize(&buffer).unwrap()
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> usize {
+        let mut buffer = Vec::new();
+        value.serialize(&mut buffer);
+        buffer.len()
+    }
+
+    #[test]
+    fn test_agg_message1_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..ITERATIONS {
+            let (message1, _) = step_one(Keypair::generate(&mut rng));
+            assert!(roundtrip(&message1) == message1);
+        }
+    }
+
+    #[test]
+    fn test_secret_agg_step_one_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..ITERATIONS {
+            let (_, secret) = step_one(Keypair::generate(&mut rng));
+            assert!(roundtrip(&secret) == secret);
+        }
+    }
+
+    #[test]
+    fn test_partial_signature_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..ITERATIONS {
+            let mut bytes = [0u8; 64];
+            rng.fill_bytes(&mut bytes);
+            let sig = PartialSignature(Signature::new(&bytes));
+            assert!(roundtrip(&sig) == sig);
+        }
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        value.serialize(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn test_buffer_too_short() {
+        let mut rng = rand::thread_rng();
+        let (message1, secret) = step_one(Keypair::generate(&mut rng));
+        let sig = PartialSignature(Signature::new(&[1u8; 64]));
+
+        let len = encoded_len(&message1);
+        assert_eq!(len, 98);
+        assert!(matches!(
+            AggMessage1::deserialize(&encode(&message1)[..len - 1]),
+            Err(Error::BufferTooShort)
+        ));
+
+        let len = encoded_len(&secret);
+        assert_eq!(len, 130);
+        assert!(matches!(
+            SecretAggStepOne::deserialize(&encode(&secret)[..len - 1]),
+            Err(Error::BufferTooShort)
+        ));
+
+        let len = encoded_len(&sig);
+        assert_eq!(len, 66);
+        assert!(matches!(
+            PartialSignature::deserialize(&encode(&sig)[..len - 1]),
+            Err(Error::BufferTooShort)
+        ));
+        assert!(matches!(
+            PartialSignature::deserialize(&[]),
+            Err(Error::BufferTooShort)
+        ));
+    }
+
+    #[test]
+    fn test_trailing_bytes_rejected() {
+        let mut rng = rand::thread_rng();
+        let (message1, secret) = step_one(Keypair::generate(&mut rng));
+        let sig = PartialSignature(Signature::new(&[1u8; 64]));
+
+        let mut padded = encode(&message1);
+        padded.push(0);
+        assert!(matches!(
+            AggMessage1::deserialize(&padded),
+            Err(Error::TrailingBytes(1))
+        ));
+
+        let mut padded = encode(&secret);
+        padded.extend_from_slice(&[0u8; 32]);
+        assert!(matches!(
+            SecretAggStepOne::deserialize(&padded),
+            Err(Error::TrailingBytes(32))
+        ));
+
+        let mut padded = encode(&sig);
+        padded.extend_from_slice(&[0xff; 3]);
+        assert!(matches!(
+            PartialSignature::deserialize(&padded),
+            Err(Error::TrailingBytes(3))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_version_rejected() {
+        let sig = PartialSignature(Signature::new(&[1u8; 64]));
+        let mut encoded = encode(&sig);
+        encoded[1] = 0;
+
+        assert!(matches!(
+            PartialSignature::deserialize(&encoded),
+            Err(Error::UnsupportedVersion(0))
+        ));
+    }
+
+    #[test]
+    fn test_wrong_message_type_rejected() {
+        let sig = PartialSignature(Signature::new(&[1u8; 64]));
+        let encoded = encode(&sig);
+
+        assert!(matches!(
+            AggMessage1::deserialize(&encoded),
+            Err(Error::InvalidMagic(0xA3))
+        ));
+    }
+
+    #[test]
+    fn test_headerless_blob_rejected() {
+        // Blobs from builds without the header must not be misread as valid messages
+        let mut rng = rand::thread_rng();
+        let (message1, secret) = step_one(Keypair::generate(&mut rng));
+
+        assert!(AggMessage1::deserialize(&encode(&message1)[2..]).is_err());
+        assert!(SecretAggStepOne::deserialize(&encode(&secret)[2..]).is_err());
+        assert!(PartialSignature::deserialize(&[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_pubkey_from_short_slice() {
+        let res = pubkey_from_bytes(&[0u8; 31]);
+        assert!(matches!(res, Err(Error::InvalidPubkey)));
+    }
+
+    #[test]
+    fn test_pubkey_from_exact_slice() {
+        assert!(pubkey_from_bytes(&[7u8; 32]).is_ok());
+    }
+}
@@ -6,10 +6,50 @@ use multi_party_eddsa::protocols::musig2::{self, PrivatePartialNonces, PublicPar
 use solana_sdk::signature::{Keypair, Signature, Signer, SignerError};
 use solana_sdk::{hash::Hash, pubkey::Pubkey, transaction::Transaction};
 
+use crate::Error;
+use crate::native_token::{
+    ComputeBudget, DurableNonce, TransferAsset, build_asset_transfer_in_base_units,
+    build_asset_transfer_transaction,
+};
 use crate::serialization::{
     AggMessage1, Error as DeserializationError, PartialSignature, SecretAggStepOne,
+    pubkey_from_bytes,
 };
-use crate::{Error, create_unsigned_transaction};
+
+/// Pick the keys that take part in a t-of-n signing round.
+///
+/// MuSig2 is n-of-n over whatever keys are aggregated, so the signature verifies against
+/// `key_agg(signers)`: the signing subset, in the order given, is what both `step_two` and
+/// `sign_and_broadcast` must aggregate. `signers = None` means every key in `public_keys` signs.
+/// `threshold` defaults to all keys and every signer must be a distinct member of `public_keys`.
+pub fn signing_set(
+    public_keys: &[Pubkey],
+    signers: Option<&[Pubkey]>,
+    threshold: Option<usize>,
+) -> Result<Vec<Pubkey>, Error> {
+    let threshold = threshold.unwrap_or(public_keys.len());
+    if threshold == 0 || threshold > public_keys.len() {
+        return Err(Error::InvalidThreshold {
+            threshold,
+            total: public_keys.len(),
+        });
+    }
+
+    let signers = signers.unwrap_or(public_keys);
+    for (i, signer) in signers.iter().enumerate() {
+        if !public_keys.contains(signer) || signers[..i].contains(signer) {
+            return Err(Error::InvalidSigner(*signer));
+        }
+    }
+
+    if signers.len() < threshold {
+        return Err(Error::ThresholdNotMet {
+            required: threshold,
+            signers: signers.len(),
+        });
+    }
+    Ok(signers.to_vec())
+}
 
 /// Create the aggregate public key, pass key=None if you don't care about the coefficient
 pub fn key_agg(keys: Vec<Pubkey>, key: Option<Pubkey>) -> Result<musig2::PublicKeyAgg, Error> {
@@ -51,8 +91,11 @@ pub fn step_one(keypair: Keypair) -> (AggMessage1, SecretAggStepOne) {
 pub fn step_two(
     keypair: Keypair,
     amount: f64,
+    asset: TransferAsset,
     to: Pubkey,
     memo: Option<String>,
+    budget: ComputeBudget, // Every signer must use the same budget, it is part of the message
+    nonce: Option<DurableNonce>, // When set, `recent_block_hash` is the nonce account's value
     recent_block_hash: Hash,
     keys: Vec<Pubkey>,
     first_messages: Vec<AggMessage1>,
@@ -65,11 +108,12 @@ pub fn step_two(
 
     // Generate the aggregate key together with the coefficient of the current keypair
     let aggkey = key_agg(keys, Some(keypair.pubkey()))?;
-    let aggpubkey = Pubkey::new(&*aggkey.agg_public_key.to_bytes(true));
+    let aggpubkey = pubkey_from_bytes(&*aggkey.agg_public_key.to_bytes(true))?;
     let extended_kepair = ExpandedKeyPair::create_from_private_key(keypair.secret().to_bytes());
 
     // Create the unsigned transaction
-    let mut tx = create_unsigned_transaction(amount, &to, memo, &aggpubkey);
+    let mut tx =
+        build_asset_transfer_transaction(&aggpubkey, &to, amount, asset, memo, budget, nonce)?;
 
     let signer = PartialSigner {
         signer_private_nonce: secret_state.private_nonces,
@@ -84,16 +128,22 @@ pub fn step_two(
     Ok(PartialSignature(sig))
 }
 
+/// Aggregate the partial signatures over the transfer of `base_units` (lamports for SOL). The
+/// amount is taken in base units so a caller holding an exact amount signs exactly that.
+#[allow(clippy::too_many_arguments)]
 pub fn sign_and_broadcast(
-    amount: f64,
+    base_units: u64,
+    asset: TransferAsset,
     to: Pubkey,
     memo: Option<String>,
+    budget: ComputeBudget,
+    nonce: Option<DurableNonce>,
     recent_block_hash: Hash,
     keys: Vec<Pubkey>,
     signatures: Vec<PartialSignature>,
 ) -> Result<Transaction, Error> {
     let aggkey = key_agg(keys, None)?;
-    let aggpubkey = Pubkey::new(&*aggkey.agg_public_key.to_bytes(true));
+    let aggpubkey = pubkey_from_bytes(&*aggkey.agg_public_key.to_bytes(true))?;
 
     // Make sure all the `R`s are the same
     if !signatures[1..]
@@ -135,14 +185,17 @@ pub fn sign_and_broadcast(
     let sig = Signature::new(&sig_bytes);
 
     // Create the same transaction again
-    let mut tx = create_unsigned_transaction(amount, &to, memo, &aggpubkey);
+    let mut tx = build_asset_transfer_in_base_units(
+        &aggpubkey, &to, base_units, asset, memo, budget, nonce,
+    )?;
     // Insert the recent_block_hash and the signature to the right places
     tx.message.recent_blockhash = recent_block_hash;
     assert_eq!(tx.signatures.len(), 1);
     tx.signatures[0] = sig;
 
-    // Make sure the resulting transaction is actually valid.
-    if tx.verify().is_err() {
+    // Check the aggregate against the aggregated key before anyone spends an RPC round trip on it;
+    // a single bad partial signature makes the whole aggregate invalid
+    if !sig.verify(aggpubkey.as_ref(), &tx.message_data()) || tx.verify().is_err() {
         return Err(Error::InvalidSignature);
     }
     Ok(tx)
@@ -158,9 +211,8 @@ struct PartialSigner {
 
 impl Signer for PartialSigner {
     fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
-        Ok(Pubkey::new(
-            &*self.aggregated_pubkey.agg_public_key.to_bytes(true),
-        ))
+        pubkey_from_bytes(&*self.aggregated_pubkey.agg_public_key.to_bytes(true))
+            .map_err(|e| SignerError::Custom(e.to_string()))
     }
 
     fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
@@ -185,11 +237,14 @@ impl Signer for PartialSigner {
 
 #[cfg(test)]
 mod tests {
-    use crate::native_token::lamports_to_sol;
-    use crate::serialization::Serialize;
-    use crate::tss::{key_agg, sign_and_broadcast, step_one, step_two};
+    use crate::native_token::{ComputeBudget, TransferAsset, lamports_to_sol, sol_to_lamports};
+    use crate::serialization::Error;
+    use crate::serialization::{PartialSignature, Serialize, pubkey_from_bytes};
+    use crate::tss::{key_agg, sign_and_broadcast, signing_set, step_one, step_two};
+    use rust_decimal::prelude::ToPrimitive;
+    use solana_sdk::hash::Hash;
     use solana_sdk::pubkey::Pubkey;
-    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::signature::{Keypair, Signature, Signer};
     use solana_streamer::socket::SocketAddrSpace;
     use solana_test_validator::TestValidator;
 
@@ -209,7 +264,7 @@ mod tests {
         let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
         // Key Generation
         let aggpubkey = key_agg(pubkeys.clone(), None).unwrap().agg_public_key;
-        let aggpubkey_solana = Pubkey::new(&*aggpubkey.to_bytes(true));
+        let aggpubkey_solana = pubkey_from_bytes(&*aggpubkey.to_bytes(true)).unwrap();
         let full_amount = 500_000_000;
         // Get some money in it
         let testnet =
@@ -223,7 +278,7 @@ mod tests {
 
         let recent_block_hash = rpc_client.get_latest_blockhash().unwrap();
         // step 2
-        let amount = lamports_to_sol(full_amount / 2);
+        let amount = lamports_to_sol(full_amount / 2).to_f64().unwrap();
         let memo = Some("test_roundtrip".to_string());
 
         let partial_sigs: Vec<_> = keys
@@ -237,8 +292,11 @@ mod tests {
                 step_two(
                     key,
                     amount,
+                    TransferAsset::Sol,
                     to.pubkey(),
                     memo.clone(),
+                    ComputeBudget::default(),
+                    None,
                     recent_block_hash,
                     pubkeys.clone(),
                     first_msgs,
@@ -249,9 +307,12 @@ mod tests {
             .collect();
 
         let full_tx = sign_and_broadcast(
-            amount,
+            full_amount / 2,
+            TransferAsset::Sol,
             to.pubkey(),
             memo,
+            ComputeBudget::default(),
+            None,
             recent_block_hash,
             pubkeys,
             partial_sigs,
@@ -264,4 +325,126 @@ mod tests {
             .confirm_transaction_with_spinner(&sig, &recent_block_hash, rpc_client.commitment())
             .unwrap();
     }
+
+    /// Run both MuSig2 rounds for signers 0 and 2 of a 2-of-3 set
+    fn two_of_three_partials() -> (Vec<Pubkey>, Pubkey, Vec<PartialSignature>) {
+        let mut rng = rand07::thread_rng();
+        let keys: Vec<_> = (0..3).map(|_| Keypair::generate(&mut rng)).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|k| k.pubkey()).collect();
+        let signers = signing_set(&pubkeys, Some(&[pubkeys[0], pubkeys[2]]), Some(2)).unwrap();
+
+        let signing_keys = [clone_keypair(&keys[0]), clone_keypair(&keys[2])];
+        let (first_msgs, first_secrets): (Vec<_>, Vec<_>) =
+            signing_keys.iter().map(clone_keypair).map(step_one).unzip();
+
+        let to = Keypair::generate(&mut rng).pubkey();
+        let recent_block_hash = Hash::default();
+        let partial_sigs: Vec<_> = signing_keys
+            .iter()
+            .map(clone_keypair)
+            .zip(first_secrets)
+            .enumerate()
+            .map(|(i, (key, secret))| {
+                let mut first_msgs: Vec<_> = first_msgs.iter().map(clone_serialize).collect();
+                first_msgs.remove(i);
+                step_two(
+                    key,
+                    0.1,
+                    TransferAsset::Sol,
+                    to,
+                    None,
+                    ComputeBudget::default(),
+                    None,
+                    recent_block_hash,
+                    signers.clone(),
+                    first_msgs,
+                    secret,
+                )
+                .unwrap()
+            })
+            .collect();
+        (signers, to, partial_sigs)
+    }
+
+    #[test]
+    fn test_two_of_three_sign() {
+        let (signers, to, partial_sigs) = two_of_three_partials();
+        let recent_block_hash = Hash::default();
+
+        // sign_and_broadcast verifies the aggregated signature before returning
+        let tx = sign_and_broadcast(
+            sol_to_lamports(0.1).unwrap(),
+            TransferAsset::Sol,
+            to,
+            None,
+            ComputeBudget::default(),
+            None,
+            recent_block_hash,
+            signers.clone(),
+            partial_sigs,
+        )
+        .unwrap();
+        let subset_key = key_agg(signers, None).unwrap().agg_public_key;
+        assert_eq!(
+            tx.message.account_keys[0],
+            pubkey_from_bytes(&*subset_key.to_bytes(true)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_tampered_partial_signature_fails_locally() {
+        let (signers, to, mut partial_sigs) = two_of_three_partials();
+
+        // Keep the shared R but swap in a different, still well-formed, s
+        let mut bytes = [0u8; 64];
+        bytes.copy_from_slice(partial_sigs[1].0.as_ref());
+        bytes[32..].copy_from_slice(&partial_sigs[0].0.as_ref()[32..]);
+        partial_sigs[1] = PartialSignature(Signature::new(&bytes));
+
+        let result = sign_and_broadcast(
+            sol_to_lamports(0.1).unwrap(),
+            TransferAsset::Sol,
+            to,
+            None,
+            ComputeBudget::default(),
+            None,
+            Hash::default(),
+            signers,
+            partial_sigs,
+        );
+        assert!(matches!(result, Err(Error::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_signing_set_validation() {
+        let pubkeys: Vec<_> = (0..3).map(|_| Keypair::new().pubkey()).collect();
+
+        assert_eq!(signing_set(&pubkeys, None, None).unwrap(), pubkeys);
+        assert!(matches!(
+            signing_set(&pubkeys, Some(&pubkeys[..1]), Some(2)),
+            Err(Error::ThresholdNotMet {
+                required: 2,
+                signers: 1
+            })
+        ));
+        assert!(matches!(
+            signing_set(&pubkeys, Some(&[pubkeys[0], pubkeys[0]]), Some(2)),
+            Err(Error::InvalidSigner(_))
+        ));
+        assert!(matches!(
+            signing_set(
+                &pubkeys,
+                Some(&[pubkeys[0], Keypair::new().pubkey()]),
+                Some(2)
+            ),
+            Err(Error::InvalidSigner(_))
+        ));
+        assert!(matches!(
+            signing_set(&pubkeys, None, Some(4)),
+            Err(Error::InvalidThreshold {
+                threshold: 4,
+                total: 3
+            })
+        ));
+    }
 }
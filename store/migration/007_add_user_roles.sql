@@ -0,0 +1,6 @@
+CREATE TYPE user_role AS ENUM ('user', 'admin', 'operator');
+
+ALTER TABLE users ADD COLUMN role user_role NOT NULL DEFAULT 'user';
+
+-- Index for role breakdowns on the admin dashboard
+CREATE INDEX idx_users_role ON users(role);
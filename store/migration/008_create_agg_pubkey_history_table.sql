@@ -0,0 +1,10 @@
+CREATE TABLE agg_pubkey_history (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    agg_pubkey TEXT NOT NULL, -- Aggregated public key that was replaced
+    rotated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Index for faster history lookups (by user and by old address)
+CREATE INDEX idx_agg_pubkey_history_user_id ON agg_pubkey_history(user_id);
+CREATE INDEX idx_agg_pubkey_history_agg_pubkey ON agg_pubkey_history(agg_pubkey);
@@ -0,0 +1,4 @@
+-- Network the keyshare's wallet lives on (mainnet, devnet, testnet)
+ALTER TABLE mpc_keyshares ADD COLUMN network VARCHAR(16) NOT NULL DEFAULT 'devnet';
+
+CREATE INDEX idx_mpc_keyshares_user_network ON mpc_keyshares(user_id, network);
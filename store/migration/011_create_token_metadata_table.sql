@@ -0,0 +1,19 @@
+CREATE TABLE token_metadata (
+    mint VARCHAR(44) PRIMARY KEY, -- Solana token mint address (base58)
+    symbol VARCHAR(10) NOT NULL, -- Token symbol (USDC, USDT, etc.)
+    name TEXT NOT NULL,
+    decimals INTEGER NOT NULL, -- Token decimals
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Symbols resolve case-insensitively, so they must be unique regardless of case
+CREATE UNIQUE INDEX idx_token_metadata_symbol ON token_metadata(LOWER(symbol));
+
+CREATE TRIGGER update_token_metadata_updated_at BEFORE UPDATE ON token_metadata FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+INSERT INTO token_metadata (mint, symbol, name, decimals) VALUES
+    ('So11111111111111111111111111111111111111112', 'SOL', 'Wrapped SOL', 9),
+    ('EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v', 'USDC', 'USD Coin', 6),
+    ('Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB', 'USDT', 'Tether USD', 6)
+ON CONFLICT (mint) DO NOTHING;
@@ -0,0 +1,5 @@
+-- Internal swaps are recorded as two linked legs sharing a swap_id and the exchange rate used
+ALTER TABLE transactions ADD COLUMN swap_id UUID;
+ALTER TABLE transactions ADD COLUMN rate DECIMAL(30, 12);
+
+CREATE INDEX idx_transactions_swap_id ON transactions(swap_id) WHERE swap_id IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Supports per-node keyshare listings filtered by network
+CREATE INDEX idx_mpc_keyshares_node_network ON mpc_keyshares(mpc_node_id, network);
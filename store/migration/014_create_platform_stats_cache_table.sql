@@ -0,0 +1,13 @@
+-- Snapshots of dashboard counters, refreshed periodically instead of computed per request
+CREATE TABLE platform_stats_cache (
+    id BIGSERIAL PRIMARY KEY,
+    total_users BIGINT NOT NULL,
+    total_sol_locked DECIMAL(20, 8) NOT NULL, -- Sum of user SOL balances
+    total_transactions BIGINT NOT NULL,
+    pending_transactions BIGINT NOT NULL,
+    failed_transactions BIGINT NOT NULL,
+    total_volume DECIMAL(30, 8) NOT NULL, -- Sum of confirmed transaction amounts
+    computed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_platform_stats_cache_computed_at ON platform_stats_cache(computed_at DESC);
@@ -0,0 +1,2 @@
+-- Why a transaction was failed (e.g. account suspension), NULL for other statuses
+ALTER TABLE transactions ADD COLUMN failure_reason TEXT;
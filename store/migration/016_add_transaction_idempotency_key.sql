@@ -0,0 +1,6 @@
+-- Client-supplied key so retried requests can find the transaction they already created
+ALTER TABLE transactions ADD COLUMN idempotency_key VARCHAR(128);
+
+CREATE UNIQUE INDEX idx_transactions_user_idempotency_key
+    ON transactions(user_id, idempotency_key)
+    WHERE idempotency_key IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Slot at which the transaction was confirmed on-chain (from getSignatureStatuses), for finality tracking
+ALTER TABLE transactions ADD COLUMN confirmed_slot BIGINT;
+
+CREATE INDEX idx_transactions_confirmed_slot ON transactions(confirmed_slot) WHERE confirmed_slot IS NOT NULL;
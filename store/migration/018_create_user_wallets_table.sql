@@ -0,0 +1,8 @@
+-- Aggregated public key per network, so a user can run separate devnet and mainnet setups
+CREATE TABLE user_wallets (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    network VARCHAR(16) NOT NULL,
+    agg_pubkey TEXT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (user_id, network)
+);
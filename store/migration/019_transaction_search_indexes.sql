@@ -0,0 +1,4 @@
+-- Prefix search over signatures and addresses (LIKE 'abc%') for support lookups
+CREATE INDEX idx_transactions_signature_prefix ON transactions(tx_signature varchar_pattern_ops);
+CREATE INDEX idx_transactions_from_address_prefix ON transactions(from_address varchar_pattern_ops);
+CREATE INDEX idx_transactions_to_address_prefix ON transactions(to_address varchar_pattern_ops);
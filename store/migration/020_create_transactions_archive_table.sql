@@ -0,0 +1,9 @@
+-- Cold storage for old confirmed transactions moved out of the hot table.
+-- Rows are copied by column name, so add any new transactions column here too.
+CREATE TABLE transactions_archive (
+    LIKE transactions INCLUDING DEFAULTS,
+    archived_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX idx_transactions_archive_user_created ON transactions_archive(user_id, created_at DESC);
@@ -0,0 +1,14 @@
+-- Latest state of every account seen by the indexer, so it survives restarts
+CREATE TABLE indexed_accounts (
+    pubkey VARCHAR(44) PRIMARY KEY,
+    lamports BIGINT NOT NULL,
+    owner VARCHAR(44) NOT NULL,
+    executable BOOLEAN NOT NULL,
+    rent_epoch NUMERIC(20, 0) NOT NULL, -- u64, rent-exempt accounts report u64::MAX
+    data BYTEA NOT NULL,
+    write_version BIGINT NOT NULL,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_indexed_accounts_owner ON indexed_accounts(owner);
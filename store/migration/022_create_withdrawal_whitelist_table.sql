@@ -0,0 +1,8 @@
+-- Pre-approved withdrawal destinations; a user with no entries may withdraw anywhere
+CREATE TABLE withdrawal_whitelist (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    address VARCHAR(44) NOT NULL,
+    label VARCHAR(64),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, address)
+);
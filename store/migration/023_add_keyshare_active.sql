@@ -0,0 +1,2 @@
+-- Inactive shares (e.g. a node down for maintenance) don't count toward the signing threshold
+ALTER TABLE mpc_keyshares ADD COLUMN active BOOLEAN NOT NULL DEFAULT true;
@@ -0,0 +1,6 @@
+-- Highest slot each indexer has processed, so a restart knows where it left off
+CREATE TABLE indexer_checkpoint (
+    name VARCHAR(64) PRIMARY KEY,
+    slot BIGINT NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,11 @@
+-- Balances for mints without metadata used to be stamped 'UNKNOWN' with 6 decimals; store NULL instead
+ALTER TABLE token_balances ALTER COLUMN token_symbol DROP NOT NULL;
+ALTER TABLE token_balances ALTER COLUMN decimals DROP NOT NULL;
+ALTER TABLE token_balances ALTER COLUMN decimals DROP DEFAULT;
+
+UPDATE token_balances tb
+SET token_symbol = tm.symbol, decimals = tm.decimals
+FROM token_metadata tm
+WHERE tb.token_mint = tm.mint AND tb.token_symbol = 'UNKNOWN';
+
+UPDATE token_balances SET token_symbol = NULL, decimals = NULL WHERE token_symbol = 'UNKNOWN';
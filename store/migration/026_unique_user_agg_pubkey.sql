@@ -0,0 +1,2 @@
+-- Deposits are attributed by aggregated pubkey, so it must identify exactly one user
+CREATE UNIQUE INDEX idx_users_agg_pubkey ON users(agg_pubkey) WHERE agg_pubkey IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Serves per-user time-window queries (statements, reconciliation) and keyset paging
+CREATE INDEX idx_transactions_user_created ON transactions(user_id, created_at);
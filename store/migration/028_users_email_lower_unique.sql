@@ -0,0 +1,6 @@
+-- Emails are stored trimmed and lowercased; the index stops case variants of one address
+-- from registering twice and serves the lower(email) lookups.
+-- Fails if case-variant duplicates already exist; those accounts must be merged by hand first.
+UPDATE users SET email = lower(trim(email)) WHERE email <> lower(trim(email));
+
+CREATE UNIQUE INDEX idx_users_email_lower ON users (lower(email));
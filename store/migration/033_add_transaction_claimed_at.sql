@@ -0,0 +1,10 @@
+-- Set when a processor claims a pending transaction; claims older than the lease may be
+-- taken over by another worker
+ALTER TABLE transactions ADD COLUMN claimed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_transactions_pending_type_created
+    ON transactions(transaction_type, created_at)
+    WHERE status = 'pending';
+
+-- Mirror the column in transactions_archive
+ALTER TABLE transactions_archive ADD COLUMN claimed_at TIMESTAMP WITH TIME ZONE;
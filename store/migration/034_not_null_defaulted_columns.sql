@@ -0,0 +1,58 @@
+-- Columns with a default were left nullable, so the query macros typed them as Option and
+-- the store's non-optional fields didn't match. Backfill any NULLs, then enforce NOT NULL.
+UPDATE users SET balance = 0.0 WHERE balance IS NULL;
+UPDATE users SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE users SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE users
+    ALTER COLUMN balance SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE mpc_keyshares SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE mpc_keyshares SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE mpc_keyshares
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE token_balances SET balance = 0.0 WHERE balance IS NULL;
+UPDATE token_balances SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE token_balances SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE token_balances
+    ALTER COLUMN balance SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE transactions SET status = 'pending' WHERE status IS NULL;
+UPDATE transactions SET fee = 0 WHERE fee IS NULL;
+UPDATE transactions SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE transactions SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE transactions
+    ALTER COLUMN status SET NOT NULL,
+    ALTER COLUMN fee SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE transactions_archive SET status = 'pending' WHERE status IS NULL;
+UPDATE transactions_archive SET fee = 0 WHERE fee IS NULL;
+UPDATE transactions_archive SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE transactions_archive SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE transactions_archive
+    ALTER COLUMN status SET NOT NULL,
+    ALTER COLUMN fee SET NOT NULL,
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE token_metadata SET created_at = NOW() WHERE created_at IS NULL;
+UPDATE token_metadata SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE token_metadata
+    ALTER COLUMN created_at SET NOT NULL,
+    ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE agg_pubkey_history SET rotated_at = NOW() WHERE rotated_at IS NULL;
+ALTER TABLE agg_pubkey_history ALTER COLUMN rotated_at SET NOT NULL;
+
+UPDATE user_wallets SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE user_wallets ALTER COLUMN updated_at SET NOT NULL;
+
+UPDATE indexed_accounts SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE indexed_accounts ALTER COLUMN updated_at SET NOT NULL;
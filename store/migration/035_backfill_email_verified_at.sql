@@ -0,0 +1,7 @@
+-- Accounts created before email verification existed were never sent a token, so nothing
+-- could ever verify them; treat them as verified so REQUIRE_VERIFIED_EMAIL doesn't lock them out
+UPDATE users SET verified_at = created_at
+WHERE verified_at IS NULL
+  AND NOT EXISTS (
+      SELECT 1 FROM email_verification_tokens t WHERE t.user_id = users.id
+  );
@@ -0,0 +1,12 @@
+-- Balance movements that have no transactions row: internal transfers, admin overrides and
+-- direct credits/debits. Together with confirmed transactions they account for every balance.
+CREATE TABLE balance_adjustments (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_mint VARCHAR(44), -- NULL for SOL
+    amount DECIMAL(20, 8) NOT NULL, -- Signed change, negative for debits
+    operation VARCHAR(32) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_balance_adjustments_user ON balance_adjustments(user_id);
@@ -0,0 +1,10 @@
+-- When the share material was last rotated. updated_at also moves on unrelated changes such
+-- as toggling active, which would silently postpone a rotation that is due.
+ALTER TABLE mpc_keyshares ADD COLUMN rotated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+-- updated_at is the best record of the last rotation so far; keep the trigger from bumping it
+ALTER TABLE mpc_keyshares DISABLE TRIGGER update_mpc_keyshares_updated_at;
+UPDATE mpc_keyshares SET rotated_at = updated_at;
+ALTER TABLE mpc_keyshares ENABLE TRIGGER update_mpc_keyshares_updated_at;
+
+CREATE INDEX idx_mpc_keyshares_rotated_at ON mpc_keyshares(rotated_at);
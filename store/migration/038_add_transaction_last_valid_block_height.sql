@@ -0,0 +1,8 @@
+-- Block height after which a sent transaction's blockhash has expired, so it can never land.
+-- Settlement fails a withdrawal still unseen past this height, releasing its hold.
+-- Withdrawals sent before the height was recorded are left NULL: they are never treated as
+-- expired, and stay held until the chain reports them landed or rejected.
+ALTER TABLE transactions ADD COLUMN last_valid_block_height BIGINT;
+
+-- Mirror the column in transactions_archive
+ALTER TABLE transactions_archive ADD COLUMN last_valid_block_height BIGINT;
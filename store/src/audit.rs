@@ -0,0 +1,215 @@
+use crate::Store;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Asset name used in audit records for the native SOL balance
+pub const SOL_ASSET: &str = "SOL";
+
+/// One balance change, emitted after the mutation has committed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceAuditRecord {
+    pub user_id: Uuid,
+    pub actor: String, // Who caused the change: a user id, "system" or "admin"
+    pub operation: &'static str,
+    pub asset: String,   // "SOL" or the token mint
+    pub amount: Decimal, // Signed change, negative for debits
+    pub balance_before: Decimal,
+    pub balance_after: Decimal,
+    pub reference_id: Option<Uuid>, // Transaction or swap the change belongs to
+    pub recorded_at: DateTime<Utc>,
+}
+
+impl BalanceAuditRecord {
+    pub fn new(
+        user_id: Uuid,
+        actor: impl Into<String>,
+        operation: &'static str,
+        asset: impl Into<String>,
+        balance_before: Decimal,
+        balance_after: Decimal,
+        reference_id: Option<Uuid>,
+    ) -> Self {
+        Self {
+            user_id,
+            actor: actor.into(),
+            operation,
+            asset: asset.into(),
+            amount: balance_after - balance_before,
+            balance_before,
+            balance_after,
+            reference_id,
+            recorded_at: Utc::now(),
+        }
+    }
+}
+
+/// Destination for the append-only balance audit log
+pub trait BalanceAuditSink: Send + Sync {
+    fn record(&self, record: &BalanceAuditRecord);
+}
+
+/// Writes each record as one JSON line to stdout
+pub struct StdoutAuditSink;
+
+impl BalanceAuditSink for StdoutAuditSink {
+    fn record(&self, record: &BalanceAuditRecord) {
+        if let Ok(line) = serde_json::to_string(record) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Appends each record as one JSON line to a file
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+
+impl FileAuditSink {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl BalanceAuditSink for FileAuditSink {
+    fn record(&self, record: &BalanceAuditRecord) {
+        let Ok(line) = serde_json::to_string(record) else {
+            return;
+        };
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl Store {
+    /// Emit audit records to the configured sink, if any
+    pub(crate) fn audit_balance_changes(&self, records: &[BalanceAuditRecord]) {
+        if let Some(sink) = &self.audit_sink {
+            for record in records {
+                sink.record(record);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BalanceAuditRecord, BalanceAuditSink, SOL_ASSET};
+    use crate::Store;
+    use crate::testing::insert_user;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::sync::{Arc, Mutex};
+    use uuid::Uuid;
+
+    #[derive(Default)]
+    struct CaptureSink(Mutex<Vec<BalanceAuditRecord>>);
+
+    impl BalanceAuditSink for CaptureSink {
+        fn record(&self, record: &BalanceAuditRecord) {
+            self.0.lock().unwrap().push(record.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deposit_and_withdrawal_records() {
+        let sink = Arc::new(CaptureSink::default());
+        let store = Store::for_tests("postgres://localhost/unused").with_audit_sink(sink.clone());
+
+        let user_id = Uuid::new_v4();
+        let transaction_id = Uuid::new_v4();
+        store.audit_balance_changes(&[
+            BalanceAuditRecord::new(
+                user_id,
+                "system",
+                "deposit",
+                SOL_ASSET,
+                Decimal::new(100, 2),
+                Decimal::new(350, 2),
+                Some(transaction_id),
+            ),
+            BalanceAuditRecord::new(
+                user_id,
+                "system",
+                "withdrawal",
+                SOL_ASSET,
+                Decimal::new(350, 2),
+                Decimal::new(300, 2),
+                Some(transaction_id),
+            ),
+        ]);
+
+        let records = sink.0.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].amount, Decimal::new(250, 2));
+        assert_eq!(records[1].amount, Decimal::new(-50, 2));
+
+        let json = serde_json::to_value(&records[1]).unwrap();
+        assert_eq!(json["operation"], "withdrawal");
+        assert_eq!(json["actor"], "system");
+        assert_eq!(json["balance_before"], "3.50");
+        assert_eq!(json["balance_after"], "3.00");
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_direct_balance_adjustments_are_audited(pool: PgPool) {
+        let sink = Arc::new(CaptureSink::default());
+        let store = Store::from_pool(pool).with_audit_sink(sink.clone());
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+        store
+            .add_user_balance(user_id, Decimal::new(5, 0))
+            .await
+            .unwrap();
+        store
+            .subtract_user_balance(user_id, Decimal::new(2, 0))
+            .await
+            .unwrap();
+        store
+            .update_token_balance(user_id, mint, "USDC", Decimal::ZERO, 6)
+            .await
+            .unwrap();
+        store
+            .add_token_balance(user_id, mint, Decimal::new(3, 0))
+            .await
+            .unwrap();
+        store
+            .subtract_token_balance(user_id, mint, Decimal::new(1, 0))
+            .await
+            .unwrap();
+
+        let records = sink.0.lock().unwrap();
+        let summary: Vec<_> = records
+            .iter()
+            .filter(|record| record.operation != "set_balance")
+            .map(|record| {
+                (
+                    record.operation,
+                    record.asset.as_str(),
+                    record.amount,
+                    record.balance_after,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("credit", SOL_ASSET, Decimal::new(5, 0), Decimal::new(5, 0)),
+                ("debit", SOL_ASSET, Decimal::new(-2, 0), Decimal::new(3, 0)),
+                ("credit", mint, Decimal::new(3, 0), Decimal::new(3, 0)),
+                ("debit", mint, Decimal::new(-1, 0), Decimal::new(2, 0)),
+            ]
+        );
+        assert!(records.iter().all(|record| record.user_id == user_id));
+    }
+}
@@ -0,0 +1,54 @@
+use rust_decimal::Decimal;
+use serde::Serializer;
+
+/// Number of decimals in a SOL amount (1 SOL = 10^9 lamports)
+pub const SOL_DECIMALS: u32 = 9;
+
+/// Number of decimals the ledger keeps: amounts and balances are `DECIMAL(20, 8)` columns, so
+/// Postgres rounds anything finer
+pub const LEDGER_DECIMALS: u32 = 8;
+
+/// Format an amount with a fixed scale, e.g. `12.345` with 9 decimals becomes `"12.345000000"`.
+/// Amounts are sent as strings because some JSON clients lose precision on large decimals.
+pub fn format_decimal(value: Decimal, decimals: u32) -> String {
+    let mut value = value;
+    value.rescale(decimals.min(Decimal::MAX_SCALE));
+    value.to_string()
+}
+
+/// `serialize_with` helper for SOL balances
+pub fn serialize_sol<S>(value: &Decimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_decimal(*value, SOL_DECIMALS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SOL_DECIMALS, format_decimal};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_format_decimal_pads_to_scale() {
+        let value = Decimal::from_str("12.345").unwrap();
+        assert_eq!(format_decimal(value, SOL_DECIMALS), "12.345000000");
+        assert_eq!(format_decimal(value, 6), "12.345000");
+    }
+
+    #[test]
+    fn test_serialize_sol_as_string() {
+        #[derive(serde::Serialize)]
+        struct Wrapper {
+            #[serde(serialize_with = "super::serialize_sol")]
+            balance: Decimal,
+        }
+
+        let json = serde_json::to_string(&Wrapper {
+            balance: Decimal::from_str("1.5").unwrap(),
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"balance":"1.500000000"}"#);
+    }
+}
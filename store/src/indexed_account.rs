@@ -0,0 +1,199 @@
+use crate::Store;
+use crate::user::StoreError;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct IndexedAccount {
+    pub pubkey: String,
+    pub lamports: i64,
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: Decimal,
+    pub data: Vec<u8>,
+    pub write_version: i64,
+    pub slot: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl IndexedAccount {
+    /// Build a row from the unsigned values in a Geyser account update
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        pubkey: String,
+        lamports: u64,
+        owner: String,
+        executable: bool,
+        rent_epoch: u64,
+        data: Vec<u8>,
+        write_version: u64,
+        slot: u64,
+    ) -> Result<Self, StoreError> {
+        let to_i64 = |field: &str, value: u64| {
+            i64::try_from(value).map_err(|_| {
+                StoreError::InvalidInput(format!("{} {} does not fit in BIGINT", field, value))
+            })
+        };
+
+        Ok(Self {
+            lamports: to_i64("lamports", lamports)?,
+            write_version: to_i64("write_version", write_version)?,
+            slot: to_i64("slot", slot)?,
+            pubkey,
+            owner,
+            executable,
+            rent_epoch: Decimal::from(rent_epoch),
+            data,
+            updated_at: Utc::now(),
+        })
+    }
+}
+
+impl Store {
+    /// Save an account update unless a newer one (by slot, then write_version) is already stored.
+    /// Returns whether the row was written.
+    pub async fn upsert_indexed_account(
+        &self,
+        account: &IndexedAccount,
+    ) -> Result<bool, StoreError> {
+        let written = sqlx::query!(
+            r#"
+            INSERT INTO indexed_accounts (pubkey, lamports, owner, executable, rent_epoch, data, write_version, slot, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (pubkey) DO UPDATE SET
+                lamports = EXCLUDED.lamports,
+                owner = EXCLUDED.owner,
+                executable = EXCLUDED.executable,
+                rent_epoch = EXCLUDED.rent_epoch,
+                data = EXCLUDED.data,
+                write_version = EXCLUDED.write_version,
+                slot = EXCLUDED.slot,
+                updated_at = EXCLUDED.updated_at
+            WHERE (indexed_accounts.slot, indexed_accounts.write_version)
+                < (EXCLUDED.slot, EXCLUDED.write_version)
+            "#,
+            account.pubkey,
+            account.lamports,
+            account.owner,
+            account.executable,
+            account.rent_epoch,
+            account.data,
+            account.write_version,
+            account.slot,
+            account.updated_at
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(written > 0)
+    }
+
+    /// Get the latest indexed state of an account
+    pub async fn get_indexed_account(
+        &self,
+        pubkey: &str,
+    ) -> Result<Option<IndexedAccount>, StoreError> {
+        let account = sqlx::query_as!(
+            IndexedAccount,
+            "SELECT pubkey, lamports, owner, executable, rent_epoch, data, write_version, slot, updated_at
+             FROM indexed_accounts WHERE pubkey = $1",
+            pubkey
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(account)
+    }
+
+    /// Get every indexed account owned by a program or wallet
+    pub async fn get_indexed_accounts_by_owner(
+        &self,
+        owner: &str,
+    ) -> Result<Vec<IndexedAccount>, StoreError> {
+        let accounts = sqlx::query_as!(
+            IndexedAccount,
+            "SELECT pubkey, lamports, owner, executable, rent_epoch, data, write_version, slot, updated_at
+             FROM indexed_accounts WHERE owner = $1 ORDER BY pubkey",
+            owner
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(accounts)
+    }
+
+    /// Record that the indexer called `name` has processed `slot`; the checkpoint never moves back
+    pub async fn save_indexer_checkpoint(&self, name: &str, slot: u64) -> Result<(), StoreError> {
+        let slot = i64::try_from(slot).map_err(|_| {
+            StoreError::InvalidInput(format!("slot {} does not fit in BIGINT", slot))
+        })?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO indexer_checkpoint (name, slot, updated_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (name) DO UPDATE SET
+                slot = GREATEST(indexer_checkpoint.slot, EXCLUDED.slot),
+                updated_at = EXCLUDED.updated_at
+            "#,
+            name,
+            slot,
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the highest slot the indexer called `name` has processed, if it ever ran
+    pub async fn get_indexer_checkpoint(&self, name: &str) -> Result<Option<u64>, StoreError> {
+        let slot = sqlx::query_scalar!("SELECT slot FROM indexer_checkpoint WHERE name = $1", name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(slot.map(|slot| slot as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IndexedAccount;
+    use crate::user::StoreError;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_new_converts_unsigned_fields() {
+        let account = IndexedAccount::new(
+            "11111111111111111111111111111112".to_string(),
+            1_000,
+            "11111111111111111111111111111111".to_string(),
+            false,
+            u64::MAX,
+            vec![1, 2, 3],
+            7,
+            250_000_000,
+        )
+        .unwrap();
+
+        assert_eq!(account.lamports, 1_000);
+        assert_eq!(account.rent_epoch, Decimal::from(u64::MAX));
+        assert_eq!((account.slot, account.write_version), (250_000_000, 7));
+
+        let err = IndexedAccount::new(
+            String::new(),
+            u64::MAX,
+            String::new(),
+            false,
+            0,
+            Vec::new(),
+            0,
+            0,
+        )
+        .unwrap_err();
+        assert!(matches!(err, StoreError::InvalidInput(_)));
+    }
+}
@@ -1,34 +1,189 @@
+pub mod audit;
+pub mod decimal;
+pub mod indexed_account;
+pub mod pagination;
+pub mod password_reset;
+pub mod refresh_token;
+pub mod retry;
+pub mod revoked_token;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+pub mod token;
 pub mod transaction;
 pub mod user;
+pub mod verification;
+pub mod whitelist;
+use std::sync::Arc;
 use std::time::Duration;
 
+use rust_decimal::Decimal;
 use sqlx::{PgPool, postgres::PgPoolOptions};
 
+use crate::audit::BalanceAuditSink;
+use crate::user::StoreError;
+
+/// Connection pool sizing and timeouts; `Default` matches what `Store::new` uses
+#[derive(Debug, Clone)]
+pub struct StorePoolConfig {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Option<Duration>,
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for StorePoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections: 20,
+            min_connections: 5,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            max_lifetime: Some(Duration::from_secs(1800)),
+        }
+    }
+}
+
+impl StorePoolConfig {
+    pub fn validate(&self) -> Result<(), StoreError> {
+        if self.max_connections == 0 {
+            return Err(StoreError::InvalidInput(
+                "max_connections must be at least 1".to_string(),
+            ));
+        }
+        if self.min_connections > self.max_connections {
+            return Err(StoreError::InvalidInput(format!(
+                "min_connections ({}) must not exceed max_connections ({})",
+                self.min_connections, self.max_connections
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn pool_options(&self) -> PgPoolOptions {
+        PgPoolOptions::new()
+            .max_connections(self.max_connections)
+            .min_connections(self.min_connections)
+            .acquire_timeout(self.acquire_timeout)
+            .idle_timeout(self.idle_timeout)
+            .max_lifetime(self.max_lifetime)
+    }
+}
+
 pub struct Store {
     pub pool: PgPool,
+    /// Optional read replica for read-only listings and stats; writes always use `pool`
+    pub replica: Option<PgPool>,
+    /// Smallest amount accepted for transfers and withdrawals, to prevent dust spam
+    pub min_transfer_amount: Decimal,
+    /// Optional append-only log of every balance change
+    pub audit_sink: Option<Arc<dyn BalanceAuditSink>>,
+    /// Whether withdrawals require the user to have verified their email
+    pub require_verified_email: bool,
 }
 
 impl Store {
     pub async fn new(database_url: &str) -> Result<Self, sqlx::Error> {
-        let pool = PgPoolOptions::new()
-            .max_connections(20)
-            .min_connections(5)
-            .acquire_timeout(Duration::from_secs(30))
-            .idle_timeout(Duration::from_secs(600))
-            .max_lifetime(Duration::from_secs(1800))
-            .connect(database_url)
-            .await?;
+        Self::connect(database_url, StorePoolConfig::default().pool_options()).await
+    }
+
+    /// Connect with a custom pool, rejecting a config whose minimum exceeds its maximum
+    pub async fn with_options(
+        database_url: &str,
+        config: StorePoolConfig,
+    ) -> Result<Self, StoreError> {
+        config.validate()?;
+        Ok(Self::connect(database_url, config.pool_options()).await?)
+    }
+
+    async fn connect(database_url: &str, options: PgPoolOptions) -> Result<Self, sqlx::Error> {
+        let pool = options.connect(database_url).await?;
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Wrap an existing pool, with every optional setting off
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self {
+            pool,
+            replica: None,
+            min_transfer_amount: Decimal::ZERO,
+            audit_sink: None,
+            require_verified_email: false,
+        }
+    }
+
+    /// A store whose pool only connects on first use, for tests that never reach the database.
+    /// Other crates get it through the `test-utils` feature, enabled only as a dev-dependency.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn for_tests(database_url: &str) -> Self {
+        Self::from_pool(
+            PgPoolOptions::new()
+                .connect_lazy(database_url)
+                .expect("invalid test database url"),
+        )
+    }
+
+    /// Set the minimum amount for transfers and withdrawals
+    pub fn with_min_transfer_amount(self, min_transfer_amount: Decimal) -> Self {
+        Self {
+            min_transfer_amount,
+            ..self
+        }
+    }
 
-        Ok(Self { pool })
+    /// Block withdrawals until the user has verified their email
+    pub fn with_require_verified_email(self, require_verified_email: bool) -> Self {
+        Self {
+            require_verified_email,
+            ..self
+        }
+    }
+
+    /// Route read-only queries to `replica`
+    pub fn with_read_replica(self, replica: PgPool) -> Self {
+        Self {
+            replica: Some(replica),
+            ..self
+        }
+    }
+
+    /// Pool for read-only queries: the replica when configured, else the primary. Reads that
+    /// must see the caller's own just-committed writes should use `pool` instead.
+    pub fn reader(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+
+    /// Emit an audit record for every balance change to `sink`
+    pub fn with_audit_sink(self, sink: Arc<dyn BalanceAuditSink>) -> Self {
+        Self {
+            audit_sink: Some(sink),
+            ..self
+        }
+    }
+
+    /// Reject transfer/withdrawal amounts below the configured minimum
+    pub(crate) fn check_min_transfer_amount(&self, amount: Decimal) -> Result<(), StoreError> {
+        if amount < self.min_transfer_amount {
+            return Err(StoreError::InvalidInput(format!(
+                "Amount must be at least {}",
+                self.min_transfer_amount
+            )));
+        }
+
+        Ok(())
     }
 
     /// Run database migrations
     pub async fn migrate(&self) -> Result<(), sqlx::migrate::MigrateError> {
-        sqlx::migrate!("./migrations").run(&self.pool).await
+        sqlx::migrate!("./migration").run(&self.pool).await
     }
 
     /// Close the database connection pool
     pub async fn close(&self) {
+        if let Some(replica) = &self.replica {
+            replica.close().await;
+        }
         self.pool.close().await;
     }
 
@@ -41,3 +196,77 @@ impl Store {
             .or(Ok(false))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Store, StorePoolConfig};
+    use crate::user::StoreError;
+    use rust_decimal::Decimal;
+    use sqlx::postgres::PgPoolOptions;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_min_transfer_amount() {
+        let store = Store::for_tests("postgres://localhost/unused")
+            .with_min_transfer_amount(Decimal::new(1, 3));
+
+        assert!(store.check_min_transfer_amount(Decimal::new(1, 4)).is_err());
+        assert!(store.check_min_transfer_amount(Decimal::new(1, 3)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_custom_small_pool() {
+        let config = StorePoolConfig {
+            max_connections: 2,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(1),
+            idle_timeout: None,
+            max_lifetime: None,
+        };
+        config.validate().unwrap();
+
+        let store = Store::from_pool(
+            config
+                .pool_options()
+                .connect_lazy("postgres://localhost/unused")
+                .unwrap(),
+        );
+        let options = store.pool.options();
+        assert_eq!(options.get_max_connections(), 2);
+        assert_eq!(options.get_min_connections(), 0);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(1));
+        assert_eq!(options.get_idle_timeout(), None);
+    }
+
+    #[tokio::test]
+    async fn test_pool_min_above_max_is_rejected() {
+        let config = StorePoolConfig {
+            max_connections: 2,
+            min_connections: 3,
+            ..StorePoolConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Store::with_options("postgres://localhost/unused", config).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reads_use_primary_without_replica() {
+        let store = Store::for_tests("postgres://localhost/unused");
+        assert!(std::ptr::eq(store.reader(), &store.pool));
+
+        let replica = PgPoolOptions::new()
+            .connect_lazy("postgres://localhost/unused")
+            .unwrap();
+        let store = store.with_read_replica(replica);
+        assert!(std::ptr::eq(
+            store.reader(),
+            store.replica.as_ref().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::user::StoreError;
+
+/// Keyset position for listings ordered by `(created_at DESC, id DESC)`.
+/// Unlike an offset, it stays stable when rows are inserted while a client pages through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    /// Opaque form handed to clients; they should only ever pass it back
+    pub fn encode(&self) -> String {
+        let raw = format!("{}|{}", self.created_at.timestamp_micros(), self.id);
+        bs58::encode(raw).into_string()
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, StoreError> {
+        let invalid = || StoreError::InvalidInput("Invalid cursor".to_string());
+        let raw = bs58::decode(cursor).into_vec().map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (micros, id) = raw.split_once('|').ok_or_else(invalid)?;
+
+        let micros = micros.parse::<i64>().map_err(|_| invalid())?;
+        Ok(Self {
+            created_at: DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?,
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A page of keyset results; `next_cursor` is `None` once the listing is exhausted
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Build a page from a query that fetched `limit + 1` rows, the extra row signalling more remain
+    pub(crate) fn from_rows(mut rows: Vec<T>, limit: i64, key: impl Fn(&T) -> Cursor) -> Self {
+        let limit = usize::try_from(limit).unwrap_or(0);
+        let next_cursor = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|row| key(row).encode())
+        } else {
+            None
+        };
+
+        Self {
+            items: rows,
+            next_cursor,
+        }
+    }
+}
+
+/// Rows a keyset query fetches: one past `limit`, so `Page::from_rows` can tell whether more remain
+pub(crate) fn fetch_limit(limit: i64) -> Result<i64, StoreError> {
+    limit
+        .checked_add(1)
+        .ok_or_else(|| StoreError::InvalidInput("Invalid limit".to_string()))
+}
+
+/// Decode an optional client cursor into the bind parameters of a keyset query
+pub(crate) fn cursor_bounds(
+    cursor: Option<&str>,
+) -> Result<(Option<DateTime<Utc>>, Option<Uuid>), StoreError> {
+    Ok(match cursor.map(Cursor::decode).transpose()? {
+        Some(cursor) => (Some(cursor.created_at), Some(cursor.id)),
+        None => (None, None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cursor, fetch_limit};
+    use crate::Store;
+    use crate::testing::{insert_transaction, insert_user};
+    use crate::user::{StoreError, TransactionStatus, TransactionType};
+    use chrono::DateTime;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::collections::HashSet;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let at = DateTime::from_timestamp_micros(1_700_000_000_123_456).unwrap();
+        let cursor = Cursor::new(at, Uuid::new_v4());
+        assert_eq!(Cursor::decode(&cursor.encode()).unwrap(), cursor);
+
+        assert!(matches!(
+            Cursor::decode("not a cursor"),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Cursor::decode(&bs58::encode("12|not-a-uuid").into_string()),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_fetch_limit_rejects_overflow() {
+        assert_eq!(fetch_limit(3).unwrap(), 4);
+        assert!(matches!(
+            fetch_limit(i64::MAX),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_user_paging_survives_concurrent_inserts(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        for _ in 0..10 {
+            insert_user(&store, Decimal::ZERO).await;
+        }
+        let original: HashSet<Uuid> = store
+            .list_users(100, 0)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|user| user.id)
+            .collect();
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = store.list_users_after(cursor.as_deref(), 3).await.unwrap();
+            seen.extend(page.items.iter().map(|user| user.id));
+
+            // A user signs up mid-iteration; being newest, it never shifts later pages
+            if seen.len() == 3 {
+                insert_user(&store, Decimal::ZERO).await;
+            }
+
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        let unique: HashSet<Uuid> = seen.iter().copied().collect();
+        assert_eq!(unique.len(), seen.len(), "a user was returned twice");
+        assert_eq!(unique, original);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_transaction_pages_match_offsets(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for _ in 0..5 {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Deposit,
+                TransactionStatus::Confirmed,
+                Decimal::ONE,
+            )
+            .await;
+        }
+
+        let first = store
+            .get_user_transactions_after(user_id, None, 3, None, None)
+            .await
+            .unwrap();
+        let rest = store
+            .get_user_transactions_after(user_id, first.next_cursor.as_deref(), 3, None, None)
+            .await
+            .unwrap();
+        assert_eq!(first.items.len(), 3);
+        assert_eq!(rest.items.len(), 2);
+        assert!(rest.next_cursor.is_none());
+
+        let by_offset = store
+            .get_user_transactions(user_id, 3, 3, None, None)
+            .await
+            .unwrap();
+        let keyset: Vec<Uuid> = rest.items.iter().map(|tx| tx.id).collect();
+        let offset: Vec<Uuid> = by_offset.iter().map(|tx| tx.id).collect();
+        assert_eq!(offset, keyset);
+
+        assert!(matches!(
+            store
+                .get_user_transactions_after(user_id, None, i64::MAX, None, None)
+                .await,
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+}
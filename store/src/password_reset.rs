@@ -0,0 +1,188 @@
+use crate::Store;
+use crate::user::{StoreError, hash_password, normalize_email, validate_password};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// How long a reset token stays valid after it is issued
+pub const PASSWORD_RESET_TTL: Duration = Duration::minutes(30);
+
+/// 244 random bits from two v4 UUIDs, base58 so it survives being pasted into a URL
+pub(crate) fn generate_secret_token() -> String {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(Uuid::new_v4().as_bytes());
+    bytes[16..].copy_from_slice(Uuid::new_v4().as_bytes());
+    bs58::encode(bytes).into_string()
+}
+
+/// Tokens are looked up by their SHA-256 so a database leak doesn't hand out live tokens
+pub(crate) fn hash_secret_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// A reset or verification token is redeemable once, and only before it expires
+pub(crate) fn check_token_redeemable(
+    expires_at: DateTime<Utc>,
+    used_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(), StoreError> {
+    if used_at.is_some() {
+        return Err(StoreError::InvalidInput(
+            "Token has already been used".to_string(),
+        ));
+    }
+    if now >= expires_at {
+        return Err(StoreError::InvalidInput("Token has expired".to_string()));
+    }
+    Ok(())
+}
+
+impl Store {
+    /// Issue a reset token for the account behind `email`. The plaintext token is returned
+    /// once for delivery to the user; only its hash is kept.
+    pub async fn create_password_reset(&self, email: &str) -> Result<String, StoreError> {
+        let email = normalize_email(email);
+        let user = sqlx::query!("SELECT id FROM users WHERE lower(email) = $1", email)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(StoreError::UserNotFound)?;
+
+        let token = generate_secret_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            hash_secret_token(&token),
+            user.id,
+            Utc::now() + PASSWORD_RESET_TTL
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Redeem a reset token, setting a new password. Every outstanding token for the user is
+    /// spent along with it, so an older emailed link can't be replayed afterwards, and every
+    /// refresh token is revoked so sessions opened with the old password end too.
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<(), StoreError> {
+        validate_password(new_password)?;
+
+        // Hash off the async runtime and before any row is locked
+        let password = new_password.to_string();
+        let password_hash = tokio::task::spawn_blocking(move || hash_password(&password))
+            .await
+            .map_err(|e| StoreError::PasswordError(e.to_string()))??;
+
+        let mut tx = self.pool.begin().await?;
+
+        let reset = sqlx::query!(
+            r#"
+            SELECT user_id, expires_at, used_at
+            FROM password_reset_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            hash_secret_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| StoreError::InvalidInput("Invalid reset token".to_string()))?;
+
+        let now = Utc::now();
+        check_token_redeemable(reset.expires_at, reset.used_at, now)?;
+
+        sqlx::query!(
+            "UPDATE users SET password_hash = $1, updated_at = $2 WHERE id = $3",
+            password_hash,
+            now,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE password_reset_tokens SET used_at = $1 WHERE user_id = $2 AND used_at IS NULL",
+            now,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL",
+            now,
+            reset.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PASSWORD_RESET_TTL, check_token_redeemable, generate_secret_token, hash_secret_token,
+    };
+    use crate::user::StoreError;
+    use chrono::{Duration, Utc};
+
+    fn rejection(result: Result<(), StoreError>) -> String {
+        match result {
+            Err(StoreError::InvalidInput(message)) => message,
+            other => panic!("expected rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fresh_token_is_accepted() {
+        let issued = Utc::now();
+        assert!(check_token_redeemable(issued + PASSWORD_RESET_TTL, None, issued).is_ok());
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let issued = Utc::now();
+        let expires_at = issued + PASSWORD_RESET_TTL;
+
+        let message = rejection(check_token_redeemable(expires_at, None, expires_at));
+        assert!(message.contains("expired"));
+        let message = rejection(check_token_redeemable(
+            expires_at,
+            None,
+            expires_at + Duration::seconds(1),
+        ));
+        assert!(message.contains("expired"));
+    }
+
+    #[test]
+    fn test_used_token_is_rejected() {
+        let issued = Utc::now();
+        let message = rejection(check_token_redeemable(
+            issued + PASSWORD_RESET_TTL,
+            Some(issued),
+            issued + Duration::minutes(1),
+        ));
+        assert!(message.contains("already been used"));
+    }
+
+    #[test]
+    fn test_tokens_are_unique_and_hashed() {
+        let first = generate_secret_token();
+        let second = generate_secret_token();
+        assert_ne!(first, second);
+
+        let hash = hash_secret_token(&first);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_secret_token(&first));
+        assert_ne!(hash, hash_secret_token(&second));
+    }
+}
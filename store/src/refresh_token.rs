@@ -0,0 +1,267 @@
+use crate::Store;
+use crate::password_reset::{generate_secret_token, hash_secret_token};
+use crate::user::StoreError;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+/// How long a refresh token can be exchanged after it is issued
+pub const REFRESH_TOKEN_TTL: Duration = Duration::days(30);
+
+/// Outcome of presenting a refresh token
+#[derive(Debug, PartialEq, Eq)]
+enum RefreshCheck {
+    Valid,
+    /// Already rotated away; someone is replaying a copy, so the family is compromised
+    Replayed,
+    Revoked,
+    Expired,
+}
+
+fn check_refresh_token(
+    expires_at: DateTime<Utc>,
+    rotated_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> RefreshCheck {
+    if revoked_at.is_some() {
+        RefreshCheck::Revoked
+    } else if rotated_at.is_some() {
+        RefreshCheck::Replayed
+    } else if now >= expires_at {
+        RefreshCheck::Expired
+    } else {
+        RefreshCheck::Valid
+    }
+}
+
+async fn insert_refresh_token(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    family_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<String, StoreError> {
+    let token = generate_secret_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, user_id, family_id, expires_at, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        hash_secret_token(&token),
+        user_id,
+        family_id,
+        now + REFRESH_TOKEN_TTL,
+        now
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(token)
+}
+
+impl Store {
+    /// Start a new refresh token family for a fresh sign-in, returning the plaintext token
+    pub async fn issue_refresh_token(&self, user_id: Uuid) -> Result<String, StoreError> {
+        let mut conn = self.pool.acquire().await?;
+        insert_refresh_token(&mut conn, user_id, Uuid::new_v4(), Utc::now()).await
+    }
+
+    /// Exchange a refresh token for its successor, returning the user and the new token.
+    /// Presenting a token that was already rotated revokes its whole family.
+    pub async fn rotate_refresh_token(&self, token: &str) -> Result<(Uuid, String), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query!(
+            r#"
+            SELECT user_id, family_id, expires_at, rotated_at, revoked_at
+            FROM refresh_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            hash_secret_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| StoreError::InvalidRefreshToken("unknown token".to_string()))?;
+
+        let now = Utc::now();
+        match check_refresh_token(
+            current.expires_at,
+            current.rotated_at,
+            current.revoked_at,
+            now,
+        ) {
+            RefreshCheck::Valid => {}
+            RefreshCheck::Replayed => {
+                sqlx::query!(
+                    "UPDATE refresh_tokens SET revoked_at = $1 WHERE family_id = $2 AND revoked_at IS NULL",
+                    now,
+                    current.family_id
+                )
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+
+                return Err(StoreError::RefreshTokenReplayed);
+            }
+            RefreshCheck::Revoked => {
+                return Err(StoreError::InvalidRefreshToken("token revoked".to_string()));
+            }
+            RefreshCheck::Expired => {
+                return Err(StoreError::InvalidRefreshToken("token expired".to_string()));
+            }
+        }
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET rotated_at = $1 WHERE token_hash = $2",
+            now,
+            hash_secret_token(token)
+        )
+        .execute(&mut *tx)
+        .await?;
+        let next = insert_refresh_token(&mut tx, current.user_id, current.family_id, now).await?;
+
+        tx.commit().await?;
+
+        Ok((current.user_id, next))
+    }
+
+    /// Revoke the family of a refresh token `user_id` holds, ending that session for good
+    pub async fn revoke_refresh_token_family(
+        &self,
+        user_id: Uuid,
+        token: &str,
+    ) -> Result<(), StoreError> {
+        let family_id = sqlx::query_scalar!(
+            "SELECT family_id FROM refresh_tokens WHERE token_hash = $1 AND user_id = $2",
+            hash_secret_token(token),
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| StoreError::InvalidRefreshToken("unknown token".to_string()))?;
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked_at = $1 WHERE family_id = $2 AND revoked_at IS NULL",
+            Utc::now(),
+            family_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{REFRESH_TOKEN_TTL, RefreshCheck, check_refresh_token};
+    use crate::Store;
+    use crate::testing::insert_user;
+    use crate::user::StoreError;
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+
+    #[test]
+    fn test_fresh_token_is_valid() {
+        let now = Utc::now();
+        assert_eq!(
+            check_refresh_token(now + REFRESH_TOKEN_TTL, None, None, now),
+            RefreshCheck::Valid
+        );
+    }
+
+    #[test]
+    fn test_rotated_token_is_rejected_as_replay() {
+        let issued = Utc::now();
+        let rotated = issued + Duration::minutes(5);
+        assert_eq!(
+            check_refresh_token(
+                issued + REFRESH_TOKEN_TTL,
+                Some(rotated),
+                None,
+                rotated + Duration::minutes(1)
+            ),
+            RefreshCheck::Replayed
+        );
+    }
+
+    #[test]
+    fn test_revoked_and_expired_tokens_are_rejected() {
+        let issued = Utc::now();
+        let expires_at = issued + REFRESH_TOKEN_TTL;
+
+        // Once a family is revoked, its successor tokens stop working too
+        assert_eq!(
+            check_refresh_token(expires_at, None, Some(issued), issued),
+            RefreshCheck::Revoked
+        );
+        assert_eq!(
+            check_refresh_token(expires_at, None, None, expires_at),
+            RefreshCheck::Expired
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_replayed_token_revokes_family(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let first = store.issue_refresh_token(user_id).await.unwrap();
+        let (_, second) = store.rotate_refresh_token(&first).await.unwrap();
+
+        assert!(matches!(
+            store.rotate_refresh_token(&first).await,
+            Err(StoreError::RefreshTokenReplayed)
+        ));
+        assert!(matches!(
+            store.rotate_refresh_token(&second).await,
+            Err(StoreError::InvalidRefreshToken(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_logout_revokes_family(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let other_user = insert_user(&store, Decimal::ZERO).await;
+        let first = store.issue_refresh_token(user_id).await.unwrap();
+        let (_, current) = store.rotate_refresh_token(&first).await.unwrap();
+
+        // Someone else's session can't be ended with a token they don't hold
+        assert!(matches!(
+            store
+                .revoke_refresh_token_family(other_user, &current)
+                .await,
+            Err(StoreError::InvalidRefreshToken(_))
+        ));
+
+        store
+            .revoke_refresh_token_family(user_id, &first)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.rotate_refresh_token(&current).await,
+            Err(StoreError::InvalidRefreshToken(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_password_reset_revokes_refresh_tokens(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let email = store.get_user(user_id).await.unwrap().email;
+        let refresh_token = store.issue_refresh_token(user_id).await.unwrap();
+
+        let reset_token = store.create_password_reset(&email).await.unwrap();
+        store
+            .reset_password(&reset_token, "a-new-long-password")
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            store.rotate_refresh_token(&refresh_token).await,
+            Err(StoreError::InvalidRefreshToken(_))
+        ));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::user::StoreError;
+use std::future::Future;
+use std::time::Duration;
+
+/// Attempts made by `retry_on_conflict`, including the first
+pub const MAX_ATTEMPTS: u32 = 3;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(20);
+
+/// SQLSTATEs Postgres raises when a transaction lost a race and would succeed if rerun:
+/// `40001` serialization_failure and `40P01` deadlock_detected
+fn is_retryable_code(code: &str) -> bool {
+    matches!(code, "40001" | "40P01")
+}
+
+/// Whether rerunning the whole transaction could succeed: serialization failures, deadlocks,
+/// and pool timeouts, which fail before any statement runs. I/O errors are not retried: one
+/// during COMMIT leaves it unknown whether the commit landed. Business errors never are.
+pub fn is_transient(e: &StoreError) -> bool {
+    match e {
+        StoreError::DatabaseError(sqlx::Error::Database(db)) => {
+            db.code().is_some_and(|code| is_retryable_code(&code))
+        }
+        StoreError::DatabaseError(sqlx::Error::PoolTimedOut) => true,
+        _ => false,
+    }
+}
+
+/// Run `operation` up to `MAX_ATTEMPTS` times, backing off between attempts while it fails
+/// transiently. `operation` must be a whole transaction so a failed attempt leaves no trace.
+pub(crate) async fn retry_on_conflict<T, F, Fut>(mut operation: F) -> Result<T, StoreError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, StoreError>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < MAX_ATTEMPTS && is_transient(&e) => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_ATTEMPTS, is_retryable_code, is_transient, retry_on_conflict};
+    use crate::user::StoreError;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_classifies_transient_errors() {
+        assert!(is_retryable_code("40001"));
+        assert!(is_retryable_code("40P01"));
+        assert!(!is_retryable_code("23505"));
+
+        assert!(is_transient(&StoreError::DatabaseError(
+            sqlx::Error::PoolTimedOut
+        )));
+        assert!(!is_transient(&StoreError::DatabaseError(
+            sqlx::Error::RowNotFound
+        )));
+        assert!(!is_transient(&StoreError::DatabaseError(sqlx::Error::Io(
+            std::io::ErrorKind::ConnectionReset.into()
+        ))));
+        assert!(!is_transient(&StoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_fails_once_then_succeeds() {
+        let calls = &Cell::new(0);
+        let result = retry_on_conflict(|| async move {
+            calls.set(calls.get() + 1);
+            if calls.get() == 1 {
+                Err(StoreError::DatabaseError(sqlx::Error::PoolTimedOut))
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let calls = &Cell::new(0);
+        let result: Result<(), _> = retry_on_conflict(|| async move {
+            calls.set(calls.get() + 1);
+            Err(StoreError::DatabaseError(sqlx::Error::PoolTimedOut))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn test_business_errors_are_not_retried() {
+        let calls = &Cell::new(0);
+        let result: Result<(), _> = retry_on_conflict(|| async move {
+            calls.set(calls.get() + 1);
+            Err(StoreError::InvalidInput(
+                "Amount must be positive".to_string(),
+            ))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
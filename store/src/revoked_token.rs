@@ -0,0 +1,54 @@
+use crate::Store;
+use crate::user::StoreError;
+use chrono::{DateTime, Utc};
+
+impl Store {
+    /// Blacklist an access token by its `jti` until `expires_at`, when it would lapse anyway
+    pub async fn revoke_token(
+        &self,
+        jti: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO revoked_tokens (jti, expires_at)
+            VALUES ($1, $2)
+            ON CONFLICT (jti) DO NOTHING
+            "#,
+            jti,
+            expires_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every revoked token that has not yet expired, as `(jti, expires_at)`
+    pub async fn get_revoked_tokens(&self) -> Result<Vec<(String, DateTime<Utc>)>, StoreError> {
+        let rows = sqlx::query!(
+            "SELECT jti, expires_at FROM revoked_tokens WHERE expires_at > $1",
+            Utc::now()
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.jti, row.expires_at))
+            .collect())
+    }
+
+    /// Drop blacklist entries for tokens that have expired, returning how many were removed
+    pub async fn purge_expired_revoked_tokens(&self) -> Result<u64, StoreError> {
+        let purged = sqlx::query!(
+            "DELETE FROM revoked_tokens WHERE expires_at <= $1",
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(purged)
+    }
+}
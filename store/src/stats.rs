@@ -0,0 +1,192 @@
+use crate::Store;
+use crate::user::{StoreError, TransactionStatus};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PlatformStats {
+    pub total_users: i64,
+    pub total_sol_locked: Decimal,
+    pub total_transactions: i64,
+    pub pending_transactions: i64,
+    pub failed_transactions: i64,
+    pub total_volume: Decimal, // Sum of confirmed transaction amounts
+    pub computed_at: DateTime<Utc>,
+}
+
+/// SOL a user's on-chain wallet should hold according to the ledger
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ExpectedBalance {
+    pub user_id: Uuid,
+    pub agg_pubkey: String,
+    pub balance: Decimal,
+}
+
+/// Token amount a user's on-chain wallet should hold for one mint according to the ledger
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct ExpectedTokenBalance {
+    pub user_id: Uuid,
+    pub agg_pubkey: String,
+    pub token_mint: String,
+    pub balance: Decimal,
+}
+
+impl Store {
+    /// Get the recorded SOL balance of every user with an on-chain wallet, for reconciliation
+    pub async fn get_expected_balances(&self) -> Result<Vec<ExpectedBalance>, StoreError> {
+        let balances = sqlx::query_as!(
+            ExpectedBalance,
+            r#"
+            SELECT id as user_id, agg_pubkey as "agg_pubkey!", balance
+            FROM users
+            WHERE agg_pubkey IS NOT NULL
+            ORDER BY id
+            "#
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(balances)
+    }
+
+    /// Get every recorded token balance of users with an on-chain wallet, for reconciliation
+    pub async fn get_expected_token_balances(
+        &self,
+    ) -> Result<Vec<ExpectedTokenBalance>, StoreError> {
+        let balances = sqlx::query_as!(
+            ExpectedTokenBalance,
+            r#"
+            SELECT u.id as user_id, u.agg_pubkey as "agg_pubkey!", tb.token_mint, tb.balance
+            FROM token_balances tb
+            JOIN users u ON u.id = tb.user_id
+            WHERE u.agg_pubkey IS NOT NULL
+            ORDER BY u.id, tb.token_mint
+            "#
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(balances)
+    }
+
+    /// Recompute platform-wide counters and store them as the latest snapshot
+    pub async fn refresh_platform_stats(&self) -> Result<PlatformStats, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Every count reads from the same snapshot so the numbers agree with each other
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")
+            .execute(&mut *tx)
+            .await?;
+
+        let users = sqlx::query!(
+            "SELECT COUNT(*) AS total_users, COALESCE(SUM(balance), 0) AS total_sol_locked FROM users"
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let transactions = sqlx::query!(
+            "SELECT COUNT(*) AS total_transactions,
+                    COUNT(*) FILTER (WHERE status = $1) AS pending_transactions,
+                    COUNT(*) FILTER (WHERE status = $2) AS failed_transactions,
+                    COALESCE(SUM(amount) FILTER (WHERE status = $3), 0) AS total_volume
+             FROM transactions",
+            TransactionStatus::Pending as TransactionStatus,
+            TransactionStatus::Failed as TransactionStatus,
+            TransactionStatus::Confirmed as TransactionStatus
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let stats = sqlx::query_as!(
+            PlatformStats,
+            r#"INSERT INTO platform_stats_cache
+                (total_users, total_sol_locked, total_transactions, pending_transactions, failed_transactions, total_volume)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING total_users, total_sol_locked, total_transactions, pending_transactions,
+                       failed_transactions, total_volume, computed_at"#,
+            users.total_users.unwrap_or(0),
+            users.total_sol_locked.unwrap_or(Decimal::ZERO),
+            transactions.total_transactions.unwrap_or(0),
+            transactions.pending_transactions.unwrap_or(0),
+            transactions.failed_transactions.unwrap_or(0),
+            transactions.total_volume.unwrap_or(Decimal::ZERO)
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(stats)
+    }
+
+    /// Get the most recent platform stats snapshot, if one has been computed
+    pub async fn get_cached_platform_stats(&self) -> Result<Option<PlatformStats>, StoreError> {
+        let stats = sqlx::query_as!(
+            PlatformStats,
+            "SELECT total_users, total_sol_locked, total_transactions, pending_transactions,
+                    failed_transactions, total_volume, computed_at
+             FROM platform_stats_cache ORDER BY computed_at DESC, id DESC LIMIT 1"
+        )
+        .fetch_optional(self.reader())
+        .await?;
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Store;
+    use crate::testing::{insert_transaction, insert_user};
+    use crate::user::{TransactionStatus, TransactionType};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_refresh_updates_cached_platform_stats(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        assert!(store.get_cached_platform_stats().await.unwrap().is_none());
+
+        let before = store.refresh_platform_stats().await.unwrap();
+        let cached = store.get_cached_platform_stats().await.unwrap().unwrap();
+        assert_eq!(cached.total_users, before.total_users);
+        assert_eq!(cached.computed_at, before.computed_at);
+
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        for status in [
+            TransactionStatus::Confirmed,
+            TransactionStatus::Pending,
+            TransactionStatus::Failed,
+        ] {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Deposit,
+                status,
+                Decimal::TWO,
+            )
+            .await;
+        }
+
+        // The cache only moves when refreshed
+        let cached = store.get_cached_platform_stats().await.unwrap().unwrap();
+        assert_eq!(cached.total_transactions, before.total_transactions);
+
+        let after = store.refresh_platform_stats().await.unwrap();
+        assert_eq!(after.total_users, before.total_users + 1);
+        assert_eq!(
+            after.total_sol_locked,
+            before.total_sol_locked + Decimal::TEN
+        );
+        assert_eq!(after.total_transactions, before.total_transactions + 3);
+        assert_eq!(after.pending_transactions, before.pending_transactions + 1);
+        assert_eq!(after.failed_transactions, before.failed_transactions + 1);
+        assert_eq!(after.total_volume, before.total_volume + Decimal::TWO);
+
+        let cached = store.get_cached_platform_stats().await.unwrap().unwrap();
+        assert_eq!(cached.total_transactions, after.total_transactions);
+        assert_eq!(cached.computed_at, after.computed_at);
+    }
+}
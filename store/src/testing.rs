@@ -0,0 +1,65 @@
+//! Row fixtures for `#[sqlx::test]` database tests
+
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::Store;
+use crate::user::{TransactionStatus, TransactionType};
+
+/// Insert a user holding `balance` SOL, bypassing sign-up validation and password hashing
+pub(crate) async fn insert_user(store: &Store, balance: Decimal) -> Uuid {
+    sqlx::query_scalar!(
+        "INSERT INTO users (email, password_hash, balance) VALUES ($1, 'unused', $2) RETURNING id",
+        format!("{}@example.com", Uuid::new_v4()),
+        balance
+    )
+    .fetch_one(&store.pool)
+    .await
+    .expect("failed to insert test user")
+}
+
+/// Insert a transaction row directly, skipping the balance checks `create_transaction` applies
+pub(crate) async fn insert_transaction(
+    store: &Store,
+    user_id: Uuid,
+    transaction_type: TransactionType,
+    status: TransactionStatus,
+    amount: Decimal,
+) -> Uuid {
+    sqlx::query_scalar!(
+        "INSERT INTO transactions (user_id, transaction_type, status, amount)
+         VALUES ($1, $2, $3, $4) RETURNING id",
+        user_id,
+        transaction_type as TransactionType,
+        status as TransactionStatus,
+        amount
+    )
+    .fetch_one(&store.pool)
+    .await
+    .expect("failed to insert test transaction")
+}
+
+/// Give a user an aggregated wallet address, as completed MPC setup would
+pub(crate) async fn set_agg_pubkey(store: &Store, user_id: Uuid, agg_pubkey: &str) {
+    sqlx::query!(
+        "UPDATE users SET agg_pubkey = $1 WHERE id = $2",
+        agg_pubkey,
+        user_id
+    )
+    .execute(&store.pool)
+    .await
+    .expect("failed to set test agg_pubkey");
+}
+
+/// Insert an active keyshare for `mpc_node_id` with placeholder key material
+pub(crate) async fn insert_keyshare(store: &Store, user_id: Uuid, mpc_node_id: i32) {
+    sqlx::query!(
+        "INSERT INTO mpc_keyshares (user_id, mpc_node_id, private_key_share, public_key)
+         VALUES ($1, $2, 'unused', 'unused')",
+        user_id,
+        mpc_node_id
+    )
+    .execute(&store.pool)
+    .await
+    .expect("failed to insert test keyshare");
+}
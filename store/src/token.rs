@@ -0,0 +1,235 @@
+use crate::Store;
+use crate::user::StoreError;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+use sqlx::prelude::FromRow;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub mint: String,
+    pub symbol: String,
+    pub name: String,
+    pub decimals: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CustodyTotal {
+    pub mint: String,
+    pub symbol: Option<String>,
+    pub raw_total: Option<String>, // Sum of base units (balance × 10^decimals) as an integer; None if any holder's decimals are unknown
+    pub ui_total: Decimal, // Sum of balances across users; balances are stored as UI amounts
+    pub holder_count: i64,
+}
+
+/// Symbol and decimals to record on a new balance row; `None` where nothing trustworthy is known
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TokenInfo {
+    pub symbol: Option<String>,
+    pub decimals: Option<i32>,
+}
+
+/// Prefer registered metadata, falling back to what an existing holder of the mint already records
+fn resolve_token_info(metadata: Option<TokenInfo>, holder: Option<TokenInfo>) -> TokenInfo {
+    match (metadata, holder) {
+        (Some(metadata), _) => metadata,
+        (None, Some(holder)) => holder,
+        (None, None) => TokenInfo::default(),
+    }
+}
+
+/// Look up the symbol and decimals to record for `mint` when crediting a new holder
+pub(crate) async fn lookup_token_info(
+    conn: &mut PgConnection,
+    mint: &str,
+) -> Result<TokenInfo, StoreError> {
+    let metadata = sqlx::query!(
+        "SELECT symbol, decimals FROM token_metadata WHERE mint = $1",
+        mint
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| TokenInfo {
+        symbol: Some(row.symbol),
+        decimals: Some(row.decimals),
+    });
+
+    let holder = sqlx::query!(
+        "SELECT token_symbol, decimals FROM token_balances
+         WHERE token_mint = $1 AND decimals IS NOT NULL LIMIT 1",
+        mint
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .map(|row| TokenInfo {
+        symbol: row.token_symbol,
+        decimals: row.decimals,
+    });
+
+    Ok(resolve_token_info(metadata, holder))
+}
+
+impl Store {
+    /// Get token metadata by mint address
+    pub async fn get_token_metadata_by_mint(
+        &self,
+        mint: &str,
+    ) -> Result<Option<TokenMetadata>, StoreError> {
+        let metadata = sqlx::query_as!(
+            TokenMetadata,
+            "SELECT mint, symbol, name, decimals, created_at, updated_at
+             FROM token_metadata WHERE mint = $1",
+            mint
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(metadata)
+    }
+
+    /// Get token metadata by symbol (case-insensitive)
+    pub async fn get_token_metadata_by_symbol(
+        &self,
+        symbol: &str,
+    ) -> Result<Option<TokenMetadata>, StoreError> {
+        let metadata = sqlx::query_as!(
+            TokenMetadata,
+            "SELECT mint, symbol, name, decimals, created_at, updated_at
+             FROM token_metadata WHERE LOWER(symbol) = LOWER($1)",
+            symbol
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(metadata)
+    }
+
+    /// Get total custodied balance per mint, in UI amounts and base units (custody dashboard)
+    pub async fn get_custody_totals(&self) -> Result<Vec<CustodyTotal>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT tb.token_mint as "mint!",
+                   COALESCE(tm.symbol, MAX(tb.token_symbol)) as "symbol",
+                   COALESCE(SUM(tb.balance), 0) as "ui_total!",
+                   CASE WHEN BOOL_AND(COALESCE(tm.decimals, tb.decimals) IS NOT NULL)
+                        THEN COALESCE(SUM(tb.balance * POWER(10::numeric, COALESCE(tm.decimals, tb.decimals))), 0)
+                   END as "raw_total",
+                   COUNT(DISTINCT tb.user_id) FILTER (WHERE tb.balance > 0) as "holder_count!"
+            FROM token_balances tb
+            LEFT JOIN token_metadata tm ON tm.mint = tb.token_mint
+            GROUP BY tb.token_mint, tm.symbol
+            ORDER BY tb.token_mint
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let totals = rows
+            .into_iter()
+            .map(|row| CustodyTotal {
+                mint: row.mint,
+                symbol: row.symbol,
+                raw_total: row.raw_total.map(|raw| raw.round().normalize().to_string()),
+                ui_total: row.ui_total,
+                holder_count: row.holder_count,
+            })
+            .collect();
+
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TokenInfo, resolve_token_info};
+    use crate::Store;
+    use crate::testing::insert_user;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+
+    fn info(symbol: &str, decimals: i32) -> TokenInfo {
+        TokenInfo {
+            symbol: Some(symbol.to_string()),
+            decimals: Some(decimals),
+        }
+    }
+
+    #[test]
+    fn test_new_holder_gets_metadata_decimals() {
+        // A transfer to a brand-new holder of a registered 9-decimal mint
+        let resolved = resolve_token_info(Some(info("BONK", 9)), Some(info("BONK", 9)));
+        assert_eq!(resolved, info("BONK", 9));
+
+        // Metadata wins over a stale holder row
+        let resolved = resolve_token_info(Some(info("BONK", 9)), Some(info("BNK", 6)));
+        assert_eq!(resolved.decimals, Some(9));
+    }
+
+    #[test]
+    fn test_new_holder_copies_sender_decimals_without_metadata() {
+        let resolved = resolve_token_info(None, Some(info("XYZ", 9)));
+        assert_eq!(resolved.decimals, Some(9));
+    }
+
+    #[test]
+    fn test_unknown_mint_records_nothing() {
+        let resolved = resolve_token_info(None, None);
+        assert_eq!(resolved.symbol, None);
+        assert_eq!(resolved.decimals, None);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_custody_totals_sum_base_units_per_mint(pool: PgPool) {
+        const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        const BONK: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+        const MYSTERY: &str = "Mystery1111111111111111111111111111111111111";
+
+        let store = Store::from_pool(pool);
+        let alice = insert_user(&store, Decimal::ZERO).await;
+        let bob = insert_user(&store, Decimal::ZERO).await;
+        let carol = insert_user(&store, Decimal::ZERO).await;
+        // USDC decimals come from metadata; the others only from the holder rows
+        for (user_id, mint, balance, decimals) in [
+            (alice, USDC, Decimal::new(15, 1), None),
+            (bob, USDC, Decimal::new(225, 2), None),
+            (carol, USDC, Decimal::ZERO, None),
+            (alice, BONK, Decimal::new(12345, 5), Some(5)),
+            (bob, BONK, Decimal::new(1, 0), Some(5)),
+            (carol, MYSTERY, Decimal::new(7, 0), None),
+        ] {
+            sqlx::query!(
+                "INSERT INTO token_balances (user_id, token_mint, balance, decimals) VALUES ($1, $2, $3, $4)",
+                user_id,
+                mint,
+                balance,
+                decimals
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let totals = store.get_custody_totals().await.unwrap();
+        let total = |mint: &str| totals.iter().find(|total| total.mint == mint).unwrap();
+
+        let usdc = total(USDC);
+        assert_eq!(usdc.symbol.as_deref(), Some("USDC"));
+        assert_eq!(usdc.raw_total.as_deref(), Some("3750000"));
+        assert_eq!(usdc.ui_total, Decimal::new(375, 2));
+        assert_eq!(usdc.holder_count, 2);
+
+        let bonk = total(BONK);
+        assert_eq!(bonk.raw_total.as_deref(), Some("112345"));
+        assert_eq!(bonk.ui_total, Decimal::new(112345, 5));
+        assert_eq!(bonk.holder_count, 2);
+
+        // Without decimals the base-unit total can't be known
+        let mystery = total(MYSTERY);
+        assert_eq!(mystery.raw_total, None);
+        assert_eq!(mystery.ui_total, Decimal::new(7, 0));
+        assert_eq!(mystery.holder_count, 1);
+    }
+}
@@ -1,11 +1,370 @@
 use crate::Store;
-use crate::user::{StoreError, Transaction, TransactionStatus, TransactionType};
-use chrono::Utc;
+use crate::audit::{BalanceAuditRecord, SOL_ASSET};
+use crate::pagination::{Cursor, Page, cursor_bounds, fetch_limit};
+use crate::retry::retry_on_conflict;
+use crate::token::lookup_token_info;
+use crate::user::{
+    InternalSwap, MpcKeyshare, StatementEntry, StoreError, Transaction, TransactionStatus,
+    TransactionType, User, has_sufficient_keyshares,
+};
+use chrono::{DateTime, Duration, Utc};
 use rust_decimal::Decimal;
+use sqlx::PgConnection;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
+/// Check that an optional address is a valid base58-encoded 32-byte Solana pubkey
+pub(crate) fn validate_address(field_name: &str, address: Option<&str>) -> Result<(), StoreError> {
+    let Some(address) = address else {
+        return Ok(());
+    };
+
+    let is_valid = bs58::decode(address)
+        .into_vec()
+        .map(|bytes| bytes.len() == 32)
+        .unwrap_or(false);
+
+    if !is_valid {
+        return Err(StoreError::InvalidInput(format!(
+            "Invalid {}: {}",
+            field_name, address
+        )));
+    }
+
+    Ok(())
+}
+
+/// Longest idempotency key accepted, matching the column width
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 128;
+
+/// Check that an optional idempotency key is 1 to `MAX_IDEMPOTENCY_KEY_LEN` characters
+fn validate_idempotency_key(idempotency_key: Option<&str>) -> Result<(), StoreError> {
+    match idempotency_key {
+        Some(key) if key.is_empty() || key.chars().count() > MAX_IDEMPOTENCY_KEY_LEN => {
+            Err(StoreError::InvalidInput(format!(
+                "Idempotency key must be 1 to {} characters",
+                MAX_IDEMPOTENCY_KEY_LEN
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Map a unique violation on the user's idempotency key (an earlier or concurrent request
+/// already used it) to `IdempotencyKeyReused`
+fn idempotency_key_insert_error(e: sqlx::Error, idempotency_key: Option<&str>) -> StoreError {
+    match (&e, idempotency_key) {
+        (sqlx::Error::Database(db), Some(key))
+            if db.constraint() == Some("idx_transactions_user_idempotency_key") =>
+        {
+            StoreError::IdempotencyKeyReused(key.to_string())
+        }
+        _ => e.into(),
+    }
+}
+
+/// `failure_reason` recorded on pending transactions failed by `expire_stale_transactions`
+pub const EXPIRED_FAILURE_REASON: &str = "timeout";
+
+/// A signature can only ever be attributed to one user; a retry naming someone else is a bug upstream
+fn check_signature_owner(existing: Transaction, user_id: Uuid) -> Result<Transaction, StoreError> {
+    if existing.user_id != user_id {
+        return Err(StoreError::InvalidInput(format!(
+            "Signature {} is already recorded for another user",
+            existing.tx_signature.as_deref().unwrap_or_default()
+        )));
+    }
+    Ok(existing)
+}
+
+/// Record a balance change that has no `transactions` row, so `verify_balance_integrity` can
+/// still account for it. `token_mint` is `None` for SOL; `amount` is negative for debits.
+pub(crate) async fn record_balance_adjustment(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    token_mint: Option<&str>,
+    amount: Decimal,
+    operation: &str,
+) -> Result<(), StoreError> {
+    sqlx::query!(
+        "INSERT INTO balance_adjustments (user_id, token_mint, amount, operation) VALUES ($1, $2, $3, $4)",
+        user_id,
+        token_mint,
+        amount,
+        operation
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Check that the user can spend `amount` of an asset (`token_mint` is `None` for SOL). Pending
+/// withdrawals of the same asset count against the balance: they hold their amount from opening
+/// until they settle, so neither a second withdrawal nor a transfer, swap or manual debit can
+/// claim funds a signed transfer is about to take. Every debit except a withdrawal's own goes
+/// through here; the user row lock serializes them.
+pub(crate) async fn check_available_balance(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    token_mint: Option<&str>,
+    amount: Decimal,
+) -> Result<(), StoreError> {
+    let sol_balance = sqlx::query_scalar!(
+        "SELECT balance FROM users WHERE id = $1 FOR UPDATE",
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(StoreError::UserNotFound)?;
+
+    let balance = match token_mint {
+        Some(token_mint) => sqlx::query_scalar!(
+            "SELECT balance FROM token_balances WHERE user_id = $1 AND token_mint = $2",
+            user_id,
+            token_mint
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or(Decimal::ZERO),
+        None => sol_balance,
+    };
+
+    let held = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(amount), 0) as "held!"
+        FROM transactions
+        WHERE user_id = $1 AND transaction_type = $2 AND status = $3
+          AND token_mint IS NOT DISTINCT FROM $4
+        "#,
+        user_id,
+        TransactionType::Withdrawal as TransactionType,
+        TransactionStatus::Pending as TransactionStatus,
+        token_mint
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let available = balance - held;
+    if available < amount {
+        return Err(StoreError::InsufficientBalance {
+            required: amount,
+            available,
+        });
+    }
+
+    Ok(())
+}
+
+/// Add a confirmed deposit to the user's SOL or token balance, returning the asset and new balance
+async fn credit_deposit(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    token_mint: Option<String>,
+    amount: Decimal,
+) -> Result<(String, Decimal), StoreError> {
+    if let Some(token_mint) = token_mint {
+        let token_info = lookup_token_info(&mut *conn, &token_mint).await?;
+        let new_balance = sqlx::query_scalar!(
+            r#"
+            INSERT INTO token_balances (user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            ON CONFLICT (user_id, token_mint)
+            DO UPDATE SET
+                balance = token_balances.balance + EXCLUDED.balance,
+                token_symbol = COALESCE(token_balances.token_symbol, EXCLUDED.token_symbol),
+                decimals = COALESCE(token_balances.decimals, EXCLUDED.decimals),
+                updated_at = EXCLUDED.updated_at
+            RETURNING balance
+            "#,
+            user_id,
+            token_mint,
+            token_info.symbol,
+            amount,
+            token_info.decimals,
+            Utc::now()
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok((token_mint, new_balance))
+    } else {
+        let new_balance = sqlx::query_scalar!(
+            "UPDATE users SET balance = balance + $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+            amount,
+            Utc::now(),
+            user_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok((SOL_ASSET.to_string(), new_balance))
+    }
+}
+
+/// A pending withdrawal row, locked for processing
+struct PendingWithdrawal {
+    user_id: Uuid,
+    amount: Decimal,
+    token_mint: Option<String>,
+    to_address: Option<String>,
+}
+
+/// Lock a pending withdrawal; the row lock stops two workers processing the same withdrawal
+async fn lock_pending_withdrawal(
+    conn: &mut PgConnection,
+    transaction_id: Uuid,
+) -> Result<PendingWithdrawal, StoreError> {
+    let transaction = sqlx::query!(
+        r#"
+        SELECT user_id, amount, token_mint, to_address, transaction_type as "transaction_type: TransactionType"
+        FROM transactions WHERE id = $1 AND status = $2
+        FOR UPDATE
+        "#,
+        transaction_id,
+        TransactionStatus::Pending as TransactionStatus
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(StoreError::InvalidInput("Pending transaction not found".to_string()))?;
+
+    // Verify it's a withdrawal transaction
+    if !matches!(transaction.transaction_type, TransactionType::Withdrawal) {
+        return Err(StoreError::InvalidInput(
+            "Transaction is not a withdrawal".to_string(),
+        ));
+    }
+
+    Ok(PendingWithdrawal {
+        user_id: transaction.user_id,
+        amount: transaction.amount,
+        token_mint: transaction.token_mint,
+        to_address: transaction.to_address,
+    })
+}
+
+/// Take a locked pending withdrawal out of the user's balance and confirm it with its signature,
+/// returning the asset debited and its new balance
+async fn debit_withdrawal(
+    conn: &mut PgConnection,
+    transaction_id: Uuid,
+    withdrawal: &PendingWithdrawal,
+    tx_signature: &str,
+) -> Result<(String, Decimal), StoreError> {
+    // Lock the user row like every other debit, so `check_available_balance` never sees the
+    // balance debited while this withdrawal still counts as held
+    sqlx::query!(
+        "SELECT id FROM users WHERE id = $1 FOR UPDATE",
+        withdrawal.user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Check and update balances
+    let (asset, new_balance) = if let Some(token_mint) = &withdrawal.token_mint {
+        // Token withdrawal - check and update token balance
+        let current_balance = sqlx::query_scalar!(
+            "SELECT balance FROM token_balances WHERE user_id = $1 AND token_mint = $2 FOR UPDATE",
+            withdrawal.user_id,
+            token_mint
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or(Decimal::ZERO);
+
+        if current_balance < withdrawal.amount {
+            return Err(StoreError::InsufficientBalance {
+                required: withdrawal.amount,
+                available: current_balance,
+            });
+        }
+
+        let new_balance = sqlx::query_scalar!(
+            "UPDATE token_balances SET balance = balance - $1, updated_at = $2 WHERE user_id = $3 AND token_mint = $4 RETURNING balance",
+            withdrawal.amount,
+            Utc::now(),
+            withdrawal.user_id,
+            token_mint
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        (token_mint.clone(), new_balance)
+    } else {
+        // SOL withdrawal - check and update user balance
+        let current_balance = sqlx::query_scalar!(
+            "SELECT balance FROM users WHERE id = $1 FOR UPDATE",
+            withdrawal.user_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        if current_balance < withdrawal.amount {
+            return Err(StoreError::InsufficientBalance {
+                required: withdrawal.amount,
+                available: current_balance,
+            });
+        }
+
+        let new_balance = sqlx::query_scalar!(
+            "UPDATE users SET balance = balance - $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+            withdrawal.amount,
+            Utc::now(),
+            withdrawal.user_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        (SOL_ASSET.to_string(), new_balance)
+    };
+
+    // Update transaction status
+    sqlx::query!(
+        "UPDATE transactions SET status = $1, tx_signature = $2, updated_at = $3 WHERE id = $4",
+        TransactionStatus::Confirmed as TransactionStatus,
+        tx_signature,
+        Utc::now(),
+        transaction_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok((asset, new_balance))
+}
+
+/// A withdrawal sent on chain whose outcome hasn't been recorded yet
+#[derive(Debug, Clone)]
+pub struct SubmittedWithdrawal {
+    pub id: Uuid,
+    pub tx_signature: String,
+    /// Chain height after which the transfer can no longer land; `None` if it was never recorded
+    pub last_valid_block_height: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Half-open time window `[from, to)`; either bound may be left open
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    pub fn new(from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> Result<Self, StoreError> {
+        if from.zip(to).is_some_and(|(from, to)| from >= to) {
+            return Err(StoreError::InvalidInput(
+                "Range start must be before its end".to_string(),
+            ));
+        }
+        Ok(Self { from, to })
+    }
+
+    /// Same bounds the range queries use: `from` inclusive, `to` exclusive
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at < to)
+    }
+}
+
 impl Store {
-    /// Create a new transaction record
+    /// Create a new transaction record. A client-supplied `idempotency_key` is stored with it so
+    /// a retry can look the row up instead of creating another; reusing one is refused.
+    #[allow(clippy::too_many_arguments)]
     pub async fn create_transaction(
         &self,
         user_id: Uuid,
@@ -15,6 +374,7 @@ impl Store {
         from_address: Option<String>,
         to_address: Option<String>,
         fee: Option<Decimal>,
+        idempotency_key: Option<&str>,
     ) -> Result<Transaction, StoreError> {
         // Validate that user exists
         sqlx::query!("SELECT id FROM users WHERE id = $1", user_id)
@@ -28,15 +388,88 @@ impl Store {
             ));
         }
 
+        if matches!(
+            transaction_type,
+            TransactionType::Withdrawal | TransactionType::Transfer
+        ) {
+            self.check_min_transfer_amount(amount)?;
+        }
+
+        validate_address("from_address", from_address.as_deref())?;
+        validate_address("to_address", to_address.as_deref())?;
+        validate_idempotency_key(idempotency_key)?;
+
+        if matches!(transaction_type, TransactionType::Withdrawal) {
+            self.check_verified_email(user_id).await?;
+            self.check_withdrawal_whitelist(user_id, to_address.as_deref())
+                .await?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        if matches!(transaction_type, TransactionType::Withdrawal) {
+            check_available_balance(&mut tx, user_id, token_mint.as_deref(), amount).await?;
+        }
+
         let transaction = sqlx::query_as!(
             Transaction,
             r#"
-            INSERT INTO transactions (user_id, transaction_type, status, amount, token_mint, from_address, to_address, fee, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9)
+            INSERT INTO transactions (user_id, transaction_type, status, amount, token_mint, from_address, to_address, fee, created_at, updated_at, idempotency_key)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $9, $10)
             RETURNING id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType", 
-                      status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
+                      status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            "#,
+            user_id,
+            transaction_type as TransactionType,
+            TransactionStatus::Pending as TransactionStatus,
+            amount,
+            token_mint,
+            from_address,
+            to_address,
+            fee.unwrap_or(Decimal::ZERO),
+            Utc::now(),
+            idempotency_key
+        )
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| idempotency_key_insert_error(e, idempotency_key))?;
+
+        tx.commit().await?;
+        Ok(transaction)
+    }
+
+    /// Record a transaction already known by its on-chain signature, e.g. from a deposit webhook.
+    /// Retries return the existing row instead of inserting a duplicate; the flag is true when a row was created.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upsert_transaction_by_signature(
+        &self,
+        user_id: Uuid,
+        tx_signature: &str,
+        transaction_type: TransactionType,
+        amount: Decimal,
+        token_mint: Option<String>,
+        from_address: Option<String>,
+        to_address: Option<String>,
+        fee: Option<Decimal>,
+    ) -> Result<(Transaction, bool), StoreError> {
+        if amount <= Decimal::ZERO {
+            return Err(StoreError::InvalidInput(
+                "Amount must be positive".to_string(),
+            ));
+        }
+        validate_address("from_address", from_address.as_deref())?;
+        validate_address("to_address", to_address.as_deref())?;
+
+        let inserted = sqlx::query_as!(
+            Transaction,
+            r#"
+            INSERT INTO transactions (user_id, tx_signature, transaction_type, status, amount, token_mint, from_address, to_address, fee, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10)
+            ON CONFLICT (tx_signature) WHERE tx_signature IS NOT NULL DO NOTHING
+            RETURNING id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                      status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
             "#,
             user_id,
+            tx_signature,
             transaction_type as TransactionType,
             TransactionStatus::Pending as TransactionStatus,
             amount,
@@ -46,10 +479,17 @@ impl Store {
             fee.unwrap_or(Decimal::ZERO),
             Utc::now()
         )
-        .fetch_one(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
 
-        Ok(transaction)
+        match inserted {
+            Some(transaction) => Ok((transaction, true)),
+            // The signature was recorded by an earlier attempt; hand back that row
+            None => {
+                let existing = self.get_transaction_by_signature(tx_signature).await?;
+                Ok((check_signature_owner(existing, user_id)?, false))
+            }
+        }
     }
 
     /// Update transaction status and signature
@@ -79,12 +519,96 @@ impl Store {
         Ok(())
     }
 
+    /// Mark a submitted transaction confirmed at the slot reported by `getSignatureStatuses`.
+    /// Only pending rows move, so a transaction already failed can't be resurrected.
+    pub async fn confirm_transaction_at_slot(
+        &self,
+        transaction_id: Uuid,
+        slot: i64,
+    ) -> Result<(), StoreError> {
+        if slot < 0 {
+            return Err(StoreError::InvalidInput(
+                "Slot must not be negative".to_string(),
+            ));
+        }
+
+        let updated_rows = sqlx::query!(
+            "UPDATE transactions SET status = $1, confirmed_slot = $2, updated_at = $3
+             WHERE id = $4 AND tx_signature IS NOT NULL AND status = $5",
+            TransactionStatus::Confirmed as TransactionStatus,
+            slot,
+            Utc::now(),
+            transaction_id,
+            TransactionStatus::Pending as TransactionStatus
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if updated_rows == 0 {
+            return Err(StoreError::InvalidInput(
+                "Pending submitted transaction not found".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Get transactions confirmed after `slot`, in slot order, e.g. to re-check finality
+    pub async fn get_transactions_confirmed_after_slot(
+        &self,
+        slot: i64,
+        limit: i64,
+    ) -> Result<Vec<(Transaction, i64)>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason,
+                   created_at, updated_at, confirmed_slot as "confirmed_slot!"
+            FROM transactions
+            WHERE confirmed_slot > $1
+            ORDER BY confirmed_slot, id
+            LIMIT $2
+            "#,
+            slot,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let transactions = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    Transaction {
+                        id: row.id,
+                        user_id: row.user_id,
+                        tx_signature: row.tx_signature,
+                        transaction_type: row.transaction_type,
+                        status: row.status,
+                        amount: row.amount,
+                        token_mint: row.token_mint,
+                        from_address: row.from_address,
+                        to_address: row.to_address,
+                        fee: row.fee,
+                        failure_reason: row.failure_reason,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                    row.confirmed_slot,
+                )
+            })
+            .collect();
+
+        Ok(transactions)
+    }
+
     pub async fn get_transaction(&self, transaction_id: Uuid) -> Result<Transaction, StoreError> {
         let transaction = sqlx::query_as!(
             Transaction,
             r#"
             SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
             FROM transactions WHERE id = $1
             "#,
             transaction_id
@@ -105,7 +629,7 @@ impl Store {
             Transaction,
             r#"
             SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
             FROM transactions WHERE tx_signature = $1
             "#,
             tx_signature
@@ -117,113 +641,55 @@ impl Store {
         Ok(transaction)
     }
 
-    /// Get user transactions with pagination
-    pub async fn get_user_transactions(
+    /// Get the transaction a user created with the given idempotency key, if any
+    pub async fn get_transaction_by_idempotency_key(
         &self,
         user_id: Uuid,
-        limit: i64,
-        offset: i64,
-        status_filter: Option<TransactionStatus>,
-        transaction_type_filter: Option<TransactionType>,
-    ) -> Result<Vec<Transaction>, StoreError> {
-        let transactions = match (status_filter, transaction_type_filter) {
-            (Some(status), Some(tx_type)) => {
-                sqlx::query_as!(
-                    Transaction,
-                    r#"
-                    SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                           status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
-                    FROM transactions 
-                    WHERE user_id = $1 AND status = $2 AND transaction_type = $3
-                    ORDER BY created_at DESC 
-                    LIMIT $4 OFFSET $5
-                    "#,
-                    user_id,
-                    status as TransactionStatus,
-                    tx_type as TransactionType,
-                    limit,
-                    offset
-                )
-                .fetch_all(&self.pool)
-                .await?
-            }
-            (Some(status), None) => {
-                sqlx::query_as!(
-                    Transaction,
-                    r#"
-                    SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                           status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
-                    FROM transactions 
-                    WHERE user_id = $1 AND status = $2
-                    ORDER BY created_at DESC 
-                    LIMIT $3 OFFSET $4
-                    "#,
-                    user_id,
-                    status as TransactionStatus,
-                    limit,
-                    offset
-                )
-                .fetch_all(&self.pool)
-                .await?
-            }
-            (None, Some(tx_type)) => {
-                sqlx::query_as!(
-                    Transaction,
-                    r#"
-                    SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                           status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
-                    FROM transactions 
-                    WHERE user_id = $1 AND transaction_type = $2
-                    ORDER BY created_at DESC 
-                    LIMIT $3 OFFSET $4
-                    "#,
-                    user_id,
-                    tx_type as TransactionType,
-                    limit,
-                    offset
-                )
-                .fetch_all(&self.pool)
-                .await?
-            }
-            (None, None) => {
-                sqlx::query_as!(
-                    Transaction,
-                    r#"
-                    SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                           status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
-                    FROM transactions 
-                    WHERE user_id = $1
-                    ORDER BY created_at DESC 
-                    LIMIT $2 OFFSET $3
-                    "#,
-                    user_id,
-                    limit,
-                    offset
-                )
-                .fetch_all(&self.pool)
-                .await?
-            }
-        };
+        idempotency_key: &str,
+    ) -> Result<Option<Transaction>, StoreError> {
+        let transaction = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions WHERE user_id = $1 AND idempotency_key = $2
+            "#,
+            user_id,
+            idempotency_key
+        )
+        .fetch_optional(&self.pool)
+        .await?;
 
-        Ok(transactions)
+        Ok(transaction)
     }
 
-    /// Get pending transactions (for processing)
-    pub async fn get_pending_transactions(
+    /// Find transactions whose signature or either address starts with `query` (support lookups).
+    /// Base58 is case-sensitive, so matching uses LIKE rather than ILIKE to stay on the prefix indexes.
+    pub async fn search_transactions(
         &self,
+        query: &str,
         limit: i64,
     ) -> Result<Vec<Transaction>, StoreError> {
+        let query = query.trim();
+        if query.len() < MIN_SEARCH_LEN {
+            return Err(StoreError::InvalidInput(format!(
+                "Search query must be at least {} characters",
+                MIN_SEARCH_LEN
+            )));
+        }
+        let pattern = like_prefix_pattern(query);
+
         let transactions = sqlx::query_as!(
             Transaction,
             r#"
             SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
-                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, created_at, updated_at
-            FROM transactions 
-            WHERE status = $1
-            ORDER BY created_at ASC 
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions
+            WHERE tx_signature LIKE $1 OR from_address LIKE $1 OR to_address LIKE $1
+            ORDER BY created_at DESC
             LIMIT $2
             "#,
-            TransactionStatus::Pending as TransactionStatus,
+            pattern,
             limit
         )
         .fetch_all(&self.pool)
@@ -232,56 +698,471 @@ impl Store {
         Ok(transactions)
     }
 
-    /// Count user transactions
-    pub async fn count_user_transactions(
+    /// Get user transactions with pagination
+    pub async fn get_user_transactions(
         &self,
         user_id: Uuid,
+        limit: i64,
+        offset: i64,
         status_filter: Option<TransactionStatus>,
         transaction_type_filter: Option<TransactionType>,
-    ) -> Result<i64, StoreError> {
-        let count = match (status_filter, transaction_type_filter) {
-            (Some(status), Some(tx_type)) => {
-                sqlx::query_scalar!(
-                    "SELECT COUNT(*) FROM transactions WHERE user_id = $1 AND status = $2 AND transaction_type = $3",
-                    user_id,
-                    status as TransactionStatus,
-                    tx_type as TransactionType
-                )
-                .fetch_one(&self.pool)
-                .await?
-                .unwrap_or(0)
-            }
-            (Some(status), None) => {
-                sqlx::query_scalar!(
-                    "SELECT COUNT(*) FROM transactions WHERE user_id = $1 AND status = $2",
-                    user_id,
-                    status as TransactionStatus
-                )
-                .fetch_one(&self.pool)
-                .await?
-                .unwrap_or(0)
-            }
-            (None, Some(tx_type)) => {
-                sqlx::query_scalar!(
-                    "SELECT COUNT(*) FROM transactions WHERE user_id = $1 AND transaction_type = $2",
-                    user_id,
-                    tx_type as TransactionType
-                )
-                .fetch_one(&self.pool)
-                .await?
-                .unwrap_or(0)
-            }
-            (None, None) => {
-                sqlx::query_scalar!(
-                    "SELECT COUNT(*) FROM transactions WHERE user_id = $1",
-                    user_id
-                )
-                .fetch_one(&self.pool)
-                .await?
-                .unwrap_or(0)
-            }
-        };
-
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions
+            WHERE user_id = $1
+              AND ($2::transaction_status IS NULL OR status = $2)
+              AND ($3::transaction_type IS NULL OR transaction_type = $3)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            status_filter as Option<TransactionStatus>,
+            transaction_type_filter as Option<TransactionType>,
+            limit,
+            offset
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Get user transactions newest first, continuing after `cursor` from a previous page
+    pub async fn get_user_transactions_after(
+        &self,
+        user_id: Uuid,
+        cursor: Option<&str>,
+        limit: i64,
+        status_filter: Option<TransactionStatus>,
+        transaction_type_filter: Option<TransactionType>,
+    ) -> Result<Page<Transaction>, StoreError> {
+        let (created_at, id) = cursor_bounds(cursor)?;
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions
+            WHERE user_id = $1
+              AND ($2::transaction_status IS NULL OR status = $2)
+              AND ($3::transaction_type IS NULL OR transaction_type = $3)
+              AND ($4::timestamptz IS NULL OR (created_at, id) < ($4, $5))
+            ORDER BY created_at DESC, id DESC
+            LIMIT $6
+            "#,
+            user_id,
+            status_filter as Option<TransactionStatus>,
+            transaction_type_filter as Option<TransactionType>,
+            created_at,
+            id,
+            fetch_limit(limit)?
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(Page::from_rows(transactions, limit, |tx| {
+            Cursor::new(tx.created_at, tx.id)
+        }))
+    }
+
+    /// Get one offset page of a user's transactions, newest first, with the total number of
+    /// matching rows. Both come from a single query so they always agree.
+    pub async fn get_user_transactions_page(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+        status_filter: Option<TransactionStatus>,
+        transaction_type_filter: Option<TransactionType>,
+    ) -> Result<(Vec<Transaction>, i64), StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at,
+                   COUNT(*) OVER () as "total!"
+            FROM transactions
+            WHERE user_id = $1
+              AND ($2::transaction_status IS NULL OR status = $2)
+              AND ($3::transaction_type IS NULL OR transaction_type = $3)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4 OFFSET $5
+            "#,
+            user_id,
+            status_filter.clone() as Option<TransactionStatus>,
+            transaction_type_filter.clone() as Option<TransactionType>,
+            limit,
+            offset
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        let rows: Vec<(Transaction, i64)> = rows
+            .into_iter()
+            .map(|row| {
+                let transaction = Transaction {
+                    id: row.id,
+                    user_id: row.user_id,
+                    tx_signature: row.tx_signature,
+                    transaction_type: row.transaction_type,
+                    status: row.status,
+                    amount: row.amount,
+                    token_mint: row.token_mint,
+                    from_address: row.from_address,
+                    to_address: row.to_address,
+                    fee: row.fee,
+                    failure_reason: row.failure_reason,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                };
+                (transaction, row.total)
+            })
+            .collect();
+
+        match split_total(rows) {
+            // Past the last row the window has nothing to report, so count separately
+            (transactions, None) if offset > 0 => {
+                let total = self
+                    .count_user_transactions(user_id, status_filter, transaction_type_filter)
+                    .await?;
+                Ok((transactions, total))
+            }
+            (transactions, total) => Ok((transactions, total.unwrap_or(0))),
+        }
+    }
+
+    /// Get a user's transactions created within `range`, oldest first (statements, reconciliation)
+    pub async fn get_transactions_in_range(
+        &self,
+        user_id: Uuid,
+        range: TimeRange,
+        status_filter: Option<TransactionStatus>,
+        transaction_type_filter: Option<TransactionType>,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions
+            WHERE user_id = $1
+              AND ($2::transaction_status IS NULL OR status = $2)
+              AND ($3::transaction_type IS NULL OR transaction_type = $3)
+              AND ($4::timestamptz IS NULL OR created_at >= $4)
+              AND ($5::timestamptz IS NULL OR created_at < $5)
+            ORDER BY created_at, id
+            LIMIT $6
+            "#,
+            user_id,
+            status_filter as Option<TransactionStatus>,
+            transaction_type_filter as Option<TransactionType>,
+            range.from,
+            range.to,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Get pending transactions (for processing)
+    pub async fn get_pending_transactions(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions 
+            WHERE status = $1
+            ORDER BY created_at ASC 
+            LIMIT $2
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Get pending transactions of one type, oldest first, without claiming them
+    pub async fn get_pending_transactions_by_type(
+        &self,
+        transaction_type: TransactionType,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions
+            WHERE status = $1 AND transaction_type = $2
+            ORDER BY created_at ASC
+            LIMIT $3
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            transaction_type as TransactionType,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Claim up to `limit` pending transactions of one type for this worker, oldest first.
+    /// Rows another worker is claiming right now are skipped rather than waited on, and a claim
+    /// expires after `CLAIM_LEASE` so a crashed worker's rows are picked up again.
+    pub async fn claim_pending_transactions(
+        &self,
+        transaction_type: TransactionType,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let now = Utc::now();
+        let mut transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            WITH claimable AS (
+                SELECT id FROM transactions
+                WHERE status = $1 AND transaction_type = $2
+                  AND (claimed_at IS NULL OR claimed_at < $3)
+                ORDER BY created_at ASC
+                LIMIT $4
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE transactions t SET claimed_at = $5
+            FROM claimable
+            WHERE t.id = claimable.id
+            RETURNING t.id, t.user_id, t.tx_signature, t.transaction_type as "transaction_type: TransactionType",
+                      t.status as "status: TransactionStatus", t.amount, t.token_mint, t.from_address, t.to_address, t.fee, t.failure_reason, t.created_at, t.updated_at
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            transaction_type as TransactionType,
+            claim_cutoff(now),
+            limit,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // RETURNING doesn't preserve the claim order
+        transactions.sort_by_key(|transaction| (transaction.created_at, transaction.id));
+        Ok(transactions)
+    }
+
+    /// Count pending transactions per type, so processors can monitor their backlog.
+    /// Every type is listed, with zero when nothing of that type is pending.
+    pub async fn get_pending_count_by_type(
+        &self,
+    ) -> Result<Vec<(TransactionType, i64)>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT types.transaction_type as "transaction_type!: TransactionType", COUNT(t.id) as "count!"
+            FROM UNNEST(ENUM_RANGE(NULL::transaction_type)) AS types(transaction_type)
+            LEFT JOIN transactions t ON t.transaction_type = types.transaction_type AND t.status = $1
+            GROUP BY types.transaction_type
+            ORDER BY types.transaction_type
+            "#,
+            TransactionStatus::Pending as TransactionStatus
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.transaction_type, row.count))
+            .collect())
+    }
+
+    /// Count all transactions per status, for the ops health panel
+    pub async fn get_transaction_status_counts(
+        &self,
+    ) -> Result<Vec<(TransactionStatus, i64)>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT status as "status: TransactionStatus", COUNT(*) as "count!"
+            FROM transactions
+            GROUP BY status
+            ORDER BY status
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.status, row.count))
+            .collect())
+    }
+
+    /// Get transactions that were submitted on-chain but not yet confirmed (for reconciliation)
+    pub async fn get_submitted_unconfirmed_transactions(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions 
+            WHERE status = $1 AND tx_signature IS NOT NULL
+            ORDER BY created_at ASC 
+            LIMIT $2
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Attach the signature of a signed withdrawal and the block height its blockhash expires at,
+    /// before it is sent
+    pub async fn mark_withdrawal_submitted(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: &str,
+        last_valid_block_height: u64,
+    ) -> Result<(), StoreError> {
+        let last_valid_block_height = i64::try_from(last_valid_block_height)
+            .map_err(|_| StoreError::InvalidInput("Block height out of range".to_string()))?;
+        let updated_rows = sqlx::query!(
+            "UPDATE transactions SET tx_signature = $1, last_valid_block_height = $2, updated_at = $3
+             WHERE id = $4 AND status = $5 AND transaction_type = $6",
+            tx_signature,
+            last_valid_block_height,
+            Utc::now(),
+            transaction_id,
+            TransactionStatus::Pending as TransactionStatus,
+            TransactionType::Withdrawal as TransactionType
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if updated_rows == 0 {
+            return Err(StoreError::InvalidInput(
+                "Pending withdrawal not found".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Get a page of withdrawals sent on chain but still pending, last touched before `idle_since`,
+    /// oldest first. Pass the last row of a page as `after` to continue past rows left unresolved.
+    pub async fn get_submitted_withdrawals(
+        &self,
+        idle_since: DateTime<Utc>,
+        after: Option<Cursor>,
+        limit: i64,
+    ) -> Result<Vec<SubmittedWithdrawal>, StoreError> {
+        let withdrawals = sqlx::query_as!(
+            SubmittedWithdrawal,
+            r#"
+            SELECT id, tx_signature as "tx_signature!", last_valid_block_height, created_at
+            FROM transactions
+            WHERE status = $1 AND transaction_type = $2 AND tx_signature IS NOT NULL
+              AND updated_at < $3
+              AND ($4::timestamptz IS NULL OR (created_at, id) > ($4, $5))
+            ORDER BY created_at, id
+            LIMIT $6
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            TransactionType::Withdrawal as TransactionType,
+            idle_since,
+            after.map(|cursor| cursor.created_at),
+            after.map(|cursor| cursor.id),
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(withdrawals)
+    }
+
+    /// Get the most recent transactions across all users with the owner's email (admin feed)
+    pub async fn get_recent_transactions(
+        &self,
+        limit: i64,
+        status_filter: Option<TransactionStatus>,
+    ) -> Result<Vec<(Transaction, String)>, StoreError> {
+        let transactions = sqlx::query!(
+            r#"
+            SELECT t.id, t.user_id, t.tx_signature, t.transaction_type as "transaction_type: TransactionType",
+                   t.status as "status: TransactionStatus", t.amount, t.token_mint, t.from_address, t.to_address, t.fee, t.failure_reason,
+                   t.created_at, t.updated_at, u.email
+            FROM transactions t
+            JOIN users u ON u.id = t.user_id
+            WHERE $1::transaction_status IS NULL OR t.status = $1
+            ORDER BY t.created_at DESC
+            LIMIT $2
+            "#,
+            status_filter as Option<TransactionStatus>,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| {
+            (
+                Transaction {
+                    id: row.id,
+                    user_id: row.user_id,
+                    tx_signature: row.tx_signature,
+                    transaction_type: row.transaction_type,
+                    status: row.status,
+                    amount: row.amount,
+                    token_mint: row.token_mint,
+                    from_address: row.from_address,
+                    to_address: row.to_address,
+                    fee: row.fee,
+                    failure_reason: row.failure_reason,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                },
+                row.email,
+            )
+        })
+        .collect();
+
+        Ok(transactions)
+    }
+
+    /// Count user transactions
+    pub async fn count_user_transactions(
+        &self,
+        user_id: Uuid,
+        status_filter: Option<TransactionStatus>,
+        transaction_type_filter: Option<TransactionType>,
+    ) -> Result<i64, StoreError> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM transactions
+            WHERE user_id = $1
+              AND ($2::transaction_status IS NULL OR status = $2)
+              AND ($3::transaction_type IS NULL OR transaction_type = $3)
+            "#,
+            user_id,
+            status_filter as Option<TransactionStatus>,
+            transaction_type_filter as Option<TransactionType>
+        )
+        .fetch_one(self.reader())
+        .await?;
+
         Ok(count)
     }
 
@@ -289,7 +1170,7 @@ impl Store {
     pub async fn get_transaction_stats(&self) -> Result<(i64, i64, i64, Decimal), StoreError> {
         // Total transactions, pending, failed, total volume
         let total_transactions = sqlx::query_scalar!("SELECT COUNT(*) FROM transactions")
-            .fetch_one(&self.pool)
+            .fetch_one(self.reader())
             .await?
             .unwrap_or(0);
 
@@ -297,7 +1178,7 @@ impl Store {
             "SELECT COUNT(*) FROM transactions WHERE status = $1",
             TransactionStatus::Pending as TransactionStatus
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.reader())
         .await?
         .unwrap_or(0);
 
@@ -305,7 +1186,7 @@ impl Store {
             "SELECT COUNT(*) FROM transactions WHERE status = $1",
             TransactionStatus::Failed as TransactionStatus
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.reader())
         .await?
         .unwrap_or(0);
 
@@ -313,7 +1194,7 @@ impl Store {
             "SELECT COALESCE(SUM(amount), 0) FROM transactions WHERE status = $1",
             TransactionStatus::Confirmed as TransactionStatus
         )
-        .fetch_one(&self.pool)
+        .fetch_one(self.reader())
         .await?
         .unwrap_or(Decimal::ZERO);
 
@@ -325,11 +1206,20 @@ impl Store {
         ))
     }
 
-    /// Process a deposit transaction (updates balance and transaction status)
+    /// Process a deposit transaction (updates balance and transaction status), retrying
+    /// transient conflicts
     pub async fn process_deposit(
         &self,
         transaction_id: Uuid,
         tx_signature: String,
+    ) -> Result<(), StoreError> {
+        retry_on_conflict(|| self.process_deposit_once(transaction_id, tx_signature.clone())).await
+    }
+
+    async fn process_deposit_once(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: String,
     ) -> Result<(), StoreError> {
         // Use transaction for atomic operation
         let mut tx = self.pool.begin().await?;
@@ -339,6 +1229,7 @@ impl Store {
             r#"
             SELECT user_id, amount, token_mint, transaction_type as "transaction_type: TransactionType"
             FROM transactions WHERE id = $1 AND status = $2
+            FOR UPDATE
             "#,
             transaction_id,
             TransactionStatus::Pending as TransactionStatus
@@ -354,36 +1245,13 @@ impl Store {
             ));
         }
 
-        // Update balances
-        if let Some(token_mint) = transaction.token_mint {
-            // Token deposit - update token balance
-            sqlx::query!(
-                r#"
-                INSERT INTO token_balances (user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at)
-                VALUES ($1, $2, 'UNKNOWN', $3, 6, $4, $4)
-                ON CONFLICT (user_id, token_mint) 
-                DO UPDATE SET 
-                    balance = token_balances.balance + EXCLUDED.balance,
-                    updated_at = EXCLUDED.updated_at
-                "#,
-                transaction.user_id,
-                token_mint,
-                transaction.amount,
-                Utc::now()
-            )
-            .execute(&mut *tx)
-            .await?;
-        } else {
-            // SOL deposit - update user balance
-            sqlx::query!(
-                "UPDATE users SET balance = balance + $1, updated_at = $2 WHERE id = $3",
-                transaction.amount,
-                Utc::now(),
-                transaction.user_id
-            )
-            .execute(&mut *tx)
-            .await?;
-        }
+        let (asset, new_balance) = credit_deposit(
+            &mut tx,
+            transaction.user_id,
+            transaction.token_mint,
+            transaction.amount,
+        )
+        .await?;
 
         // Update transaction status
         sqlx::query!(
@@ -397,114 +1265,177 @@ impl Store {
         .await?;
 
         tx.commit().await?;
+
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            transaction.user_id,
+            "system",
+            "deposit",
+            asset,
+            new_balance - transaction.amount,
+            new_balance,
+            Some(transaction_id),
+        )]);
         Ok(())
     }
 
-    /// Process a withdrawal transaction (updates balance and transaction status)
-    pub async fn process_withdrawal(
+    /// Record a deposit seen on chain for the wallet `agg_pubkey` and credit the balance. Keyed by
+    /// signature through `upsert_transaction_by_signature`, so a repeated observation returns the
+    /// original row (flag false) without crediting again; one left pending is credited now.
+    pub async fn record_observed_deposit(
         &self,
-        transaction_id: Uuid,
-        tx_signature: String,
-    ) -> Result<(), StoreError> {
-        // Use transaction for atomic operation
-        let mut tx = self.pool.begin().await?;
-
-        // Get transaction details
-        let transaction = sqlx::query!(
-            r#"
-            SELECT user_id, amount, token_mint, transaction_type as "transaction_type: TransactionType"
-            FROM transactions WHERE id = $1 AND status = $2
-            "#,
-            transaction_id,
-            TransactionStatus::Pending as TransactionStatus
-        )
-        .fetch_optional(&mut *tx)
-        .await?
-        .ok_or(StoreError::InvalidInput("Pending transaction not found".to_string()))?;
+        agg_pubkey: &str,
+        amount: Decimal,
+        token_mint: Option<String>,
+        tx_signature: &str,
+    ) -> Result<(Transaction, bool), StoreError> {
+        let user = self.get_user_by_agg_pubkey(agg_pubkey).await?;
+        let (transaction, _) = self
+            .upsert_transaction_by_signature(
+                user.id,
+                tx_signature,
+                TransactionType::Deposit,
+                amount,
+                token_mint,
+                None,
+                Some(agg_pubkey.to_string()),
+                None,
+            )
+            .await?;
 
-        // Verify it's a withdrawal transaction
-        if !matches!(transaction.transaction_type, TransactionType::Withdrawal) {
-            return Err(StoreError::InvalidInput(
-                "Transaction is not a withdrawal".to_string(),
-            ));
+        // Already credited by an earlier observation, or recorded as something else
+        if !matches!(transaction.transaction_type, TransactionType::Deposit)
+            || !matches!(transaction.status, TransactionStatus::Pending)
+        {
+            return Ok((transaction, false));
         }
 
-        // Check and update balances
-        if let Some(token_mint) = transaction.token_mint {
-            // Token withdrawal - check and update token balance
-            let current_balance = sqlx::query_scalar!(
-                "SELECT balance FROM token_balances WHERE user_id = $1 AND token_mint = $2",
-                transaction.user_id,
-                token_mint
-            )
-            .fetch_optional(&mut *tx)
-            .await?
-            .unwrap_or(Decimal::ZERO);
-
-            if current_balance < transaction.amount {
-                return Err(StoreError::InsufficientBalance);
+        // Credits and confirms in one DB transaction, and only while the row is still pending
+        if let Err(e) = self
+            .process_deposit(transaction.id, tx_signature.to_string())
+            .await
+        {
+            // A concurrent observation may have credited it first
+            let current = self.get_transaction(transaction.id).await?;
+            if matches!(current.status, TransactionStatus::Confirmed) {
+                return Ok((current, false));
             }
+            return Err(e);
+        }
 
-            sqlx::query!(
-                "UPDATE token_balances SET balance = balance - $1, updated_at = $2 WHERE user_id = $3 AND token_mint = $4",
-                transaction.amount,
-                Utc::now(),
-                transaction.user_id,
-                token_mint
-            )
-            .execute(&mut *tx)
-            .await?;
-        } else {
-            // SOL withdrawal - check and update user balance
-            let current_balance = sqlx::query_scalar!(
-                "SELECT balance FROM users WHERE id = $1",
-                transaction.user_id
-            )
-            .fetch_one(&mut *tx)
-            .await?;
+        Ok((self.get_transaction(transaction.id).await?, true))
+    }
 
-            if current_balance < transaction.amount {
-                return Err(StoreError::InsufficientBalance);
-            }
+    /// Process a withdrawal transaction (updates balance and transaction status), retrying
+    /// transient conflicts
+    pub async fn process_withdrawal(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: String,
+    ) -> Result<(), StoreError> {
+        retry_on_conflict(|| self.process_withdrawal_once(transaction_id, tx_signature.clone()))
+            .await
+    }
 
-            sqlx::query!(
-                "UPDATE users SET balance = balance - $1, updated_at = $2 WHERE id = $3",
-                transaction.amount,
-                Utc::now(),
-                transaction.user_id
-            )
-            .execute(&mut *tx)
+    async fn process_withdrawal_once(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: String,
+    ) -> Result<(), StoreError> {
+        // Use transaction for atomic operation
+        let mut tx = self.pool.begin().await?;
+        let withdrawal = lock_pending_withdrawal(&mut tx, transaction_id).await?;
+
+        // Re-checked here in case the whitelist changed while the withdrawal was pending
+        self.check_withdrawal_whitelist(withdrawal.user_id, withdrawal.to_address.as_deref())
             .await?;
-        }
 
-        // Update transaction status
-        sqlx::query!(
-            "UPDATE transactions SET status = $1, tx_signature = $2, updated_at = $3 WHERE id = $4",
-            TransactionStatus::Confirmed as TransactionStatus,
-            tx_signature,
-            Utc::now(),
-            transaction_id
+        self.check_min_transfer_amount(withdrawal.amount)?;
+
+        // Funds can only leave custody once the user's MPC wallet is fully set up. Locking the user
+        // row serializes concurrent withdrawals for this user through the balance check below.
+        let agg_pubkey = sqlx::query_scalar!(
+            "SELECT agg_pubkey FROM users WHERE id = $1 FOR UPDATE",
+            withdrawal.user_id
         )
-        .execute(&mut *tx)
+        .fetch_one(&mut *tx)
         .await?;
+        if agg_pubkey.is_none() {
+            return Err(StoreError::MpcSetupIncomplete(
+                "User has no aggregated public key".to_string(),
+            ));
+        }
+        if !has_sufficient_keyshares(&mut tx, withdrawal.user_id, None).await? {
+            return Err(StoreError::MpcSetupIncomplete(
+                "User does not have enough keyshares to sign".to_string(),
+            ));
+        }
+
+        let (asset, new_balance) =
+            debit_withdrawal(&mut tx, transaction_id, &withdrawal, &tx_signature).await?;
+        tx.commit().await?;
+
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            withdrawal.user_id,
+            "system",
+            "withdrawal",
+            asset,
+            new_balance + withdrawal.amount,
+            new_balance,
+            Some(transaction_id),
+        )]);
+        Ok(())
+    }
+
+    /// Debit a withdrawal that has already landed on chain and mark it confirmed, retrying
+    /// transient conflicts. The policy checks `process_withdrawal` applies ran before the transfer
+    /// was signed and are not repeated: the funds have left custody, so a whitelist change since
+    /// must not leave the transfer undebited.
+    pub async fn record_withdrawal_debit(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: String,
+    ) -> Result<(), StoreError> {
+        retry_on_conflict(|| self.record_withdrawal_debit_once(transaction_id, &tx_signature)).await
+    }
 
+    async fn record_withdrawal_debit_once(
+        &self,
+        transaction_id: Uuid,
+        tx_signature: &str,
+    ) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+        let withdrawal = lock_pending_withdrawal(&mut tx, transaction_id).await?;
+
+        let (asset, new_balance) =
+            debit_withdrawal(&mut tx, transaction_id, &withdrawal, tx_signature).await?;
         tx.commit().await?;
+
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            withdrawal.user_id,
+            "system",
+            "withdrawal",
+            asset,
+            new_balance + withdrawal.amount,
+            new_balance,
+            Some(transaction_id),
+        )]);
         Ok(())
     }
 
-    /// Mark transaction as failed
+    /// Mark a pending transaction as failed. A row that already settled is left as it is and
+    /// reported as not found, so a late failure can't undo a recorded debit or credit.
     pub async fn fail_transaction(
         &self,
         transaction_id: Uuid,
         reason: Option<String>,
     ) -> Result<(), StoreError> {
-        // For failed transactions, we might want to store the failure reason
-        // For now, we'll just update the status
         let updated_rows = sqlx::query!(
-            "UPDATE transactions SET status = $1, updated_at = $2 WHERE id = $3",
+            "UPDATE transactions SET status = $1, failure_reason = $2, updated_at = $3 WHERE id = $4 AND status = $5",
             TransactionStatus::Failed as TransactionStatus,
+            reason,
             Utc::now(),
-            transaction_id
+            transaction_id,
+            TransactionStatus::Pending as TransactionStatus
         )
         .execute(&self.pool)
         .await?
@@ -512,13 +1443,139 @@ impl Store {
 
         if updated_rows == 0 {
             return Err(StoreError::InvalidInput(
-                "Transaction not found".to_string(),
+                "Pending transaction not found".to_string(),
             ));
         }
 
         Ok(())
     }
 
+    /// Fail every pending transaction of a user (e.g. on account suspension), returning how many were failed.
+    /// Balances are only debited when a withdrawal is processed, so failing a pending row just releases its hold.
+    /// Rows that already carry a signature may still land on chain, so they are left for settlement to resolve.
+    pub async fn fail_all_pending_for_user(
+        &self,
+        user_id: Uuid,
+        reason: &str,
+    ) -> Result<u64, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Make sure the user exists and lock the row for the duration of the update
+        sqlx::query!("SELECT id FROM users WHERE id = $1 FOR UPDATE", user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(StoreError::UserNotFound)?;
+
+        let failed = sqlx::query!(
+            "UPDATE transactions SET status = $1, failure_reason = $2, updated_at = $3
+             WHERE user_id = $4 AND status = $5 AND tx_signature IS NULL",
+            TransactionStatus::Failed as TransactionStatus,
+            reason,
+            Utc::now(),
+            user_id,
+            TransactionStatus::Pending as TransactionStatus
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(failed)
+    }
+
+    /// Move confirmed transactions created before `cutoff` into `transactions_archive`, returning how many moved
+    pub async fn archive_transactions_before(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<u64, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Delete and copy in one statement so a row can't be confirmed-and-archived twice
+        let archived_count = sqlx::query!(
+            r#"
+            WITH moved AS (
+                DELETE FROM transactions WHERE status = $1 AND created_at < $2
+                RETURNING id, user_id, tx_signature, transaction_type, status, amount, token_mint,
+                          from_address, to_address, fee, created_at, updated_at, swap_id, rate,
+                          failure_reason, idempotency_key, confirmed_slot, claimed_at,
+                          last_valid_block_height
+            )
+            INSERT INTO transactions_archive (
+                id, user_id, tx_signature, transaction_type, status, amount, token_mint,
+                from_address, to_address, fee, created_at, updated_at, swap_id, rate,
+                failure_reason, idempotency_key, confirmed_slot, claimed_at,
+                last_valid_block_height, archived_at
+            )
+            SELECT id, user_id, tx_signature, transaction_type, status, amount, token_mint,
+                   from_address, to_address, fee, created_at, updated_at, swap_id, rate,
+                   failure_reason, idempotency_key, confirmed_slot, claimed_at,
+                   last_valid_block_height, $3
+            FROM moved
+            "#,
+            TransactionStatus::Confirmed as TransactionStatus,
+            cutoff,
+            Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(archived_count)
+    }
+
+    /// Get a user's archived transactions, newest first
+    pub async fn get_archived_transactions(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<Transaction>, StoreError> {
+        let transactions = sqlx::query_as!(
+            Transaction,
+            r#"
+            SELECT id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                   status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+            FROM transactions_archive
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(transactions)
+    }
+
+    /// Estimate the total fee cost of a batch of pending transactions, e.g. before a node processes
+    /// withdrawals. Transactions with a zero stored fee count as `DEFAULT_FEE_ESTIMATE`.
+    pub async fn estimate_pending_fees(
+        &self,
+        transaction_ids: Vec<Uuid>,
+    ) -> Result<Decimal, StoreError> {
+        let fees = sqlx::query_scalar!(
+            "SELECT fee FROM transactions WHERE id = ANY($1) AND status = $2",
+            &transaction_ids,
+            TransactionStatus::Pending as TransactionStatus
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Duplicated ids match a single row, so compare against the distinct count
+        let requested = transaction_ids.iter().collect::<HashSet<_>>().len();
+        if fees.len() != requested {
+            return Err(StoreError::InvalidInput(format!(
+                "{} of {} transactions are missing or no longer pending",
+                requested - fees.len(),
+                requested
+            )));
+        }
+
+        Ok(estimated_fee_total(fees))
+    }
+
     /// Calculate user's total transaction fees
     pub async fn get_user_total_fees(&self, user_id: Uuid) -> Result<Decimal, StoreError> {
         let total_fees = sqlx::query_scalar!(
@@ -532,4 +1589,1977 @@ impl Store {
 
         Ok(total_fees)
     }
+
+    /// Sum fees collected on confirmed transactions within [start, end), optionally for one token
+    pub async fn sum_platform_fees(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        token_mint: Option<String>,
+    ) -> Result<Decimal, StoreError> {
+        let total_fees = match token_mint {
+            Some(token_mint) => sqlx::query_scalar!(
+                "SELECT COALESCE(SUM(fee), 0) FROM transactions
+                 WHERE status = $1 AND created_at >= $2 AND created_at < $3 AND token_mint = $4",
+                TransactionStatus::Confirmed as TransactionStatus,
+                start,
+                end,
+                token_mint
+            )
+            .fetch_one(&self.pool)
+            .await?
+            .unwrap_or(Decimal::ZERO),
+            None => sqlx::query_scalar!(
+                "SELECT COALESCE(SUM(fee), 0) FROM transactions
+                 WHERE status = $1 AND created_at >= $2 AND created_at < $3",
+                TransactionStatus::Confirmed as TransactionStatus,
+                start,
+                end
+            )
+            .fetch_one(&self.pool)
+            .await?
+            .unwrap_or(Decimal::ZERO),
+        };
+
+        Ok(total_fees)
+    }
+
+    /// Fail pending transactions created more than `older_than` ago with reason `"timeout"`, returning how many expired.
+    /// Rows that already carry a signature may still land on chain, so they are left alone unless `include_signed`.
+    pub async fn expire_stale_transactions(
+        &self,
+        older_than: Duration,
+        include_signed: bool,
+    ) -> Result<u64, StoreError> {
+        let cutoff = Utc::now() - older_than;
+
+        let expired = sqlx::query!(
+            r#"
+            UPDATE transactions
+            SET status = $1, failure_reason = $2, updated_at = $3
+            WHERE status = $4 AND created_at < $5 AND ($6 OR tx_signature IS NULL)
+            "#,
+            TransactionStatus::Failed as TransactionStatus,
+            EXPIRED_FAILURE_REASON,
+            Utc::now(),
+            TransactionStatus::Pending as TransactionStatus,
+            cutoff,
+            include_signed
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(expired)
+    }
+
+    /// Get users with pending transactions older than the cutoff, with a count of those transactions
+    pub async fn get_users_with_stale_pending(
+        &self,
+        older_than: Duration,
+    ) -> Result<Vec<(User, i64)>, StoreError> {
+        let cutoff = Utc::now() - older_than;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT u.id, u.email, u.agg_pubkey, u.balance, u.created_at, u.updated_at,
+                   COUNT(t.id) as "stale_count!"
+            FROM users u
+            JOIN transactions t ON t.user_id = u.id
+            WHERE t.status = $1 AND t.created_at < $2
+            GROUP BY u.id
+            ORDER BY COUNT(t.id) DESC
+            "#,
+            TransactionStatus::Pending as TransactionStatus,
+            cutoff
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let users = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    User {
+                        id: row.id,
+                        email: row.email,
+                        agg_pubkey: row.agg_pubkey,
+                        balance: row.balance,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                    row.stale_count,
+                )
+            })
+            .collect();
+
+        Ok(users)
+    }
+
+    /// Get a user's ledger entries, newest first, each with the balance after it. `token_mint = None`
+    /// selects the SOL ledger. Entries are the same ones `verify_balance_integrity` sums: confirmed
+    /// transactions (archived included), where deposits credit and everything else debits the
+    /// amount, and balance adjustments, so the newest running balance is the stored balance.
+    pub async fn get_user_statement(
+        &self,
+        user_id: Uuid,
+        token_mint: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<StatementEntry>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id AS "id!", user_id AS "user_id!", tx_signature, transaction_type AS "transaction_type: TransactionType",
+                   status AS "status: TransactionStatus", amount AS "amount!", token_mint, from_address, to_address,
+                   fee AS "fee!", failure_reason, created_at AS "created_at!", updated_at AS "updated_at!", operation,
+                   delta AS "delta!", SUM(delta) OVER (ORDER BY created_at, id) AS "running_balance!"
+            FROM (
+                SELECT id, user_id, tx_signature, transaction_type, status, amount, token_mint, from_address, to_address,
+                       fee, failure_reason, created_at, updated_at, NULL::varchar AS operation,
+                       CASE WHEN transaction_type = 'deposit' THEN amount ELSE -amount END AS delta
+                FROM transactions
+                WHERE user_id = $1 AND status = $2 AND token_mint IS NOT DISTINCT FROM $3
+                UNION ALL
+                SELECT id, user_id, tx_signature, transaction_type, status, amount, token_mint, from_address, to_address,
+                       fee, failure_reason, created_at, updated_at, NULL,
+                       CASE WHEN transaction_type = 'deposit' THEN amount ELSE -amount END
+                FROM transactions_archive
+                WHERE user_id = $1 AND status = $2 AND token_mint IS NOT DISTINCT FROM $3
+                UNION ALL
+                SELECT id, user_id, NULL, NULL, NULL, ABS(amount), token_mint, NULL, NULL,
+                       0, NULL, created_at, created_at, operation, amount
+                FROM balance_adjustments
+                WHERE user_id = $1 AND token_mint IS NOT DISTINCT FROM $3
+            ) ledger
+            ORDER BY created_at DESC, id DESC
+            LIMIT $4
+            "#,
+            user_id,
+            TransactionStatus::Confirmed as TransactionStatus,
+            token_mint,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let entries = rows
+            .into_iter()
+            .map(|row| {
+                // Adjustment rows carry no transaction type or status
+                let transaction = match (row.transaction_type, row.status) {
+                    (Some(transaction_type), Some(status)) => Some(Transaction {
+                        id: row.id,
+                        user_id: row.user_id,
+                        tx_signature: row.tx_signature,
+                        transaction_type,
+                        status,
+                        amount: row.amount,
+                        token_mint: row.token_mint,
+                        from_address: row.from_address,
+                        to_address: row.to_address,
+                        fee: row.fee,
+                        failure_reason: row.failure_reason,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    }),
+                    _ => None,
+                };
+                StatementEntry {
+                    transaction,
+                    operation: row.operation,
+                    delta: row.delta,
+                    running_balance: row.running_balance,
+                    occurred_at: row.created_at,
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    /// Atomically swap between a user's SOL balance and one of their token balances at `rate`
+    /// (units of the output asset per unit of the input asset). Exactly one of `from_mint` and
+    /// `to_mint` must be `None`, meaning SOL. Both legs are recorded as confirmed transactions
+    /// sharing a swap_id and the rate, so audits can reconstruct the conversion.
+    pub async fn internal_swap_with_rate(
+        &self,
+        user_id: Uuid,
+        from_mint: Option<String>,
+        to_mint: Option<String>,
+        amount: Decimal,
+        rate: Decimal,
+    ) -> Result<InternalSwap, StoreError> {
+        if amount <= Decimal::ZERO || rate <= Decimal::ZERO {
+            return Err(StoreError::InvalidInput(
+                "Amount and rate must be positive".to_string(),
+            ));
+        }
+        if from_mint.is_some() == to_mint.is_some() {
+            return Err(StoreError::InvalidInput(
+                "Swap must be between SOL and a token".to_string(),
+            ));
+        }
+
+        let output_amount = amount
+            .checked_mul(rate)
+            .ok_or_else(|| StoreError::InvalidInput("Swap amount overflows".to_string()))?;
+        let swap_id = Uuid::new_v4();
+
+        let mut tx = self.pool.begin().await?;
+
+        // Debit the input asset; funds held by pending withdrawals can't be swapped away
+        check_available_balance(&mut tx, user_id, from_mint.as_deref(), amount).await?;
+
+        let debited_balance = match &from_mint {
+            Some(mint) => {
+                sqlx::query_scalar!(
+                    "UPDATE token_balances SET balance = balance - $1, updated_at = $2 WHERE user_id = $3 AND token_mint = $4 RETURNING balance",
+                    amount,
+                    Utc::now(),
+                    user_id,
+                    mint
+                )
+                .fetch_one(&mut *tx)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!(
+                    "UPDATE users SET balance = balance - $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+                    amount,
+                    Utc::now(),
+                    user_id
+                )
+                .fetch_one(&mut *tx)
+                .await?
+            }
+        };
+
+        // Credit the output asset
+        let credited_balance = match &to_mint {
+            Some(mint) => {
+                let token_info = lookup_token_info(&mut tx, mint).await?;
+                sqlx::query_scalar!(
+                    r#"
+                    INSERT INTO token_balances (user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $6)
+                    ON CONFLICT (user_id, token_mint)
+                    DO UPDATE SET
+                        balance = token_balances.balance + EXCLUDED.balance,
+                        token_symbol = COALESCE(token_balances.token_symbol, EXCLUDED.token_symbol),
+                        decimals = COALESCE(token_balances.decimals, EXCLUDED.decimals),
+                        updated_at = EXCLUDED.updated_at
+                    RETURNING balance
+                    "#,
+                    user_id,
+                    mint,
+                    token_info.symbol,
+                    output_amount,
+                    token_info.decimals,
+                    Utc::now()
+                )
+                .fetch_one(&mut *tx)
+                .await?
+            }
+            None => {
+                sqlx::query_scalar!(
+                    "UPDATE users SET balance = balance + $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+                    output_amount,
+                    Utc::now(),
+                    user_id
+                )
+                .fetch_one(&mut *tx)
+                .await?
+            }
+        };
+
+        // Record both legs: the debit as a transfer out, the credit as a deposit in
+        let mut legs = Vec::with_capacity(2);
+        for (transaction_type, leg_amount, leg_mint) in [
+            (TransactionType::Transfer, amount, &from_mint),
+            (TransactionType::Deposit, output_amount, &to_mint),
+        ] {
+            let leg = sqlx::query_as!(
+                Transaction,
+                r#"
+                INSERT INTO transactions (user_id, transaction_type, status, amount, token_mint, fee, swap_id, rate, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, 0, $6, $7, $8, $8)
+                RETURNING id, user_id, tx_signature, transaction_type as "transaction_type: TransactionType",
+                          status as "status: TransactionStatus", amount, token_mint, from_address, to_address, fee, failure_reason, created_at, updated_at
+                "#,
+                user_id,
+                transaction_type as TransactionType,
+                TransactionStatus::Confirmed as TransactionStatus,
+                leg_amount,
+                leg_mint.as_deref(),
+                swap_id,
+                rate,
+                Utc::now()
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            legs.push(leg);
+        }
+
+        tx.commit().await?;
+
+        let asset_name =
+            |mint: &Option<String>| mint.clone().unwrap_or_else(|| SOL_ASSET.to_string());
+        self.audit_balance_changes(&[
+            BalanceAuditRecord::new(
+                user_id,
+                user_id.to_string(),
+                "swap",
+                asset_name(&from_mint),
+                debited_balance + amount,
+                debited_balance,
+                Some(swap_id),
+            ),
+            BalanceAuditRecord::new(
+                user_id,
+                user_id.to_string(),
+                "swap",
+                asset_name(&to_mint),
+                credited_balance - output_amount,
+                credited_balance,
+                Some(swap_id),
+            ),
+        ]);
+
+        let credit = legs.pop().expect("credit leg recorded");
+        let debit = legs.pop().expect("debit leg recorded");
+
+        Ok(InternalSwap {
+            swap_id,
+            rate,
+            debit,
+            credit,
+        })
+    }
+
+    /// Get pending withdrawals whose user holds an active keyshare on the given node, i.e. the signing
+    /// ceremonies that node must join, oldest first
+    pub async fn get_pending_withdrawals_for_node(
+        &self,
+        mpc_node_id: i32,
+        limit: i64,
+    ) -> Result<Vec<(Transaction, MpcKeyshare)>, StoreError> {
+        if mpc_node_id < 1 || mpc_node_id > 5 {
+            return Err(StoreError::InvalidInput("Invalid MPC node ID".to_string()));
+        }
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT t.id, t.user_id, t.tx_signature, t.transaction_type as "transaction_type: TransactionType",
+                   t.status as "status: TransactionStatus", t.amount, t.token_mint, t.from_address, t.to_address, t.fee, t.failure_reason,
+                   t.created_at, t.updated_at,
+                   k.id as keyshare_id, k.private_key_share, k.public_key, k.threshold, k.total_shares, k.active,
+                   k.created_at as keyshare_created_at, k.updated_at as keyshare_updated_at
+            FROM transactions t
+            JOIN mpc_keyshares k ON k.user_id = t.user_id AND k.mpc_node_id = $1 AND k.active
+            WHERE t.status = $2 AND t.transaction_type = $3
+            ORDER BY t.created_at, t.id
+            LIMIT $4
+            "#,
+            mpc_node_id,
+            TransactionStatus::Pending as TransactionStatus,
+            TransactionType::Withdrawal as TransactionType,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let withdrawals = rows
+            .into_iter()
+            .map(|row| {
+                (
+                    Transaction {
+                        id: row.id,
+                        user_id: row.user_id,
+                        tx_signature: row.tx_signature,
+                        transaction_type: row.transaction_type,
+                        status: row.status,
+                        amount: row.amount,
+                        token_mint: row.token_mint,
+                        from_address: row.from_address,
+                        to_address: row.to_address,
+                        fee: row.fee,
+                        failure_reason: row.failure_reason,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                    MpcKeyshare {
+                        id: row.keyshare_id,
+                        user_id: row.user_id,
+                        mpc_node_id,
+                        private_key_share: row.private_key_share,
+                        public_key: row.public_key,
+                        threshold: row.threshold,
+                        total_shares: row.total_shares,
+                        active: row.active,
+                        created_at: row.keyshare_created_at,
+                        updated_at: row.keyshare_updated_at,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(withdrawals)
+    }
+
+    /// Check that a user's stored SOL and token balances equal the sum of their confirmed
+    /// transactions (deposits credit, withdrawals and transfers debit), returning
+    /// `StoreError::LedgerMismatch` for the first asset that drifted
+    pub async fn verify_balance_integrity(&self, user_id: Uuid) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Read everything from one snapshot so in-flight transfers can't cause false alarms
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        let stored_sol = sqlx::query_scalar!("SELECT balance FROM users WHERE id = $1", user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(StoreError::UserNotFound)?;
+
+        // Archived rows are all confirmed and still part of the ledger, as are the movements
+        // (transfers, admin overrides, direct credits and debits) that write no transaction
+        let ledger = sqlx::query!(
+            r#"
+            SELECT token_mint, SUM(amount) as "expected!"
+            FROM (
+                SELECT token_mint, CASE WHEN transaction_type = 'deposit' THEN amount ELSE -amount END AS amount
+                FROM transactions WHERE user_id = $1 AND status = $2
+                UNION ALL
+                SELECT token_mint, CASE WHEN transaction_type = 'deposit' THEN amount ELSE -amount END AS amount
+                FROM transactions_archive WHERE user_id = $1 AND status = $2
+                UNION ALL
+                SELECT token_mint, amount FROM balance_adjustments WHERE user_id = $1
+            ) ledger
+            GROUP BY token_mint
+            "#,
+            user_id,
+            TransactionStatus::Confirmed as TransactionStatus
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let stored_tokens = sqlx::query!(
+            "SELECT token_mint, balance FROM token_balances WHERE user_id = $1",
+            user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        check_ledger(
+            stored_sol,
+            stored_tokens
+                .into_iter()
+                .map(|row| (row.token_mint, row.balance)),
+            ledger
+                .into_iter()
+                .map(|row| (row.token_mint, row.expected))
+                .collect(),
+        )
+    }
+}
+
+/// Fee assumed for a transaction with no stored fee: one signature at 5000 lamports
+pub const DEFAULT_FEE_ESTIMATE: Decimal = Decimal::from_parts(5000, 0, 0, false, 9);
+
+fn estimated_fee_total(fees: impl IntoIterator<Item = Decimal>) -> Decimal {
+    fees.into_iter()
+        .map(|fee| {
+            if fee.is_zero() {
+                DEFAULT_FEE_ESTIMATE
+            } else {
+                fee
+            }
+        })
+        .sum()
+}
+
+/// Shorter fragments would match most of the table
+const MIN_SEARCH_LEN: usize = 4;
+
+/// Escape LIKE wildcards in user input and turn it into a prefix pattern
+fn like_prefix_pattern(query: &str) -> String {
+    let mut pattern = String::with_capacity(query.len() + 1);
+    for c in query.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// How long a claim on a pending transaction holds before another worker may take it over
+pub const CLAIM_LEASE: Duration = Duration::minutes(5);
+
+/// Claims made before this instant have lapsed
+fn claim_cutoff(now: DateTime<Utc>) -> DateTime<Utc> {
+    now - CLAIM_LEASE
+}
+
+/// Compare stored balances against the ledger totals per asset (`None` for SOL), failing on the
+/// first drift, negative balance, or ledger activity for a token without a balance row
+fn check_ledger(
+    stored_sol: Decimal,
+    stored_tokens: impl IntoIterator<Item = (String, Decimal)>,
+    mut expected: HashMap<Option<String>, Decimal>,
+) -> Result<(), StoreError> {
+    let mut check = |asset: Option<String>, actual: Decimal| {
+        let expected = expected.remove(&asset).unwrap_or(Decimal::ZERO);
+        if expected != actual || actual < Decimal::ZERO {
+            return Err(StoreError::LedgerMismatch {
+                asset: asset.unwrap_or_else(|| SOL_ASSET.to_string()),
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    };
+
+    check(None, stored_sol)?;
+    for (token_mint, balance) in stored_tokens {
+        check(Some(token_mint), balance)?;
+    }
+
+    // Ledger activity for a token the user no longer has a balance row for
+    if let Some((asset, expected)) = expected.into_iter().find(|(_, amount)| !amount.is_zero()) {
+        return Err(StoreError::LedgerMismatch {
+            asset: asset.unwrap_or_else(|| SOL_ASSET.to_string()),
+            expected,
+            actual: Decimal::ZERO,
+        });
+    }
+
+    Ok(())
+}
+
+/// Separate rows carrying a `COUNT(*) OVER ()` total; `None` when the page came back empty
+pub(crate) fn split_total<T>(rows: Vec<(T, i64)>) -> (Vec<T>, Option<i64>) {
+    let total = rows.first().map(|(_, total)| *total);
+    (rows.into_iter().map(|(row, _)| row).collect(), total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CLAIM_LEASE, DEFAULT_FEE_ESTIMATE, EXPIRED_FAILURE_REASON, TimeRange, check_ledger,
+        estimated_fee_total, like_prefix_pattern, split_total, validate_address,
+    };
+    use crate::Store;
+    use crate::audit::SOL_ASSET;
+    use crate::pagination::Cursor;
+    use crate::testing::{insert_keyshare, insert_transaction, insert_user, set_agg_pubkey};
+    use crate::user::{StoreError, TransactionStatus, TransactionType};
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::collections::{HashMap, HashSet};
+    use uuid::Uuid;
+
+    async fn insert_pending_withdrawals(store: &Store, count: usize) {
+        let user_id = insert_user(store, Decimal::ZERO).await;
+        for _ in 0..count {
+            insert_transaction(
+                store,
+                user_id,
+                TransactionType::Withdrawal,
+                TransactionStatus::Pending,
+                Decimal::ONE,
+            )
+            .await;
+        }
+    }
+
+    /// Claim batches until the queue runs dry, returning every id this worker took
+    async fn drain_claims(store: &Store) -> Vec<Uuid> {
+        let mut mine = Vec::new();
+        loop {
+            let batch = store
+                .claim_pending_transactions(TransactionType::Withdrawal, 3)
+                .await
+                .unwrap();
+            if batch.is_empty() {
+                return mine;
+            }
+            mine.extend(batch.into_iter().map(|transaction| transaction.id));
+        }
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_concurrent_claimers_do_not_overlap(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        insert_pending_withdrawals(&store, 50).await;
+
+        let (first, second) = tokio::join!(drain_claims(&store), drain_claims(&store));
+
+        let first_ids: HashSet<_> = first.iter().collect();
+        assert!(second.iter().all(|id| !first_ids.contains(id)));
+        assert_eq!(first.len() + second.len(), 50);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_lapsed_claim_is_taken_over(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        insert_pending_withdrawals(&store, 2).await;
+        let claimed = store
+            .claim_pending_transactions(TransactionType::Withdrawal, 10)
+            .await
+            .unwrap();
+        assert_eq!(claimed.len(), 2);
+
+        // The first worker died holding both rows; only the lapsed claim is released
+        sqlx::query!(
+            "UPDATE transactions SET claimed_at = $1 WHERE id = $2",
+            Utc::now() - CLAIM_LEASE - Duration::seconds(1),
+            claimed[0].id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let retaken = store
+            .claim_pending_transactions(TransactionType::Withdrawal, 10)
+            .await
+            .unwrap();
+        assert_eq!(retaken.len(), 1);
+        assert_eq!(retaken[0].id, claimed[0].id);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_upsert_by_signature_yields_one_row(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let upsert = |user_id| {
+            store.upsert_transaction_by_signature(
+                user_id,
+                "sig-1",
+                TransactionType::Deposit,
+                Decimal::ONE,
+                None,
+                None,
+                None,
+                None,
+            )
+        };
+
+        let (first, inserted) = upsert(user_id).await.unwrap();
+        assert!(inserted);
+        assert!(matches!(first.status, TransactionStatus::Pending));
+
+        let (again, inserted) = upsert(user_id).await.unwrap();
+        assert!(!inserted);
+        assert_eq!(again.id, first.id);
+        let rows = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM transactions WHERE tx_signature = 'sig-1'"#
+        )
+        .fetch_one(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, 1);
+
+        // The signature already belongs to someone else
+        let other = insert_user(&store, Decimal::ZERO).await;
+        assert!(matches!(
+            upsert(other).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_duplicate_deposit_signature_credits_once(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let agg_pubkey = "11111111111111111111111111111112";
+        set_agg_pubkey(&store, user_id, agg_pubkey).await;
+        let amount = Decimal::new(15, 1);
+
+        let (first, inserted) = store
+            .record_observed_deposit(agg_pubkey, amount, None, "sig-1")
+            .await
+            .unwrap();
+        assert!(inserted);
+
+        // The repeated observation gets the original row back and credits nothing
+        let (again, inserted) = store
+            .record_observed_deposit(agg_pubkey, amount, None, "sig-1")
+            .await
+            .unwrap();
+        assert!(!inserted);
+        assert_eq!(again.id, first.id);
+        assert!(matches!(again.status, TransactionStatus::Confirmed));
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), amount);
+        let rows = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM transactions WHERE tx_signature = 'sig-1'"#
+        )
+        .fetch_one(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(rows, 1);
+
+        let (_, inserted) = store
+            .record_observed_deposit(agg_pubkey, amount, None, "sig-2")
+            .await
+            .unwrap();
+        assert!(inserted);
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            amount * Decimal::TWO
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_page_total_matches_matching_rows(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for _ in 0..7 {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Deposit,
+                TransactionStatus::Confirmed,
+                Decimal::ONE,
+            )
+            .await;
+        }
+        insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Pending,
+            Decimal::ONE,
+        )
+        .await;
+
+        let (rows, total) = store
+            .get_user_transactions_page(user_id, 3, 2, None, None)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(total, 8);
+
+        let (rows, total) = store
+            .get_user_transactions_page(user_id, 3, 6, None, Some(TransactionType::Deposit))
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(total, 7);
+
+        // Past the last row the total still comes back
+        let (rows, total) = store
+            .get_user_transactions_page(user_id, 3, 20, Some(TransactionStatus::Pending), None)
+            .await
+            .unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(total, 1);
+
+        assert_eq!(split_total::<u32>(vec![]), (vec![], None));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_withdrawal_needs_signing_threshold(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        set_agg_pubkey(&store, user_id, "11111111111111111111111111111112").await;
+        insert_keyshare(&store, user_id, 1).await;
+        let withdrawal = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Pending,
+            Decimal::ONE,
+        )
+        .await;
+
+        assert!(matches!(
+            store
+                .process_withdrawal(withdrawal, "sig-1".to_string())
+                .await,
+            Err(StoreError::MpcSetupIncomplete(_))
+        ));
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TEN);
+
+        insert_keyshare(&store, user_id, 2).await;
+        store
+            .process_withdrawal(withdrawal, "sig-1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::new(9, 0)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_concurrent_withdrawals_cannot_overdraw(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::from(5)).await;
+        set_agg_pubkey(&store, user_id, "11111111111111111111111111111112").await;
+        insert_keyshare(&store, user_id, 1).await;
+        insert_keyshare(&store, user_id, 2).await;
+        let mut withdrawals = Vec::new();
+        for _ in 0..2 {
+            withdrawals.push(
+                insert_transaction(
+                    &store,
+                    user_id,
+                    TransactionType::Withdrawal,
+                    TransactionStatus::Pending,
+                    Decimal::from(3),
+                )
+                .await,
+            );
+        }
+
+        let (first, second) = tokio::join!(
+            store.process_withdrawal(withdrawals[0], "sig-1".to_string()),
+            store.process_withdrawal(withdrawals[1], "sig-2".to_string()),
+        );
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results.iter().any(|result| matches!(
+            result,
+            Err(StoreError::InsufficientBalance { available, .. }) if *available == Decimal::TWO
+        )));
+
+        // The rejected withdrawal was rolled back and is still pending
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TWO);
+        let mut statuses = Vec::new();
+        for id in withdrawals {
+            statuses.push(store.get_transaction(id).await.unwrap().status);
+        }
+        assert!(
+            statuses
+                .iter()
+                .any(|status| matches!(status, TransactionStatus::Confirmed))
+        );
+        assert!(
+            statuses
+                .iter()
+                .any(|status| matches!(status, TransactionStatus::Pending))
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_slot_confirmation_skips_failed_rows(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let mut submitted = Vec::new();
+        for (status, signature) in [
+            (TransactionStatus::Pending, "sig-pending"),
+            (TransactionStatus::Failed, "sig-failed"),
+        ] {
+            let id = insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Withdrawal,
+                status,
+                Decimal::ONE,
+            )
+            .await;
+            sqlx::query!(
+                "UPDATE transactions SET tx_signature = $1 WHERE id = $2",
+                signature,
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+            submitted.push(id);
+        }
+
+        store
+            .confirm_transaction_at_slot(submitted[0], 42)
+            .await
+            .unwrap();
+        let confirmed = store.get_transaction(submitted[0]).await.unwrap();
+        assert!(matches!(confirmed.status, TransactionStatus::Confirmed));
+
+        assert!(matches!(
+            store.confirm_transaction_at_slot(submitted[1], 42).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+        let failed = store.get_transaction(submitted[1]).await.unwrap();
+        assert!(matches!(failed.status, TransactionStatus::Failed));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_archive_moves_confirmed_rows(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let confirmed = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::TWO,
+        )
+        .await;
+        let pending = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Pending,
+            Decimal::ONE,
+        )
+        .await;
+
+        let moved = store
+            .archive_transactions_before(Utc::now() + Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(moved, 1);
+
+        let archived = store.get_archived_transactions(user_id, 10).await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!(archived[0].id, confirmed);
+        assert_eq!(archived[0].amount, Decimal::TWO);
+        assert!(store.get_transaction(pending).await.is_ok());
+        assert!(store.get_transaction(confirmed).await.is_err());
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_recent_transactions_interleave_users(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let alice = insert_user(&store, Decimal::ZERO).await;
+        let bob = insert_user(&store, Decimal::ZERO).await;
+        let mut inserted = Vec::new();
+        for (user_id, status) in [
+            (alice, TransactionStatus::Confirmed),
+            (bob, TransactionStatus::Pending),
+            (alice, TransactionStatus::Pending),
+            (bob, TransactionStatus::Confirmed),
+        ] {
+            inserted.push(
+                insert_transaction(
+                    &store,
+                    user_id,
+                    TransactionType::Deposit,
+                    status,
+                    Decimal::ONE,
+                )
+                .await,
+            );
+        }
+
+        let feed = store.get_recent_transactions(10, None).await.unwrap();
+        let ids: Vec<Uuid> = feed.iter().map(|(tx, _)| tx.id).collect();
+        inserted.reverse();
+        assert_eq!(ids, inserted);
+        let alice_email = store.get_user(alice).await.unwrap().email;
+        assert_eq!(feed[1].1, alice_email);
+
+        let pending = store
+            .get_recent_transactions(10, Some(TransactionStatus::Pending))
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 2);
+
+        assert_eq!(
+            store
+                .count_user_transactions(alice, Some(TransactionStatus::Pending), None)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            store
+                .count_user_transactions(bob, None, Some(TransactionType::Deposit))
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_check_ledger_reports_drift() {
+        let mint = "mint".to_string();
+        let expected = HashMap::from([(None, Decimal::TEN), (Some(mint.clone()), Decimal::ONE)]);
+        assert!(
+            check_ledger(
+                Decimal::TEN,
+                [(mint.clone(), Decimal::ONE)],
+                expected.clone()
+            )
+            .is_ok()
+        );
+
+        match check_ledger(
+            Decimal::TWO,
+            [(mint.clone(), Decimal::ONE)],
+            expected.clone(),
+        ) {
+            Err(StoreError::LedgerMismatch {
+                asset,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(asset, SOL_ASSET);
+                assert_eq!(expected, Decimal::TEN);
+                assert_eq!(actual, Decimal::TWO);
+            }
+            other => panic!("expected drift, got {:?}", other),
+        }
+
+        // Ledger activity for a token whose balance row is gone
+        assert!(matches!(
+            check_ledger(Decimal::TEN, [], expected),
+            Err(StoreError::LedgerMismatch { asset, .. }) if asset == mint
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_movements_without_transactions_keep_ledger_balanced(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let alice = insert_user(&store, Decimal::ZERO).await;
+        let bob = insert_user(&store, Decimal::ZERO).await;
+        let mint = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+        store.add_user_balance(alice, Decimal::TEN).await.unwrap();
+        store
+            .transfer_sol(alice, bob, Decimal::new(3, 0))
+            .await
+            .unwrap();
+        store
+            .subtract_user_balance(bob, Decimal::ONE)
+            .await
+            .unwrap();
+        store
+            .update_user_balance(alice, Decimal::new(20, 0))
+            .await
+            .unwrap();
+        store
+            .update_token_balance(alice, mint, "USDC", Decimal::new(5, 0), 6)
+            .await
+            .unwrap();
+        store
+            .add_token_balance(alice, mint, Decimal::ONE)
+            .await
+            .unwrap();
+        store
+            .transfer_tokens(alice, bob, mint, Decimal::TWO)
+            .await
+            .unwrap();
+        store
+            .subtract_token_balance(bob, mint, Decimal::ONE)
+            .await
+            .unwrap();
+
+        store.verify_balance_integrity(alice).await.unwrap();
+        store.verify_balance_integrity(bob).await.unwrap();
+
+        // A write that bypasses the store is reported
+        sqlx::query!("UPDATE users SET balance = balance + 1 WHERE id = $1", bob)
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.verify_balance_integrity(bob).await,
+            Err(StoreError::LedgerMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_address() {
+        assert!(validate_address("to_address", Some("11111111111111111111111111111111")).is_ok());
+        assert!(validate_address("to_address", None).is_ok());
+        assert!(matches!(
+            validate_address("to_address", Some("not-a-pubkey")),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_like_prefix_pattern_signature_prefix() {
+        assert_eq!(like_prefix_pattern("5VERv8NMvzbJMEkV"), "5VERv8NMvzbJMEkV%");
+    }
+
+    #[test]
+    fn test_like_prefix_pattern_address() {
+        let address = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        assert_eq!(like_prefix_pattern(address), format!("{}%", address));
+    }
+
+    #[test]
+    fn test_like_prefix_pattern_escapes_wildcards() {
+        assert_eq!(like_prefix_pattern("ab%c_d\\"), "ab\\%c\\_d\\\\%");
+    }
+
+    #[test]
+    fn test_estimated_fee_total() {
+        let fees = vec![
+            Decimal::new(10_000, 9),
+            Decimal::new(25_000, 9),
+            Decimal::ZERO,
+        ];
+
+        // A zero fee falls back to the default estimate
+        assert_eq!(
+            estimated_fee_total(fees),
+            Decimal::new(35_000, 9) + DEFAULT_FEE_ESTIMATE
+        );
+        assert_eq!(estimated_fee_total(Vec::new()), Decimal::ZERO);
+        assert_eq!(DEFAULT_FEE_ESTIMATE, Decimal::new(5000, 9));
+    }
+
+    #[test]
+    fn test_time_range_bounds() {
+        let start = Utc::now();
+        let end = start + Duration::hours(1);
+        let range = TimeRange::new(Some(start), Some(end)).unwrap();
+
+        // Transactions at each timestamp: only those in [start, end) fall in the window
+        let created = [
+            start - Duration::seconds(1),
+            start,
+            start + Duration::minutes(30),
+            end - Duration::microseconds(1),
+            end,
+        ];
+        let inside: Vec<bool> = created.iter().map(|at| range.contains(*at)).collect();
+        assert_eq!(inside, vec![false, true, true, true, false]);
+
+        let open = TimeRange::new(None, Some(end)).unwrap();
+        assert!(open.contains(start - Duration::days(365)));
+        assert!(!open.contains(end));
+    }
+
+    #[test]
+    fn test_time_range_rejects_inverted_bounds() {
+        let now = Utc::now();
+        assert!(matches!(
+            TimeRange::new(Some(now), Some(now)),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(TimeRange::new(Some(now), None).is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_pending_withdrawals_hold_funds(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TWO).await;
+        let withdraw = |amount| {
+            store.create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                amount,
+                None,
+                None,
+                Some("11111111111111111111111111111112".to_string()),
+                None,
+                None,
+            )
+        };
+
+        let first = withdraw(Decimal::new(15, 1)).await.unwrap();
+        match withdraw(Decimal::ONE).await {
+            Err(StoreError::InsufficientBalance { available, .. }) => {
+                assert_eq!(available, Decimal::new(5, 1))
+            }
+            other => panic!("second withdrawal should be refused, got {:?}", other),
+        }
+
+        // Opening holds without debiting, and a failed withdrawal releases its hold
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TWO);
+        store.fail_transaction(first.id, None).await.unwrap();
+        assert!(withdraw(Decimal::ONE).await.is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_held_funds_cannot_be_moved_away(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::from(3)).await;
+        let other = insert_user(&store, Decimal::ZERO).await;
+        let withdrawal = store
+            .create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                Decimal::TWO,
+                None,
+                None,
+                Some("11111111111111111111111111111112".to_string()),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        match store.transfer_sol(user_id, other, Decimal::TWO).await {
+            Err(StoreError::InsufficientBalance { available, .. }) => {
+                assert_eq!(available, Decimal::ONE)
+            }
+            other => panic!("transfer of held funds should be refused, got {:?}", other),
+        }
+        assert!(matches!(
+            store.subtract_user_balance(user_id, Decimal::TWO).await,
+            Err(StoreError::InsufficientBalance { .. })
+        ));
+
+        // Whatever isn't held can still move, and the withdrawal is debited once it lands
+        store
+            .transfer_sol(user_id, other, Decimal::ONE)
+            .await
+            .unwrap();
+        store
+            .record_withdrawal_debit(withdrawal.id, "sig-held".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::ZERO
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_submitted_unconfirmed_needs_a_signature(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        let insert = |status| {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Withdrawal,
+                status,
+                Decimal::ONE,
+            )
+        };
+        let newer = insert(TransactionStatus::Pending).await;
+        let older = insert(TransactionStatus::Pending).await;
+        let unsigned = insert(TransactionStatus::Pending).await;
+        let confirmed = insert(TransactionStatus::Confirmed).await;
+        for (id, signature, age_minutes) in [
+            (newer, "sig-newer", 1),
+            (older, "sig-older", 10),
+            (confirmed, "sig-confirmed", 20),
+        ] {
+            sqlx::query!(
+                "UPDATE transactions SET tx_signature = $1, created_at = $2 WHERE id = $3",
+                signature,
+                Utc::now() - Duration::minutes(age_minutes),
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let submitted = store
+            .get_submitted_unconfirmed_transactions(10)
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = submitted.iter().map(|transaction| transaction.id).collect();
+        assert_eq!(ids, vec![older, newer]);
+        assert!(!ids.contains(&unsigned));
+
+        let first = store
+            .get_submitted_unconfirmed_transactions(1)
+            .await
+            .unwrap();
+        assert_eq!(first[0].id, older);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_sum_platform_fees_within_window(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let day_one = Utc::now() - Duration::days(2);
+        let day_two = day_one + Duration::days(1);
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+        for (status, fee, token_mint, created_at) in [
+            (TransactionStatus::Confirmed, "0.1", None, day_one),
+            (TransactionStatus::Confirmed, "0.2", Some(usdc), day_one),
+            // Failed transactions collect no fee
+            (TransactionStatus::Failed, "5", None, day_one),
+            (TransactionStatus::Confirmed, "0.4", None, day_two),
+        ] {
+            let id = insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Transfer,
+                status,
+                Decimal::ONE,
+            )
+            .await;
+            sqlx::query!(
+                "UPDATE transactions SET fee = $1, token_mint = $2, created_at = $3 WHERE id = $4",
+                fee.parse::<Decimal>().unwrap(),
+                token_mint,
+                created_at,
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let fees = |start, end, token_mint: Option<&str>| {
+            store.sum_platform_fees(start, end, token_mint.map(str::to_string))
+        };
+        assert_eq!(
+            fees(day_one, day_two, None).await.unwrap(),
+            Decimal::new(3, 1)
+        );
+        assert_eq!(
+            fees(day_one, day_two, Some(usdc)).await.unwrap(),
+            Decimal::new(2, 1)
+        );
+        assert_eq!(
+            fees(day_one, day_two + Duration::days(1), None)
+                .await
+                .unwrap(),
+            Decimal::new(7, 1)
+        );
+        // A transaction on the boundary falls in the window it starts
+        assert_eq!(
+            fees(day_two, day_two + Duration::days(1), None)
+                .await
+                .unwrap(),
+            Decimal::new(4, 1)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_users_with_stale_pending(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let stale_user = insert_user(&store, Decimal::ZERO).await;
+        let fresh_user = insert_user(&store, Decimal::ZERO).await;
+
+        for (user_id, status, age_hours) in [
+            (stale_user, TransactionStatus::Pending, 3),
+            (stale_user, TransactionStatus::Pending, 2),
+            (stale_user, TransactionStatus::Pending, 0),
+            // Settled transactions are never stale, however old
+            (fresh_user, TransactionStatus::Confirmed, 5),
+            (fresh_user, TransactionStatus::Pending, 0),
+        ] {
+            let id = insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Withdrawal,
+                status,
+                Decimal::ONE,
+            )
+            .await;
+            sqlx::query!(
+                "UPDATE transactions SET created_at = $1 WHERE id = $2",
+                Utc::now() - Duration::hours(age_hours),
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let stale = store
+            .get_users_with_stale_pending(Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(stale.len(), 1);
+        let (user, count) = &stale[0];
+        assert_eq!(user.id, stale_user);
+        assert_eq!(*count, 2);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_internal_swap_legs_share_rate_and_reconcile(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::TEN,
+        )
+        .await;
+
+        let rate = Decimal::new(150, 0);
+        let swap = store
+            .internal_swap_with_rate(user_id, None, Some(usdc.to_string()), Decimal::TWO, rate)
+            .await
+            .unwrap();
+        assert_eq!(swap.debit.amount, Decimal::TWO);
+        assert_eq!(swap.credit.amount, Decimal::new(300, 0));
+
+        let legs = sqlx::query!(
+            "SELECT rate, swap_id FROM transactions WHERE id = ANY($1)",
+            &[swap.debit.id, swap.credit.id][..]
+        )
+        .fetch_all(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(legs.len(), 2);
+        for leg in legs {
+            assert_eq!(leg.rate, Some(rate));
+            assert_eq!(leg.swap_id, Some(swap.swap_id));
+        }
+
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::new(8, 0)
+        );
+        assert_eq!(
+            store.get_token_balance(user_id, usdc).await.unwrap(),
+            Decimal::new(300, 0)
+        );
+        store.verify_balance_integrity(user_id).await.unwrap();
+
+        // And back again, token to SOL
+        store
+            .internal_swap_with_rate(
+                user_id,
+                Some(usdc.to_string()),
+                None,
+                Decimal::new(100, 0),
+                Decimal::new(5, 3),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::new(85, 1)
+        );
+        store.verify_balance_integrity(user_id).await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_fail_all_pending_releases_holds(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::from(3)).await;
+        let withdraw = |amount| {
+            store.create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                amount,
+                None,
+                None,
+                Some("11111111111111111111111111111112".to_string()),
+                None,
+                None,
+            )
+        };
+
+        let mut pending = Vec::new();
+        for _ in 0..3 {
+            pending.push(withdraw(Decimal::ONE).await.unwrap().id);
+        }
+        assert!(matches!(
+            withdraw(Decimal::ONE).await,
+            Err(StoreError::InsufficientBalance { .. })
+        ));
+        let confirmed = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::ONE,
+        )
+        .await;
+
+        let failed = store
+            .fail_all_pending_for_user(user_id, "account suspended")
+            .await
+            .unwrap();
+        assert_eq!(failed, 3);
+        for id in pending {
+            let transaction = store.get_transaction(id).await.unwrap();
+            assert!(matches!(transaction.status, TransactionStatus::Failed));
+            assert_eq!(
+                transaction.failure_reason.as_deref(),
+                Some("account suspended")
+            );
+        }
+        assert!(matches!(
+            store.get_transaction(confirmed).await.unwrap().status,
+            TransactionStatus::Confirmed
+        ));
+
+        // The holds are gone and nothing was debited
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::from(3)
+        );
+        assert!(withdraw(Decimal::from(3)).await.is_ok());
+        assert!(matches!(
+            store
+                .fail_all_pending_for_user(Uuid::new_v4(), "gone")
+                .await,
+            Err(StoreError::UserNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_suspension_leaves_submitted_withdrawals_to_settle(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::from(3)).await;
+        let withdraw = || {
+            store.create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                Decimal::ONE,
+                None,
+                None,
+                Some("11111111111111111111111111111112".to_string()),
+                None,
+                None,
+            )
+        };
+        let unsent = withdraw().await.unwrap().id;
+        let submitted = withdraw().await.unwrap().id;
+        store
+            .mark_withdrawal_submitted(submitted, "sig-submitted", 1000)
+            .await
+            .unwrap();
+
+        let failed = store
+            .fail_all_pending_for_user(user_id, "account suspended")
+            .await
+            .unwrap();
+        assert_eq!(failed, 1);
+        assert!(matches!(
+            store.get_transaction(unsent).await.unwrap().status,
+            TransactionStatus::Failed
+        ));
+        assert!(matches!(
+            store.get_transaction(submitted).await.unwrap().status,
+            TransactionStatus::Pending
+        ));
+
+        // The transfer lands after the suspension and is still debited
+        store
+            .record_withdrawal_debit(submitted, "sig-submitted".to_string())
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get_transaction(submitted).await.unwrap().status,
+            TransactionStatus::Confirmed
+        ));
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TWO);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_pending_withdrawals_for_node(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        insert_keyshare(&store, user_id, 1).await;
+        insert_keyshare(&store, user_id, 2).await;
+        let withdrawal = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Pending,
+            Decimal::ONE,
+        )
+        .await;
+        // Neither a settled withdrawal nor a pending deposit needs a signing ceremony
+        for (transaction_type, status) in [
+            (TransactionType::Withdrawal, TransactionStatus::Confirmed),
+            (TransactionType::Deposit, TransactionStatus::Pending),
+        ] {
+            insert_transaction(&store, user_id, transaction_type, status, Decimal::ONE).await;
+        }
+
+        let for_node_two = store.get_pending_withdrawals_for_node(2, 10).await.unwrap();
+        assert_eq!(for_node_two.len(), 1);
+        let (transaction, keyshare) = &for_node_two[0];
+        assert_eq!(transaction.id, withdrawal);
+        assert_eq!((keyshare.user_id, keyshare.mpc_node_id), (user_id, 2));
+
+        assert!(
+            store
+                .get_pending_withdrawals_for_node(3, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            store.get_pending_withdrawals_for_node(6, 10).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_statement_deltas_sum_to_stored_balance(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let other = insert_user(&store, Decimal::ZERO).await;
+
+        // A confirmed deposit credited to the balance, then moved to the archive
+        insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::TEN,
+        )
+        .await;
+        sqlx::query!("UPDATE users SET balance = 10 WHERE id = $1", user_id)
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        store
+            .archive_transactions_before(Utc::now() + Duration::seconds(1))
+            .await
+            .unwrap();
+
+        // An internal transfer, recorded only as a balance adjustment
+        store
+            .transfer_sol(user_id, other, Decimal::from(3))
+            .await
+            .unwrap();
+
+        // A confirmed withdrawal debits its amount; the network fee is paid from the wallet
+        let withdrawal = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Confirmed,
+            Decimal::TWO,
+        )
+        .await;
+        sqlx::query!(
+            "UPDATE transactions SET fee = 0.000005 WHERE id = $1",
+            withdrawal
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "UPDATE users SET balance = balance - 2 WHERE id = $1",
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let statement = store.get_user_statement(user_id, None, 10).await.unwrap();
+        let stored = store.get_user_balance(user_id).await.unwrap();
+        assert_eq!(stored, Decimal::from(5));
+        assert_eq!(statement.len(), 3);
+        assert_eq!(statement[0].running_balance, stored);
+        assert_eq!(
+            statement.iter().map(|entry| entry.delta).sum::<Decimal>(),
+            stored
+        );
+
+        // Each running balance is the previous one plus the entry's delta
+        for pair in statement.windows(2) {
+            assert_eq!(
+                pair[0].running_balance,
+                pair[1].running_balance + pair[0].delta
+            );
+        }
+        assert_eq!(
+            statement[0]
+                .transaction
+                .as_ref()
+                .map(|transaction| transaction.id),
+            Some(withdrawal)
+        );
+        assert_eq!(statement[1].operation.as_deref(), Some("transfer_out"));
+        assert!(statement[1].transaction.is_none());
+        assert_eq!(statement[2].delta, Decimal::TEN);
+        store.verify_balance_integrity(user_id).await.unwrap();
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_expire_stale_transactions_spares_signed_rows_unless_asked(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let pending = || {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Withdrawal,
+                TransactionStatus::Pending,
+                Decimal::ONE,
+            )
+        };
+        let stale_unsigned = pending().await;
+        let stale_signed = pending().await;
+        let fresh = pending().await;
+        for (id, signature) in [(stale_unsigned, None), (stale_signed, Some("sig-stale"))] {
+            sqlx::query!(
+                "UPDATE transactions SET created_at = $1, tx_signature = $2 WHERE id = $3",
+                Utc::now() - Duration::hours(2),
+                signature,
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+        let transaction = |id: Uuid| {
+            let store = &store;
+            async move { store.get_transaction(id).await.unwrap() }
+        };
+
+        // A signed row may still land on chain, so only the unsigned one expires
+        assert_eq!(
+            store
+                .expire_stale_transactions(Duration::hours(1), false)
+                .await
+                .unwrap(),
+            1
+        );
+        let expired = transaction(stale_unsigned).await;
+        assert!(matches!(expired.status, TransactionStatus::Failed));
+        assert_eq!(
+            expired.failure_reason.as_deref(),
+            Some(EXPIRED_FAILURE_REASON)
+        );
+        assert!(matches!(
+            transaction(stale_signed).await.status,
+            TransactionStatus::Pending
+        ));
+
+        assert_eq!(
+            store
+                .expire_stale_transactions(Duration::hours(1), true)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(matches!(
+            transaction(stale_signed).await.status,
+            TransactionStatus::Failed
+        ));
+        assert!(matches!(
+            transaction(fresh).await.status,
+            TransactionStatus::Pending
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_pending_count_by_type_reports_every_type(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for (transaction_type, status) in [
+            (TransactionType::Deposit, TransactionStatus::Pending),
+            (TransactionType::Deposit, TransactionStatus::Pending),
+            (TransactionType::Deposit, TransactionStatus::Confirmed),
+            (TransactionType::Withdrawal, TransactionStatus::Pending),
+            (TransactionType::Withdrawal, TransactionStatus::Failed),
+            (TransactionType::Transfer, TransactionStatus::Confirmed),
+        ] {
+            insert_transaction(&store, user_id, transaction_type, status, Decimal::ONE).await;
+        }
+
+        let counts: Vec<(String, i64)> = store
+            .get_pending_count_by_type()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(transaction_type, count)| (format!("{:?}", transaction_type), count))
+            .collect();
+        // Transfers have nothing pending but are still listed
+        assert_eq!(
+            counts,
+            vec![
+                ("Deposit".to_string(), 2),
+                ("Withdrawal".to_string(), 1),
+                ("Transfer".to_string(), 0),
+            ]
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_idempotency_key_lookup_is_scoped_to_user(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let alice = insert_user(&store, Decimal::ZERO).await;
+        let bob = insert_user(&store, Decimal::ZERO).await;
+        let mut ids = Vec::new();
+        // Both users picked the same key; each only ever sees their own transaction
+        for user_id in [alice, bob] {
+            let transaction = store
+                .create_transaction(
+                    user_id,
+                    TransactionType::Deposit,
+                    Decimal::ONE,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some("order-42"),
+                )
+                .await
+                .unwrap();
+            ids.push(transaction.id);
+        }
+        // A retry with a key already used is refused rather than recorded twice
+        assert!(matches!(
+            store
+                .create_transaction(
+                    alice,
+                    TransactionType::Deposit,
+                    Decimal::ONE,
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some("order-42"),
+                )
+                .await,
+            Err(StoreError::IdempotencyKeyReused(_))
+        ));
+
+        for (user_id, id) in [(alice, ids[0]), (bob, ids[1])] {
+            let found = store
+                .get_transaction_by_idempotency_key(user_id, "order-42")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(found.id, id);
+            assert_eq!(found.user_id, user_id);
+        }
+        let carol = insert_user(&store, Decimal::ZERO).await;
+        assert!(
+            store
+                .get_transaction_by_idempotency_key(carol, "order-42")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .get_transaction_by_idempotency_key(alice, "order-43")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_fail_transaction_records_reason(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let pending = || {
+            insert_transaction(
+                &store,
+                user_id,
+                TransactionType::Withdrawal,
+                TransactionStatus::Pending,
+                Decimal::ONE,
+            )
+        };
+        let rejected = pending().await;
+        let dropped = pending().await;
+
+        store
+            .fail_transaction(rejected, Some("destination not whitelisted".to_string()))
+            .await
+            .unwrap();
+        store.fail_transaction(dropped, None).await.unwrap();
+
+        let rejected = store.get_transaction(rejected).await.unwrap();
+        assert!(matches!(rejected.status, TransactionStatus::Failed));
+        assert_eq!(
+            rejected.failure_reason.as_deref(),
+            Some("destination not whitelisted")
+        );
+        let dropped = store.get_transaction(dropped).await.unwrap();
+        assert!(matches!(dropped.status, TransactionStatus::Failed));
+        assert_eq!(dropped.failure_reason, None);
+
+        assert!(matches!(
+            store.fail_transaction(Uuid::new_v4(), None).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+
+        // A settled row is not failed after the fact
+        let confirmed = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Confirmed,
+            Decimal::ONE,
+        )
+        .await;
+        assert!(matches!(
+            store.fail_transaction(confirmed, None).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            store.get_transaction(confirmed).await.unwrap().status,
+            TransactionStatus::Confirmed
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_submitted_withdrawals_page_past_unresolved_rows(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        let insert = |transaction_type| {
+            insert_transaction(
+                &store,
+                user_id,
+                transaction_type,
+                TransactionStatus::Pending,
+                Decimal::ONE,
+            )
+        };
+        let mut withdrawals = Vec::new();
+        for age_minutes in [30, 20, 10] {
+            let id = insert(TransactionType::Withdrawal).await;
+            sqlx::query!(
+                "UPDATE transactions SET created_at = $1 WHERE id = $2",
+                Utc::now() - Duration::minutes(age_minutes),
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+            store
+                .mark_withdrawal_submitted(id, &format!("sig-{}", age_minutes), 1000)
+                .await
+                .unwrap();
+            withdrawals.push(id);
+        }
+        let unsigned = insert(TransactionType::Withdrawal).await;
+        let deposit = insert(TransactionType::Deposit).await;
+        assert!(matches!(
+            store
+                .mark_withdrawal_submitted(deposit, "sig-deposit", 1000)
+                .await,
+            Err(StoreError::InvalidInput(_))
+        ));
+
+        let idle_since = Utc::now() + Duration::seconds(1);
+        let mut seen = Vec::new();
+        let mut after = None;
+        loop {
+            let page = store
+                .get_submitted_withdrawals(idle_since, after, 2)
+                .await
+                .unwrap();
+            let Some(last) = page.last() else {
+                break;
+            };
+            after = Some(Cursor::new(last.created_at, last.id));
+            seen.extend(page);
+        }
+        let ids: Vec<Uuid> = seen.iter().map(|withdrawal| withdrawal.id).collect();
+        assert_eq!(ids, withdrawals);
+        assert!(!ids.contains(&unsigned));
+        assert_eq!(seen[0].tx_signature, "sig-30");
+        assert_eq!(seen[0].last_valid_block_height, Some(1000));
+
+        // Rows touched since the cutoff are left to the request that sent them
+        assert!(
+            store
+                .get_submitted_withdrawals(Utc::now() - Duration::minutes(1), None, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -1,10 +1,20 @@
 use crate::Store;
-use bcrypt::{DEFAULT_COST, hash, verify};
-use chrono::{DateTime, Utc};
+use crate::audit::{BalanceAuditRecord, SOL_ASSET};
+use crate::decimal::{format_decimal, serialize_sol};
+use crate::pagination::{Cursor, Page, cursor_bounds, fetch_limit};
+use crate::retry::retry_on_conflict;
+use crate::token::lookup_token_info;
+use crate::transaction::{check_available_balance, record_balance_adjustment, split_total};
+use argon2::Config;
+use chrono::{DateTime, Duration, Utc};
 use rust_decimal::Decimal;
 use serde::Deserialize;
 use serde::Serialize;
+use serde::Serializer;
+use serde::ser::SerializeStruct;
+use sqlx::PgConnection;
 use sqlx::prelude::FromRow;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -17,6 +27,25 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, FromRow)]
+struct UserWithPassword {
+    pub id: Uuid,
+    pub email: String,
+    pub password_hash: String,
+    pub agg_pubkey: Option<String>,
+    pub balance: Decimal,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct AggPubkeyHistory {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub agg_pubkey: String,
+    pub rotated_at: DateTime<Utc>,
+}
+
 #[derive(Debug)]
 pub struct CreateUserRequest {
     pub email: String,
@@ -32,10 +61,29 @@ pub struct MpcKeyshare {
     pub public_key: String,
     pub threshold: i32,
     pub total_shares: i32,
+    /// Whether the node takes part in signing; off while it is down for maintenance
+    pub active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// A keyshare's signing status without its secret material
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct KeyshareParticipation {
+    pub mpc_node_id: i32,
+    pub active: bool,
+    pub threshold: i32,
+}
+
+/// The shareable half of a keyshare, safe to export for backups
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct KeysharePublicInfo {
+    pub mpc_node_id: i32,
+    pub public_key: String,
+    pub threshold: i32,
+    pub total_shares: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateKeyshareRequest {
     pub user_id: Uuid,
@@ -46,18 +94,50 @@ pub struct CreateKeyshareRequest {
     pub total_shares: Option<i32>,
 }
 
-#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+/// A node's new share from a key refresh, replacing its current share. The public key must stay
+/// the same: the user's aggregated key, and so their wallet, is derived from every node's key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshedKeyshare {
+    pub mpc_node_id: i32,
+    pub private_key_share: String,
+    pub public_key: String,
+}
+
+#[derive(Debug, Clone, FromRow, Deserialize)]
 pub struct TokenBalance {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub token_mint: String, // Solana mint address
-    pub token_symbol: String,
+    pub token_mint: String,           // Solana mint address
+    pub token_symbol: Option<String>, // None when the mint has no known metadata
     pub balance: Decimal,
-    pub decimals: i32,
+    pub decimals: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+// Serialized by hand so the balance is rendered as a string scaled to the token's decimals
+impl Serialize for TokenBalance {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("TokenBalance", 8)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("user_id", &self.user_id)?;
+        state.serialize_field("token_mint", &self.token_mint)?;
+        state.serialize_field("token_symbol", &self.token_symbol)?;
+        // Without known decimals the balance is rendered as stored rather than guessing a scale
+        let scale = self
+            .decimals
+            .map_or(self.balance.scale(), |decimals| decimals.max(0) as u32);
+        state.serialize_field("balance", &format_decimal(self.balance, scale))?;
+        state.serialize_field("decimals", &self.decimals)?;
+        state.serialize_field("created_at", &self.created_at)?;
+        state.serialize_field("updated_at", &self.updated_at)?;
+        state.end()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpdateBalanceRequest {
     pub user_id: Uuid,
@@ -77,10 +157,36 @@ pub struct Transaction {
     pub from_address: Option<String>,
     pub to_address: Option<String>,
     pub fee: Decimal,
+    pub failure_reason: Option<String>, // Set when the transaction is failed, e.g. why a withdrawal was rejected
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InternalSwap {
+    pub swap_id: Uuid,
+    pub rate: Decimal, // Units of the output asset per unit of the input asset
+    pub debit: Transaction,
+    pub credit: Transaction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementEntry {
+    pub transaction: Option<Transaction>, // None for movements recorded only as a balance adjustment
+    pub operation: Option<String>,        // The adjustment's operation (transfer_in, debit, ...)
+    pub delta: Decimal,                   // Signed change this entry made to the balance
+    pub running_balance: Decimal,         // Ledger balance after this entry
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub event_type: String, // signup, keyshare_created, transaction_created, transaction_<status>
+    pub reference_id: Uuid, // Id of the user, keyshare or transaction the event belongs to
+    pub detail: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "transaction_type", rename_all = "lowercase")]
 pub enum TransactionType {
@@ -97,38 +203,68 @@ pub enum TransactionStatus {
     Failed,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+pub enum UserRole {
+    User,
+    Admin,
+    Operator,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserBalanceResponse {
     pub user_id: Uuid,
+    #[serde(serialize_with = "serialize_sol")]
     pub sol_balance: Decimal,
     pub token_balances: Vec<TokenBalance>,
 }
 
-#[derive(Debug)]
-pub enum UserError {
-    UserExists,
-    InvalidInput(String),
-    DatabaseError(String),
-}
-
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum StoreError {
+    #[error("User already exists")]
     UserExists,
+    #[error("User not found")]
     UserNotFound,
+    #[error("Keyshare already exists")]
     KeyshareExists,
+    #[error("Keyshare not found")]
     KeyshareNotFound,
-    InsufficientBalance,
+    #[error("Keyshare public key {public_key} does not match key derived from its share {derived}")]
+    KeyshareMismatch { public_key: String, derived: String },
+    #[error("Withdrawal destination {0} is not on the whitelist")]
+    AddressNotWhitelisted(String),
+    #[error(
+        "Insufficient balance: required {required}, available {available}, short by {}",
+        .required - .available
+    )]
+    InsufficientBalance {
+        required: Decimal,
+        available: Decimal,
+    },
+    #[error("Invalid input: {0}")]
     InvalidInput(String),
-    // DatabaseError(#[from] sqlx::Error),
+    #[error("MPC setup incomplete: {0}")]
+    MpcSetupIncomplete(String),
+    #[error("Ledger mismatch for {asset}: expected {expected}, actual {actual}")]
+    LedgerMismatch {
+        asset: String,
+        expected: Decimal,
+        actual: Decimal,
+    },
+    #[error("Encryption error: {0}")]
     EncryptionError(String),
+    #[error("Invalid refresh token: {0}")]
+    InvalidRefreshToken(String),
+    #[error("Refresh token reuse detected; token family revoked")]
+    RefreshTokenReplayed,
+    #[error("Idempotency key {0} was already used")]
+    IdempotencyKeyReused(String),
+    #[error("Email address has not been verified")]
+    EmailNotVerified,
+    #[error("Password error: {0}")]
     PasswordError(String),
-    DatabaseError(sqlx::Error),
-}
-
-impl From<sqlx::Error> for StoreError {
-    fn from(err: sqlx::Error) -> Self {
-        StoreError::DatabaseError(err)
-    }
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] sqlx::Error),
 }
 
 // Helper structs for aggregated queries
@@ -139,6 +275,46 @@ pub struct UserSummary {
     pub total_token_types: i64,
 }
 
+#[derive(Debug, Serialize)]
+pub struct KeyshareStatus {
+    pub keyshare_count: i64,
+    pub active_count: i64,
+    pub threshold: Option<i32>,
+    pub total_shares: Option<i32>,
+    pub can_sign: bool, // Enough active shares present to meet the threshold
+}
+
+/// Group token balances by owner, keeping each user's rows in query order
+fn group_by_user(token_balances: Vec<TokenBalance>) -> HashMap<Uuid, Vec<TokenBalance>> {
+    let mut grouped: HashMap<Uuid, Vec<TokenBalance>> = HashMap::new();
+    for balance in token_balances {
+        grouped.entry(balance.user_id).or_default().push(balance);
+    }
+    grouped
+}
+
+/// Only active shares can join a signing ceremony
+fn can_sign(active_count: i64, threshold: Option<i32>) -> bool {
+    threshold.is_some_and(|threshold| active_count >= i64::from(threshold))
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NetworkWallet {
+    pub network: String,
+    pub agg_pubkey: Option<String>, // None until a key has been aggregated on this network
+    pub keyshare_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    pub user: User,
+    #[serde(serialize_with = "serialize_sol")]
+    pub sol_balance: Decimal,
+    pub token_balances: Vec<TokenBalance>,
+    pub keyshare_status: KeyshareStatus,
+    pub transaction_count: i64,
+}
+
 #[derive(Debug, Serialize)]
 pub struct BalanceSummary {
     pub total_users: i64,
@@ -146,46 +322,258 @@ pub struct BalanceSummary {
     pub total_transactions: i64,
 }
 
-impl std::fmt::Display for UserError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            UserError::UserExists => write!(f, "User already exists"),
-            UserError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
-            UserError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
+/// Check that a refresh replaces exactly the nodes that hold a share today, once each, that
+/// no node's public key changes, and that every new share matches its public key
+pub fn check_share_swap(
+    current: &[(i32, String)],
+    refreshed: &[RefreshedKeyshare],
+) -> Result<(), StoreError> {
+    let mut current = current.to_vec();
+    current.sort_unstable();
+    let current_node_ids: Vec<i32> = current.iter().map(|(node_id, _)| *node_id).collect();
+    let mut replaced: Vec<i32> = refreshed.iter().map(|share| share.mpc_node_id).collect();
+    replaced.sort_unstable();
+    if current_node_ids != replaced {
+        return Err(StoreError::InvalidInput(format!(
+            "Refresh must replace the shares of nodes {:?}, got {:?}",
+            current_node_ids, replaced
+        )));
+    }
+
+    for share in refreshed {
+        let public_key = current
+            .iter()
+            .find(|(node_id, _)| *node_id == share.mpc_node_id)
+            .map(|(_, public_key)| public_key);
+        if public_key != Some(&share.public_key) {
+            return Err(StoreError::InvalidInput(format!(
+                "Refresh must keep node {}'s public key, got {}",
+                share.mpc_node_id, share.public_key
+            )));
         }
     }
+
+    for share in refreshed {
+        verify_keyshare_public_key(&share.private_key_share, &share.public_key)?;
+    }
+    Ok(())
 }
 
-impl std::error::Error for UserError {}
+/// Check that `public_key` is the ed25519 point derived from `private_key_share`.
+/// Shares that are not a base58 32-byte seed or 64-byte keypair (e.g. encrypted
+/// shares) cannot be derived from and are accepted as-is.
+pub fn verify_keyshare_public_key(
+    private_key_share: &str,
+    public_key: &str,
+) -> Result<(), StoreError> {
+    let bytes = match bs58::decode(private_key_share).into_vec() {
+        Ok(bytes) if bytes.len() == 32 || bytes.len() == 64 => bytes,
+        _ => return Ok(()),
+    };
+
+    let secret = ed25519_dalek::SecretKey::from_bytes(&bytes[..32])
+        .map_err(|e| StoreError::InvalidInput(format!("Invalid private key share: {}", e)))?;
+    let derived = ed25519_dalek::PublicKey::from(&secret).to_bytes();
+
+    // A 64-byte keypair embeds its public half; it must agree with the seed too
+    let embedded_matches = bytes.len() == 32 || bytes[32..] == derived;
+    let derived = bs58::encode(derived).into_string();
+    if !embedded_matches || derived != public_key {
+        return Err(StoreError::KeyshareMismatch {
+            public_key: public_key.to_string(),
+            derived,
+        });
+    }
 
-impl Store {
-    //DONE TILL TOKEN balance store impl
+    Ok(())
+}
 
-    pub async fn create_user(&self, request: CreateUserRequest) -> Result<User, StoreError> {
-        // Validate email format
-        if !request.email.contains('@') || request.email.len() < 5 {
-            return Err(StoreError::InvalidInput("Invalid email format".to_string()));
+fn validate_sol_transfer(
+    from_user_id: Uuid,
+    to_user_id: Uuid,
+    amount: Decimal,
+) -> Result<(), StoreError> {
+    if from_user_id == to_user_id {
+        return Err(StoreError::InvalidInput(
+            "Cannot transfer to the same user".to_string(),
+        ));
+    }
+    if amount <= Decimal::ZERO {
+        return Err(StoreError::InvalidInput(
+            "Transfer amount must be positive".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Hash a password with argon2, the scheme shared by the store and the backend
+pub fn hash_password(password: &str) -> Result<String, StoreError> {
+    argon2::hash_encoded(
+        password.as_bytes(),
+        Uuid::new_v4().as_bytes(),
+        &Config::default(),
+    )
+    .map_err(|e| StoreError::PasswordError(e.to_string()))
+}
+
+/// Canonical form of an email: `Foo@X.com ` and `foo@x.com` are the same account
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Normalize a signup batch's emails and reject it if two entries name the same address
+fn prepare_user_batch(requests: &mut [CreateUserRequest]) -> Result<(), StoreError> {
+    let mut seen_emails = HashSet::new();
+    for request in requests.iter_mut() {
+        request.email = normalize_email(&request.email);
+        validate_new_user(request)?;
+        if !seen_emails.insert(request.email.clone()) {
+            return Err(StoreError::UserExists);
         }
+    }
+    Ok(())
+}
 
-        // Validate password length
-        if request.password.len() < 8 {
-            return Err(StoreError::InvalidInput(
-                "Password must be at least 8 characters".to_string(),
-            ));
+/// Map a unique violation on `users` (a concurrent signup won the race) to `UserExists`
+fn user_insert_error(e: sqlx::Error) -> StoreError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_unique_violation() => StoreError::UserExists,
+        _ => e.into(),
+    }
+}
+
+/// Check an email's shape: one `@`, a plausible local part, and a dotted domain of
+/// letter/digit/hyphen labels. Deliverability is not checked.
+pub fn validate_email(email: &str) -> Result<(), StoreError> {
+    let invalid = |reason: &str| {
+        Err(StoreError::InvalidInput(format!(
+            "Invalid email: {}",
+            reason
+        )))
+    };
+
+    if email.len() > 254 {
+        return invalid("longer than 254 characters");
+    }
+    if email.chars().any(char::is_whitespace) {
+        return invalid("contains whitespace");
+    }
+    let Some((local, domain)) = email.split_once('@') else {
+        return invalid("missing @");
+    };
+    if domain.contains('@') {
+        return invalid("more than one @");
+    }
+
+    if local.is_empty() || local.len() > 64 {
+        return invalid("local part must be 1 to 64 characters");
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return invalid("misplaced dot in local part");
+    }
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return invalid("domain has no dot");
+    }
+    for label in &labels {
+        if label.is_empty() || label.len() > 63 {
+            return invalid("empty or overlong domain label");
         }
+        if label.starts_with('-')
+            || label.ends_with('-')
+            || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            return invalid("domain labels may only use letters, digits and inner hyphens");
+        }
+    }
+    let tld = labels[labels.len() - 1];
+    if tld.len() < 2 || !tld.chars().all(|c| c.is_ascii_alphabetic()) {
+        return invalid("top-level domain must be at least two letters");
+    }
+
+    Ok(())
+}
+
+/// Validate email format and password length for a new user
+fn validate_new_user(request: &CreateUserRequest) -> Result<(), StoreError> {
+    validate_email(&request.email)?;
+    validate_password(&request.password)
+}
+
+/// Passwords must be at least 8 characters, both at signup and on reset
+pub(crate) fn validate_password(password: &str) -> Result<(), StoreError> {
+    if password.len() < 8 {
+        return Err(StoreError::InvalidInput(
+            "Password must be at least 8 characters".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Whether `user_id` holds at least `required_threshold` (default 2) active keyshares. The shares
+/// are locked until the enclosing transaction ends, so none can be deactivated under the caller.
+pub(crate) async fn has_sufficient_keyshares(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    required_threshold: Option<i32>,
+) -> Result<bool, StoreError> {
+    let threshold = required_threshold.unwrap_or(2);
+
+    let keyshare_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM (
+            SELECT 1 FROM mpc_keyshares WHERE user_id = $1 AND active FOR SHARE
+        ) shares
+        "#,
+        user_id
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(keyshare_count >= threshold as i64)
+}
+
+/// Legacy hashes were produced by bcrypt and carry a `$2a$`/`$2b$`/`$2y$` prefix
+fn is_bcrypt_hash(password_hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2y$"]
+        .iter()
+        .any(|prefix| password_hash.starts_with(prefix))
+}
+
+/// Check a password against a stored hash. New hashes are argon2; bcrypt hashes from before the
+/// switch still verify and are upgraded by `authenticate_user` on the next successful login.
+fn verify_password(password: &str, stored_hash: &str) -> Result<bool, StoreError> {
+    if is_bcrypt_hash(stored_hash) {
+        bcrypt::verify(password, stored_hash).map_err(|e| StoreError::PasswordError(e.to_string()))
+    } else {
+        argon2::verify_encoded(stored_hash, password.as_bytes())
+            .map_err(|e| StoreError::PasswordError(e.to_string()))
+    }
+}
+
+impl Store {
+    //DONE TILL TOKEN balance store impl
+
+    pub async fn create_user(&self, mut request: CreateUserRequest) -> Result<User, StoreError> {
+        request.email = normalize_email(&request.email);
+        validate_new_user(&request)?;
 
         // Check if user already exists
-        let existing_user = sqlx::query!("SELECT id FROM users WHERE email = $1", request.email)
-            .fetch_optional(&self.pool)
-            .await?;
+        let existing_user = sqlx::query!(
+            "SELECT id FROM users WHERE lower(email) = $1",
+            request.email
+        )
+        .fetch_optional(&self.pool)
+        .await?;
 
         if existing_user.is_some() {
             return Err(StoreError::UserExists);
         }
 
         // Hash the password
-        let password_hash = hash(&request.password, DEFAULT_COST)
-            .map_err(|e| StoreError::PasswordError(e.to_string()))?;
+        let password_hash = hash_password(&request.password)?;
 
         // Insert user into database
         let user = sqlx::query_as!(
@@ -201,11 +589,75 @@ impl Store {
             Utc::now()
         )
         .fetch_one(&self.pool)
-        .await?;
+        .await
+        .map_err(user_insert_error)?;
 
         Ok(user)
     }
 
+    /// Create many users atomically (for admin onboarding).
+    /// The whole batch is rolled back if any email is invalid or already taken.
+    pub async fn create_users_batch(
+        &self,
+        mut requests: Vec<CreateUserRequest>,
+    ) -> Result<Vec<User>, StoreError> {
+        prepare_user_batch(&mut requests)?;
+
+        // Hashing is CPU-bound, so hash all passwords in parallel on the blocking pool
+        let hash_tasks: Vec<_> = requests
+            .iter()
+            .map(|request| {
+                let password = request.password.clone();
+                tokio::task::spawn_blocking(move || hash_password(&password))
+            })
+            .collect();
+
+        let mut password_hashes = Vec::with_capacity(hash_tasks.len());
+        for task in hash_tasks {
+            let password_hash = task
+                .await
+                .map_err(|e| StoreError::PasswordError(e.to_string()))??;
+            password_hashes.push(password_hash);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut users = Vec::with_capacity(requests.len());
+
+        for (request, password_hash) in requests.iter().zip(password_hashes) {
+            let existing_user = sqlx::query!(
+                "SELECT id FROM users WHERE lower(email) = $1",
+                request.email
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if existing_user.is_some() {
+                return Err(StoreError::UserExists);
+            }
+
+            let user = sqlx::query_as!(
+                User,
+                r#"
+                INSERT INTO users (email, password_hash, balance, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $4)
+                RETURNING id, email, agg_pubkey, balance, created_at, updated_at
+                "#,
+                request.email,
+                password_hash,
+                Decimal::ZERO,
+                Utc::now()
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(user_insert_error)?;
+
+            users.push(user);
+        }
+
+        tx.commit().await?;
+        Ok(users)
+    }
+
     pub async fn get_user(&self, user_id: Uuid) -> Result<User, StoreError> {
         let user = sqlx::query_as!(
             User,
@@ -222,8 +674,22 @@ impl Store {
     pub async fn get_user_by_email(&self, email: &str) -> Result<User, StoreError> {
         let user = sqlx::query_as!(
             User,
-            "SELECT id, email, agg_pubkey, balance, created_at, updated_at FROM users WHERE email = $1",
-            email
+            "SELECT id, email, agg_pubkey, balance, created_at, updated_at FROM users WHERE lower(email) = $1",
+            normalize_email(email)
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        Ok(user)
+    }
+
+    /// Find the user owning an aggregated pubkey, e.g. when the indexer sees a deposit to it
+    pub async fn get_user_by_agg_pubkey(&self, agg_pubkey: &str) -> Result<User, StoreError> {
+        let user = sqlx::query_as!(
+            User,
+            "SELECT id, email, agg_pubkey, balance, created_at, updated_at FROM users WHERE agg_pubkey = $1",
+            agg_pubkey
         )
         .fetch_optional(&self.pool)
         .await?
@@ -232,25 +698,45 @@ impl Store {
         Ok(user)
     }
 
+    /// Look a user up by id or email, whichever `identifier` is (admin tools)
+    pub async fn find_user(&self, identifier: &str) -> Result<User, StoreError> {
+        match UserIdentifier::parse(identifier)? {
+            UserIdentifier::Id(user_id) => self.get_user(user_id).await,
+            UserIdentifier::Email(email) => self.get_user_by_email(email).await,
+        }
+    }
+
     /// Authenticate user with email and password
     pub async fn authenticate_user(&self, email: &str, password: &str) -> Result<User, StoreError> {
         let user_with_password = sqlx::query_as!(
             UserWithPassword,
-            "SELECT id, email, password_hash, agg_pubkey, balance, created_at, updated_at FROM users WHERE email = $1",
-            email
+            "SELECT id, email, password_hash, agg_pubkey, balance, created_at, updated_at FROM users WHERE lower(email) = $1",
+            normalize_email(email)
         )
         .fetch_optional(&self.pool)
         .await?
         .ok_or(StoreError::UserNotFound)?;
 
-        // Verify password
-        let is_valid = verify(password, &user_with_password.password_hash)
-            .map_err(|e| StoreError::PasswordError(e.to_string()))?;
-
-        if !is_valid {
+        // Verify password, detecting the scheme from the hash prefix
+        let stored_hash = &user_with_password.password_hash;
+        let is_legacy = is_bcrypt_hash(stored_hash);
+        if !verify_password(password, stored_hash)? {
             return Err(StoreError::InvalidInput("Invalid password".to_string()));
         }
 
+        // Lazily migrate legacy bcrypt hashes to argon2 on successful login
+        if is_legacy {
+            let new_hash = hash_password(password)?;
+            sqlx::query!(
+                "UPDATE users SET password_hash = $1, updated_at = $2 WHERE id = $3",
+                new_hash,
+                Utc::now(),
+                user_with_password.id
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
         Ok(User {
             id: user_with_password.id,
             email: user_with_password.email,
@@ -261,24 +747,64 @@ impl Store {
         })
     }
 
-    /// Update user's aggregated public key (after MPC key generation)
+    /// Update user's aggregated public key (after MPC key generation or rotation).
+    /// The previous key is archived so funds sent to the old address can still be attributed.
     pub async fn update_user_agg_pubkey(
         &self,
         user_id: Uuid,
         agg_pubkey: &str,
     ) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query_scalar!(
+            "SELECT agg_pubkey FROM users WHERE id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        if let Some(previous) = current.filter(|previous| previous != agg_pubkey) {
+            sqlx::query!(
+                "INSERT INTO agg_pubkey_history (user_id, agg_pubkey, rotated_at) VALUES ($1, $2, $3)",
+                user_id,
+                previous,
+                Utc::now()
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
         sqlx::query!(
             "UPDATE users SET agg_pubkey = $1, updated_at = $2 WHERE id = $3",
             agg_pubkey,
             Utc::now(),
             user_id
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        tx.commit().await?;
         Ok(())
     }
 
+    /// Get previously used aggregated public keys for a user, most recent first
+    pub async fn get_agg_pubkey_history(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<AggPubkeyHistory>, StoreError> {
+        let history = sqlx::query_as!(
+            AggPubkeyHistory,
+            "SELECT id, user_id, agg_pubkey, rotated_at
+             FROM agg_pubkey_history WHERE user_id = $1 ORDER BY rotated_at DESC",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(history)
+    }
+
     /// Get user balance (SOL only)
     pub async fn get_user_balance(&self, user_id: Uuid) -> Result<Decimal, StoreError> {
         let balance = sqlx::query_scalar!("SELECT balance FROM users WHERE id = $1", user_id)
@@ -289,26 +815,63 @@ impl Store {
         Ok(balance)
     }
 
+    /// Get SOL balances for many users in one query; unknown ids are absent from the map
+    pub async fn get_balances_for_users(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Decimal>, StoreError> {
+        let rows = sqlx::query!("SELECT id, balance FROM users WHERE id = ANY($1)", user_ids)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| (row.id, row.balance)).collect())
+    }
+
     /// Update user SOL balance
     pub async fn update_user_balance(
         &self,
         user_id: Uuid,
         new_balance: Decimal,
     ) -> Result<(), StoreError> {
-        let updated_rows = sqlx::query!(
+        let mut tx = self.pool.begin().await?;
+
+        // The previous balance feeds the ledger and audit log; the lock keeps it current
+        let previous_balance = sqlx::query_scalar!(
+            "SELECT balance FROM users WHERE id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        sqlx::query!(
             "UPDATE users SET balance = $1, updated_at = $2 WHERE id = $3",
             new_balance,
             Utc::now(),
             user_id
         )
-        .execute(&self.pool)
-        .await?
-        .rows_affected();
+        .execute(&mut *tx)
+        .await?;
 
-        if updated_rows == 0 {
-            return Err(StoreError::UserNotFound);
-        }
+        record_balance_adjustment(
+            &mut tx,
+            user_id,
+            None,
+            new_balance - previous_balance,
+            "set_balance",
+        )
+        .await?;
+        tx.commit().await?;
 
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "admin",
+            "set_balance",
+            SOL_ASSET,
+            previous_balance,
+            new_balance,
+            None,
+        )]);
         Ok(())
     }
 
@@ -324,16 +887,29 @@ impl Store {
             ));
         }
 
+        let mut tx = self.pool.begin().await?;
         let new_balance = sqlx::query_scalar!(
             "UPDATE users SET balance = balance + $1, updated_at = $2 WHERE id = $3 RETURNING balance",
             amount,
             Utc::now(),
             user_id
         )
-        .fetch_optional(&self.pool)
+        .fetch_optional(&mut *tx)
         .await?
         .ok_or(StoreError::UserNotFound)?;
 
+        record_balance_adjustment(&mut tx, user_id, None, amount, "credit").await?;
+        tx.commit().await?;
+
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "system",
+            "credit",
+            SOL_ASSET,
+            new_balance - amount,
+            new_balance,
+            None,
+        )]);
         Ok(new_balance)
     }
 
@@ -349,22 +925,30 @@ impl Store {
             ));
         }
 
-        // Check current balance first
-        let current_balance = self.get_user_balance(user_id).await?;
-        if current_balance < amount {
-            return Err(StoreError::InsufficientBalance);
-        }
-
+        // Locks the user row, so concurrent debits can't both pass the check
+        let mut tx = self.pool.begin().await?;
+        check_available_balance(&mut tx, user_id, None, amount).await?;
         let new_balance = sqlx::query_scalar!(
             "UPDATE users SET balance = balance - $1, updated_at = $2 WHERE id = $3 RETURNING balance",
             amount,
             Utc::now(),
             user_id
         )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or(StoreError::UserNotFound)?;
+        .fetch_one(&mut *tx)
+        .await?;
+
+        record_balance_adjustment(&mut tx, user_id, None, -amount, "debit").await?;
+        tx.commit().await?;
 
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "system",
+            "debit",
+            SOL_ASSET,
+            new_balance + amount,
+            new_balance,
+            None,
+        )]);
         Ok(new_balance)
     }
 
@@ -395,16 +979,81 @@ impl Store {
         })
     }
 
-    /// Get complete user balance information (SOL + all tokens)
-    pub async fn get_user_complete_balance(
-        &self,
-        user_id: Uuid,
-    ) -> Result<UserBalanceResponse, StoreError> {
-        let sol_balance = self.get_user_balance(user_id).await?;
+    /// Get everything a profile page needs, read from one snapshot for consistency
+    pub async fn get_user_profile(&self, user_id: Uuid) -> Result<UserProfile, StoreError> {
+        let mut tx = self.pool.begin().await?;
 
-        let token_balances = sqlx::query_as!(
-            TokenBalance,
-            "SELECT id, user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at 
+        // Every read below sees the same snapshot, so the counts agree with the balances
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        let user = sqlx::query_as!(
+            User,
+            "SELECT id, email, agg_pubkey, balance, created_at, updated_at FROM users WHERE id = $1",
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        let token_balances = sqlx::query_as!(
+            TokenBalance,
+            "SELECT id, user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at
+             FROM token_balances WHERE user_id = $1 ORDER BY token_symbol",
+            user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let keyshares = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "keyshare_count!", COUNT(*) FILTER (WHERE active) as "active_count!",
+                   MAX(threshold) as threshold, MAX(total_shares) as total_shares
+            FROM mpc_keyshares WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let transaction_count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM transactions WHERE user_id = $1",
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?
+        .unwrap_or(0);
+
+        tx.commit().await?;
+
+        let keyshare_status = KeyshareStatus {
+            keyshare_count: keyshares.keyshare_count,
+            active_count: keyshares.active_count,
+            threshold: keyshares.threshold,
+            total_shares: keyshares.total_shares,
+            can_sign: can_sign(keyshares.active_count, keyshares.threshold),
+        };
+
+        Ok(UserProfile {
+            sol_balance: user.balance,
+            user,
+            token_balances,
+            keyshare_status,
+            transaction_count,
+        })
+    }
+
+    /// Get complete user balance information (SOL + all tokens)
+    pub async fn get_user_complete_balance(
+        &self,
+        user_id: Uuid,
+    ) -> Result<UserBalanceResponse, StoreError> {
+        let sol_balance = self.get_user_balance(user_id).await?;
+
+        let token_balances = sqlx::query_as!(
+            TokenBalance,
+            "SELECT id, user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at 
              FROM token_balances WHERE user_id = $1 ORDER BY token_symbol",
             user_id
         )
@@ -418,31 +1067,123 @@ impl Store {
         })
     }
 
+    /// Get SOL and token balances from a single snapshot, so a transfer committing
+    /// between the two reads can't produce an inconsistent view
+    pub async fn get_user_complete_balance_consistent(
+        &self,
+        user_id: Uuid,
+    ) -> Result<UserBalanceResponse, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+
+        let sol_balance = sqlx::query_scalar!("SELECT balance FROM users WHERE id = $1", user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(StoreError::UserNotFound)?;
+
+        let token_balances = sqlx::query_as!(
+            TokenBalance,
+            "SELECT id, user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at
+             FROM token_balances WHERE user_id = $1 ORDER BY token_symbol",
+            user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(UserBalanceResponse {
+            user_id,
+            sol_balance,
+            token_balances,
+        })
+    }
+
     /// List all users (for admin purposes)
     pub async fn list_users(&self, limit: i64, offset: i64) -> Result<Vec<User>, StoreError> {
         let users = sqlx::query_as!(
             User,
-            "SELECT id, email, agg_pubkey, balance, created_at, updated_at 
-             FROM users ORDER BY created_at DESC LIMIT $1 OFFSET $2",
+            "SELECT id, email, agg_pubkey, balance, created_at, updated_at
+             FROM users ORDER BY created_at DESC, id DESC LIMIT $1 OFFSET $2",
             limit,
             offset
         )
-        .fetch_all(&self.pool)
+        .fetch_all(self.reader())
         .await?;
 
         Ok(users)
     }
 
+    /// List users newest first, continuing after `cursor` from a previous page
+    pub async fn list_users_after(
+        &self,
+        cursor: Option<&str>,
+        limit: i64,
+    ) -> Result<Page<User>, StoreError> {
+        let (created_at, id) = cursor_bounds(cursor)?;
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT id, email, agg_pubkey, balance, created_at, updated_at
+            FROM users
+            WHERE $1::timestamptz IS NULL OR (created_at, id) < ($1, $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3
+            "#,
+            created_at,
+            id,
+            fetch_limit(limit)?
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(Page::from_rows(users, limit, |user| {
+            Cursor::new(user.created_at, user.id)
+        }))
+    }
+
     /// Get total number of users
     pub async fn count_users(&self) -> Result<i64, StoreError> {
         let count = sqlx::query_scalar!("SELECT COUNT(*) FROM users")
-            .fetch_one(&self.pool)
+            .fetch_one(self.reader())
             .await?
             .unwrap_or(0);
 
         Ok(count)
     }
 
+    /// Get a user's role, e.g. to gate admin routes
+    pub async fn get_user_role(&self, user_id: Uuid) -> Result<UserRole, StoreError> {
+        let role = sqlx::query_scalar!(
+            r#"SELECT role as "role: UserRole" FROM users WHERE id = $1"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        Ok(role)
+    }
+
+    /// Get number of users per role (for admin dashboards)
+    pub async fn count_users_by_role(&self) -> Result<Vec<(UserRole, i64)>, StoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT role as "role: UserRole", COUNT(*) as "count!"
+            FROM users
+            GROUP BY role
+            ORDER BY role
+            "#
+        )
+        .fetch_all(self.reader())
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.role, row.count)).collect())
+    }
+
     // MPC
 
     pub async fn create_keyshare(
@@ -456,6 +1197,8 @@ impl Store {
             ));
         }
 
+        verify_keyshare_public_key(&request.private_key_share, &request.public_key)?;
+
         // Validate that user exists
         sqlx::query!("SELECT id FROM users WHERE id = $1", request.user_id)
             .fetch_optional(&self.pool)
@@ -481,7 +1224,7 @@ impl Store {
             r#"
             INSERT INTO mpc_keyshares (user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at)
             VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
-            RETURNING id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at
+            RETURNING id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
             "#,
             request.user_id,
             request.mpc_node_id,
@@ -505,7 +1248,7 @@ impl Store {
     ) -> Result<MpcKeyshare, StoreError> {
         let keyshare = sqlx::query_as!(
             MpcKeyshare,
-            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at
+            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
              FROM mpc_keyshares WHERE user_id = $1 AND mpc_node_id = $2",
             user_id,
             mpc_node_id
@@ -521,7 +1264,7 @@ impl Store {
     pub async fn get_user_keyshares(&self, user_id: Uuid) -> Result<Vec<MpcKeyshare>, StoreError> {
         let keyshares = sqlx::query_as!(
             MpcKeyshare,
-            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at
+            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
              FROM mpc_keyshares WHERE user_id = $1 ORDER BY mpc_node_id",
             user_id
         )
@@ -531,6 +1274,136 @@ impl Store {
         Ok(keyshares)
     }
 
+    /// Get which of a user's nodes are active and the signing threshold, without loading the secret shares
+    pub async fn get_keyshare_participation(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<KeyshareParticipation>, StoreError> {
+        let participation = sqlx::query_as!(
+            KeyshareParticipation,
+            "SELECT mpc_node_id, active, threshold FROM mpc_keyshares WHERE user_id = $1 ORDER BY mpc_node_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(participation)
+    }
+
+    /// Get the public configuration of a user's keyshares ordered by node id, never the secret shares
+    pub async fn get_keyshare_public_info(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<KeysharePublicInfo>, StoreError> {
+        let keyshares = sqlx::query_as!(
+            KeysharePublicInfo,
+            "SELECT mpc_node_id, public_key, threshold, total_shares FROM mpc_keyshares WHERE user_id = $1 ORDER BY mpc_node_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keyshares)
+    }
+
+    /// Get the public keys of a user's keyshares ordered by node id, optionally on one network only,
+    /// without loading the secret shares
+    pub async fn get_keyshare_public_keys(
+        &self,
+        user_id: Uuid,
+        network: Option<&str>,
+    ) -> Result<Vec<String>, StoreError> {
+        let public_keys = sqlx::query_scalar!(
+            "SELECT public_key FROM mpc_keyshares
+             WHERE user_id = $1 AND ($2::text IS NULL OR network = $2)
+             ORDER BY mpc_node_id",
+            user_id,
+            network
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(public_keys)
+    }
+
+    /// Get all keyshares for a user grouped by the network they belong to
+    pub async fn get_user_keyshares_by_network(
+        &self,
+        user_id: Uuid,
+    ) -> Result<HashMap<String, Vec<MpcKeyshare>>, StoreError> {
+        let rows = sqlx::query!(
+            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, network, created_at, updated_at
+             FROM mpc_keyshares WHERE user_id = $1 ORDER BY network, mpc_node_id",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut keyshares: HashMap<String, Vec<MpcKeyshare>> = HashMap::new();
+        for row in rows {
+            keyshares.entry(row.network).or_default().push(MpcKeyshare {
+                id: row.id,
+                user_id: row.user_id,
+                mpc_node_id: row.mpc_node_id,
+                private_key_share: row.private_key_share,
+                public_key: row.public_key,
+                threshold: row.threshold,
+                total_shares: row.total_shares,
+                active: row.active,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            });
+        }
+
+        Ok(keyshares)
+    }
+
+    /// Record the aggregated public key a user holds on a specific network
+    pub async fn set_network_agg_pubkey(
+        &self,
+        user_id: Uuid,
+        network: &str,
+        agg_pubkey: &str,
+    ) -> Result<(), StoreError> {
+        sqlx::query!(
+            "INSERT INTO user_wallets (user_id, network, agg_pubkey, updated_at) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (user_id, network) DO UPDATE SET agg_pubkey = EXCLUDED.agg_pubkey, updated_at = EXCLUDED.updated_at",
+            user_id,
+            network,
+            agg_pubkey,
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// List every network a user has keyshares or an aggregated wallet on
+    pub async fn get_user_networks(&self, user_id: Uuid) -> Result<Vec<NetworkWallet>, StoreError> {
+        let share_counts = sqlx::query!(
+            r#"SELECT network, COUNT(*) as "keyshare_count!" FROM mpc_keyshares WHERE user_id = $1 GROUP BY network"#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.network, row.keyshare_count))
+        .collect();
+
+        let wallets = sqlx::query!(
+            "SELECT network, agg_pubkey FROM user_wallets WHERE user_id = $1",
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(|row| (row.network, row.agg_pubkey))
+        .collect();
+
+        Ok(merge_network_wallets(share_counts, wallets))
+    }
+
     /// Get all keyshares for a specific MPC node (for node operators)
     pub async fn get_node_keyshares(
         &self,
@@ -542,7 +1415,7 @@ impl Store {
 
         let keyshares = sqlx::query_as!(
             MpcKeyshare,
-            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at
+            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
              FROM mpc_keyshares WHERE mpc_node_id = $1 ORDER BY created_at",
             mpc_node_id
         )
@@ -552,21 +1425,175 @@ impl Store {
         Ok(keyshares)
     }
 
-    /// Update keyshare private key (for key refresh operations)
+    /// Get a page of keyshares for a specific MPC node, optionally filtered by network, along with the total count
+    pub async fn get_node_keyshares_paged(
+        &self,
+        mpc_node_id: i32,
+        network: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<MpcKeyshare>, i64), StoreError> {
+        if mpc_node_id < 1 || mpc_node_id > 5 {
+            return Err(StoreError::InvalidInput("Invalid MPC node ID".to_string()));
+        }
+
+        let rows = sqlx::query!(
+            r#"SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at,
+                      COUNT(*) OVER () as "total!"
+             FROM mpc_keyshares
+             WHERE mpc_node_id = $1 AND ($2::text IS NULL OR network = $2)
+             ORDER BY created_at, id LIMIT $3 OFFSET $4"#,
+            mpc_node_id,
+            network,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let rows: Vec<(MpcKeyshare, i64)> = rows
+            .into_iter()
+            .map(|row| {
+                let keyshare = MpcKeyshare {
+                    id: row.id,
+                    user_id: row.user_id,
+                    mpc_node_id: row.mpc_node_id,
+                    private_key_share: row.private_key_share,
+                    public_key: row.public_key,
+                    threshold: row.threshold,
+                    total_shares: row.total_shares,
+                    active: row.active,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                };
+                (keyshare, row.total)
+            })
+            .collect();
+
+        match split_total(rows) {
+            // Past the last row the window has nothing to report, so count separately
+            (keyshares, None) if offset > 0 => {
+                let total = sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM mpc_keyshares WHERE mpc_node_id = $1 AND ($2::text IS NULL OR network = $2)",
+                    mpc_node_id,
+                    network
+                )
+                .fetch_one(&self.pool)
+                .await?
+                .unwrap_or(0);
+                Ok((keyshares, total))
+            }
+            (keyshares, total) => Ok((keyshares, total.unwrap_or(0))),
+        }
+    }
+
+    /// Update keyshare private key (for key refresh operations). The new share must still match
+    /// the node's stored public key, which the user's aggregated key is derived from.
     pub async fn update_keyshare(
         &self,
         user_id: Uuid,
         mpc_node_id: i32,
         new_private_key_share: &str,
     ) -> Result<(), StoreError> {
-        let updated_rows = sqlx::query!(
-            "UPDATE mpc_keyshares SET private_key_share = $1, updated_at = $2 
+        let mut tx = self.pool.begin().await?;
+
+        let public_key = sqlx::query_scalar!(
+            "SELECT public_key FROM mpc_keyshares WHERE user_id = $1 AND mpc_node_id = $2 FOR UPDATE",
+            user_id,
+            mpc_node_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(StoreError::KeyshareNotFound)?;
+        verify_keyshare_public_key(new_private_key_share, &public_key)?;
+
+        sqlx::query!(
+            "UPDATE mpc_keyshares SET private_key_share = $1, updated_at = $2, rotated_at = $2
              WHERE user_id = $3 AND mpc_node_id = $4",
             new_private_key_share,
             Utc::now(),
             user_id,
             mpc_node_id
         )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Swap all of a user's keyshares in one transaction, so signing never sees a mix of old and
+    /// new shares. Every node keeps its public key, so the aggregated key and wallet don't move.
+    pub async fn replace_keyshares(
+        &self,
+        user_id: Uuid,
+        refreshed: &[RefreshedKeyshare],
+    ) -> Result<(), StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        let current: Vec<(i32, String)> = sqlx::query!(
+            "SELECT mpc_node_id, public_key FROM mpc_keyshares WHERE user_id = $1 FOR UPDATE",
+            user_id
+        )
+        .fetch_all(&mut *tx)
+        .await?
+        .into_iter()
+        .map(|row| (row.mpc_node_id, row.public_key))
+        .collect();
+        if current.is_empty() {
+            return Err(StoreError::KeyshareNotFound);
+        }
+        check_share_swap(&current, refreshed)?;
+
+        let now = Utc::now();
+        for share in refreshed {
+            sqlx::query!(
+                "UPDATE mpc_keyshares SET private_key_share = $1, updated_at = $2, rotated_at = $2
+                 WHERE user_id = $3 AND mpc_node_id = $4",
+                share.private_key_share,
+                now,
+                user_id,
+                share.mpc_node_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get keyshares last rotated before the cutoff (due for rotation by security policy)
+    pub async fn get_keyshares_due_for_rotation(
+        &self,
+        older_than: Duration,
+    ) -> Result<Vec<MpcKeyshare>, StoreError> {
+        let cutoff = Utc::now() - older_than;
+
+        let keyshares = sqlx::query_as!(
+            MpcKeyshare,
+            "SELECT id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
+             FROM mpc_keyshares WHERE rotated_at < $1 ORDER BY rotated_at",
+            cutoff
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(keyshares)
+    }
+
+    /// Stamp a keyshare as freshly rotated
+    pub async fn mark_keyshare_rotated(
+        &self,
+        user_id: Uuid,
+        mpc_node_id: i32,
+    ) -> Result<(), StoreError> {
+        let updated_rows = sqlx::query!(
+            "UPDATE mpc_keyshares SET updated_at = $1, rotated_at = $1 WHERE user_id = $2 AND mpc_node_id = $3",
+            Utc::now(),
+            user_id,
+            mpc_node_id
+        )
         .execute(&self.pool)
         .await?
         .rows_affected();
@@ -584,17 +1611,33 @@ impl Store {
         user_id: Uuid,
         required_threshold: Option<i32>,
     ) -> Result<bool, StoreError> {
-        let threshold = required_threshold.unwrap_or(2);
+        let mut conn = self.pool.acquire().await?;
+        has_sufficient_keyshares(&mut conn, user_id, required_threshold).await
+    }
 
-        let keyshare_count = sqlx::query_scalar!(
-            "SELECT COUNT(*) FROM mpc_keyshares WHERE user_id = $1",
-            user_id
-        )
-        .fetch_one(&self.pool)
+    /// Take a node's share in or out of signing, e.g. while the node is down for maintenance
+    pub async fn set_keyshare_active(
+        &self,
+        user_id: Uuid,
+        mpc_node_id: i32,
+        active: bool,
+    ) -> Result<(), StoreError> {
+        let updated_rows = sqlx::query!(
+            "UPDATE mpc_keyshares SET active = $1, updated_at = $2 WHERE user_id = $3 AND mpc_node_id = $4",
+            active,
+            Utc::now(),
+            user_id,
+            mpc_node_id
+        )
+        .execute(&self.pool)
         .await?
-        .unwrap_or(0);
+        .rows_affected();
 
-        Ok(keyshare_count >= threshold as i64)
+        if updated_rows == 0 {
+            return Err(StoreError::KeyshareNotFound);
+        }
+
+        Ok(())
     }
 
     /// Get keyshare statistics for monitoring
@@ -647,12 +1690,14 @@ impl Store {
                 )));
             }
 
+            verify_keyshare_public_key(&private_key_share, &public_key)?;
+
             let keyshare = sqlx::query_as!(
                 MpcKeyshare,
                 r#"
                 INSERT INTO mpc_keyshares (user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at)
                 VALUES ($1, $2, $3, $4, $5, $6, $7, $7)
-                RETURNING id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, created_at, updated_at
+                RETURNING id, user_id, mpc_node_id, private_key_share, public_key, threshold, total_shares, active, created_at, updated_at
                 "#,
                 user_id,
                 mpc_node_id,
@@ -709,6 +1754,24 @@ impl Store {
         Ok(token_balances)
     }
 
+    /// Get token balances for many users in one query, keyed by user and ordered by symbol (unknown
+    /// symbols last); users without tokens are absent
+    pub async fn get_token_balances_for_users(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, Vec<TokenBalance>>, StoreError> {
+        let token_balances = sqlx::query_as!(
+            TokenBalance,
+            "SELECT id, user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at
+             FROM token_balances WHERE user_id = ANY($1) ORDER BY user_id, token_symbol, token_mint",
+            user_ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(group_by_user(token_balances))
+    }
+
     /// Create or update token balance for a user
     pub async fn update_token_balance(
         &self,
@@ -724,6 +1787,16 @@ impl Store {
             .await?
             .ok_or(StoreError::UserNotFound)?;
 
+        let mut tx = self.pool.begin().await?;
+        let previous_balance = sqlx::query_scalar!(
+            "SELECT balance FROM token_balances WHERE user_id = $1 AND token_mint = $2 FOR UPDATE",
+            user_id,
+            token_mint
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .unwrap_or(Decimal::ZERO);
+
         let token_balance = sqlx::query_as!(
             TokenBalance,
             r#"
@@ -744,9 +1817,28 @@ impl Store {
             decimals,
             Utc::now()
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        record_balance_adjustment(
+            &mut tx,
+            user_id,
+            Some(token_mint),
+            balance - previous_balance,
+            "set_balance",
+        )
         .await?;
+        tx.commit().await?;
 
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "admin",
+            "set_balance",
+            token_mint,
+            previous_balance,
+            balance,
+            None,
+        )]);
         Ok(token_balance)
     }
 
@@ -779,6 +1871,7 @@ impl Store {
             ));
         }
 
+        let mut tx = self.pool.begin().await?;
         let new_balance = sqlx::query_scalar!(
             "UPDATE token_balances SET balance = balance + $1, updated_at = $2 
              WHERE user_id = $3 AND token_mint = $4 
@@ -788,9 +1881,21 @@ impl Store {
             user_id,
             token_mint
         )
-        .fetch_one(&self.pool)
+        .fetch_one(&mut *tx)
         .await?;
 
+        record_balance_adjustment(&mut tx, user_id, Some(token_mint), amount, "credit").await?;
+        tx.commit().await?;
+
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "system",
+            "credit",
+            token_mint,
+            new_balance - amount,
+            new_balance,
+            None,
+        )]);
         Ok(new_balance)
     }
 
@@ -807,27 +1912,33 @@ impl Store {
             ));
         }
 
-        // Check current balance first
-        let current_balance = self.get_token_balance(user_id, token_mint).await?;
-        if current_balance < amount {
-            return Err(StoreError::InsufficientBalance);
-        }
-
+        // Locks the user row, so concurrent debits can't both pass the check
+        let mut tx = self.pool.begin().await?;
+        check_available_balance(&mut tx, user_id, Some(token_mint), amount).await?;
         let new_balance = sqlx::query_scalar!(
-            "UPDATE token_balances SET balance = balance - $1, updated_at = $2 
-             WHERE user_id = $3 AND token_mint = $4 
+            "UPDATE token_balances SET balance = balance - $1, updated_at = $2
+             WHERE user_id = $3 AND token_mint = $4
              RETURNING balance",
             amount,
             Utc::now(),
             user_id,
             token_mint
         )
-        .fetch_optional(&self.pool)
-        .await?
-        .ok_or(StoreError::InvalidInput(
-            "Token balance record not found".to_string(),
-        ))?;
+        .fetch_one(&mut *tx)
+        .await?;
+
+        record_balance_adjustment(&mut tx, user_id, Some(token_mint), -amount, "debit").await?;
+        tx.commit().await?;
 
+        self.audit_balance_changes(&[BalanceAuditRecord::new(
+            user_id,
+            "system",
+            "debit",
+            token_mint,
+            new_balance + amount,
+            new_balance,
+            None,
+        )]);
         Ok(new_balance)
     }
 
@@ -853,41 +1964,42 @@ impl Store {
         Ok(token_balance)
     }
 
-    /// Transfer tokens between users (internal transfer)
+    /// Transfer tokens between users (internal transfer), retrying transient conflicts
     pub async fn transfer_tokens(
         &self,
         from_user_id: Uuid,
         to_user_id: Uuid,
         token_mint: &str,
         amount: Decimal,
+    ) -> Result<(Decimal, Decimal), StoreError> {
+        retry_on_conflict(|| {
+            self.transfer_tokens_once(from_user_id, to_user_id, token_mint, amount)
+        })
+        .await
+    }
+
+    async fn transfer_tokens_once(
+        &self,
+        from_user_id: Uuid,
+        to_user_id: Uuid,
+        token_mint: &str,
+        amount: Decimal,
     ) -> Result<(Decimal, Decimal), StoreError> {
         if amount <= Decimal::ZERO {
             return Err(StoreError::InvalidInput(
                 "Transfer amount must be positive".to_string(),
             ));
         }
+        self.check_min_transfer_amount(amount)?;
 
         // Use transaction for atomic transfer
         let mut tx = self.pool.begin().await?;
 
-        // Check sender balance
-        let sender_balance: Decimal = sqlx::query_scalar!(
-            "SELECT balance FROM token_balances WHERE user_id = $1 AND token_mint = $2",
-            from_user_id,
-            token_mint
-        )
-        .fetch_optional(&mut *tx)
-        .await?
-        .unwrap_or(Decimal::ZERO);
-
-        if sender_balance < amount {
-            return Err(StoreError::InsufficientBalance);
-        }
-
-        // Subtract from sender
+        // Funds held by pending withdrawals can't be transferred away
+        check_available_balance(&mut tx, from_user_id, Some(token_mint), amount).await?;
         let new_sender_balance = sqlx::query_scalar!(
-            "UPDATE token_balances SET balance = balance - $1, updated_at = $2 
-             WHERE user_id = $3 AND token_mint = $4 
+            "UPDATE token_balances SET balance = balance - $1, updated_at = $2
+             WHERE user_id = $3 AND token_mint = $4
              RETURNING balance",
             amount,
             Utc::now(),
@@ -898,25 +2010,133 @@ impl Store {
         .await?;
 
         // Add to receiver (create record if doesn't exist)
+        let token_info = lookup_token_info(&mut tx, token_mint).await?;
         let new_receiver_balance = sqlx::query_scalar!(
             r#"
             INSERT INTO token_balances (user_id, token_mint, token_symbol, balance, decimals, created_at, updated_at)
-            VALUES ($1, $2, 'UNKNOWN', $3, 6, $4, $4)
-            ON CONFLICT (user_id, token_mint) 
-            DO UPDATE SET 
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            ON CONFLICT (user_id, token_mint)
+            DO UPDATE SET
                 balance = token_balances.balance + EXCLUDED.balance,
+                token_symbol = COALESCE(token_balances.token_symbol, EXCLUDED.token_symbol),
+                decimals = COALESCE(token_balances.decimals, EXCLUDED.decimals),
                 updated_at = EXCLUDED.updated_at
             RETURNING balance
             "#,
             to_user_id,
             token_mint,
+            token_info.symbol,
             amount,
+            token_info.decimals,
             Utc::now()
         )
         .fetch_one(&mut *tx)
         .await?;
 
+        record_balance_adjustment(
+            &mut tx,
+            from_user_id,
+            Some(token_mint),
+            -amount,
+            "transfer_out",
+        )
+        .await?;
+        record_balance_adjustment(&mut tx, to_user_id, Some(token_mint), amount, "transfer_in")
+            .await?;
+        tx.commit().await?;
+
+        self.audit_balance_changes(&[
+            BalanceAuditRecord::new(
+                from_user_id,
+                from_user_id.to_string(),
+                "transfer_out",
+                token_mint,
+                new_sender_balance + amount,
+                new_sender_balance,
+                None,
+            ),
+            BalanceAuditRecord::new(
+                to_user_id,
+                from_user_id.to_string(),
+                "transfer_in",
+                token_mint,
+                new_receiver_balance - amount,
+                new_receiver_balance,
+                None,
+            ),
+        ]);
+        Ok((new_sender_balance, new_receiver_balance))
+    }
+
+    /// Move native SOL between two internal users in one transaction, returning the sender's
+    /// and receiver's new balances. Nothing changes if either side fails; transient conflicts
+    /// are retried.
+    pub async fn transfer_sol(
+        &self,
+        from_user_id: Uuid,
+        to_user_id: Uuid,
+        amount: Decimal,
+    ) -> Result<(Decimal, Decimal), StoreError> {
+        retry_on_conflict(|| self.transfer_sol_once(from_user_id, to_user_id, amount)).await
+    }
+
+    async fn transfer_sol_once(
+        &self,
+        from_user_id: Uuid,
+        to_user_id: Uuid,
+        amount: Decimal,
+    ) -> Result<(Decimal, Decimal), StoreError> {
+        validate_sol_transfer(from_user_id, to_user_id, amount)?;
+        self.check_min_transfer_amount(amount)?;
+
+        let mut tx = self.pool.begin().await?;
+        let now = Utc::now();
+
+        // Funds held by pending withdrawals can't be transferred away
+        check_available_balance(&mut tx, from_user_id, None, amount).await?;
+        let new_sender_balance = sqlx::query_scalar!(
+            "UPDATE users SET balance = balance - $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+            amount,
+            now,
+            from_user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let new_receiver_balance = sqlx::query_scalar!(
+            "UPDATE users SET balance = balance + $1, updated_at = $2 WHERE id = $3 RETURNING balance",
+            amount,
+            now,
+            to_user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(StoreError::UserNotFound)?;
+
+        record_balance_adjustment(&mut tx, from_user_id, None, -amount, "transfer_out").await?;
+        record_balance_adjustment(&mut tx, to_user_id, None, amount, "transfer_in").await?;
         tx.commit().await?;
+
+        self.audit_balance_changes(&[
+            BalanceAuditRecord::new(
+                from_user_id,
+                from_user_id.to_string(),
+                "transfer_out",
+                SOL_ASSET,
+                new_sender_balance + amount,
+                new_sender_balance,
+                None,
+            ),
+            BalanceAuditRecord::new(
+                to_user_id,
+                from_user_id.to_string(),
+                "transfer_in",
+                SOL_ASSET,
+                new_receiver_balance - amount,
+                new_receiver_balance,
+                None,
+            ),
+        ]);
         Ok((new_sender_balance, new_receiver_balance))
     }
 
@@ -939,4 +2159,1465 @@ impl Store {
 
         Ok(deleted_count)
     }
+
+    /// Delete token balances too small for their token to represent (below 10^-decimals).
+    /// `threshold_per_token` overrides the cutoff for specific mints. Returns the number of rows deleted.
+    pub async fn cleanup_dust_balances(
+        &self,
+        threshold_per_token: &HashMap<String, Decimal>,
+    ) -> Result<u64, StoreError> {
+        let mut tx = self.pool.begin().await?;
+        let overridden_mints: Vec<String> = threshold_per_token.keys().cloned().collect();
+
+        // Written-off dust goes to the ledger so `verify_balance_integrity` still balances
+        let deleted = sqlx::query_scalar!(
+            r#"
+            WITH deleted AS (
+                DELETE FROM token_balances
+                WHERE balance < power(10::numeric, -decimals) AND NOT (token_mint = ANY($1))
+                RETURNING user_id, token_mint, balance
+            ), written_off AS (
+                INSERT INTO balance_adjustments (user_id, token_mint, amount, operation)
+                SELECT user_id, token_mint, -balance, 'dust_cleanup' FROM deleted WHERE balance <> 0
+            )
+            SELECT COUNT(*) as "count!" FROM deleted
+            "#,
+            &overridden_mints
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        let mut deleted_count = deleted as u64;
+
+        for (token_mint, threshold) in threshold_per_token {
+            let deleted = sqlx::query_scalar!(
+                r#"
+                WITH deleted AS (
+                    DELETE FROM token_balances WHERE token_mint = $1 AND balance < $2
+                    RETURNING user_id, token_mint, balance
+                ), written_off AS (
+                    INSERT INTO balance_adjustments (user_id, token_mint, amount, operation)
+                    SELECT user_id, token_mint, -balance, 'dust_cleanup' FROM deleted WHERE balance <> 0
+                )
+                SELECT COUNT(*) as "count!" FROM deleted
+                "#,
+                token_mint,
+                threshold
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            deleted_count += deleted as u64;
+        }
+
+        tx.commit().await?;
+        Ok(deleted_count)
+    }
+
+    /// Find token balance records whose user no longer exists
+    pub async fn find_orphaned_token_balances(&self) -> Result<Vec<TokenBalance>, StoreError> {
+        let token_balances = sqlx::query_as!(
+            TokenBalance,
+            "SELECT tb.id, tb.user_id, tb.token_mint, tb.token_symbol, tb.balance, tb.decimals, tb.created_at, tb.updated_at
+             FROM token_balances tb
+             LEFT JOIN users u ON u.id = tb.user_id
+             WHERE u.id IS NULL
+             ORDER BY tb.created_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(token_balances)
+    }
+
+    /// Delete token balance records whose user no longer exists (cleanup)
+    pub async fn cleanup_orphaned_token_balances(&self) -> Result<u64, StoreError> {
+        let deleted_count = sqlx::query!(
+            "DELETE FROM token_balances tb
+             WHERE NOT EXISTS (SELECT 1 FROM users u WHERE u.id = tb.user_id)"
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(deleted_count)
+    }
+
+    /// Get a user's most recent activity (signup, keyshares, transactions and their settlement), newest first
+    pub async fn get_user_activity_timeline(
+        &self,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<TimelineEvent>, StoreError> {
+        // Transactions keep no status history, so settlement is dated by the row's last update
+        let events = sqlx::query_as!(
+            TimelineEvent,
+            r#"
+            SELECT event_type AS "event_type!", reference_id AS "reference_id!", detail, occurred_at AS "occurred_at!"
+            FROM (
+                SELECT 'signup' AS event_type, id AS reference_id, email AS detail, created_at AS occurred_at
+                FROM users WHERE id = $1
+                UNION ALL
+                SELECT 'keyshare_created', id, 'node ' || mpc_node_id, created_at
+                FROM mpc_keyshares WHERE user_id = $1
+                UNION ALL
+                SELECT 'transaction_created', id, transaction_type::text || ' ' || amount, created_at
+                FROM transactions WHERE user_id = $1
+                UNION ALL
+                SELECT 'transaction_' || status::text, id, tx_signature, updated_at
+                FROM transactions WHERE user_id = $1 AND status <> 'pending'
+            ) events
+            ORDER BY occurred_at DESC, event_type
+            LIMIT $2
+            "#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(events)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum UserIdentifier<'a> {
+    Id(Uuid),
+    Email(&'a str),
+}
+
+impl<'a> UserIdentifier<'a> {
+    fn parse(identifier: &'a str) -> Result<Self, StoreError> {
+        let identifier = identifier.trim();
+        if identifier.is_empty() {
+            return Err(StoreError::InvalidInput(
+                "User identifier must not be empty".to_string(),
+            ));
+        }
+
+        Ok(match Uuid::parse_str(identifier) {
+            Ok(user_id) => UserIdentifier::Id(user_id),
+            Err(_) => UserIdentifier::Email(identifier),
+        })
+    }
+}
+
+/// Combine per-network keyshare counts with per-network wallets, sorted by network
+fn merge_network_wallets(
+    share_counts: HashMap<String, i64>,
+    mut wallets: HashMap<String, String>,
+) -> Vec<NetworkWallet> {
+    let mut networks: Vec<NetworkWallet> = share_counts
+        .into_iter()
+        .map(|(network, keyshare_count)| NetworkWallet {
+            agg_pubkey: wallets.remove(&network),
+            network,
+            keyshare_count,
+        })
+        .collect();
+
+    // Wallets whose keyshares have since been removed are still reported
+    networks.extend(
+        wallets
+            .into_iter()
+            .map(|(network, agg_pubkey)| NetworkWallet {
+                network,
+                agg_pubkey: Some(agg_pubkey),
+                keyshare_count: 0,
+            }),
+    );
+
+    networks.sort_by(|a, b| a.network.cmp(&b.network));
+    networks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CreateUserRequest, NetworkWallet, RefreshedKeyshare, StoreError, TokenBalance,
+        TransactionStatus, TransactionType, UserIdentifier, UserRole, can_sign, check_share_swap,
+        group_by_user, hash_password, merge_network_wallets, normalize_email, prepare_user_batch,
+        validate_email, validate_sol_transfer, verify_keyshare_public_key, verify_password,
+    };
+    use crate::Store;
+    use crate::testing::{insert_keyshare, insert_transaction, insert_user};
+    use chrono::{Duration, Utc};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_insufficient_balance_shortfall() {
+        let err = StoreError::InsufficientBalance {
+            required: Decimal::new(250, 2),
+            available: Decimal::new(100, 2),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Insufficient balance: required 2.50, available 1.00, short by 1.50"
+        );
+    }
+
+    #[test]
+    fn test_database_error_chains_source() {
+        use std::error::Error;
+
+        let err: StoreError = sqlx::Error::RowNotFound.into();
+        assert!(matches!(err, StoreError::DatabaseError(_)));
+        assert!(err.to_string().starts_with("Database error: "));
+        assert!(
+            err.source()
+                .is_some_and(|source| source.downcast_ref::<sqlx::Error>().is_some())
+        );
+        assert!(StoreError::UserNotFound.source().is_none());
+    }
+
+    #[test]
+    fn test_merge_network_wallets_reports_each_network() {
+        let share_counts = HashMap::from([("devnet".to_string(), 3), ("mainnet".to_string(), 2)]);
+        let wallets = HashMap::from([
+            ("devnet".to_string(), "DevAggPubkey".to_string()),
+            ("mainnet".to_string(), "MainAggPubkey".to_string()),
+        ]);
+
+        assert_eq!(
+            merge_network_wallets(share_counts, wallets),
+            vec![
+                NetworkWallet {
+                    network: "devnet".to_string(),
+                    agg_pubkey: Some("DevAggPubkey".to_string()),
+                    keyshare_count: 3,
+                },
+                NetworkWallet {
+                    network: "mainnet".to_string(),
+                    agg_pubkey: Some("MainAggPubkey".to_string()),
+                    keyshare_count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_network_wallets_without_aggregated_key() {
+        let share_counts = HashMap::from([("testnet".to_string(), 1)]);
+        let wallets = HashMap::from([("devnet".to_string(), "DevAggPubkey".to_string())]);
+
+        let networks = merge_network_wallets(share_counts, wallets);
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].network, "devnet");
+        assert_eq!(networks[0].keyshare_count, 0);
+        assert_eq!(networks[1].network, "testnet");
+        assert_eq!(networks[1].agg_pubkey, None);
+    }
+
+    #[test]
+    fn test_user_identifier_by_uuid() {
+        let user_id = Uuid::new_v4();
+        assert_eq!(
+            UserIdentifier::parse(&user_id.to_string()).unwrap(),
+            UserIdentifier::Id(user_id)
+        );
+    }
+
+    #[test]
+    fn test_user_identifier_by_email() {
+        assert_eq!(
+            UserIdentifier::parse(" alice@example.com ").unwrap(),
+            UserIdentifier::Email("alice@example.com")
+        );
+    }
+
+    #[test]
+    fn test_user_identifier_empty() {
+        assert!(matches!(
+            UserIdentifier::parse("  "),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    fn keypair_from_seed(seed: [u8; 32]) -> (String, String) {
+        let secret = ed25519_dalek::SecretKey::from_bytes(&seed).unwrap();
+        let public = ed25519_dalek::PublicKey::from(&secret).to_bytes();
+        let mut keypair = seed.to_vec();
+        keypair.extend_from_slice(&public);
+        (
+            bs58::encode(keypair).into_string(),
+            bs58::encode(public).into_string(),
+        )
+    }
+
+    #[test]
+    fn test_keyshare_public_key_matches() {
+        let (share, public_key) = keypair_from_seed([7u8; 32]);
+        assert!(verify_keyshare_public_key(&share, &public_key).is_ok());
+
+        let seed_only = bs58::encode([7u8; 32]).into_string();
+        assert!(verify_keyshare_public_key(&seed_only, &public_key).is_ok());
+    }
+
+    #[test]
+    fn test_keyshare_public_key_mismatch_rejected() {
+        let (share, _) = keypair_from_seed([7u8; 32]);
+        let (_, other_public_key) = keypair_from_seed([9u8; 32]);
+        let err = verify_keyshare_public_key(&share, &other_public_key).unwrap_err();
+        assert!(matches!(
+            err,
+            StoreError::KeyshareMismatch { ref public_key, .. } if *public_key == other_public_key
+        ));
+    }
+
+    #[test]
+    fn test_keypair_with_foreign_public_half_rejected() {
+        let (share, public_key) = keypair_from_seed([7u8; 32]);
+        let (_, other_public_key) = keypair_from_seed([9u8; 32]);
+        let mut bytes = bs58::decode(&share).into_vec().unwrap();
+        bytes[32..].copy_from_slice(&bs58::decode(&other_public_key).into_vec().unwrap());
+        let tampered = bs58::encode(bytes).into_string();
+        assert!(matches!(
+            verify_keyshare_public_key(&tampered, &public_key),
+            Err(StoreError::KeyshareMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_opaque_keyshare_skips_derivation() {
+        assert!(verify_keyshare_public_key("enc:v1:aGVsbG8=", "anything").is_ok());
+    }
+
+    #[test]
+    fn test_deactivating_shares_below_threshold_disables_signing() {
+        // 3 shares with a 2-of-3 threshold, then nodes go down for maintenance one by one
+        assert!(can_sign(3, Some(2)));
+        assert!(can_sign(2, Some(2)));
+        assert!(!can_sign(1, Some(2)));
+        assert!(!can_sign(3, None));
+    }
+
+    #[test]
+    fn test_sol_transfer_validation() {
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        assert!(validate_sol_transfer(alice, bob, Decimal::new(5, 1)).is_ok());
+        assert!(matches!(
+            validate_sol_transfer(alice, alice, Decimal::ONE),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            validate_sol_transfer(alice, bob, Decimal::ZERO),
+            Err(StoreError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            validate_sol_transfer(alice, bob, Decimal::NEGATIVE_ONE),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_group_token_balances_by_user() {
+        let balance = |user_id: Uuid, mint: &str| TokenBalance {
+            id: Uuid::new_v4(),
+            user_id,
+            token_mint: mint.to_string(),
+            token_symbol: None,
+            balance: Decimal::ONE,
+            decimals: Some(6),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        let (alice, bob, unknown) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let grouped = group_by_user(vec![
+            balance(alice, "mint-a"),
+            balance(alice, "mint-b"),
+            balance(bob, "mint-a"),
+        ]);
+
+        assert_eq!(grouped.len(), 2);
+        let alice_mints: Vec<&str> = grouped[&alice]
+            .iter()
+            .map(|balance| balance.token_mint.as_str())
+            .collect();
+        assert_eq!(alice_mints, vec!["mint-a", "mint-b"]);
+        assert_eq!(grouped[&bob].len(), 1);
+        assert!(!grouped.contains_key(&unknown));
+    }
+
+    #[test]
+    fn test_signup_hash_verifies_on_signin() {
+        // Sign-up stores `hash_password` output; sign-in checks it with `verify_password`
+        let stored = hash_password("correct horse battery").unwrap();
+        assert!(stored.starts_with("$argon2"));
+        assert!(verify_password("correct horse battery", &stored).unwrap());
+        assert!(!verify_password("wrong password", &stored).unwrap());
+    }
+
+    #[test]
+    fn test_legacy_bcrypt_hash_still_verifies() {
+        let legacy = bcrypt::hash("hunter22", 4).unwrap();
+        assert!(verify_password("hunter22", &legacy).unwrap());
+        assert!(!verify_password("hunter23", &legacy).unwrap());
+    }
+
+    #[test]
+    fn test_share_swap_must_cover_current_nodes() {
+        let share = |mpc_node_id| RefreshedKeyshare {
+            mpc_node_id,
+            // Not a base58 seed, so treated as an encrypted share
+            private_key_share: format!("encrypted-{}", mpc_node_id),
+            public_key: format!("pubkey-{}", mpc_node_id),
+        };
+
+        let current: Vec<(i32, String)> = (1..=3)
+            .map(|mpc_node_id| (mpc_node_id, format!("pubkey-{}", mpc_node_id)))
+            .collect();
+
+        assert!(check_share_swap(&current, &[share(3), share(1), share(2)]).is_ok());
+        for refreshed in [
+            vec![share(1), share(2)],
+            vec![share(1), share(2), share(4)],
+            vec![share(1), share(1), share(2), share(3)],
+        ] {
+            assert!(matches!(
+                check_share_swap(&current, &refreshed),
+                Err(StoreError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_share_swap_must_keep_public_keys() {
+        // A new public key for any node would change the aggregated key, and so the wallet
+        let refreshed = RefreshedKeyshare {
+            mpc_node_id: 1,
+            private_key_share: "encrypted-1".to_string(),
+            public_key: "pubkey-new".to_string(),
+        };
+
+        assert!(matches!(
+            check_share_swap(&[(1, "pubkey-1".to_string())], &[refreshed]),
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_share_swap_checks_public_keys() {
+        let seed = [7u8; 32];
+        let public_key = bs58::encode([0u8; 32]).into_string();
+        let refreshed = RefreshedKeyshare {
+            mpc_node_id: 1,
+            private_key_share: bs58::encode(seed).into_string(),
+            public_key: public_key.clone(),
+        };
+
+        assert!(matches!(
+            check_share_swap(&[(1, public_key)], &[refreshed]),
+            Err(StoreError::KeyshareMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_email_case_variants_collide() {
+        assert_eq!(normalize_email("  Foo@Example.COM "), "foo@example.com");
+
+        let request = |email: &str| CreateUserRequest {
+            email: email.to_string(),
+            password: "correct-horse".to_string(),
+        };
+        let mut batch = vec![request("Foo@x.com"), request(" foo@X.com")];
+        assert!(matches!(
+            prepare_user_batch(&mut batch),
+            Err(StoreError::UserExists)
+        ));
+
+        let mut batch = vec![request(" Foo@x.com"), request("bar@x.com")];
+        prepare_user_batch(&mut batch).unwrap();
+        assert_eq!(batch[0].email, "foo@x.com");
+    }
+
+    #[test]
+    fn test_validate_email() {
+        for valid in [
+            "user@example.com",
+            "first.last+tag@sub.example.co.uk",
+            "x@my-domain.io",
+        ] {
+            assert!(validate_email(valid).is_ok(), "{} should be valid", valid);
+        }
+
+        for (invalid, reason) in [
+            ("a@b", "no dot"),
+            ("user.example.com", "missing @"),
+            ("us er@example.com", "whitespace"),
+            ("a@b@example.com", "more than one @"),
+            ("@example.com", "local part"),
+            (".user@example.com", "dot"),
+            ("user@-example.com", "hyphens"),
+            ("user@example..com", "domain label"),
+            ("user@example.c", "top-level"),
+            ("user@example.123", "top-level"),
+        ] {
+            match validate_email(invalid) {
+                Err(StoreError::InvalidInput(message)) => {
+                    assert!(message.contains(reason), "{}: {}", invalid, message)
+                }
+                other => panic!("{} should be rejected, got {:?}", invalid, other),
+            }
+        }
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_user_profile_reads_one_snapshot(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TWO).await;
+        insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::TWO,
+        )
+        .await;
+
+        let profile = store.get_user_profile(user_id).await.unwrap();
+        assert_eq!(profile.sol_balance, Decimal::TWO);
+        assert_eq!(profile.transaction_count, 1);
+        assert_eq!(profile.keyshare_status.keyshare_count, 0);
+
+        assert!(matches!(
+            store.get_user_profile(Uuid::new_v4()).await,
+            Err(StoreError::UserNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_keyshare_public_keys_ordered_without_secrets(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for mpc_node_id in [3, 1, 2] {
+            insert_keyshare(&store, user_id, mpc_node_id).await;
+        }
+        sqlx::query!(
+            "UPDATE mpc_keyshares SET public_key = 'public-' || mpc_node_id,
+                 private_key_share = 'secret-' || mpc_node_id
+             WHERE user_id = $1",
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            store.get_keyshare_public_keys(user_id, None).await.unwrap(),
+            vec!["public-1", "public-2", "public-3"]
+        );
+        assert!(
+            store
+                .get_keyshare_public_keys(Uuid::new_v4(), None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_replace_keyshares_swaps_every_node(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        insert_keyshare(&store, user_id, 1).await;
+        insert_keyshare(&store, user_id, 2).await;
+        let share = |mpc_node_id| RefreshedKeyshare {
+            mpc_node_id,
+            private_key_share: format!("refreshed-{}", mpc_node_id),
+            // insert_keyshare's public key, which a refresh must keep
+            public_key: "unused".to_string(),
+        };
+        let mut rekeyed = share(2);
+        rekeyed.public_key = "refreshed-public-2".to_string();
+
+        // A refresh that skips node 2, or changes its public key, is rejected and leaves both
+        // shares alone
+        for refreshed in [vec![share(1)], vec![share(1), rekeyed]] {
+            assert!(matches!(
+                store.replace_keyshares(user_id, &refreshed).await,
+                Err(StoreError::InvalidInput(_))
+            ));
+        }
+        let keyshares = store.get_user_keyshares(user_id).await.unwrap();
+        assert!(keyshares.iter().all(|k| k.private_key_share == "unused"));
+
+        store
+            .replace_keyshares(user_id, &[share(1), share(2)])
+            .await
+            .unwrap();
+        let keyshares = store.get_user_keyshares(user_id).await.unwrap();
+        assert_eq!(keyshares.len(), 2);
+        for keyshare in keyshares {
+            assert_eq!(
+                keyshare.private_key_share,
+                format!("refreshed-{}", keyshare.mpc_node_id)
+            );
+            assert_eq!(keyshare.public_key, "unused");
+        }
+
+        assert!(matches!(
+            store.replace_keyshares(Uuid::new_v4(), &[share(1)]).await,
+            Err(StoreError::KeyshareNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_update_keyshare_checks_stored_public_key(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        insert_keyshare(&store, user_id, 1).await;
+        let (share, public_key) = keypair_from_seed([7u8; 32]);
+        let (other_share, _) = keypair_from_seed([8u8; 32]);
+        sqlx::query!(
+            "UPDATE mpc_keyshares SET public_key = $1 WHERE user_id = $2",
+            public_key,
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        // A seed for some other key is refused and the stored share is left alone
+        assert!(matches!(
+            store.update_keyshare(user_id, 1, &other_share).await,
+            Err(StoreError::KeyshareMismatch { .. })
+        ));
+        let keyshares = store.get_user_keyshares(user_id).await.unwrap();
+        assert_eq!(keyshares[0].private_key_share, "unused");
+
+        store.update_keyshare(user_id, 1, &share).await.unwrap();
+        let keyshares = store.get_user_keyshares(user_id).await.unwrap();
+        assert_eq!(keyshares[0].private_key_share, share);
+
+        assert!(matches!(
+            store.update_keyshare(user_id, 2, &share).await,
+            Err(StoreError::KeyshareNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_concurrent_subtractions_cannot_overdraw(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::from(5)).await;
+
+        let (first, second) = tokio::join!(
+            store.subtract_user_balance(user_id, Decimal::from(3)),
+            store.subtract_user_balance(user_id, Decimal::from(3)),
+        );
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(
+            results
+                .iter()
+                .any(|result| matches!(result, Err(StoreError::InsufficientBalance { .. })))
+        );
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TWO);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_transfer_sol_conserves_and_rolls_back(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let sender = insert_user(&store, Decimal::from(5)).await;
+        let receiver = insert_user(&store, Decimal::ONE).await;
+
+        let (sender_balance, receiver_balance) = store
+            .transfer_sol(sender, receiver, Decimal::from(3))
+            .await
+            .unwrap();
+        assert_eq!(sender_balance, Decimal::TWO);
+        assert_eq!(receiver_balance, Decimal::from(4));
+        assert_eq!(
+            store.get_user_balance(sender).await.unwrap()
+                + store.get_user_balance(receiver).await.unwrap(),
+            Decimal::from(6)
+        );
+
+        assert!(matches!(
+            store.transfer_sol(sender, receiver, Decimal::from(3)).await,
+            Err(StoreError::InsufficientBalance { .. })
+        ));
+
+        // The debit has already run when the missing receiver is found; it must not stick
+        assert!(matches!(
+            store
+                .transfer_sol(sender, Uuid::new_v4(), Decimal::ONE)
+                .await,
+            Err(StoreError::UserNotFound)
+        ));
+        assert_eq!(store.get_user_balance(sender).await.unwrap(), Decimal::TWO);
+        assert_eq!(
+            store.get_user_balance(receiver).await.unwrap(),
+            Decimal::from(4)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_count_users_by_role(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        for _ in 0..3 {
+            insert_user(&store, Decimal::ZERO).await;
+        }
+        let admins = [
+            insert_user(&store, Decimal::ZERO).await,
+            insert_user(&store, Decimal::ZERO).await,
+        ];
+        sqlx::query!(
+            "UPDATE users SET role = 'admin' WHERE id = ANY($1)",
+            &admins[..]
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+
+        let counts = store.count_users_by_role().await.unwrap();
+        assert!(
+            matches!(counts[..], [(UserRole::User, 3), (UserRole::Admin, 2)]),
+            "{:?}",
+            counts
+        );
+
+        assert_eq!(
+            store.get_user_role(admins[0]).await.unwrap(),
+            UserRole::Admin
+        );
+        assert!(matches!(
+            store.get_user_role(Uuid::new_v4()).await,
+            Err(StoreError::UserNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_bcrypt_user_is_rehashed_on_login(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let legacy_hash = bcrypt::hash("password123", 4).unwrap();
+        sqlx::query!(
+            "UPDATE users SET email = 'legacy@example.com', password_hash = $1 WHERE id = $2",
+            legacy_hash,
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        let stored_hash = || {
+            sqlx::query_scalar!("SELECT password_hash FROM users WHERE id = $1", user_id)
+                .fetch_one(&store.pool)
+        };
+
+        // A wrong password leaves the legacy hash untouched
+        assert!(
+            store
+                .authenticate_user("legacy@example.com", "wrong-password")
+                .await
+                .is_err()
+        );
+        assert_eq!(stored_hash().await.unwrap(), legacy_hash);
+
+        let user = store
+            .authenticate_user("legacy@example.com", "password123")
+            .await
+            .unwrap();
+        assert_eq!(user.id, user_id);
+        let migrated = stored_hash().await.unwrap();
+        assert!(migrated.starts_with("$argon2"), "{}", migrated);
+
+        // The new hash keeps working
+        assert!(
+            store
+                .authenticate_user("legacy@example.com", "password123")
+                .await
+                .is_ok()
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_node_keyshares_page_through(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        for _ in 0..5 {
+            let user_id = insert_user(&store, Decimal::ZERO).await;
+            insert_keyshare(&store, user_id, 2).await;
+            insert_keyshare(&store, user_id, 3).await;
+        }
+
+        let mut seen = Vec::new();
+        for (offset, expected) in [(0, 2), (2, 2), (4, 1), (6, 0)] {
+            let (page, total) = store
+                .get_node_keyshares_paged(2, None, 2, offset)
+                .await
+                .unwrap();
+            assert_eq!(total, 5);
+            assert_eq!(page.len(), expected);
+            assert!(page.iter().all(|keyshare| keyshare.mpc_node_id == 2));
+            seen.extend(page.into_iter().map(|keyshare| keyshare.id));
+        }
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), 5);
+
+        assert!(matches!(
+            store.get_node_keyshares_paged(9, None, 2, 0).await,
+            Err(StoreError::InvalidInput(_))
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_node_keyshares_filter_by_network(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        for network in ["mainnet", "mainnet", "mainnet", "devnet", "devnet"] {
+            let user_id = insert_user(&store, Decimal::ZERO).await;
+            insert_keyshare(&store, user_id, 1).await;
+            sqlx::query!(
+                "UPDATE mpc_keyshares SET network = $1 WHERE user_id = $2",
+                network,
+                user_id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let (first, total) = store
+            .get_node_keyshares_paged(1, Some("mainnet"), 2, 0)
+            .await
+            .unwrap();
+        assert_eq!((first.len(), total), (2, 3));
+        let (rest, total) = store
+            .get_node_keyshares_paged(1, Some("mainnet"), 2, 2)
+            .await
+            .unwrap();
+        assert_eq!((rest.len(), total), (1, 3));
+        assert!(first.iter().all(|keyshare| keyshare.id != rest[0].id));
+
+        let (_, total) = store
+            .get_node_keyshares_paged(1, None, 10, 0)
+            .await
+            .unwrap();
+        assert_eq!(total, 5);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_orphaned_token_balances_found_and_removed(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        store
+            .update_token_balance(user_id, "mint-kept", "KEPT", Decimal::ONE, 6)
+            .await
+            .unwrap();
+
+        // The foreign key now prevents orphans, so older data is mimicked with its check off
+        let orphan_owner = Uuid::new_v4();
+        let mut conn = store.pool.acquire().await.unwrap();
+        sqlx::query("SET session_replication_role = replica")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "INSERT INTO token_balances (user_id, token_mint, token_symbol, balance)
+             VALUES ($1, 'mint-orphaned', 'ORPH', 5)",
+            orphan_owner
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query("RESET session_replication_role")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let orphans = store.find_orphaned_token_balances().await.unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].user_id, orphan_owner);
+
+        assert_eq!(store.cleanup_orphaned_token_balances().await.unwrap(), 1);
+        assert!(
+            store
+                .find_orphaned_token_balances()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            store.get_token_balance(user_id, "mint-kept").await.unwrap(),
+            Decimal::ONE
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_rotating_agg_pubkey_archives_the_prior_key(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+
+        // The first key and a repeat of it have nothing to archive
+        store
+            .update_user_agg_pubkey(user_id, "key-1")
+            .await
+            .unwrap();
+        store
+            .update_user_agg_pubkey(user_id, "key-1")
+            .await
+            .unwrap();
+        assert!(
+            store
+                .get_agg_pubkey_history(user_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        store
+            .update_user_agg_pubkey(user_id, "key-2")
+            .await
+            .unwrap();
+        store
+            .update_user_agg_pubkey(user_id, "key-3")
+            .await
+            .unwrap();
+
+        let history = store.get_agg_pubkey_history(user_id).await.unwrap();
+        let keys: Vec<&str> = history
+            .iter()
+            .map(|entry| entry.agg_pubkey.as_str())
+            .collect();
+        assert_eq!(keys, vec!["key-2", "key-1"]);
+        assert!(history.iter().all(|entry| entry.user_id == user_id));
+        assert_eq!(
+            store.get_user(user_id).await.unwrap().agg_pubkey.as_deref(),
+            Some("key-3")
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_keyshares_grouped_by_network(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for (mpc_node_id, network) in [(1, "mainnet"), (2, "devnet"), (3, "mainnet")] {
+            insert_keyshare(&store, user_id, mpc_node_id).await;
+            sqlx::query!(
+                "UPDATE mpc_keyshares SET network = $1 WHERE user_id = $2 AND mpc_node_id = $3",
+                network,
+                user_id,
+                mpc_node_id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let by_network = store.get_user_keyshares_by_network(user_id).await.unwrap();
+        let nodes = |network: &str| -> Vec<i32> {
+            by_network[network]
+                .iter()
+                .map(|keyshare| keyshare.mpc_node_id)
+                .collect()
+        };
+        assert_eq!(by_network.len(), 2);
+        assert_eq!(nodes("mainnet"), vec![1, 3]);
+        assert_eq!(nodes("devnet"), vec![2]);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_users_batch_rolls_back_on_duplicate(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let request = |email: &str| CreateUserRequest {
+            email: email.to_string(),
+            password: "correct-horse".to_string(),
+        };
+
+        let users = store
+            .create_users_batch(vec![
+                request("ann@example.com"),
+                request("bob@example.com"),
+                request("cat@example.com"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(users.len(), 3);
+
+        // The duplicate comes last, after three users were inserted in the same transaction
+        let result = store
+            .create_users_batch(vec![
+                request("dan@example.com"),
+                request("eve@example.com"),
+                request("fay@example.com"),
+                request("Bob@example.com"),
+            ])
+            .await;
+        assert!(matches!(result, Err(StoreError::UserExists)));
+        for email in ["dan@example.com", "eve@example.com", "fay@example.com"] {
+            assert!(matches!(
+                store.get_user_by_email(email).await,
+                Err(StoreError::UserNotFound)
+            ));
+        }
+        assert!(store.get_user_by_email("ann@example.com").await.is_ok());
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_only_old_keyshares_are_due_for_rotation(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for (mpc_node_id, age_days) in [(1, 120), (2, 5)] {
+            sqlx::query!(
+                "INSERT INTO mpc_keyshares (user_id, mpc_node_id, private_key_share, public_key, rotated_at)
+                 VALUES ($1, $2, 'unused', 'unused', $3)",
+                user_id,
+                mpc_node_id,
+                Utc::now() - Duration::days(age_days)
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let due = store
+            .get_keyshares_due_for_rotation(Duration::days(90))
+            .await
+            .unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!((due[0].user_id, due[0].mpc_node_id), (user_id, 1));
+
+        // Toggling a share for maintenance is not a rotation
+        store.set_keyshare_active(user_id, 1, false).await.unwrap();
+        store.set_keyshare_active(user_id, 1, true).await.unwrap();
+        let due = store
+            .get_keyshares_due_for_rotation(Duration::days(90))
+            .await
+            .unwrap();
+        assert_eq!(due.len(), 1);
+
+        store.mark_keyshare_rotated(user_id, 1).await.unwrap();
+        assert!(
+            store
+                .get_keyshares_due_for_rotation(Duration::days(90))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            store.mark_keyshare_rotated(user_id, 3).await,
+            Err(StoreError::KeyshareNotFound)
+        ));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_consistent_balance_snapshot_during_swaps(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let user_id = insert_user(&store, Decimal::TEN).await;
+
+        // At a rate of 1 every swap moves value between SOL and USDC without changing the total
+        let swaps = async {
+            for round in 0..20 {
+                let (from_mint, to_mint) = if round % 2 == 0 {
+                    (None, Some(usdc.to_string()))
+                } else {
+                    (Some(usdc.to_string()), None)
+                };
+                store
+                    .internal_swap_with_rate(
+                        user_id,
+                        from_mint,
+                        to_mint,
+                        Decimal::ONE,
+                        Decimal::ONE,
+                    )
+                    .await
+                    .unwrap();
+            }
+        };
+        let snapshots = async {
+            let mut totals = Vec::new();
+            for _ in 0..20 {
+                let balance = store
+                    .get_user_complete_balance_consistent(user_id)
+                    .await
+                    .unwrap();
+                let tokens: Decimal = balance
+                    .token_balances
+                    .iter()
+                    .map(|token| token.balance)
+                    .sum();
+                totals.push(balance.sol_balance + tokens);
+                tokio::task::yield_now().await;
+            }
+            totals
+        };
+
+        let ((), totals) = tokio::join!(swaps, snapshots);
+        assert!(
+            totals.iter().all(|total| *total == Decimal::TEN),
+            "{:?}",
+            totals
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_activity_timeline_interleaves_sources(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        let pending = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Withdrawal,
+            TransactionStatus::Pending,
+            Decimal::ONE,
+        )
+        .await;
+        insert_keyshare(&store, user_id, 1).await;
+        let confirmed = insert_transaction(
+            &store,
+            user_id,
+            TransactionType::Deposit,
+            TransactionStatus::Confirmed,
+            Decimal::TWO,
+        )
+        .await;
+
+        // Spread the sources out so they interleave; the update trigger dates the settlement now
+        let hours_ago = |hours| Utc::now() - Duration::hours(hours);
+        sqlx::query!(
+            "UPDATE users SET created_at = $1 WHERE id = $2",
+            hours_ago(5),
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "UPDATE mpc_keyshares SET created_at = $1 WHERE user_id = $2",
+            hours_ago(3),
+            user_id
+        )
+        .execute(&store.pool)
+        .await
+        .unwrap();
+        for (id, age_hours) in [(pending, 4), (confirmed, 2)] {
+            sqlx::query!(
+                "UPDATE transactions SET created_at = $1 WHERE id = $2",
+                hours_ago(age_hours),
+                id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+
+        let timeline = store.get_user_activity_timeline(user_id, 10).await.unwrap();
+        let events: Vec<&str> = timeline
+            .iter()
+            .map(|event| event.event_type.as_str())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "transaction_confirmed",
+                "transaction_created",
+                "keyshare_created",
+                "transaction_created",
+                "signup",
+            ]
+        );
+        assert_eq!(timeline[0].reference_id, confirmed);
+        assert_eq!(timeline[1].reference_id, confirmed);
+        assert_eq!(timeline[2].detail.as_deref(), Some("node 1"));
+        assert_eq!(timeline[3].reference_id, pending);
+        assert_eq!(timeline[4].reference_id, user_id);
+
+        let latest = store.get_user_activity_timeline(user_id, 2).await.unwrap();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[1].event_type, "transaction_created");
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_dust_balances_are_cleaned(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for (mint, symbol, balance, decimals) in [
+            // Half of USDC's smallest unit
+            ("mint-dust", "DUST", Decimal::new(5, 7), 6),
+            ("mint-smallest", "MIN", Decimal::new(1, 6), 6),
+            ("mint-override", "OVR", Decimal::new(5, 1), 2),
+            ("mint-whole", "WHL", Decimal::ONE, 2),
+        ] {
+            store
+                .update_token_balance(user_id, mint, symbol, balance, decimals)
+                .await
+                .unwrap();
+        }
+
+        let thresholds = HashMap::from([("mint-override".to_string(), Decimal::ONE)]);
+        store.cleanup_dust_balances(&thresholds).await.unwrap();
+
+        let mut remaining: Vec<String> = store
+            .get_user_token_balances(user_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|balance| balance.token_mint)
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["mint-smallest", "mint-whole"]);
+
+        let written_off = sqlx::query_scalar!(
+            "SELECT SUM(amount) FROM balance_adjustments WHERE user_id = $1 AND operation = 'dust_cleanup'",
+            user_id
+        )
+        .fetch_one(&store.pool)
+        .await
+        .unwrap();
+        assert_eq!(written_off, Some(-Decimal::new(5000005, 7)));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_concurrent_token_debits_cannot_overdraw(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        store
+            .update_token_balance(user_id, "mint-debit", "DEB", Decimal::from(5), 6)
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            store.subtract_token_balance(user_id, "mint-debit", Decimal::from(3)),
+            store.subtract_token_balance(user_id, "mint-debit", Decimal::from(3)),
+        );
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(
+            results
+                .iter()
+                .any(|result| matches!(result, Err(StoreError::InsufficientBalance { .. })))
+        );
+        assert_eq!(
+            store
+                .get_token_balance(user_id, "mint-debit")
+                .await
+                .unwrap(),
+            Decimal::TWO
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_concurrent_token_transfers_cannot_overdraw(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let sender = insert_user(&store, Decimal::ZERO).await;
+        let receiver = insert_user(&store, Decimal::ZERO).await;
+        store
+            .update_token_balance(sender, "mint-transfer", "TRF", Decimal::from(5), 6)
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(
+            store.transfer_tokens(sender, receiver, "mint-transfer", Decimal::from(3)),
+            store.transfer_tokens(sender, receiver, "mint-transfer", Decimal::from(3)),
+        );
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(
+            results
+                .iter()
+                .any(|result| matches!(result, Err(StoreError::InsufficientBalance { .. })))
+        );
+        assert_eq!(
+            store
+                .get_token_balance(sender, "mint-transfer")
+                .await
+                .unwrap(),
+            Decimal::TWO
+        );
+        assert_eq!(
+            store
+                .get_token_balance(receiver, "mint-transfer")
+                .await
+                .unwrap(),
+            Decimal::from(3)
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_network_agg_pubkeys_are_kept_per_network(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        for (mpc_node_id, network) in [(1, "devnet"), (2, "devnet"), (3, "mainnet")] {
+            insert_keyshare(&store, user_id, mpc_node_id).await;
+            sqlx::query!(
+                "UPDATE mpc_keyshares SET network = $1, public_key = 'public-' || mpc_node_id
+                 WHERE user_id = $2 AND mpc_node_id = $3",
+                network,
+                user_id,
+                mpc_node_id
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+        assert_eq!(
+            store
+                .get_keyshare_public_keys(user_id, Some("devnet"))
+                .await
+                .unwrap(),
+            vec!["public-1", "public-2"]
+        );
+
+        store
+            .set_network_agg_pubkey(user_id, "devnet", "devnet-key")
+            .await
+            .unwrap();
+        store
+            .set_network_agg_pubkey(user_id, "mainnet", "mainnet-key-1")
+            .await
+            .unwrap();
+        // Rotating one network's key leaves the other untouched
+        store
+            .set_network_agg_pubkey(user_id, "mainnet", "mainnet-key-2")
+            .await
+            .unwrap();
+
+        let networks = store.get_user_networks(user_id).await.unwrap();
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0].network, "devnet");
+        assert_eq!(networks[0].keyshare_count, 2);
+        assert_eq!(networks[0].agg_pubkey.as_deref(), Some("devnet-key"));
+        assert_eq!(networks[1].network, "mainnet");
+        assert_eq!(networks[1].keyshare_count, 1);
+        assert_eq!(networks[1].agg_pubkey.as_deref(), Some("mainnet-key-2"));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_balances_for_users_skip_unknown_ids(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let alice = insert_user(&store, Decimal::new(15, 1)).await;
+        let bob = insert_user(&store, Decimal::ZERO).await;
+        let carol = insert_user(&store, Decimal::TWO).await;
+        let missing = Uuid::new_v4();
+        for (user_id, mint, symbol) in [
+            (
+                alice,
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                Some("USDC"),
+            ),
+            (alice, "Mystery1111111111111111111111111111111111111", None),
+            (
+                alice,
+                "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263",
+                Some("BONK"),
+            ),
+            (
+                bob,
+                "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+                Some("USDC"),
+            ),
+        ] {
+            sqlx::query!(
+                "INSERT INTO token_balances (user_id, token_mint, token_symbol, balance) VALUES ($1, $2, $3, 1)",
+                user_id,
+                mint,
+                symbol
+            )
+            .execute(&store.pool)
+            .await
+            .unwrap();
+        }
+        let ids = [alice, bob, carol, missing];
+
+        let balances = store.get_balances_for_users(&ids).await.unwrap();
+        assert_eq!(
+            balances,
+            HashMap::from([
+                (alice, Decimal::new(15, 1)),
+                (bob, Decimal::ZERO),
+                (carol, Decimal::TWO),
+            ])
+        );
+
+        let tokens = store.get_token_balances_for_users(&ids).await.unwrap();
+        let symbols = |user_id: Uuid| -> Vec<Option<&str>> {
+            tokens[&user_id]
+                .iter()
+                .map(|balance| balance.token_symbol.as_deref())
+                .collect()
+        };
+        // Only users holding tokens are keyed; unknown symbols sort last
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(symbols(alice), vec![Some("BONK"), Some("USDC"), None]);
+        assert_eq!(symbols(bob), vec![Some("USDC")]);
+        assert!(tokens.iter().all(|(user_id, balances)| {
+            balances.iter().all(|balance| balance.user_id == *user_id)
+        }));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_user_by_agg_pubkey_follows_rotation(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let other = insert_user(&store, Decimal::ZERO).await;
+        store
+            .update_user_agg_pubkey(user_id, "key-1")
+            .await
+            .unwrap();
+        store
+            .update_user_agg_pubkey(other, "key-other")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_user_by_agg_pubkey("key-1").await.unwrap().id,
+            user_id
+        );
+        assert!(matches!(
+            store.get_user_by_agg_pubkey("key-unknown").await,
+            Err(StoreError::UserNotFound)
+        ));
+
+        // After rotation the new key resolves; the old one only survives in the history
+        store
+            .update_user_agg_pubkey(user_id, "key-2")
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_user_by_agg_pubkey("key-2").await.unwrap().id,
+            user_id
+        );
+        assert!(matches!(
+            store.get_user_by_agg_pubkey("key-1").await,
+            Err(StoreError::UserNotFound)
+        ));
+        assert_eq!(
+            store.get_user_by_agg_pubkey("key-other").await.unwrap().id,
+            other
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_find_user_by_id_or_email(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::ZERO).await;
+        let email = store.get_user(user_id).await.unwrap().email;
+
+        assert_eq!(
+            store.find_user(&user_id.to_string()).await.unwrap().id,
+            user_id
+        );
+        // Emails match regardless of case and surrounding whitespace, as at sign-in
+        assert_eq!(store.find_user(&email).await.unwrap().id, user_id);
+        assert_eq!(
+            store
+                .find_user(&format!("  {}  ", email.to_uppercase()))
+                .await
+                .unwrap()
+                .id,
+            user_id
+        );
+
+        for missing in [Uuid::new_v4().to_string(), "nobody@example.com".to_string()] {
+            assert!(matches!(
+                store.find_user(&missing).await,
+                Err(StoreError::UserNotFound)
+            ));
+        }
+    }
 }
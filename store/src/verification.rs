@@ -0,0 +1,169 @@
+use crate::Store;
+use crate::password_reset::{check_token_redeemable, generate_secret_token, hash_secret_token};
+use crate::user::StoreError;
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+/// How long an email verification token stays valid after it is issued
+pub const EMAIL_VERIFICATION_TTL: Duration = Duration::hours(24);
+
+/// Gated operations pass when verification isn't required or the user has verified
+fn check_email_verified(
+    required: bool,
+    verified_at: Option<DateTime<Utc>>,
+) -> Result<(), StoreError> {
+    if required && verified_at.is_none() {
+        return Err(StoreError::EmailNotVerified);
+    }
+    Ok(())
+}
+
+impl Store {
+    /// Issue a verification token for `user_id`, returned in plaintext once for delivery
+    pub async fn create_email_verification(&self, user_id: Uuid) -> Result<String, StoreError> {
+        let token = generate_secret_token();
+        sqlx::query!(
+            r#"
+            INSERT INTO email_verification_tokens (token_hash, user_id, expires_at)
+            VALUES ($1, $2, $3)
+            "#,
+            hash_secret_token(&token),
+            user_id,
+            Utc::now() + EMAIL_VERIFICATION_TTL
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Redeem a verification token, marking the user's email verified and returning their id.
+    /// Verifying twice keeps the original `verified_at`.
+    pub async fn verify_email(&self, token: &str) -> Result<Uuid, StoreError> {
+        let mut tx = self.pool.begin().await?;
+
+        let verification = sqlx::query!(
+            r#"
+            SELECT user_id, expires_at, used_at
+            FROM email_verification_tokens
+            WHERE token_hash = $1
+            FOR UPDATE
+            "#,
+            hash_secret_token(token)
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| StoreError::InvalidInput("Invalid verification token".to_string()))?;
+
+        let now = Utc::now();
+        check_token_redeemable(verification.expires_at, verification.used_at, now)?;
+
+        sqlx::query!(
+            "UPDATE users SET verified_at = COALESCE(verified_at, $1), updated_at = $1 WHERE id = $2",
+            now,
+            verification.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE email_verification_tokens SET used_at = $1 WHERE user_id = $2 AND used_at IS NULL",
+            now,
+            verification.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(verification.user_id)
+    }
+
+    /// When the user verified their email, if they have
+    pub async fn get_email_verified_at(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<DateTime<Utc>>, StoreError> {
+        let user = sqlx::query!("SELECT verified_at FROM users WHERE id = $1", user_id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(StoreError::UserNotFound)?;
+
+        Ok(user.verified_at)
+    }
+
+    /// Reject a gated operation for an unverified user while verification is required
+    pub(crate) async fn check_verified_email(&self, user_id: Uuid) -> Result<(), StoreError> {
+        if !self.require_verified_email {
+            return Ok(());
+        }
+        let verified_at = self.get_email_verified_at(user_id).await?;
+        check_email_verified(self.require_verified_email, verified_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_email_verified;
+    use crate::Store;
+    use crate::testing::insert_user;
+    use crate::user::{StoreError, TransactionType};
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_unverified_user_is_blocked() {
+        assert!(matches!(
+            check_email_verified(true, None),
+            Err(StoreError::EmailNotVerified)
+        ));
+    }
+
+    #[test]
+    fn test_verified_user_passes() {
+        assert!(check_email_verified(true, Some(Utc::now())).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_gate_is_off_unless_required() {
+        assert!(check_email_verified(false, None).is_ok());
+
+        // With the gate off nothing is looked up, so an unreachable database is fine
+        let store = Store::for_tests("postgres://localhost/unused");
+        assert!(store.check_verified_email(Uuid::new_v4()).await.is_ok());
+        assert!(
+            store
+                .with_require_verified_email(true)
+                .require_verified_email
+        );
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_withdrawal_requires_verified_email(pool: PgPool) {
+        let store = Store::from_pool(pool).with_require_verified_email(true);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        let withdraw = || {
+            store.create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                Decimal::ONE,
+                None,
+                None,
+                Some("11111111111111111111111111111112".to_string()),
+                None,
+                None,
+            )
+        };
+
+        assert!(matches!(
+            withdraw().await,
+            Err(StoreError::EmailNotVerified)
+        ));
+
+        let token = store.create_email_verification(user_id).await.unwrap();
+        store.verify_email(&token).await.unwrap();
+        assert!(withdraw().await.is_ok());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::Store;
+use crate::transaction::validate_address;
+use crate::user::StoreError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct WhitelistEntry {
+    pub user_id: Uuid,
+    pub address: String,
+    pub label: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An empty whitelist allows every destination; otherwise the destination must be listed
+fn whitelist_allows(entries: i64, matched: i64) -> bool {
+    entries == 0 || matched > 0
+}
+
+impl Store {
+    /// Approve a withdrawal destination, updating the label if it is already listed
+    pub async fn add_whitelist_address(
+        &self,
+        user_id: Uuid,
+        address: &str,
+        label: Option<&str>,
+    ) -> Result<WhitelistEntry, StoreError> {
+        validate_address("address", Some(address))?;
+
+        let entry = sqlx::query_as!(
+            WhitelistEntry,
+            r#"
+            INSERT INTO withdrawal_whitelist (user_id, address, label)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, address) DO UPDATE SET label = EXCLUDED.label
+            RETURNING user_id, address, label, created_at
+            "#,
+            user_id,
+            address,
+            label
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// Remove a withdrawal destination, returning whether it was listed
+    pub async fn remove_whitelist_address(
+        &self,
+        user_id: Uuid,
+        address: &str,
+    ) -> Result<bool, StoreError> {
+        let removed = sqlx::query!(
+            "DELETE FROM withdrawal_whitelist WHERE user_id = $1 AND address = $2",
+            user_id,
+            address
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(removed > 0)
+    }
+
+    /// List a user's approved withdrawal destinations, oldest first
+    pub async fn list_whitelist(&self, user_id: Uuid) -> Result<Vec<WhitelistEntry>, StoreError> {
+        let entries = sqlx::query_as!(
+            WhitelistEntry,
+            r#"
+            SELECT user_id, address, label, created_at
+            FROM withdrawal_whitelist
+            WHERE user_id = $1
+            ORDER BY created_at ASC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Whether the user may withdraw to `address`; always true while the whitelist is empty
+    pub async fn is_whitelisted(&self, user_id: Uuid, address: &str) -> Result<bool, StoreError> {
+        self.destination_allowed(user_id, Some(address)).await
+    }
+
+    /// Reject a withdrawal whose destination is missing from a non-empty whitelist
+    pub(crate) async fn check_withdrawal_whitelist(
+        &self,
+        user_id: Uuid,
+        to_address: Option<&str>,
+    ) -> Result<(), StoreError> {
+        if !self.destination_allowed(user_id, to_address).await? {
+            return Err(StoreError::AddressNotWhitelisted(
+                to_address.unwrap_or("<none>").to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn destination_allowed(
+        &self,
+        user_id: Uuid,
+        address: Option<&str>,
+    ) -> Result<bool, StoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "entries!", COUNT(*) FILTER (WHERE address = $2) as "matched!"
+            FROM withdrawal_whitelist
+            WHERE user_id = $1
+            "#,
+            user_id,
+            address
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(whitelist_allows(row.entries, row.matched))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::whitelist_allows;
+    use crate::Store;
+    use crate::testing::{insert_user, set_agg_pubkey};
+    use crate::user::{StoreError, TransactionStatus, TransactionType};
+    use rust_decimal::Decimal;
+    use sqlx::PgPool;
+
+    const LISTED: &str = "11111111111111111111111111111112";
+    const UNLISTED: &str = "So11111111111111111111111111111111111111112";
+
+    #[test]
+    fn test_empty_whitelist_allows_all() {
+        assert!(whitelist_allows(0, 0));
+    }
+
+    #[test]
+    fn test_whitelist_enforced_once_populated() {
+        assert!(whitelist_allows(2, 1));
+        assert!(!whitelist_allows(2, 0));
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_withdrawals_follow_the_whitelist(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        set_agg_pubkey(&store, user_id, LISTED).await;
+        let withdraw = |to: &str| {
+            store.create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                Decimal::ONE,
+                None,
+                None,
+                Some(to.to_string()),
+                None,
+                None,
+            )
+        };
+
+        // Any destination is fine until the first address is listed
+        assert!(withdraw(UNLISTED).await.is_ok());
+
+        store
+            .add_whitelist_address(user_id, LISTED, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            withdraw(UNLISTED).await,
+            Err(StoreError::AddressNotWhitelisted(address)) if address == UNLISTED
+        ));
+        let pending = withdraw(LISTED).await.unwrap();
+
+        // Delisting while the withdrawal is pending stops it from being processed
+        store
+            .add_whitelist_address(user_id, UNLISTED, None)
+            .await
+            .unwrap();
+        store
+            .remove_whitelist_address(user_id, LISTED)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store
+                .process_withdrawal(pending.id, "sig".to_string())
+                .await,
+            Err(StoreError::AddressNotWhitelisted(_))
+        ));
+        let pending = store.get_transaction(pending.id).await.unwrap();
+        assert!(matches!(pending.status, TransactionStatus::Pending));
+        assert_eq!(store.get_user_balance(user_id).await.unwrap(), Decimal::TEN);
+    }
+
+    #[sqlx::test(migrations = "./migration")]
+    async fn test_landed_withdrawal_is_debited_after_delisting(pool: PgPool) {
+        let store = Store::from_pool(pool);
+        let user_id = insert_user(&store, Decimal::TEN).await;
+        set_agg_pubkey(&store, user_id, LISTED).await;
+        store
+            .add_whitelist_address(user_id, LISTED, None)
+            .await
+            .unwrap();
+        let pending = store
+            .create_transaction(
+                user_id,
+                TransactionType::Withdrawal,
+                Decimal::ONE,
+                None,
+                None,
+                Some(LISTED.to_string()),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        // Delisted after the transfer was signed and sent: it still has to be debited
+        store
+            .add_whitelist_address(user_id, UNLISTED, None)
+            .await
+            .unwrap();
+        store
+            .remove_whitelist_address(user_id, LISTED)
+            .await
+            .unwrap();
+        store
+            .record_withdrawal_debit(pending.id, "sig".to_string())
+            .await
+            .unwrap();
+
+        let confirmed = store.get_transaction(pending.id).await.unwrap();
+        assert!(matches!(confirmed.status, TransactionStatus::Confirmed));
+        assert_eq!(confirmed.tx_signature.as_deref(), Some("sig"));
+        assert_eq!(
+            store.get_user_balance(user_id).await.unwrap(),
+            Decimal::new(9, 0)
+        );
+    }
+}